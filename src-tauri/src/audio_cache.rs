@@ -1,10 +1,58 @@
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fs;
-use std::path::PathBuf;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use futures_util::StreamExt;
 use reqwest::Client;
+use serde::{Deserialize, Serialize};
 use tokio::fs as async_fs;
 use tokio::io::AsyncWriteExt;
+use tokio::sync::futures::OwnedNotified;
+use tokio::sync::{Mutex, Notify};
+use log::{info, warn};
+
+// Minimum spacing between progress callbacks while downloading
+const PROGRESS_BYTES_INTERVAL: u64 = 256 * 1024;
+const PROGRESS_TIME_INTERVAL_MS: u128 = 200;
+
+// Sidecar extensions for embedded cover art and their mime types
+const COVER_ART_EXTENSIONS: [(&str, &str); 2] = [("jpg", "image/jpeg"), ("png", "image/png")];
+
+// Default disk budget for cached audio
+const DEFAULT_MAX_CACHE_BYTES: u64 = 2 * 1024 * 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CacheUsage {
+    pub entries: usize,
+    pub total_bytes: u64,
+    pub max_entries: usize,
+    pub max_bytes: u64,
+    pub pinned: usize,
+    pub cache_dir: String,
+    pub error: Option<String>, // Why nothing is being cached, when the directory is unusable
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CacheStats {
+    pub file_count: usize,
+    pub total_bytes: u64,
+}
+
+// Which version of a song gets downloaded for offline use
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum DownloadQuality {
+    Original,
+    Transcoded(i64), // max bitrate in bits per second
+}
+
+// Where a song stands locally, for badges in the UI
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum CacheStatus {
+    Downloaded, // pinned for offline use
+    Cached,
+    NotCached,
+}
 
 #[derive(Debug, Clone)]
 struct CacheEntry {
@@ -13,41 +61,140 @@ struct CacheEntry {
     file_size: u64,
 }
 
+// Cache keys being written, each with the Notify that anyone waiting for that file is parked on
+type InFlight = Arc<std::sync::Mutex<HashMap<String, Arc<Notify>>>>;
+
+// A cache file being written under a temporary name. Until it's dropped the key can't be evicted
+// and other writers of the same key wait for it; dropping it wakes them and removes what's left
+// of an unfinished file, so failed and cancelled downloads never become cache entries
+struct PendingFile {
+    in_flight: InFlight,
+    key: String,
+    file_path: PathBuf,
+    part_path: PathBuf,
+    done: Arc<Notify>,
+}
+
+impl Drop for PendingFile {
+    fn drop(&mut self) {
+        // Already gone once the finished file was renamed into place
+        let _ = fs::remove_file(&self.part_path);
+        let mut in_flight = self.in_flight.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        in_flight.remove(&self.key);
+        self.done.notify_waiters();
+    }
+}
+
 pub struct AudioCache {
     cache_dir: PathBuf,
     entries: HashMap<String, CacheEntry>,
     access_order: VecDeque<String>, // For LRU tracking
+    pinned: HashSet<String>,        // Kept for offline use, never evicted
+    qualities: HashMap<String, DownloadQuality>, // Quality of explicit downloads, for later upgrades
     max_entries: usize,
+    max_bytes: u64,
+    in_use: HashSet<String>,     // Song ids currently playing, never evicted
+    in_flight: InFlight,         // Keys being written, never evicted
+    evicted: Vec<String>,        // Evicted keys not yet reported through take_evicted
+    unusable: Option<String>,    // Why cache_dir can't be used; nothing is cached while set
     client: Client,
 }
 
 impl AudioCache {
-    pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
-        let cache_dir = std::env::temp_dir().join("bloodin_audio_cache");
-        
-        // Create cache directory if it doesn't exist
-        if !cache_dir.exists() {
-            fs::create_dir_all(&cache_dir)?;
-        }
-        
-        let client = Client::builder()
+    fn build_client(custom_headers: &HashMap<String, String>) -> Result<Client, Box<dyn std::error::Error>> {
+        Ok(Client::builder()
             .timeout(std::time::Duration::from_secs(120)) // 2 minutes for large files
-            .build()?;
-        
-        let mut cache = Self {
+            .default_headers(crate::jellyfin::custom_header_map(custom_headers))
+            .build()?)
+    }
+    
+    // Downloads need the same proxy headers as API requests
+    pub fn set_custom_headers(&mut self, custom_headers: &HashMap<String, String>) -> Result<(), Box<dyn std::error::Error>> {
+        self.client = Self::build_client(custom_headers)?;
+        Ok(())
+    }
+    
+    pub fn default_dir() -> PathBuf {
+        std::env::temp_dir().join("bloodin_audio_cache")
+    }
+    
+    // Cache in the default directory. If that can't be used the cache starts out disabled,
+    // reporting why through usage() and download errors until set_cache_dir finds a usable one
+    pub fn new() -> Self {
+        Self::with_dir_or_disabled(Self::default_dir())
+    }
+    
+    fn with_dir_or_disabled(cache_dir: PathBuf) -> Self {
+        Self::with_dir(cache_dir.clone()).unwrap_or_else(|e| {
+            warn!("Audio cache disabled: {}", e);
+            Self::empty(cache_dir, Client::new(), Some(e.to_string()))
+        })
+    }
+    
+    fn empty(cache_dir: PathBuf, client: Client, unusable: Option<String>) -> Self {
+        Self {
             cache_dir,
             entries: HashMap::new(),
             access_order: VecDeque::new(),
+            pinned: HashSet::new(),
+            qualities: HashMap::new(),
             max_entries: 100,
+            max_bytes: DEFAULT_MAX_CACHE_BYTES,
+            in_use: HashSet::new(),
+            in_flight: InFlight::default(),
+            evicted: Vec::new(),
+            unusable,
             client,
-        };
+        }
+    }
+    
+    pub fn with_dir(cache_dir: PathBuf) -> Result<Self, Box<dyn std::error::Error>> {
+        // Create cache directory if it doesn't exist
+        if !cache_dir.exists() {
+            fs::create_dir_all(&cache_dir)?;
+        }
+        Self::check_writable(&cache_dir)?;
+        
+        let client = Self::build_client(&HashMap::new())?;
+        let mut cache = Self::empty(cache_dir, client, None);
         
         // Load existing cache entries
         cache.load_existing_entries()?;
+        cache.load_pinned();
+        cache.load_qualities();
         
         Ok(cache)
     }
     
+    // Fail early on read-only or otherwise unusable directories instead of on the first download
+    fn check_writable(dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let probe = dir.join(".write_test");
+        fs::write(&probe, b"")
+            .map_err(|e| format!("Cache directory {} is not writable: {}", dir.display(), e))?;
+        let _ = fs::remove_file(&probe);
+        Ok(())
+    }
+    
+    pub fn cache_dir(&self) -> &Path {
+        &self.cache_dir
+    }
+    
+    // Re-initialize the cache in another directory, picking up any files already there.
+    // Limits, headers and in-use songs carry over; files in the old directory are left alone
+    pub fn set_cache_dir(&mut self, cache_dir: PathBuf) -> Result<(), Box<dyn std::error::Error>> {
+        let mut relocated = Self::with_dir(cache_dir)?;
+        relocated.max_entries = self.max_entries;
+        relocated.max_bytes = self.max_bytes;
+        relocated.in_use = std::mem::take(&mut self.in_use);
+        relocated.in_flight = self.in_flight.clone();
+        relocated.client = self.client.clone();
+        
+        *self = relocated;
+        self.enforce_budget();
+        info!("Audio cache now at {}", self.cache_dir.display());
+        Ok(())
+    }
+    
     fn load_existing_entries(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         if !self.cache_dir.exists() {
             return Ok(());
@@ -65,6 +212,10 @@ impl AudioCache {
                             let song_id = file_name_str.replace(".audio", "");
                             
                             let metadata = fs::metadata(&path)?;
+                            // Left behind by a download that never got going
+                            if metadata.len() == 0 {
+                                continue;
+                            }
                             let last_accessed = metadata
                                 .accessed()
                                 .or_else(|_| metadata.modified())
@@ -95,10 +246,146 @@ impl AudioCache {
             a_time.cmp(&b_time)
         });
         
-        println!("📦 Loaded {} cached audio files", self.entries.len());
+        info!("Loaded {} cached audio files", self.entries.len());
         Ok(())
     }
     
+    fn pinned_file(&self) -> PathBuf {
+        self.cache_dir.join("pinned.json")
+    }
+    
+    fn load_pinned(&mut self) {
+        if let Ok(contents) = fs::read_to_string(self.pinned_file()) {
+            if let Ok(pinned) = serde_json::from_str::<HashSet<String>>(&contents) {
+                // Only keep pins whose files are still around
+                self.pinned = pinned.into_iter().filter(|id| self.entries.contains_key(id)).collect();
+            }
+        }
+    }
+    
+    fn save_pinned(&self) {
+        match serde_json::to_string(&self.pinned) {
+            Ok(contents) => {
+                if let Err(e) = fs::write(self.pinned_file(), contents) {
+                    warn!("Failed to save pinned cache entries: {}", e);
+                }
+            }
+            Err(e) => warn!("Failed to serialize pinned cache entries: {}", e),
+        }
+    }
+    
+    fn qualities_file(&self) -> PathBuf {
+        self.cache_dir.join("qualities.json")
+    }
+    
+    fn load_qualities(&mut self) {
+        if let Ok(contents) = fs::read_to_string(self.qualities_file()) {
+            if let Ok(qualities) = serde_json::from_str::<HashMap<String, DownloadQuality>>(&contents) {
+                self.qualities = qualities.into_iter().filter(|(id, _)| self.entries.contains_key(id)).collect();
+            }
+        }
+    }
+    
+    fn save_qualities(&self) {
+        match serde_json::to_string(&self.qualities) {
+            Ok(contents) => {
+                if let Err(e) = fs::write(self.qualities_file(), contents) {
+                    warn!("Failed to save download qualities: {}", e);
+                }
+            }
+            Err(e) => warn!("Failed to serialize download qualities: {}", e),
+        }
+    }
+    
+    // Quality a song was explicitly downloaded at, if known
+    pub fn get_quality(&self, song_id: &str) -> Option<&DownloadQuality> {
+        self.qualities.get(song_id)
+    }
+    
+    pub fn set_quality(&mut self, song_id: &str, quality: DownloadQuality) {
+        if self.entries.contains_key(song_id) {
+            self.qualities.insert(song_id.to_string(), quality);
+            self.save_qualities();
+        }
+    }
+    
+    // Drop a cached song so it can be downloaded again, e.g. at a different quality
+    pub fn evict(&mut self, song_id: &str) {
+        self.remove_entry(song_id);
+    }
+    
+    // Evict whichever entry is stored at file_path, reporting it through take_evicted.
+    // False for paths that aren't one of this cache's files, which are never touched
+    pub fn evict_file(&mut self, file_path: &Path) -> bool {
+        if file_path.parent() != Some(self.cache_dir.as_path()) {
+            return false;
+        }
+        let key = self.entries
+            .iter()
+            .find(|(_, entry)| entry.file_path == file_path)
+            .map(|(key, _)| key.clone());
+        let Some(key) = key else {
+            return false;
+        };
+        
+        self.remove_entry(&key);
+        self.evicted.push(key);
+        true
+    }
+    
+    // Cache keys holding a song, at any quality
+    pub fn song_keys(&self, song_id: &str) -> Vec<String> {
+        let quality_prefix = format!("{}_", song_id);
+        self.entries
+            .keys()
+            .filter(|key| *key == song_id || key.starts_with(&quality_prefix))
+            .cloned()
+            .collect()
+    }
+    
+    // Status of a song at any quality, without touching its LRU position
+    pub fn cache_status(&self, song_id: &str) -> CacheStatus {
+        let keys = self.song_keys(song_id);
+        let on_disk = keys.iter().any(|key| self.entries[key].file_path.exists());
+        
+        if !on_disk {
+            CacheStatus::NotCached
+        } else if keys.iter().any(|key| self.pinned.contains(key)) {
+            CacheStatus::Downloaded
+        } else {
+            CacheStatus::Cached
+        }
+    }
+    
+    pub fn is_pinned(&self, song_id: &str) -> bool {
+        self.pinned.contains(song_id)
+    }
+    
+    // Pin a cached song so it survives eviction
+    pub fn pin(&mut self, song_id: &str) -> bool {
+        if !self.entries.contains_key(song_id) {
+            return false;
+        }
+        if self.pinned.insert(song_id.to_string()) {
+            self.save_pinned();
+        }
+        true
+    }
+    
+    // Cache key for a song at a quality; originals keep the bare song id.
+    // Jellyfin ids never contain '_', so the song id is everything before it.
+    pub fn cache_key(song_id: &str, quality: &DownloadQuality) -> String {
+        match quality {
+            DownloadQuality::Original => song_id.to_string(),
+            DownloadQuality::Transcoded(max_bitrate) => format!("{}_{}", song_id, max_bitrate),
+        }
+    }
+    
+    // The song cached at the given quality
+    pub fn get_cached_path_for_quality(&mut self, song_id: &str, quality: &DownloadQuality) -> Option<PathBuf> {
+        self.get_cached_path(&Self::cache_key(song_id, quality))
+    }
+    
     pub fn get_cached_path(&mut self, song_id: &str) -> Option<PathBuf> {
         // Check if entry exists and file exists
         if let Some(entry) = self.entries.get(song_id) {
@@ -106,7 +393,7 @@ impl AudioCache {
                 let file_path = entry.file_path.clone();
                 // Update access time and move to end of LRU queue
                 self.update_access_time(song_id);
-                println!("🎵 Cache hit for song: {}", song_id);
+                info!("Cache hit for song: {}", song_id);
                 return Some(file_path);
             }
         }
@@ -119,44 +406,139 @@ impl AudioCache {
         None
     }
     
-    pub async fn cache_audio(&mut self, song_id: &str, stream_url: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
-        // Check if already cached
-        if let Some(cached_path) = self.get_cached_path(song_id) {
-            return Ok(cached_path);
-        }
-        
-        println!("⬇️ Downloading and caching audio for song: {}", song_id);
-        
-        // Ensure we don't exceed max entries
-        self.ensure_cache_size();
+    // Download a song into the cache, reporting (downloaded_bytes, total_bytes) through on_progress
+    // as chunks arrive. The cache is only locked to look the song up and to add the finished file,
+    // never across the download, so lookups and other songs carry on meanwhile. A second request
+    // for a song that's already downloading waits for that download and gets its file back
+    pub async fn cache_audio<F>(cache: &Mutex<Self>, song_id: &str, stream_url: &str, mut on_progress: F) -> Result<PathBuf, Box<dyn std::error::Error>>
+    where
+        F: FnMut(u64, Option<u64>) + Send,
+    {
+        let (pending, client) = loop {
+            let mut cache = cache.lock().await;
+            if let Some(cached_path) = cache.get_cached_path(song_id) {
+                return Ok(cached_path);
+            }
+            cache.check_usable()?;
+            match cache.begin_write(song_id) {
+                Ok(pending) => {
+                    // Make room for the new file under both limits
+                    cache.ensure_cache_size();
+                    break (pending, cache.client.clone());
+                }
+                Err(finished) => {
+                    drop(cache);
+                    // Look again once that download is done; if it failed, this call takes over
+                    finished.await;
+                }
+            }
+        };
         
-        // Download the audio file
-        let response = self.client.get(stream_url).send().await?;
+        info!("Downloading and caching audio for song: {}", song_id);
         
+        let response = client.get(stream_url).send().await?;
         if !response.status().is_success() {
             return Err(format!("Failed to download audio: {}", response.status()).into());
         }
         
-        let file_path = self.cache_dir.join(format!("{}.audio", song_id));
-        let mut file = async_fs::File::create(&file_path).await?;
+        let total_bytes = response.content_length();
+        let mut file = async_fs::File::create(&pending.part_path).await?;
+        
+        // Stream the content to file, reporting progress (debounced)
+        let mut stream = response.bytes_stream();
+        let mut downloaded: u64 = 0;
+        let mut last_reported_bytes: u64 = 0;
+        let mut last_reported_at = Instant::now();
+        on_progress(0, total_bytes);
         
-        // Stream the content to file
-        let bytes = response.bytes().await?;
-        file.write_all(&bytes).await?;
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+            file.write_all(&chunk).await?;
+            downloaded += chunk.len() as u64;
+            
+            if downloaded - last_reported_bytes >= PROGRESS_BYTES_INTERVAL
+                || last_reported_at.elapsed().as_millis() >= PROGRESS_TIME_INTERVAL_MS
+            {
+                on_progress(downloaded, total_bytes);
+                last_reported_bytes = downloaded;
+                last_reported_at = Instant::now();
+            }
+        }
         file.flush().await?;
+        // The handle must be closed before the rename, or it fails on Windows
+        drop(file);
+        on_progress(downloaded, total_bytes);
+        
+        // A dropped connection can end the stream early without an error
+        if let Some(expected) = total_bytes {
+            if downloaded != expected {
+                return Err(format!("Incomplete download for {}: got {} of {} bytes", song_id, downloaded, expected).into());
+            }
+        }
+        
+        async_fs::rename(&pending.part_path, &pending.file_path).await?;
+        save_embedded_art(&pending.file_path, song_id).await;
+        
+        let mut cache = cache.lock().await;
+        cache.add_entry(song_id, pending.file_path.clone(), downloaded);
+        Ok(pending.file_path.clone())
+    }
+    
+    // Cache audio the player already downloaded in full, so it isn't fetched a second time
+    pub async fn store_audio(&mut self, song_id: &str, data: &[u8]) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        if let Some(cached_path) = self.get_cached_path(song_id) {
+            return Ok(cached_path);
+        }
+        self.check_usable()?;
+        let Ok(pending) = self.begin_write(song_id) else {
+            return Err(format!("{} is already being downloaded", song_id).into());
+        };
         
-        // Get file size
-        let metadata = async_fs::metadata(&file_path).await?;
-        let file_size = metadata.len();
+        self.ensure_cache_size();
+        async_fs::write(&pending.part_path, data).await?;
+        async_fs::rename(&pending.part_path, &pending.file_path).await?;
+        save_embedded_art(&pending.file_path, song_id).await;
         
-        // Add to cache
+        self.add_entry(song_id, pending.file_path.clone(), data.len() as u64);
+        Ok(pending.file_path.clone())
+    }
+    
+    fn check_usable(&self) -> Result<(), Box<dyn std::error::Error>> {
+        match &self.unusable {
+            Some(reason) => Err(format!("Audio cache is disabled: {}", reason).into()),
+            None => Ok(()),
+        }
+    }
+    
+    // Claim a key for writing, or get a future that completes when whoever holds it is done
+    fn begin_write(&self, song_id: &str) -> Result<PendingFile, OwnedNotified> {
+        let mut in_flight = self.in_flight.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        // Created under the lock, so the wakeup can't slip past between the check and the wait
+        if let Some(done) = in_flight.get(song_id) {
+            return Err(done.clone().notified_owned());
+        }
+        
+        let done = Arc::new(Notify::new());
+        in_flight.insert(song_id.to_string(), done.clone());
+        let file_path = self.cache_dir.join(format!("{}.audio", song_id));
+        Ok(PendingFile {
+            in_flight: self.in_flight.clone(),
+            key: song_id.to_string(),
+            part_path: file_path.with_extension("audio.part"),
+            file_path,
+            done,
+        })
+    }
+    
+    // Add a fully written file to the cache
+    fn add_entry(&mut self, song_id: &str, file_path: PathBuf, file_size: u64) {
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
             .as_secs();
         
         let cache_entry = CacheEntry {
-            file_path: file_path.clone(),
+            file_path,
             last_accessed: now,
             file_size,
         };
@@ -164,9 +546,22 @@ impl AudioCache {
         self.entries.insert(song_id.to_string(), cache_entry);
         self.access_order.push_back(song_id.to_string());
         
-        println!("💾 Cached audio file: {} ({} bytes)", song_id, file_size);
+        info!("Cached audio file: {} ({} bytes)", song_id, file_size);
         
-        Ok(file_path)
+        // The new file may have pushed us over the byte budget
+        self.enforce_budget();
+    }
+    
+    fn is_being_written(&self, key: &str) -> bool {
+        self.in_flight.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).contains_key(key)
+    }
+    
+    // Sidecar cover art extracted from a cached file, as (mime_type, bytes)
+    pub fn get_cover_art(&self, song_id: &str) -> Option<(String, Vec<u8>)> {
+        COVER_ART_EXTENSIONS.iter().find_map(|(extension, mime_type)| {
+            let path = self.cache_dir.join(format!("{}.{}", song_id, extension));
+            fs::read(path).ok().map(|data| (mime_type.to_string(), data))
+        })
     }
     
     fn update_access_time(&mut self, song_id: &str) {
@@ -186,22 +581,96 @@ impl AudioCache {
         self.access_order.push_back(song_id.to_string());
     }
     
-    fn ensure_cache_size(&mut self) {
-        while self.entries.len() >= self.max_entries {
-            if let Some(oldest_id) = self.access_order.pop_front() {
+    fn is_evictable(&self, key: &str) -> bool {
+        let song_id = key.split('_').next().unwrap_or(key);
+        !self.pinned.contains(key)
+            && !self.in_use.contains(song_id)
+            && !self.is_being_written(key)
+    }
+    
+    // Evict the least recently used entry that may be evicted; false if there's none
+    fn evict_oldest(&mut self) -> bool {
+        let oldest_id = self.access_order
+            .iter()
+            .find(|id| self.is_evictable(id))
+            .cloned();
+        
+        match oldest_id {
+            Some(oldest_id) => {
                 self.remove_entry(&oldest_id);
-                println!("🗑️ Evicted old cached file: {}", oldest_id);
-            } else {
+                info!("Evicted old cached file: {}", oldest_id);
+                self.evicted.push(oldest_id);
+                true
+            }
+            None => false,
+        }
+    }
+    
+    fn ensure_cache_size(&mut self) {
+        while self.entries.len() >= self.max_entries || self.total_bytes() > self.max_bytes {
+            if !self.evict_oldest() {
+                break;
+            }
+        }
+    }
+    
+    fn total_bytes(&self) -> u64 {
+        self.entries.values().map(|e| e.file_size).sum()
+    }
+    
+    // Evict until both the entry limit and the byte budget hold again
+    pub fn enforce_budget(&mut self) {
+        while self.entries.len() > self.max_entries || self.total_bytes() > self.max_bytes {
+            if !self.evict_oldest() {
                 break;
             }
         }
     }
     
+    pub fn set_in_use(&mut self, song_ids: HashSet<String>) {
+        self.in_use = song_ids;
+    }
+    
+    pub fn set_cache_limits(&mut self, max_entries: usize, max_bytes: u64) {
+        self.max_entries = max_entries;
+        self.max_bytes = max_bytes;
+        self.enforce_budget();
+    }
+    
+    // Keys evicted since the last call
+    pub fn take_evicted(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.evicted)
+    }
+    
+    pub fn usage(&self) -> CacheUsage {
+        CacheUsage {
+            entries: self.entries.len(),
+            total_bytes: self.total_bytes(),
+            max_entries: self.max_entries,
+            max_bytes: self.max_bytes,
+            pinned: self.pinned.len(),
+            cache_dir: self.cache_dir().display().to_string(),
+            error: self.unusable.clone(),
+        }
+    }
+    
     fn remove_entry(&mut self, song_id: &str) {
+        if self.pinned.remove(song_id) {
+            self.save_pinned();
+        }
+        if self.qualities.remove(song_id).is_some() {
+            self.save_qualities();
+        }
+        
         if let Some(entry) = self.entries.remove(song_id) {
             // Try to delete the file
             if let Err(e) = fs::remove_file(&entry.file_path) {
-                println!("⚠️ Failed to delete cache file {}: {}", entry.file_path.display(), e);
+                warn!("Failed to delete cache file {}: {}", entry.file_path.display(), e);
+            }
+            
+            // And its cover art sidecar, if any
+            for (extension, _) in COVER_ART_EXTENSIONS {
+                let _ = fs::remove_file(self.cache_dir.join(format!("{}.{}", song_id, extension)));
             }
         }
         
@@ -211,16 +680,322 @@ impl AudioCache {
         }
     }
     
-    pub fn _get_cache_stats(&self) -> (usize, u64) {
-        let total_size: u64 = self.entries.values().map(|e| e.file_size).sum();
-        (self.entries.len(), total_size)
+    pub fn get_cache_stats(&self) -> CacheStats {
+        CacheStats {
+            file_count: self.entries.len(),
+            total_bytes: self.total_bytes(),
+        }
     }
     
-    pub fn _clear_cache(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        for (song_id, _) in self.entries.clone() {
-            self.remove_entry(&song_id);
+    // Removes every cached file except pinned ones and the ones playing or being written right now;
+    // removed keys are reported through take_evicted
+    pub fn clear_cache(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let removable: Vec<String> = self.entries
+            .keys()
+            .filter(|key| self.is_evictable(key))
+            .cloned()
+            .collect();
+        
+        for key in removable {
+            self.remove_entry(&key);
+            self.evicted.push(key);
         }
-        println!("🧹 Cleared audio cache");
+        info!("Cleared audio cache");
         Ok(())
     }
-} 
\ No newline at end of file
+}
+
+// Keep any embedded cover art next to a cached file so it can be shown offline
+async fn save_embedded_art(file_path: &Path, song_id: &str) {
+    let art_source = file_path.to_path_buf();
+    let art_result = tokio::task::spawn_blocking(move || extract_embedded_art(&art_source)).await;
+    if let Ok(Some((extension, data))) = art_result {
+        let art_path = file_path.with_file_name(format!("{}.{}", song_id, extension));
+        match async_fs::write(&art_path, data).await {
+            Ok(()) => info!("Saved embedded cover art for song: {}", song_id),
+            Err(e) => warn!("Failed to save cover art for {}: {}", song_id, e),
+        }
+    }
+}
+
+// Pull the first embedded picture out of an audio file's tags, as (extension, bytes)
+fn extract_embedded_art(path: &std::path::Path) -> Option<(&'static str, Vec<u8>)> {
+    use symphonia::core::io::MediaSourceStream;
+    use symphonia::core::formats::FormatOptions;
+    use symphonia::core::meta::MetadataOptions;
+    use symphonia::core::probe::Hint;
+    
+    let file = fs::File::open(path).ok()?;
+    let media_source_stream = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut probe_result = symphonia::default::get_probe()
+        .format(&Hint::new(), media_source_stream, &FormatOptions::default(), &MetadataOptions::default())
+        .ok()?;
+    
+    // Tags may live before the container (ID3v2) or inside it (FLAC, MP4)
+    let probed_visual = probe_result.metadata.get()
+        .and_then(|metadata| metadata.current().and_then(|rev| rev.visuals().first().cloned()));
+    let visual = probed_visual.or_else(|| {
+        probe_result.format.metadata().current().and_then(|rev| rev.visuals().first().cloned())
+    })?;
+    
+    let extension = if visual.media_type == "image/png" { "png" } else { "jpg" };
+    Some((extension, visual.data.to_vec()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+
+    // Empty cache directory of its own for each test
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("bloodin-cache-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    // Local HTTP server answering every request with body, counting the requests
+    async fn serve(body: &'static [u8]) -> (String, Arc<AtomicUsize>) {
+        serve_with_length(body, body.len()).await
+    }
+
+    // Same, announcing content_length however much of the body is actually sent
+    async fn serve_with_length(body: &'static [u8], content_length: usize) -> (String, Arc<AtomicUsize>) {
+        // Slow enough that a second caller is waiting before the first finishes
+        serve_after(body, content_length, Duration::from_millis(100)).await
+    }
+
+    // Same, answering each request only after the given delay
+    async fn serve_after(body: &'static [u8], content_length: usize, delay: Duration) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/audio", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                counter.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(async move {
+                    let mut request = Vec::new();
+                    let mut buf = [0u8; 1024];
+                    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+                        match socket.read(&mut buf).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => request.extend_from_slice(&buf[..n]),
+                        }
+                    }
+                    tokio::time::sleep(delay).await;
+                    let header = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", content_length);
+                    let _ = socket.write_all(header.as_bytes()).await;
+                    let _ = socket.write_all(body).await;
+                });
+            }
+        });
+        (url, requests)
+    }
+
+    #[tokio::test]
+    async fn concurrent_downloads_of_a_song_share_one_request() {
+        let dir = test_dir("concurrent");
+        // Shared the way AppState::audio_cache is
+        let cache = Arc::new(Mutex::new(AudioCache::with_dir(dir.clone()).unwrap()));
+        let (url, requests) = serve(b"audio bytes").await;
+
+        let downloads: Vec<_> = (0..2)
+            .map(|_| {
+                let cache = cache.clone();
+                let url = url.clone();
+                tokio::spawn(async move {
+                    AudioCache::cache_audio(&cache, "song", &url, |_, _| {}).await.map_err(|e| e.to_string())
+                })
+            })
+            .collect();
+        let mut paths = Vec::new();
+        for download in downloads {
+            paths.push(download.await.unwrap().unwrap());
+        }
+
+        assert_eq!(paths[0], paths[1]);
+        assert_eq!(fs::read(&paths[0]).unwrap(), b"audio bytes");
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn a_slow_download_holds_up_neither_other_songs_nor_lookups() {
+        let dir = test_dir("not-blocked");
+        let cache = Arc::new(Mutex::new(AudioCache::with_dir(dir.clone()).unwrap()));
+        let (slow_url, _) = serve_after(b"slow audio", 10, Duration::from_secs(2)).await;
+        let (fast_url, _) = serve_after(b"fast audio", 10, Duration::ZERO).await;
+
+        let slow = {
+            let cache = cache.clone();
+            tokio::spawn(async move {
+                AudioCache::cache_audio(&cache, "slow", &slow_url, |_, _| {}).await.map_err(|e| e.to_string())
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let lookup = tokio::time::timeout(Duration::from_millis(500), async {
+            cache.lock().await.get_cached_path("slow")
+        }).await;
+        assert_eq!(lookup.expect("lookup waited for the download"), None);
+
+        let fast = tokio::time::timeout(
+            Duration::from_secs(1),
+            AudioCache::cache_audio(&cache, "fast", &fast_url, |_, _| {}),
+        ).await;
+        let fast_path = fast.expect("download waited for another song").unwrap();
+        assert_eq!(fs::read(fast_path).unwrap(), b"fast audio");
+        assert!(!slow.is_finished());
+
+        let slow_path = slow.await.unwrap().unwrap();
+        assert_eq!(fs::read(slow_path).unwrap(), b"slow audio");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    // Cache holding 100-byte songs stored in the given order, oldest first
+    async fn cache_with_songs(dir: &Path, max_entries: usize, max_bytes: u64, song_ids: &[&str]) -> AudioCache {
+        let mut cache = AudioCache::with_dir(dir.to_path_buf()).unwrap();
+        cache.set_cache_limits(max_entries, max_bytes);
+        for song_id in song_ids {
+            cache.store_audio(song_id, &[0u8; 100]).await.unwrap();
+        }
+        cache
+    }
+
+    #[tokio::test]
+    async fn byte_budget_evicts_the_oldest_songs() {
+        let dir = test_dir("byte-budget");
+        let mut cache = cache_with_songs(&dir, 100, 250, &["a", "b", "c", "d"]).await;
+
+        assert_eq!(cache.take_evicted(), vec!["a", "b"]);
+        assert!(cache.get_cached_path("a").is_none());
+        assert!(cache.get_cached_path("d").is_some());
+        assert_eq!(cache.usage().total_bytes, 200);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn lowering_the_limits_evicts_down_to_both() {
+        let dir = test_dir("lower-limits");
+        let mut cache = cache_with_songs(&dir, 100, 1000, &["a", "b", "c", "d"]).await;
+        assert!(cache.take_evicted().is_empty());
+
+        cache.set_cache_limits(3, 1000);
+        assert_eq!(cache.take_evicted(), vec!["a"]);
+        cache.set_cache_limits(3, 150);
+        assert_eq!(cache.take_evicted(), vec!["b", "c"]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn pinned_songs_are_never_evicted_for_space() {
+        let dir = test_dir("pinned-budget");
+        let mut cache = cache_with_songs(&dir, 100, 1000, &["a", "b", "c"]).await;
+        cache.pin("a");
+
+        cache.set_cache_limits(100, 150);
+        assert_eq!(cache.take_evicted(), vec!["b", "c"]);
+        assert!(cache.get_cached_path("a").is_some());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn the_budget_is_enforced_while_a_download_is_running() {
+        let dir = test_dir("budget-during-download");
+        let cache = Arc::new(Mutex::new(cache_with_songs(&dir, 100, 1000, &["a", "b"]).await));
+        let (url, _) = serve_after(b"new audio", 9, Duration::from_secs(1)).await;
+
+        let download = {
+            let cache = cache.clone();
+            tokio::spawn(async move {
+                AudioCache::cache_audio(&cache, "new", &url, |_, _| {}).await.map_err(|e| e.to_string())
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // What the cache enforcer does every tick
+        let evicted = tokio::time::timeout(Duration::from_millis(500), async {
+            let mut cache = cache.lock().await;
+            cache.set_cache_limits(100, 150);
+            cache.enforce_budget();
+            cache.take_evicted()
+        }).await;
+        assert_eq!(evicted.expect("enforcer waited for the download"), vec!["a"]);
+        assert!(!download.is_finished());
+
+        // The song being written is never the one evicted
+        download.await.unwrap().unwrap();
+        let mut cache = cache.lock().await;
+        assert!(cache.get_cached_path("new").is_some());
+        assert_eq!(cache.usage().total_bytes, 109);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn only_the_caches_own_files_are_evicted_by_path() {
+        let dir = test_dir("evict-file");
+        let mut cache = cache_with_songs(&dir, 100, 1000, &["song"]).await;
+        let download = dir.join("downloads").join("song.audio");
+        fs::create_dir_all(download.parent().unwrap()).unwrap();
+        fs::write(&download, b"audio bytes").unwrap();
+
+        assert!(!cache.evict_file(&download));
+        assert!(download.exists());
+        assert!(cache.take_evicted().is_empty());
+
+        let cached = cache.get_cached_path("song").unwrap();
+        assert!(cache.evict_file(&cached));
+        assert!(!cached.exists());
+        assert_eq!(cache.take_evicted(), vec!["song"]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn truncated_downloads_are_not_cached() {
+        let dir = test_dir("truncated");
+        let cache = Mutex::new(AudioCache::with_dir(dir.clone()).unwrap());
+        // The connection closes 90 bytes short of the announced length
+        let (url, _) = serve_with_length(&[0u8; 10], 100).await;
+
+        assert!(AudioCache::cache_audio(&cache, "song", &url, |_, _| {}).await.is_err());
+        assert!(cache.lock().await.get_cached_path("song").is_none());
+        assert!(!dir.join("song.audio").exists());
+        assert!(!dir.join("song.audio.part").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn an_unusable_directory_disables_the_cache_instead_of_failing() {
+        let dir = test_dir("unusable");
+        fs::create_dir_all(&dir).unwrap();
+        // A directory can't be created under a regular file, whoever runs the test
+        fs::write(dir.join("file"), b"").unwrap();
+        let mut cache = AudioCache::with_dir_or_disabled(dir.join("file").join("cache"));
+
+        let error = cache.usage().error.expect("the cache should say why it's disabled");
+        let stored = cache.store_audio("song", b"audio bytes").await.unwrap_err().to_string();
+        assert!(stored.contains(&error), "{}", stored);
+
+        cache.set_cache_dir(dir.join("cache")).unwrap();
+        assert_eq!(cache.usage().error, None);
+        assert!(cache.store_audio("song", b"audio bytes").await.is_ok());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn empty_files_are_skipped_when_loading_the_cache() {
+        let dir = test_dir("empty-files");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("empty.audio"), b"").unwrap();
+        fs::write(dir.join("full.audio"), b"audio bytes").unwrap();
+
+        let mut cache = AudioCache::with_dir(dir.clone()).unwrap();
+        assert!(cache.get_cached_path("empty").is_none());
+        assert!(cache.get_cached_path("full").is_some());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
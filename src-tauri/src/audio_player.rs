@@ -1,16 +1,24 @@
 use rodio::{OutputStream, OutputStreamHandle, Sink, Source};
+use rodio::cpal::traits::HostTrait;
+use rodio::DeviceTrait;
 use serde::{Deserialize, Serialize};
-use std::collections::VecDeque;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
 use std::time::{Duration, Instant};
 use tokio::sync::{broadcast, mpsc, oneshot};
-use symphonia::core::io::MediaSourceStream;
+use symphonia::core::io::{MediaSource, MediaSourceStream};
 use symphonia::core::probe::Hint;
 use symphonia::core::formats::FormatOptions;
 use symphonia::core::meta::MetadataOptions;
 use symphonia::core::audio::SampleBuffer;
 use symphonia::core::units::{Time, TimeBase};
 use symphonia::core::formats::{SeekMode, SeekTo};
-use std::io::Cursor;
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use futures_util::StreamExt;
+use crate::equalizer::{Equalizer, EQ_BAND_COUNT, MAX_BAND_GAIN_DB};
+use crate::jellyfin::{self, MusicItem, StreamingProfile, UserData};
+use log::{debug, error, info, warn};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PlaybackState {
@@ -18,9 +26,27 @@ pub struct PlaybackState {
     pub current_position: f64, // in seconds
     pub duration: f64,         // in seconds
     pub volume: f32,           // 0.0 to 1.0
-    pub is_shuffled: bool,
+    pub balance: f32,          // -1.0 (left) to 1.0 (right)
+    pub gain: f32,             // 1.0 to MAX_GAIN, applied after volume
+    pub shuffle_mode: ShuffleMode,
     pub repeat_mode: RepeatMode,
+    pub transition_mode: TransitionMode,
     pub current_song: Option<QueueItem>,
+    pub is_transcoding: bool,
+    pub transcode_reason: Option<String>,
+    // Times playback stalled waiting on the network
+    pub buffering_underruns: u32,
+    pub trim_silence: bool,
+    pub end_of_queue_fade: f32, // seconds, 0 for an abrupt stop
+    pub crossfade_seconds: f32, // overlap between crossfaded tracks, 0 when tracks never overlap
+    pub normalization_enabled: bool,
+    pub sleep_timer_remaining_secs: Option<u32>,
+    // Speed changes pitch along with tempo (no time-stretching), so it only applies while enabled
+    pub playback_speed: f32,
+    pub playback_speed_enabled: bool,
+    pub output_device: Option<String>, // None plays through the system default
+    pub equalizer_enabled: bool,
+    pub equalizer_bands: Vec<f32>, // dB per band, lowest frequency first
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -30,6 +56,58 @@ pub enum RepeatMode {
     All,
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum ShuffleMode {
+    #[default]
+    Off,
+    Tracks,
+    Albums, // albums in random order, each played through in disc and track order
+}
+
+// Read a saved shuffle mode, accepting the is_shuffled flag saved by older versions
+pub fn deserialize_shuffle_mode<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<ShuffleMode, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Saved {
+        Mode(ShuffleMode),
+        Shuffled(bool),
+    }
+
+    Ok(match Saved::deserialize(deserializer)? {
+        Saved::Mode(mode) => mode,
+        Saved::Shuffled(true) => ShuffleMode::Tracks,
+        Saved::Shuffled(false) => ShuffleMode::Off,
+    })
+}
+
+// What the sleep timer does when it runs out
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SleepTimerMode {
+    Pause,
+    Stop,
+}
+
+// How one track hands over to the next
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum TransitionMode {
+    Hard,
+    Gapless,
+    Crossfade(f32), // seconds
+    Smart,          // Gapless within an album, crossfade otherwise
+}
+
+const SMART_CROSSFADE_SECONDS: f32 = 4.0;
+const MAX_CROSSFADE_SECONDS: f32 = 12.0;
+// Shortest fade when a crossfade only gets going right at the end of a track
+const MIN_CROSSFADE_SECONDS: f64 = 0.1;
+const MAX_END_OF_QUEUE_FADE_SECONDS: f32 = 30.0;
+const MIN_PLAYBACK_SPEED: f32 = 0.5;
+const MAX_PLAYBACK_SPEED: f32 = 2.0;
+// The sleep timer fades the volume down over its final stretch
+const SLEEP_FADE_SECONDS: f64 = 20.0;
+// Volume on first run, before the user has picked one
+pub const DEFAULT_VOLUME: f32 = 0.7;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QueueItem {
     pub id: String,
@@ -37,8 +115,131 @@ pub struct QueueItem {
     pub artists: Vec<String>,
     pub artist_ids: Option<Vec<String>>, // Artist IDs for navigation
     pub album: Option<String>,
+    #[serde(default)]
+    pub album_id: Option<String>,
+    #[serde(default)]
+    pub disc_number: Option<i32>,
+    #[serde(default)]
+    pub track_number: Option<i32>,
     pub duration_ticks: Option<i64>,
     pub stream_url: String,
+    #[serde(default)]
+    pub remote_stream_url: Option<String>, // Original server URL when stream_url points at a cached file
+    #[serde(default)]
+    pub original_stream_url: Option<String>, // Untranscoded stream, for when a transcode doesn't decode
+    #[serde(default)]
+    pub is_transcoding: bool,
+    #[serde(default)]
+    pub transcode_reason: Option<String>,
+    #[serde(default)]
+    pub chapters: Vec<Chapter>,
+    #[serde(default)]
+    pub is_favorite: bool,
+    #[serde(default)]
+    pub user_data: Option<UserData>,
+    #[serde(default)]
+    pub normalization_gain: Option<f32>, // dB
+    #[serde(default)]
+    pub blurhash: Option<String>, // placeholder for the primary image while it loads
+}
+
+// Everything needed to bring the queue back after a restart
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueSnapshot {
+    pub items: Vec<QueueItem>,
+    pub current_index: Option<usize>,
+    pub current_position: f64, // in seconds
+    pub repeat_mode: RepeatMode,
+    #[serde(default, alias = "is_shuffled", deserialize_with = "deserialize_shuffle_mode")]
+    pub shuffle_mode: ShuffleMode,
+}
+
+// A track the player moved away from, with how much of it was heard
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub item_id: String,
+    pub name: String,
+    pub artist: Option<String>,
+    pub played_at: u64, // unix seconds when playback started
+    pub completion: f64, // 0.0..=1.0
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Chapter {
+    pub name: Option<String>,
+    pub start: f64, // in seconds
+}
+
+// Samples quieter than this (about -60 dBFS) count as silence at gapless joins
+const SILENCE_THRESHOLD: f32 = 0.001;
+// Never trim more than this from either end, so quiet intros and fade-outs survive
+const MAX_SILENCE_TRIM_SECONDS: f64 = 2.0;
+// Audio kept either side of the detected edge so attacks and decays aren't clipped
+const SILENCE_TRIM_MARGIN_SECONDS: f64 = 0.02;
+
+// A seek landing this close to the end of a track counts as finishing it
+const SEEK_END_EPSILON_SECONDS: f64 = 0.5;
+
+// Going to the previous chapter this far into the current one restarts it instead
+const CHAPTER_RESTART_THRESHOLD_SECONDS: f64 = 3.0;
+
+impl QueueItem {
+    pub fn from_music_item(item: &MusicItem, stream_url: String) -> Self {
+        // Fall back to the album artist when the track has no artists of its own
+        let artists = match item.artists.as_ref() {
+            Some(artists) if !artists.is_empty() => artists.clone(),
+            _ => match item.album_artist.as_ref() {
+                Some(album_artist) => vec![album_artist.clone()],
+                None => vec!["Unknown Artist".to_string()],
+            },
+        };
+
+        let artist_ids = item.artist_items
+            .as_ref()
+            .map(|artist_items| artist_items.iter().map(|artist| artist.id.clone()).collect());
+
+        Self {
+            id: item.id.clone(),
+            name: item.name.clone(),
+            artists,
+            artist_ids,
+            album: item.album.clone(),
+            album_id: item.album_id.clone(),
+            disc_number: item.disc_number,
+            track_number: item.track_number,
+            duration_ticks: item.runtime_ticks,
+            stream_url,
+            remote_stream_url: None,
+            original_stream_url: None,
+            is_transcoding: false,
+            transcode_reason: None,
+            chapters: item.chapters
+                .as_ref()
+                .map(|chapters| chapters.iter().map(|chapter| Chapter {
+                    name: chapter.name.clone(),
+                    start: chapter.start_position_ticks as f64 / 10_000_000.0,
+                }).collect())
+                .unwrap_or_default(),
+            is_favorite: item.user_data.as_ref().map(|user_data| user_data.is_favorite).unwrap_or(false),
+            user_data: item.user_data.clone(),
+            normalization_gain: item.normalization_gain,
+            blurhash: item.primary_blurhash().map(str::to_string),
+        }
+    }
+
+    // Record whether the stream URL was built from a transcoding profile
+    pub fn apply_streaming_profile(&mut self, profile: Option<&StreamingProfile>) {
+        match profile {
+            Some(profile) if !profile.direct_play && jellyfin::is_transcoding_url(&self.stream_url) => {
+                self.is_transcoding = true;
+                self.transcode_reason = profile.transcode_reason.clone();
+            }
+            _ => {
+                self.is_transcoding = false;
+                self.transcode_reason = None;
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -46,20 +247,65 @@ pub enum PlayerEvent {
     StateChanged(PlaybackState),
     TrackChanged(Option<QueueItem>),
     PositionUpdate(f64),
+    AudioCacheRefreshed(String),
+    Clipping(f32),
+    Buffering(bool),
+    Reconnecting(bool),
+    SlowConnection(String),
     Error(String),
+    // A song's audio was downloaded in full from url while playing it, for the cache to keep
+    AudioDownloaded { item_id: String, url: String, data: AudioData },
+    // A local file a song played from doesn't decode; the cache drops it if the file is its own
+    LocalFileUnusable { item_id: String, path: String },
+}
+
+enum InstantSeekError {
+    // The in-memory audio data can no longer be decoded
+    CorruptData(String),
+    Failed(String),
 }
 
 #[derive(Debug)]
 pub enum PlayerCommand {
-    PlayItem { item: QueueItem, response: oneshot::Sender<Result<(), String>> },
+    PlayItem { item: Box<QueueItem>, position: f64, response: oneshot::Sender<Result<(), String>> },
+    SetQueue { items: Vec<QueueItem>, start_index: usize, response: oneshot::Sender<Result<(), String>> },
+    MoveInQueue { from: usize, to: usize, response: oneshot::Sender<Result<(), String>> },
+    AppendToQueue(Vec<QueueItem>),
+    ApplyResolvedUrls(HashMap<String, String>),
+    GetQueue { response: oneshot::Sender<Vec<QueueItem>> },
+    GetQueueSnapshot { response: oneshot::Sender<QueueSnapshot> },
+    RestoreQueue { snapshot: Box<QueueSnapshot>, response: oneshot::Sender<Result<(), String>> },
     Pause,
     Resume,
     Stop,
     SetVolume(f32),
+    SetBalance(f32),
+    SetGain(f32),
+    SetEqualizer(Vec<f32>),
+    SetEqualizerEnabled(bool),
     Seek(f64),
+    SeekPercent(f64),
+    SeekWithinChapter(f64),
+    NextChapter,
+    PreviousChapter,
     ToggleShuffle,
+    SetShuffleMode(ShuffleMode),
     SetRepeatMode(RepeatMode),
+    SetTransitionMode(TransitionMode),
+    SetWarmWindow(usize),
+    SetTrimSilence(bool),
+    SetNormalizationEnabled(bool),
+    SetEndOfQueueFade(f32),
+    SetSleepTimer { minutes: Option<u32>, mode: SleepTimerMode, fade: bool },
+    SetPlaybackSpeed { speed: f32, enabled: bool },
+    SetOutputDevice { name: Option<String>, response: oneshot::Sender<Result<(), String>> },
+    SetCustomHeaders(HashMap<String, String>),
+    SetFavorite { item_id: String, is_favorite: bool },
     GetState { response: oneshot::Sender<PlaybackState> },
+    GetAudioInfo { response: oneshot::Sender<Option<AudioInfo>> },
+    GetBufferDiagnostics { response: oneshot::Sender<Option<BufferDiagnostics>> },
+    RetryPlayback { response: oneshot::Sender<Result<(), String>> },
+    GetHistory { response: oneshot::Sender<Vec<HistoryEntry>> },
     NextTrack,
     PreviousTrack,
     Shutdown,
@@ -72,8 +318,7 @@ pub struct AudioPlayer {
 }
 
 struct AudioPlayerWorker {
-    _stream: OutputStream,
-    stream_handle: OutputStreamHandle,
+    output: Option<(OutputStream, OutputStreamHandle)>, // the stream has to be kept alive for its handle
     sink: Option<Sink>,
     symphonia_source: Option<SymphoniaSource>, // Store for seeking
     state: PlaybackState,
@@ -82,12 +327,248 @@ struct AudioPlayerWorker {
     command_receiver: mpsc::UnboundedReceiver<PlayerCommand>,
     event_sender: broadcast::Sender<PlayerEvent>,
     last_position_update: Instant,
-    // Track actual audio playback time vs visual position
+    // Position at the last anchor (start, seek, resume or gapless handover), advanced by what's
+    // been played since: samples the sink pulled from the source, or the clock without one
     audio_start_time: Option<Instant>,
     visual_position: f64,
+    played_at_anchor: f64, // playing_stats' played seconds at the anchor
     // Cache audio data to avoid re-downloading on seek
-    cached_audio_data: Option<Vec<u8>>,
+    cached_audio_data: Option<AudioData>,
     cached_song_id: Option<String>,
+    processing: Arc<ProcessingSettings>,
+    // Stats of the source currently inside the sink (which the sink owns)
+    playing_stats: Option<Arc<BufferStats>>,
+    // Used for all audio downloads so proxy headers ride along
+    http_client: reqwest::Client,
+    // Whether clipping has already been reported for the current gain
+    clip_warned: bool,
+    // Audio data for queue neighbours, keyed by song id, for instant next/prev
+    warm_audio: HashMap<String, AudioData>,
+    warm_window: usize,
+    warming: HashSet<String>,
+    warm_sender: mpsc::UnboundedSender<(String, Result<AudioData, String>)>,
+    warm_receiver: mpsc::UnboundedReceiver<(String, Result<AudioData, String>)>,
+    // Track whose stream failed to load, held so playback can resume where it was
+    pending_recovery: Option<PendingRecovery>,
+    // Silence at the end of the current track that's cut when handing over gaplessly
+    trailing_silence: f64,
+    // Most recent plays, oldest first, capped at HISTORY_CAPACITY
+    history: VecDeque<HistoryEntry>,
+    current_started_at: u64,
+    // Volume ramp while the last track of the queue fades out
+    end_fade: Option<EndFade>,
+    // Set once playback runs off the end of the current track, until the tick advances
+    track_ended: bool,
+    // Queue in insertion order while shuffled, restored when shuffle is turned off
+    unshuffled_queue: Option<VecDeque<QueueItem>>,
+    // Song playing progressively while its bytes are still downloading
+    active_stream: Option<ActiveStream>,
+    // Next track already appended to the sink, waiting for the current one to run out
+    gapless_next: Option<PreparedTrack>,
+    // Outgoing track still fading out during a crossfade
+    fading_sink: Option<Sink>,
+    sleep_timer: Option<SleepTimer>,
+    // Fires when the running sleep timer's task wakes at its deadline
+    sleep_timer_sender: mpsc::UnboundedSender<()>,
+    sleep_timer_receiver: mpsc::UnboundedReceiver<()>,
+    // Last time the chosen output device was looked for, to notice it being unplugged
+    last_device_check: Instant,
+    // Set while a lookup runs on the blocking pool; device enumeration can take a while
+    device_check_pending: bool,
+    device_check_sender: mpsc::UnboundedSender<(String, bool)>,
+    device_check_receiver: mpsc::UnboundedReceiver<(String, bool)>,
+    // Trailing silence measured on the blocking pool, keyed by the song it was measured for
+    silence_scan_sender: mpsc::UnboundedSender<(String, f64)>,
+    silence_scan_receiver: mpsc::UnboundedReceiver<(String, f64)>,
+}
+
+struct SleepTimer {
+    deadline: Instant,
+    mode: SleepTimerMode,
+    fade: bool, // ramp the volume down over the final SLEEP_FADE_SECONDS
+    task: tokio::task::JoinHandle<()>, // sleeps until the deadline, aborted on cancel
+}
+
+// Next track decoded ahead of a gapless or crossfaded transition
+// A whole song's bytes, shared rather than copied between the player's buffers and decoders
+type AudioData = Arc<[u8]>;
+
+struct PreparedTrack {
+    index: usize,
+    item: QueueItem,
+    start: f64, // where its source starts, past any trimmed leading silence
+    data: AudioData,
+    stats: Arc<BufferStats>,
+}
+
+struct ActiveStream {
+    song_id: String,
+    url: String,
+    buffer: Arc<StreamBuffer>,
+}
+
+// Track positions (seconds) between which the sink ramps from full volume to silence
+#[derive(Clone, Copy)]
+struct EndFade {
+    start: f64,
+    end: f64,
+}
+
+#[derive(Clone)]
+struct PendingRecovery {
+    item: QueueItem,
+    position: f64,
+    attempts: u32,
+    retry_at: Option<Instant>, // None once automatic retries are used up
+}
+
+// A network load that blocks playback longer than this counts as an underrun
+const UNDERRUN_THRESHOLD: Duration = Duration::from_secs(2);
+// Underruns after which we suggest a lower bitrate
+const UNDERRUN_SUGGESTION_COUNT: u32 = 3;
+
+// Delay between automatic attempts to reload a stream that failed mid-session
+const RECOVERY_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+// Automatic attempts before giving up; retry_playback still works afterwards
+const MAX_RECOVERY_ATTEMPTS: u32 = 6;
+
+// Plays kept in the in-memory history
+const HISTORY_CAPACITY: usize = 500;
+
+// How long before the end of a track the next one is appended for a gapless handover
+const GAPLESS_PREPARE_SECONDS: f64 = 5.0;
+
+// How often a wait for the rest of a progressive download checks on it
+const STREAM_POLL_INTERVAL: Duration = Duration::from_millis(50);
+// Longest a decoder read waits on a stalled download before failing, so nothing blocks forever
+const STREAM_READ_TIMEOUT: Duration = Duration::from_secs(30);
+// Slack when guessing whether the bytes for a seek target have arrived yet
+const STREAM_SEEK_MARGIN_BYTES: u64 = 256 * 1024;
+
+// How often a chosen output device is checked for still being connected
+const OUTPUT_DEVICE_CHECK_INTERVAL: Duration = Duration::from_secs(3);
+
+// Number of queue neighbours on each side kept warm by default
+const DEFAULT_WARM_WINDOW: usize = 1;
+
+// Bytes of a song still arriving over HTTP, shared by the download task and the decoders reading them
+struct StreamBuffer {
+    state: std::sync::Mutex<StreamBufferState>,
+    grew: std::sync::Condvar,
+    total_len: Option<u64>, // from Content-Length
+}
+
+#[derive(Default)]
+struct StreamBufferState {
+    data: Vec<u8>,
+    complete: bool,
+    error: Option<String>,
+    abandoned: bool, // nobody will play it anymore, so readers get EOF and the download stops
+}
+
+impl StreamBuffer {
+    fn new(total_len: Option<u64>) -> Self {
+        Self {
+            state: std::sync::Mutex::new(StreamBufferState::default()),
+            grew: std::sync::Condvar::new(),
+            total_len,
+        }
+    }
+
+    // Change the state and wake any reader waiting for more bytes
+    fn update(&self, change: impl FnOnce(&mut StreamBufferState)) {
+        if let Ok(mut state) = self.state.lock() {
+            change(&mut state);
+        }
+        self.grew.notify_all();
+    }
+
+    fn downloaded(&self) -> u64 {
+        self.state.lock().map(|state| state.data.len() as u64).unwrap_or(0)
+    }
+
+    fn is_abandoned(&self) -> bool {
+        self.state.lock().map(|state| state.abandoned).unwrap_or(true)
+    }
+
+    fn abandon(&self) {
+        self.update(|state| state.abandoned = true);
+    }
+
+    // None while bytes are still arriving, then the full data or why the download failed
+    fn outcome(&self) -> Option<Result<Vec<u8>, String>> {
+        let state = self.state.lock().ok()?;
+        if let Some(error) = &state.error {
+            return Some(Err(error.clone()));
+        }
+        state.complete.then(|| Ok(state.data.clone()))
+    }
+}
+
+// Read + Seek view of a StreamBuffer for symphonia. Reads past the downloaded bytes block until
+// they arrive, so a stalled connection stalls the decoder rather than ending the track
+struct StreamingReader {
+    buffer: Arc<StreamBuffer>,
+    position: u64,
+}
+
+impl StreamingReader {
+    fn new(buffer: Arc<StreamBuffer>) -> Self {
+        Self { buffer, position: 0 }
+    }
+}
+
+impl Read for StreamingReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let poisoned = || std::io::Error::other("Audio stream buffer poisoned");
+        let mut state = self.buffer.state.lock().map_err(|_| poisoned())?;
+        loop {
+            if state.abandoned {
+                return Ok(0);
+            }
+            if let Some(available) = state.data.get(self.position as usize..).filter(|rest| !rest.is_empty()) {
+                let count = buf.len().min(available.len());
+                buf[..count].copy_from_slice(&available[..count]);
+                self.position += count as u64;
+                return Ok(count);
+            }
+            if let Some(error) = &state.error {
+                return Err(std::io::Error::other(error.clone()));
+            }
+            if state.complete {
+                return Ok(0);
+            }
+            let (next, wait) = self.buffer.grew.wait_timeout(state, STREAM_READ_TIMEOUT).map_err(|_| poisoned())?;
+            if wait.timed_out() {
+                return Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "Audio stream stalled"));
+            }
+            state = next;
+        }
+    }
+}
+
+impl Seek for StreamingReader {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(delta) => self.position.checked_add_signed(delta),
+            SeekFrom::End(delta) => self.buffer.total_len.and_then(|len| len.checked_add_signed(delta)),
+        };
+        self.position = position.ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "Invalid seek in audio stream")
+        })?;
+        Ok(self.position)
+    }
+}
+
+impl MediaSource for StreamingReader {
+    fn is_seekable(&self) -> bool {
+        true
+    }
+
+    fn byte_len(&self) -> Option<u64> {
+        self.buffer.total_len
+    }
 }
 
 // Custom symphonia-based audio source for instant seeking
@@ -100,13 +581,129 @@ struct SymphoniaSource {
     sample_rate: u32,
     channels: u16,
     total_duration: Option<Duration>,
+    time_base: TimeBase,
+    codec_name: String,
+    // VBR MP3s without a frame count/seek index only support approximate seeking
+    can_seek_accurately: bool,
+    stats: Arc<BufferStats>,
+    fade_out: Option<(u64, u64)>, // (total, remaining) samples of a crossfade fade-out
+}
+
+// Counters kept by a SymphoniaSource so its buffer can be inspected after the sink takes ownership
+#[derive(Default)]
+struct BufferStats {
+    queued_samples: AtomicUsize,
+    samples_decoded: AtomicU64,
+    end_of_stream: AtomicBool,
+    cancelled: AtomicBool, // makes the source end early, e.g. a gapless next track that's no longer next
+    fade_out_samples: AtomicU64, // set to start fading out over that many samples, then end
+    // Samples the sink has pulled from the source, which is what has actually been played
+    samples_played: AtomicU64,
+    samples_per_second: AtomicU64, // sample rate times channels
+    last_error: std::sync::Mutex<Option<String>>,
+}
+
+impl BufferStats {
+    // Track time played through this source; pauses and stalls don't count since the sink stops pulling
+    fn played_seconds(&self) -> f64 {
+        let samples_per_second = self.samples_per_second.load(Ordering::Relaxed);
+        if samples_per_second == 0 {
+            return 0.0;
+        }
+        self.samples_played.load(Ordering::Relaxed) as f64 / samples_per_second as f64
+    }
+
+    fn record_error(&self, error: String) {
+        if let Ok(mut last_error) = self.last_error.lock() {
+            *last_error = Some(error);
+        }
+    }
+
+    fn snapshot(&self) -> BufferDiagnostics {
+        BufferDiagnostics {
+            queued_samples: self.queued_samples.load(Ordering::Relaxed),
+            samples_decoded: self.samples_decoded.load(Ordering::Relaxed),
+            last_decode_error: self.last_error.lock().ok().and_then(|error| error.clone()),
+            end_of_stream: self.end_of_stream.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BufferDiagnostics {
+    pub queued_samples: usize,
+    pub samples_decoded: u64,
+    pub last_decode_error: Option<String>,
+    pub end_of_stream: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AudioInfo {
+    pub codec: String,
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub can_seek_accurately: bool,
 }
 
 impl SymphoniaSource {
-    fn from_data(audio_data: Vec<u8>) -> Result<Self, String> {
+    fn samples_per_second(&self) -> f64 {
+        self.sample_rate as f64 * self.channels as f64
+    }
+
+    // Seconds of silence before the first audible sample; 0 if the whole window is quiet
+    fn leading_silence(&mut self) -> f64 {
+        let samples_per_second = self.samples_per_second();
+        let window = (MAX_SILENCE_TRIM_SECONDS * samples_per_second) as usize;
+        match self.by_ref().take(window).position(|sample| sample.abs() > SILENCE_THRESHOLD) {
+            Some(index) => (index as f64 / samples_per_second - SILENCE_TRIM_MARGIN_SECONDS).max(0.0),
+            None => 0.0,
+        }
+    }
+
+    // Skip the leading silence, measured on this source so the track is only decoded once
+    fn skip_leading_silence(&mut self) -> Result<f64, String> {
+        let silence = self.leading_silence();
+        // Measuring read from the start; none of that has been played
+        self.stats.samples_played.store(0, Ordering::Relaxed);
+        self.seek_exactly(silence)
+    }
+
+    // Seconds of silence after the last audible sample; 0 if the whole window is quiet
+    fn trailing_silence(&mut self) -> f64 {
+        let Some(total) = self.total_duration.map(|duration| duration.as_secs_f64()) else {
+            return 0.0;
+        };
+        if total <= MAX_SILENCE_TRIM_SECONDS * 2.0 || self.seek_to_time(total - MAX_SILENCE_TRIM_SECONDS).is_err() {
+            return 0.0;
+        }
+
+        let samples_per_second = self.samples_per_second();
+        let mut last_audible = None;
+        let mut sample_count = 0;
+        for (index, sample) in self.by_ref().enumerate() {
+            if sample.abs() > SILENCE_THRESHOLD {
+                last_audible = Some(index);
+            }
+            sample_count = index + 1;
+        }
+
+        match last_audible {
+            Some(index) => ((sample_count - index - 1) as f64 / samples_per_second - SILENCE_TRIM_MARGIN_SECONDS).max(0.0),
+            None => 0.0,
+        }
+    }
+
+    fn from_data(audio_data: AudioData) -> Result<Self, String> {
         // Create media source from audio data
-        let cursor = Cursor::new(audio_data);
-        let media_source = Box::new(cursor);
+        Self::from_media_source(Box::new(Cursor::new(audio_data)))
+    }
+    
+    // Decode a song while it downloads; probing blocks until the header bytes are in
+    fn from_stream(buffer: Arc<StreamBuffer>) -> Result<Self, String> {
+        Self::from_media_source(Box::new(StreamingReader::new(buffer)))
+    }
+    
+    fn from_media_source(media_source: Box<dyn MediaSource>) -> Result<Self, String> {
         let media_source_stream = MediaSourceStream::new(media_source, Default::default());
         
         // Create probe and format options
@@ -146,6 +743,14 @@ impl SymphoniaSource {
         let total_duration = track.codec_params.n_frames
             .map(|frames| Duration::from_secs_f64(frames as f64 / sample_rate as f64));
         
+        let time_base = track.codec_params.time_base.unwrap_or_else(|| TimeBase::new(1, sample_rate));
+        let codec_name = symphonia::default::get_codecs()
+            .get_codec(track.codec_params.codec)
+            .map(|descriptor| descriptor.short_name.to_string())
+            .unwrap_or_else(|| "unknown".to_string());
+        let can_seek_accurately = track.codec_params.codec != symphonia::core::codecs::CODEC_TYPE_MP3
+            || track.codec_params.n_frames.is_some();
+        
         Ok(Self {
             format_reader,
             decoder,
@@ -155,31 +760,82 @@ impl SymphoniaSource {
             sample_rate,
             channels,
             total_duration,
+            time_base,
+            codec_name,
+            can_seek_accurately,
+            stats: Arc::new(BufferStats {
+                samples_per_second: AtomicU64::new(sample_rate as u64 * channels as u64),
+                ..BufferStats::default()
+            }),
+            fade_out: None,
         })
     }
     
-    // INSTANT SEEK! 🚀
-    fn seek_to_time(&mut self, time_seconds: f64) -> Result<(), String> {
+    // Chapter markers embedded in the container (e.g. cue sheets)
+    fn chapters(&self) -> Vec<Chapter> {
+        use symphonia::core::meta::StandardTagKey;
+        
+        self.format_reader
+            .cues()
+            .iter()
+            .map(|cue| {
+                let time = self.time_base.calc_time(cue.start_ts);
+                let name = cue.tags
+                    .iter()
+                    .find(|tag| tag.std_key == Some(StandardTagKey::TrackTitle))
+                    .map(|tag| tag.value.to_string());
+                Chapter {
+                    name,
+                    start: time.seconds as f64 + time.frac,
+                }
+            })
+            .collect()
+    }
+    
+    fn audio_info(&self) -> AudioInfo {
+        AudioInfo {
+            codec: self.codec_name.clone(),
+            sample_rate: self.sample_rate,
+            channels: self.channels,
+            can_seek_accurately: self.can_seek_accurately,
+        }
+    }
+    
+    // INSTANT SEEK! 🚀 Returns the position actually reached, which may differ from the
+    // requested one on formats without an accurate seek index
+    fn seek_to_time(&mut self, time_seconds: f64) -> Result<f64, String> {
         if time_seconds <= 0.0 {
-            return Ok(());
+            return Ok(0.0);
         }
+        self.seek_exactly(time_seconds)
+    }
+
+    // Seek even to the very start, for a source that has already been read from
+    fn seek_exactly(&mut self, time_seconds: f64) -> Result<f64, String> {
+        // Never ask the demuxer for a timestamp past the end of the stream
+        let time_seconds = match self.total_duration {
+            Some(total) => time_seconds.min(total.as_secs_f64()),
+            None => time_seconds,
+        };
         
-        println!("🚀 INSTANT SEEK to {}s using native symphonia seeking!", time_seconds);
+        info!("INSTANT SEEK to {}s using native symphonia seeking!", time_seconds);
         
         // Convert to symphonia time units
-        let time_base = TimeBase::new(1, self.sample_rate);
-        let timestamp = time_base.calc_timestamp(Time::from(time_seconds));
+        let timestamp = self.time_base.calc_timestamp(Time::from(time_seconds));
         
         // Perform native seek - THIS IS INSTANT! 🚀
-        self.format_reader
+        let seeked_to = self.format_reader
             .seek(SeekMode::Accurate, SeekTo::TimeStamp { ts: timestamp, track_id: self.track_id })
             .map_err(|e| format!("Seek failed: {}", e))?;
         
         // Clear any buffered samples
         self.sample_queue.clear();
         
-        println!("🚀 INSTANT SEEK COMPLETE! No sample iteration needed!");
-        Ok(())
+        let actual_time = self.time_base.calc_time(seeked_to.actual_ts);
+        let actual_seconds = actual_time.seconds as f64 + actual_time.frac;
+        
+        info!("INSTANT SEEK COMPLETE! Landed at {:.3}s (requested {:.3}s)", actual_seconds, time_seconds);
+        Ok(actual_seconds)
     }
     
     fn fill_sample_buffer(&mut self) -> Result<(), String> {
@@ -206,27 +862,220 @@ impl SymphoniaSource {
                             if let Some(ref mut buf) = self.sample_buffer {
                                 buf.copy_interleaved_ref(audio_buf);
                                 self.sample_queue.extend(buf.samples().iter().cloned());
+                                self.stats.samples_decoded.fetch_add(buf.samples().len() as u64, Ordering::Relaxed);
                             }
                         }
-                        Err(_) => return Err("Decode error".to_string()),
+                        Err(e) => {
+                            self.stats.record_error(e.to_string());
+                            return Err("Decode error".to_string());
+                        }
                     }
                 }
             }
-            Err(_) => return Err("End of stream".to_string()),
+            Err(e) => {
+                // An unexpected EOF is the normal end of the stream; anything else is worth reporting
+                let is_eof = matches!(&e, symphonia::core::errors::Error::IoError(io) if io.kind() == std::io::ErrorKind::UnexpectedEof);
+                if !is_eof {
+                    self.stats.record_error(e.to_string());
+                }
+                self.stats.end_of_stream.store(true, Ordering::Relaxed);
+                return Err("End of stream".to_string());
+            }
         }
         
+        self.stats.queued_samples.store(self.sample_queue.len(), Ordering::Relaxed);
         Ok(())
     }
 }
 
+// Check that a file actually decodes by probing it and decoding the first packet
+pub fn probe_audio_file(path: &std::path::Path) -> Result<AudioInfo, String> {
+    let data = std::fs::read(path).map_err(|e| format!("Failed to read audio file: {}", e))?;
+    if data.is_empty() {
+        return Err("Audio file is empty".to_string());
+    }
+    
+    let mut source = SymphoniaSource::from_data(data.into())?;
+    // Packets from other tracks (e.g. embedded art) decode to nothing, so allow a few
+    for _ in 0..8 {
+        source.fill_sample_buffer()?;
+        if !source.sample_queue.is_empty() {
+            break;
+        }
+    }
+    if source.sample_queue.is_empty() {
+        return Err("No audio samples decoded".to_string());
+    }
+    
+    Ok(source.audio_info())
+}
+
+// Live processing settings shared between the worker and every playing ProcessingSource,
+// so changes apply immediately and carry across tracks
+struct ProcessingSettings {
+    balance: AtomicU32, // f32 bits
+    gain: AtomicU32,    // f32 bits
+    clipped: AtomicBool,
+    eq_enabled: AtomicBool,
+    eq_gains: std::sync::Mutex<[f32; EQ_BAND_COUNT]>,
+    // Bumped on every equalizer change so sources only read the gains when they move
+    eq_version: AtomicU64,
+}
+
+impl ProcessingSettings {
+    fn new() -> Self {
+        ProcessingSettings {
+            balance: AtomicU32::new(0.0f32.to_bits()),
+            gain: AtomicU32::new(1.0f32.to_bits()),
+            clipped: AtomicBool::new(false),
+            eq_enabled: AtomicBool::new(false),
+            eq_gains: std::sync::Mutex::new([0.0; EQ_BAND_COUNT]),
+            eq_version: AtomicU64::new(0),
+        }
+    }
+    
+    // Filters for the current EQ settings, or None when the equalizer wouldn't change anything
+    fn equalizer(&self, sample_rate: u32, channels: u16) -> Option<Equalizer> {
+        if !self.eq_enabled.load(Ordering::Relaxed) {
+            return None;
+        }
+        let gains = *self.eq_gains.lock().ok()?;
+        let equalizer = Equalizer::new(&gains, sample_rate, channels);
+        (!equalizer.is_flat()).then_some(equalizer)
+    }
+}
+
+// Maximum gain stage boost on top of the 0-1 sink volume
+const MAX_GAIN: f32 = 2.0;
+// Above this level boosted samples are compressed rather than passed through
+const SOFT_CLIP_THRESHOLD: f32 = 0.8;
+
+// Smoothly squash samples above the threshold so they approach, but never exceed, full scale
+fn soft_clip(sample: f32) -> f32 {
+    let magnitude = sample.abs();
+    if magnitude <= SOFT_CLIP_THRESHOLD {
+        return sample;
+    }
+    let headroom = 1.0 - SOFT_CLIP_THRESHOLD;
+    let compressed = SOFT_CLIP_THRESHOLD + headroom * ((magnitude - SOFT_CLIP_THRESHOLD) / headroom).tanh();
+    compressed.copysign(sample)
+}
+
+// Applies the player's equalizer, gain stage and stereo balance to an interleaved f32 stream.
+// Mono sources are left untouched by balance (panned center); channels beyond the first two pass through.
+struct ProcessingSource<S: Source<Item = f32>> {
+    inner: S,
+    settings: Arc<ProcessingSettings>,
+    channel_index: u16,
+    equalizer: Option<Equalizer>,
+    eq_version: Option<u64>, // None until the filters are first built
+}
+
+impl<S: Source<Item = f32>> ProcessingSource<S> {
+    fn new(inner: S, settings: Arc<ProcessingSettings>) -> Self {
+        ProcessingSource {
+            inner,
+            settings,
+            channel_index: 0,
+            equalizer: None,
+            eq_version: None,
+        }
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for ProcessingSource<S> {
+    type Item = f32;
+    
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut sample = self.inner.next()?;
+        let channels = self.inner.channels().max(1);
+        let channel = self.channel_index;
+        self.channel_index = (self.channel_index + 1) % channels;
+        
+        let eq_version = self.settings.eq_version.load(Ordering::Relaxed);
+        if self.eq_version != Some(eq_version) {
+            self.eq_version = Some(eq_version);
+            self.equalizer = self.settings.equalizer(self.inner.sample_rate(), channels);
+        }
+        if let Some(equalizer) = &mut self.equalizer {
+            sample = equalizer.process(sample, channel as usize);
+            // Boosted bands share the gain stage's soft clipper rather than wrapping past full scale
+            if equalizer.is_boosted() {
+                sample = soft_clip(sample);
+            }
+        }
+        
+        let gain = f32::from_bits(self.settings.gain.load(Ordering::Relaxed));
+        if gain != 1.0 {
+            sample *= gain;
+            if gain > 1.0 {
+                if sample.abs() > 1.0 {
+                    self.settings.clipped.store(true, Ordering::Relaxed);
+                }
+                sample = soft_clip(sample);
+            }
+        }
+        
+        if channels < 2 {
+            return Some(sample);
+        }
+        
+        let balance = f32::from_bits(self.settings.balance.load(Ordering::Relaxed));
+        let channel_gain = match channel {
+            0 => 1.0 - balance.max(0.0), // Turning right attenuates left
+            1 => 1.0 + balance.min(0.0), // Turning left attenuates right
+            _ => 1.0,
+        };
+        Some(sample * channel_gain)
+    }
+}
+
+impl<S: Source<Item = f32>> Source for ProcessingSource<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.inner.current_frame_len()
+    }
+    
+    fn channels(&self) -> u16 {
+        self.inner.channels()
+    }
+    
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+    
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+}
+
 // Implement rodio's Source trait for compatibility
 impl Iterator for SymphoniaSource {
     type Item = f32;
     
     fn next(&mut self) -> Option<Self::Item> {
+        if self.stats.cancelled.load(Ordering::Relaxed) {
+            return None;
+        }
+        let requested_fade = self.stats.fade_out_samples.swap(0, Ordering::Relaxed);
+        if requested_fade > 0 {
+            self.fade_out = Some((requested_fade, requested_fade));
+        }
+        
         // Try to fill buffer if empty
         if self.fill_sample_buffer().is_ok() {
-            self.sample_queue.pop_front()
+            let sample = self.sample_queue.pop_front();
+            self.stats.queued_samples.store(self.sample_queue.len(), Ordering::Relaxed);
+            if sample.is_some() {
+                self.stats.samples_played.fetch_add(1, Ordering::Relaxed);
+            }
+            match &mut self.fade_out {
+                Some((_, 0)) => None,
+                Some((total, left)) => {
+                    *left -= 1;
+                    sample.map(|sample| sample * *left as f32 / *total as f32)
+                }
+                None => sample,
+            }
         } else {
             None
         }
@@ -264,35 +1113,12 @@ impl AudioPlayer {
             rt.block_on(async {
                 // Create the audio output stream inside the worker thread
                 match OutputStream::try_default() {
-                    Ok((_stream, stream_handle)) => {
-                        let worker = AudioPlayerWorker {
-                            _stream,
-                            stream_handle,
-                            sink: None,
-                            symphonia_source: None,
-                            state: PlaybackState {
-                                is_playing: false,
-                                current_position: 0.0,
-                                duration: 0.0,
-                                volume: 0.7,
-                                is_shuffled: false,
-                                repeat_mode: RepeatMode::None,
-                                current_song: None,
-                            },
-                            queue: VecDeque::new(),
-                            current_index: None,
-                            command_receiver,
-                            event_sender: event_sender_clone.clone(),
-                            last_position_update: Instant::now(),
-                            audio_start_time: None,
-                            visual_position: 0.0,
-                            cached_audio_data: None,
-                            cached_song_id: None,
-                        };
+                    Ok(output) => {
+                        let worker = AudioPlayerWorker::new(Some(output), command_receiver, event_sender_clone.clone());
                         worker.run().await;
                     }
                     Err(e) => {
-                        eprintln!("Failed to create audio output stream: {}", e);
+                        error!("Failed to create audio output stream: {}", e);
                         let _ = event_sender_clone.send(PlayerEvent::Error(format!("Failed to create audio output stream: {}", e)));
                     }
                 }
@@ -309,15 +1135,75 @@ impl AudioPlayer {
         self.event_sender.subscribe()
     }
 
-    pub async fn play_item(&self, item: QueueItem) -> Result<(), String> {
+    // Play an item starting at a position in seconds, in one step
+    pub async fn play_item_at(&self, item: QueueItem, position: f64) -> Result<(), String> {
         let (response_tx, response_rx) = oneshot::channel();
         self.command_sender
-            .send(PlayerCommand::PlayItem { item, response: response_tx })
+            .send(PlayerCommand::PlayItem { item: Box::new(item), position, response: response_tx })
             .map_err(|_| "Failed to send play command")?;
         
         response_rx.await.map_err(|_| "Failed to receive response".to_string())?
     }
 
+    pub async fn set_queue(&self, items: Vec<QueueItem>, start_index: usize) -> Result<(), String> {
+        let (response_tx, response_rx) = oneshot::channel();
+        self.command_sender
+            .send(PlayerCommand::SetQueue { items, start_index, response: response_tx })
+            .map_err(|_| "Failed to send set queue command")?;
+        
+        response_rx.await.map_err(|_| "Failed to receive response".to_string())?
+    }
+
+    pub async fn move_in_queue(&self, from: usize, to: usize) -> Result<(), String> {
+        let (response_sender, response_receiver) = oneshot::channel();
+        self.command_sender
+            .send(PlayerCommand::MoveInQueue { from, to, response: response_sender })
+            .map_err(|_| "Failed to send move in queue command".to_string())?;
+        
+        response_receiver.await.map_err(|_| "Failed to receive response".to_string())?
+    }
+
+    pub fn append_to_queue(&self, items: Vec<QueueItem>) -> Result<(), String> {
+        self.command_sender
+            .send(PlayerCommand::AppendToQueue(items))
+            .map_err(|_| "Failed to send append to queue command".to_string())
+    }
+
+    // Point queue items at local files resolved in the background, keyed by item id
+    pub fn apply_resolved_urls(&self, urls: HashMap<String, String>) -> Result<(), String> {
+        self.command_sender
+            .send(PlayerCommand::ApplyResolvedUrls(urls))
+            .map_err(|_| "Failed to send resolved URLs command".to_string())
+    }
+
+    pub async fn get_queue(&self) -> Result<Vec<QueueItem>, String> {
+        let (response_sender, response_receiver) = oneshot::channel();
+        self.command_sender
+            .send(PlayerCommand::GetQueue { response: response_sender })
+            .map_err(|_| "Failed to send get queue command".to_string())?;
+        
+        response_receiver.await.map_err(|_| "Failed to receive queue".to_string())
+    }
+
+    pub async fn get_queue_snapshot(&self) -> Result<QueueSnapshot, String> {
+        let (response_sender, response_receiver) = oneshot::channel();
+        self.command_sender
+            .send(PlayerCommand::GetQueueSnapshot { response: response_sender })
+            .map_err(|_| "Failed to send get queue snapshot command".to_string())?;
+        
+        response_receiver.await.map_err(|_| "Failed to receive queue snapshot".to_string())
+    }
+
+    // Load a saved queue paused at its saved position; resume starts playing it
+    pub async fn restore_queue(&self, snapshot: QueueSnapshot) -> Result<(), String> {
+        let (response_sender, response_receiver) = oneshot::channel();
+        self.command_sender
+            .send(PlayerCommand::RestoreQueue { snapshot: Box::new(snapshot), response: response_sender })
+            .map_err(|_| "Failed to send restore queue command".to_string())?;
+        
+        response_receiver.await.map_err(|_| "Failed to receive response".to_string())?
+    }
+
     pub fn pause(&self) -> Result<(), String> {
         self.command_sender
             .send(PlayerCommand::Pause)
@@ -342,24 +1228,144 @@ impl AudioPlayer {
             .map_err(|_| "Failed to send volume command".to_string())
     }
 
-    pub fn seek(&self, position: f64) -> Result<(), String> {
+    pub fn set_balance(&self, balance: f32) -> Result<(), String> {
+        self.command_sender
+            .send(PlayerCommand::SetBalance(balance))
+            .map_err(|_| "Failed to send balance command".to_string())
+    }
+
+    pub fn set_gain(&self, gain: f32) -> Result<(), String> {
+        self.command_sender
+            .send(PlayerCommand::SetGain(gain))
+            .map_err(|_| "Failed to send gain command".to_string())
+    }
+
+    pub fn set_equalizer(&self, bands: Vec<f32>) -> Result<(), String> {
+        self.command_sender
+            .send(PlayerCommand::SetEqualizer(bands))
+            .map_err(|_| "Failed to send equalizer command".to_string())
+    }
+
+    pub fn set_equalizer_enabled(&self, enabled: bool) -> Result<(), String> {
+        self.command_sender
+            .send(PlayerCommand::SetEqualizerEnabled(enabled))
+            .map_err(|_| "Failed to send equalizer enabled command".to_string())
+    }
+
+    pub fn seek(&self, position: f64) -> Result<(), String> {
         self.command_sender
             .send(PlayerCommand::Seek(position))
             .map_err(|_| "Failed to send seek command".to_string())
     }
 
+    pub fn seek_percent(&self, percent: f64) -> Result<(), String> {
+        self.command_sender
+            .send(PlayerCommand::SeekPercent(percent))
+            .map_err(|_| "Failed to send seek percent command".to_string())
+    }
+
+    pub fn seek_within_chapter(&self, offset: f64) -> Result<(), String> {
+        self.command_sender
+            .send(PlayerCommand::SeekWithinChapter(offset))
+            .map_err(|_| "Failed to send chapter seek command".to_string())
+    }
+
+    pub fn next_chapter(&self) -> Result<(), String> {
+        self.command_sender
+            .send(PlayerCommand::NextChapter)
+            .map_err(|_| "Failed to send next chapter command".to_string())
+    }
+
+    pub fn previous_chapter(&self) -> Result<(), String> {
+        self.command_sender
+            .send(PlayerCommand::PreviousChapter)
+            .map_err(|_| "Failed to send previous chapter command".to_string())
+    }
+
     pub fn toggle_shuffle(&self) -> Result<(), String> {
         self.command_sender
             .send(PlayerCommand::ToggleShuffle)
             .map_err(|_| "Failed to send shuffle command".to_string())
     }
 
+    pub fn set_shuffle_mode(&self, mode: ShuffleMode) -> Result<(), String> {
+        self.command_sender
+            .send(PlayerCommand::SetShuffleMode(mode))
+            .map_err(|_| "Failed to send shuffle command".to_string())
+    }
+
     pub fn set_repeat_mode(&self, mode: RepeatMode) -> Result<(), String> {
         self.command_sender
             .send(PlayerCommand::SetRepeatMode(mode))
             .map_err(|_| "Failed to send repeat mode command".to_string())
     }
 
+    pub fn set_transition_mode(&self, mode: TransitionMode) -> Result<(), String> {
+        self.command_sender
+            .send(PlayerCommand::SetTransitionMode(mode))
+            .map_err(|_| "Failed to send transition mode command".to_string())
+    }
+
+    pub fn set_custom_headers(&self, headers: HashMap<String, String>) -> Result<(), String> {
+        self.command_sender
+            .send(PlayerCommand::SetCustomHeaders(headers))
+            .map_err(|_| "Failed to send custom headers command".to_string())
+    }
+
+    // Reflect a favorite change made on the server in the queue and current song
+    pub fn set_favorite(&self, item_id: String, is_favorite: bool) -> Result<(), String> {
+        self.command_sender
+            .send(PlayerCommand::SetFavorite { item_id, is_favorite })
+            .map_err(|_| "Failed to send favorite command".to_string())
+    }
+
+    pub fn set_trim_silence(&self, enabled: bool) -> Result<(), String> {
+        self.command_sender
+            .send(PlayerCommand::SetTrimSilence(enabled))
+            .map_err(|_| "Failed to send trim silence command".to_string())
+    }
+
+    pub fn set_normalization_enabled(&self, enabled: bool) -> Result<(), String> {
+        self.command_sender
+            .send(PlayerCommand::SetNormalizationEnabled(enabled))
+            .map_err(|_| "Failed to send normalization command".to_string())
+    }
+
+    pub fn set_playback_speed(&self, speed: f32, enabled: bool) -> Result<(), String> {
+        self.command_sender
+            .send(PlayerCommand::SetPlaybackSpeed { speed, enabled })
+            .map_err(|_| "Failed to send playback speed command".to_string())
+    }
+
+    // None switches back to the system default output
+    pub async fn set_output_device(&self, name: Option<String>) -> Result<(), String> {
+        let (response_sender, response_receiver) = oneshot::channel();
+        self.command_sender
+            .send(PlayerCommand::SetOutputDevice { name, response: response_sender })
+            .map_err(|_| "Failed to send output device command".to_string())?;
+        
+        response_receiver.await.map_err(|_| "Failed to receive response".to_string())?
+    }
+
+    // None cancels a running timer
+    pub fn set_sleep_timer(&self, minutes: Option<u32>, mode: SleepTimerMode, fade: bool) -> Result<(), String> {
+        self.command_sender
+            .send(PlayerCommand::SetSleepTimer { minutes, mode, fade })
+            .map_err(|_| "Failed to send sleep timer command".to_string())
+    }
+
+    pub fn set_end_of_queue_fade(&self, seconds: f32) -> Result<(), String> {
+        self.command_sender
+            .send(PlayerCommand::SetEndOfQueueFade(seconds))
+            .map_err(|_| "Failed to send end of queue fade command".to_string())
+    }
+
+    pub fn set_warm_window(&self, window: usize) -> Result<(), String> {
+        self.command_sender
+            .send(PlayerCommand::SetWarmWindow(window))
+            .map_err(|_| "Failed to send warm window command".to_string())
+    }
+
     pub async fn get_state(&self) -> Result<PlaybackState, String> {
         let (response_tx, response_rx) = oneshot::channel();
         self.command_sender
@@ -369,6 +1375,43 @@ impl AudioPlayer {
         response_rx.await.map_err(|_| "Failed to receive state response".to_string())
     }
 
+    pub async fn get_audio_info(&self) -> Result<Option<AudioInfo>, String> {
+        let (response_tx, response_rx) = oneshot::channel();
+        self.command_sender
+            .send(PlayerCommand::GetAudioInfo { response: response_tx })
+            .map_err(|_| "Failed to send get audio info command".to_string())?;
+        
+        response_rx.await.map_err(|_| "Failed to receive audio info response".to_string())
+    }
+
+    pub async fn get_buffer_diagnostics(&self) -> Result<Option<BufferDiagnostics>, String> {
+        let (response_sender, response_receiver) = oneshot::channel();
+        self.command_sender
+            .send(PlayerCommand::GetBufferDiagnostics { response: response_sender })
+            .map_err(|_| "Failed to send buffer diagnostics command".to_string())?;
+        
+        response_receiver.await.map_err(|_| "Failed to receive buffer diagnostics".to_string())
+    }
+
+    // Reload a stream that failed to load, resuming at the position it was held at
+    pub async fn retry_playback(&self) -> Result<(), String> {
+        let (response_sender, response_receiver) = oneshot::channel();
+        self.command_sender
+            .send(PlayerCommand::RetryPlayback { response: response_sender })
+            .map_err(|_| "Failed to send retry playback command".to_string())?;
+        
+        response_receiver.await.map_err(|_| "Failed to receive response".to_string())?
+    }
+
+    pub async fn get_history(&self) -> Result<Vec<HistoryEntry>, String> {
+        let (response_sender, response_receiver) = oneshot::channel();
+        self.command_sender
+            .send(PlayerCommand::GetHistory { response: response_sender })
+            .map_err(|_| "Failed to send get history command".to_string())?;
+        
+        response_receiver.await.map_err(|_| "Failed to receive history".to_string())
+    }
+
     pub fn next_track(&self) -> Result<(), String> {
         self.command_sender
             .send(PlayerCommand::NextTrack)
@@ -383,6 +1426,92 @@ impl AudioPlayer {
 }
 
 impl AudioPlayerWorker {
+    // Without an output (only in tests) every sink is an idle one that nothing plays
+    fn new(
+        output: Option<(OutputStream, OutputStreamHandle)>,
+        command_receiver: mpsc::UnboundedReceiver<PlayerCommand>,
+        event_sender: broadcast::Sender<PlayerEvent>,
+    ) -> Self {
+        let (warm_sender, warm_receiver) = mpsc::unbounded_channel();
+        let (device_check_sender, device_check_receiver) = mpsc::unbounded_channel();
+        let (silence_scan_sender, silence_scan_receiver) = mpsc::unbounded_channel();
+        let (sleep_timer_sender, sleep_timer_receiver) = mpsc::unbounded_channel();
+        AudioPlayerWorker {
+            output,
+            sink: None,
+            symphonia_source: None,
+            state: PlaybackState {
+                is_playing: false,
+                current_position: 0.0,
+                duration: 0.0,
+                volume: DEFAULT_VOLUME,
+                balance: 0.0,
+                gain: 1.0,
+                shuffle_mode: ShuffleMode::Off,
+                repeat_mode: RepeatMode::None,
+                transition_mode: TransitionMode::Hard,
+                current_song: None,
+                is_transcoding: false,
+                transcode_reason: None,
+                buffering_underruns: 0,
+                trim_silence: false,
+                end_of_queue_fade: 0.0,
+                crossfade_seconds: 0.0,
+                normalization_enabled: false,
+                sleep_timer_remaining_secs: None,
+                playback_speed: 1.0,
+                playback_speed_enabled: false,
+                output_device: None,
+                equalizer_enabled: false,
+                equalizer_bands: vec![0.0; EQ_BAND_COUNT],
+            },
+            queue: VecDeque::new(),
+            current_index: None,
+            command_receiver,
+            event_sender,
+            last_position_update: Instant::now(),
+            audio_start_time: None,
+            visual_position: 0.0,
+            played_at_anchor: 0.0,
+            cached_audio_data: None,
+            cached_song_id: None,
+            processing: Arc::new(ProcessingSettings::new()),
+            playing_stats: None,
+            http_client: reqwest::Client::new(),
+            clip_warned: false,
+            warm_audio: HashMap::new(),
+            warm_window: DEFAULT_WARM_WINDOW,
+            warming: HashSet::new(),
+            warm_sender,
+            warm_receiver,
+            pending_recovery: None,
+            trailing_silence: 0.0,
+            history: VecDeque::new(),
+            current_started_at: 0,
+            end_fade: None,
+            track_ended: false,
+            unshuffled_queue: None,
+            active_stream: None,
+            gapless_next: None,
+            fading_sink: None,
+            sleep_timer: None,
+            sleep_timer_sender,
+            sleep_timer_receiver,
+            last_device_check: Instant::now(),
+            device_check_pending: false,
+            device_check_sender,
+            device_check_receiver,
+            silence_scan_sender,
+            silence_scan_receiver,
+        }
+    }
+
+    fn new_sink(&self) -> Result<Sink, rodio::PlayError> {
+        match &self.output {
+            Some((_, stream_handle)) => Sink::try_new(stream_handle),
+            None => Ok(Sink::new_idle().0),
+        }
+    }
 
     async fn run(mut self) {
         // Create a position tracking task
@@ -393,15 +1522,44 @@ impl AudioPlayerWorker {
                 // Handle commands
                 command = self.command_receiver.recv() => {
                     match command {
-                        Some(PlayerCommand::PlayItem { item, response }) => {
-                            let result = self.play_item(item).await;
+                        Some(PlayerCommand::PlayItem { item, position, response }) => {
+                            self.cancel_sleep_timer();
+                            let result = self.play_item_at(*item, position).await;
+                            let _ = response.send(result);
+                        }
+                        Some(PlayerCommand::SetQueue { items, start_index, response }) => {
+                            self.cancel_sleep_timer();
+                            let result = self.set_queue(items, start_index).await;
                             let _ = response.send(result);
                         }
+                        Some(PlayerCommand::MoveInQueue { from, to, response }) => {
+                            let _ = response.send(self.move_in_queue(from, to));
+                        }
+                        Some(PlayerCommand::AppendToQueue(items)) => {
+                            self.append_to_queue(items);
+                        }
+                        Some(PlayerCommand::ApplyResolvedUrls(urls)) => {
+                            self.apply_resolved_urls(&urls);
+                        }
+                        Some(PlayerCommand::GetQueue { response }) => {
+                            let _ = response.send(self.queue.iter().cloned().collect());
+                        }
+                        Some(PlayerCommand::GetQueueSnapshot { response }) => {
+                            self.update_position();
+                            let _ = response.send(self.queue_snapshot());
+                        }
+                        Some(PlayerCommand::RestoreQueue { snapshot, response }) => {
+                            let _ = response.send(self.restore_queue(*snapshot));
+                        }
                         Some(PlayerCommand::Pause) => {
                             self.pause();
                         }
                         Some(PlayerCommand::Resume) => {
-                            self.resume();
+                            if self.sink.is_none() && self.state.current_song.is_some() {
+                                self.resume_restored().await;
+                            } else {
+                                self.resume();
+                            }
                         }
                         Some(PlayerCommand::Stop) => {
                             self.stop();
@@ -409,19 +1567,89 @@ impl AudioPlayerWorker {
                         Some(PlayerCommand::SetVolume(volume)) => {
                             self.set_volume(volume);
                         }
+                        Some(PlayerCommand::SetBalance(balance)) => {
+                            self.set_balance(balance);
+                        }
+                        Some(PlayerCommand::SetGain(gain)) => {
+                            self.set_gain(gain);
+                        }
+                        Some(PlayerCommand::SetEqualizer(bands)) => {
+                            self.set_equalizer(bands);
+                        }
+                        Some(PlayerCommand::SetEqualizerEnabled(enabled)) => {
+                            self.set_equalizer_enabled(enabled);
+                        }
                         Some(PlayerCommand::Seek(position)) => {
                             self.seek(position).await;
                         }
+                        Some(PlayerCommand::SeekPercent(percent)) => {
+                            self.seek_percent(percent).await;
+                        }
+                        Some(PlayerCommand::SeekWithinChapter(offset)) => {
+                            self.seek_within_chapter(offset).await;
+                        }
+                        Some(PlayerCommand::NextChapter) => {
+                            self.next_chapter().await;
+                        }
+                        Some(PlayerCommand::PreviousChapter) => {
+                            self.previous_chapter().await;
+                        }
                         Some(PlayerCommand::ToggleShuffle) => {
                             self.toggle_shuffle();
                         }
+                        Some(PlayerCommand::SetShuffleMode(mode)) => {
+                            self.set_shuffle_mode(mode);
+                        }
                         Some(PlayerCommand::SetRepeatMode(mode)) => {
                             self.set_repeat_mode(mode);
                         }
+                        Some(PlayerCommand::SetTransitionMode(mode)) => {
+                            self.set_transition_mode(mode);
+                        }
+                        Some(PlayerCommand::SetCustomHeaders(headers)) => {
+                            self.set_custom_headers(&headers);
+                        }
+                        Some(PlayerCommand::SetTrimSilence(enabled)) => {
+                            self.set_trim_silence(enabled);
+                        }
+                        Some(PlayerCommand::SetNormalizationEnabled(enabled)) => {
+                            self.set_normalization_enabled(enabled);
+                        }
+                        Some(PlayerCommand::SetPlaybackSpeed { speed, enabled }) => {
+                            self.set_playback_speed(speed, enabled);
+                        }
+                        Some(PlayerCommand::SetOutputDevice { name, response }) => {
+                            let _ = response.send(self.set_output_device(name));
+                        }
+                        Some(PlayerCommand::SetSleepTimer { minutes, mode, fade }) => {
+                            self.set_sleep_timer(minutes, mode, fade);
+                        }
+                        Some(PlayerCommand::SetEndOfQueueFade(seconds)) => {
+                            self.set_end_of_queue_fade(seconds);
+                        }
+                        Some(PlayerCommand::SetFavorite { item_id, is_favorite }) => {
+                            self.set_favorite(&item_id, is_favorite);
+                        }
+                        Some(PlayerCommand::SetWarmWindow(window)) => {
+                            self.set_warm_window(window);
+                        }
                         Some(PlayerCommand::GetState { response }) => {
                             self.update_position(); // Update position before sending state
                             let _ = response.send(self.state.clone());
                         }
+                        Some(PlayerCommand::GetBufferDiagnostics { response }) => {
+                            let _ = response.send(self.playing_stats.as_ref().map(|stats| stats.snapshot()));
+                        }
+                        Some(PlayerCommand::GetHistory { response }) => {
+                            let _ = response.send(self.history.iter().cloned().collect());
+                        }
+                        Some(PlayerCommand::RetryPlayback { response }) => {
+                            let result = self.retry_playback().await;
+                            let _ = response.send(result);
+                        }
+                        Some(PlayerCommand::GetAudioInfo { response }) => {
+                            let _ = response.send(self.symphonia_source.as_ref().map(|source| source.audio_info()));
+                        }
                         Some(PlayerCommand::NextTrack) => {
                             self.next_track().await;
                         }
@@ -435,10 +1663,50 @@ impl AudioPlayerWorker {
                     }
                 }
                 
+                // Background loads for the warm window
+                Some((song_id, result)) = self.warm_receiver.recv() => {
+                    self.finish_warming(song_id, result);
+                }
+                
+                // The sleep timer's deadline passing
+                Some(()) = self.sleep_timer_receiver.recv() => {
+                    self.finish_sleep_timer();
+                }
+                
+                // Output device lookups started by check_output_device
+                Some((name, found)) = self.device_check_receiver.recv() => {
+                    self.finish_device_check(name, found);
+                }
+                
+                // Trailing silence measured by scan_trailing_silence
+                Some((song_id, seconds)) = self.silence_scan_receiver.recv() => {
+                    self.finish_silence_scan(song_id, seconds);
+                }
+                
                 // Position tracking timer
                 _ = position_interval.tick() => {
+                    self.poll_stream();
+                    self.check_gapless_handover();
                     if self.state.is_playing {
                         self.update_position();
+                        self.check_clipping();
+                        self.step_end_fade();
+                        self.prepare_transition().await;
+                    }
+                    self.step_sleep_fade();
+                    self.check_output_device();
+                    if self.fading_sink.as_ref().is_some_and(|sink| sink.empty()) {
+                        self.fading_sink = None;
+                    }
+                    if std::mem::take(&mut self.track_ended) {
+                        self.finish_track().await;
+                    }
+                    let retry_due = self.pending_recovery
+                        .as_ref()
+                        .and_then(|recovery| recovery.retry_at)
+                        .is_some_and(|retry_at| Instant::now() >= retry_at);
+                    if retry_due {
+                        let _ = self.retry_playback().await;
                     }
                 }
             }
@@ -446,60 +1714,148 @@ impl AudioPlayerWorker {
     }
 
     async fn play_item(&mut self, item: QueueItem) -> Result<(), String> {
-        // Clear cache if playing a different song
+        self.play_item_at(item, 0.0).await
+    }
+
+    async fn play_item_at(&mut self, item: QueueItem, position: f64) -> Result<(), String> {
+        // Keep the start position inside the track when its duration is known
+        let mut position = if position.is_finite() { position.max(0.0) } else { 0.0 };
+        if let Some(ticks) = item.duration_ticks {
+            position = position.min(ticks as f64 / 10_000_000.0);
+        }
+        
+        // Swap the cache over if playing a different song, keeping the old data warm
         if self.cached_song_id.as_ref() != Some(&item.id) {
-            self.cached_audio_data = None;
-            self.cached_song_id = None;
+            if let (Some(song_id), Some(data)) = (self.cached_song_id.take(), self.cached_audio_data.take()) {
+                self.warm_audio.insert(song_id, data);
+            }
+            if let Some(data) = self.warm_audio.remove(&item.id) {
+                info!("Using warm audio data for: {}", item.name);
+                self.cached_audio_data = Some(data);
+                self.cached_song_id = Some(item.id.clone());
+            }
         }
         
-        self.play_item_with_offset(item, 0.0).await
+        let mut result = self.play_item_with_offset(item.clone(), position).await;
+        if let Err(e) = &result {
+            if let Some(original) = self.original_fallback(&item) {
+                warn!("Playback failed ({}), playing the original file instead of the transcode", e);
+                result = self.play_item_with_offset(original, position).await;
+            }
+        }
+        self.update_warm_window();
+        result
+    }
+
+    // The item streamed untranscoded, also swapped into the queue so later plays and seeks
+    // don't try the transcode again. None when it wasn't transcoded
+    fn original_fallback(&mut self, item: &QueueItem) -> Option<QueueItem> {
+        let original_url = item.original_stream_url.clone().filter(|url| *url != item.stream_url)?;
+        let mut original = item.clone();
+        original.stream_url = original_url;
+        original.remote_stream_url = None;
+        original.original_stream_url = None;
+        original.is_transcoding = false;
+        original.transcode_reason = None;
+
+        for queued in self.queue.iter_mut().filter(|queued| queued.id == item.id) {
+            *queued = original.clone();
+        }
+        if self.unshuffled_queue.is_some() {
+            for queued in self.unshuffled_queue.iter_mut().flatten().filter(|queued| queued.id == item.id) {
+                *queued = original.clone();
+            }
+        }
+        Some(original)
     }
 
     async fn play_item_with_offset(&mut self, item: QueueItem, offset_seconds: f64) -> Result<(), String> {
-        println!("🎵 Playing item: {} - {} (offset: {}s)", item.name, item.stream_url, offset_seconds);
+        info!("Playing item: {} - {} (offset: {}s)", item.name, item.id, offset_seconds);
+
+        // The cached file may have been cleared since the queue was resolved; stream it again instead
+        let mut item = item;
+        if let Some(file_path) = item.stream_url.strip_prefix("file://") {
+            if !std::path::Path::new(file_path).exists() {
+                if let Some(remote_url) = item.remote_stream_url.take() {
+                    warn!("Cached audio file is gone, streaming from the server instead");
+                    item.stream_url = remote_url;
+                }
+            }
+        }
 
-        // Always use cached data or download/load full file (HTTP range doesn't work for audio formats)
-        let audio_data = if self.cached_song_id.as_ref() == Some(&item.id) && self.cached_audio_data.is_some() {
-            println!("🎵 Using cached audio data for instant seeking");
-            self.cached_audio_data.as_ref().unwrap().clone()
+        self.settle_stream(&item.id, offset_seconds).await;
+        let has_audio_data = self.cached_song_id.as_ref() == Some(&item.id) && self.cached_audio_data.is_some();
+        let stream_buffer = self.active_stream.as_ref().map(|stream| stream.buffer.clone());
+
+        let mut symphonia_source = if let Some(buffer) = stream_buffer {
+            // Still downloading from an earlier start of this song; probing can wait on the network
+            tokio::task::spawn_blocking(move || SymphoniaSource::from_stream(buffer)).await
+                .unwrap_or_else(|e| Err(format!("Failed to resume audio stream: {}", e)))?
+        } else if !has_audio_data && !item.stream_url.starts_with("file://") && offset_seconds <= 0.0 {
+            // Nothing to seek into, so start playing as soon as the first bytes arrive
+            self.start_progressive(&item).await?
         } else {
-            if item.stream_url.starts_with("file://") {
-                // Handle local file URLs
-                println!("🎵 Loading local cached audio file");
-                let file_path = item.stream_url.strip_prefix("file://").unwrap();
-                let data = tokio::fs::read(file_path).await
-                    .map_err(|e| format!("Failed to read cached audio file: {}", e))?;
-                
-                // Cache the data for future seeks
-                self.cached_audio_data = Some(data.clone());
-                self.cached_song_id = Some(item.id.clone());
-                
-                data
+            // Always use cached data or download/load full file (HTTP range doesn't work for audio formats)
+            let audio_data = if self.cached_song_id.as_ref() == Some(&item.id) && self.cached_audio_data.is_some() {
+                debug!("Using cached audio data for instant seeking");
+                self.cached_audio_data.as_ref().unwrap().clone()
             } else {
-                // Handle HTTP/HTTPS URLs
-                println!("🎵 Downloading and caching audio data from stream");
-                let response = reqwest::get(&item.stream_url).await
-                    .map_err(|e| format!("Failed to download audio: {}", e))?;
-                let bytes = response.bytes().await
-                    .map_err(|e| format!("Failed to read audio bytes: {}", e))?;
-                let data = bytes.to_vec();
-                
-                // Cache the data for future seeks
-                self.cached_audio_data = Some(data.clone());
-                self.cached_song_id = Some(item.id.clone());
-                
-                data
-            }
-        };
+                if item.stream_url.starts_with("file://") {
+                    // Handle local file URLs
+                    info!("Loading local cached audio file");
+                    let file_path = item.stream_url.strip_prefix("file://").unwrap();
+                    let data: AudioData = tokio::fs::read(file_path).await
+                        .map_err(|e| format!("Failed to read cached audio file: {}", e))?
+                        .into();
+                    
+                    // Cache the data for future seeks
+                    self.cached_audio_data = Some(data.clone());
+                    self.cached_song_id = Some(item.id.clone());
+                    
+                    data
+                } else {
+                    // Handle HTTP/HTTPS URLs
+                    info!("Downloading and caching audio data from stream");
+                    let _ = self.event_sender.send(PlayerEvent::Buffering(true));
+                    let download_started = Instant::now();
+                    let download = load_audio_data(&self.http_client, &item.stream_url).await;
+                    let _ = self.event_sender.send(PlayerEvent::Buffering(false));
+                    self.record_buffering(download_started.elapsed());
+                    let data: AudioData = match download {
+                        Ok(data) => {
+                            if self.pending_recovery.take().is_some() {
+                                info!("Stream reloaded, playback recovered");
+                                let _ = self.event_sender.send(PlayerEvent::Reconnecting(false));
+                            }
+                            data.into()
+                        }
+                        Err(e) => {
+                            self.hold_for_recovery(&item, offset_seconds);
+                            return Err(e);
+                        }
+                    };
+                    
+                    self.announce_download(&item.id, &item.stream_url, &data);
+                    
+                    // Cache the data for future seeks
+                    self.cached_audio_data = Some(data.clone());
+                    self.cached_song_id = Some(item.id.clone());
+                    
+                    data
+                }
+            };
 
-        // Create SymphoniaSource for INSTANT seeking! 🚀
-        println!("🚀 Creating SymphoniaSource for instant seeking capabilities");
-        let mut symphonia_source = SymphoniaSource::from_data(audio_data)?;
+            // Create SymphoniaSource for INSTANT seeking! 🚀
+            info!("Creating SymphoniaSource for instant seeking capabilities");
+            SymphoniaSource::from_data(audio_data)?
+        };
         
         // Perform instant seek if needed
-        if offset_seconds > 0.0 {
-            symphonia_source.seek_to_time(offset_seconds)?;
-        }
+        let offset_seconds = if offset_seconds > 0.0 {
+            symphonia_source.seek_to_time(offset_seconds)?
+        } else {
+            offset_seconds
+        };
 
         // Get duration if available
         let duration = item.duration_ticks
@@ -512,49 +1868,253 @@ impl AudioPlayerWorker {
             });
 
         // Create new sink
-        let sink = Sink::try_new(&self.stream_handle)
+        let sink = self.new_sink()
             .map_err(|e| format!("Failed to create sink: {}", e))?;
         
         // Set volume
-        sink.set_volume(self.state.volume);
+        sink.set_volume(self.volume_for(Some(&item)));
 
         // Add the symphonia source to sink
-        sink.append(symphonia_source);
+        self.playing_stats = Some(symphonia_source.stats.clone());
+        sink.append(ProcessingSource::new(symphonia_source.speed(self.effective_speed()), self.processing.clone()));
 
         // Store the symphonia source for future seeking
         // Note: We need to create a new one since the old one is consumed by sink
-        let mut seeking_source = SymphoniaSource::from_data(
-            self.cached_audio_data.as_ref().unwrap().clone()
-        )?;
+        let mut seeking_source = self.open_current_source()?;
         if offset_seconds > 0.0 {
             // Keep the seeking source in sync
             let _ = seeking_source.seek_to_time(offset_seconds);
         }
         self.symphonia_source = Some(seeking_source);
 
+        // Log the outgoing song before its position and duration are overwritten
+        let song_changed = self.state.current_song.as_ref().map(|song| &song.id) != Some(&item.id);
+        if song_changed {
+            self.record_history();
+            self.current_started_at = unix_now_secs();
+        }
+
         // Update state
         self.state.is_playing = !sink.is_paused();
         self.state.current_position = offset_seconds;
         self.state.duration = duration;
+        self.set_transcoding_state(&item);
         self.state.current_song = Some(item.clone());
+        if song_changed {
+            self.scan_trailing_silence();
+        }
         
-        // Set tracking variables
+        // Set tracking variables; the source is new, so everything it has played counts
         self.audio_start_time = Some(Instant::now());
         self.visual_position = offset_seconds;
+        self.played_at_anchor = 0.0;
 
         // Store the sink
         self.sink = Some(sink);
+        self.end_fade = None;
+        self.track_ended = false;
+        self.gapless_next = None;
 
         // Emit events
         let _ = self.event_sender.send(PlayerEvent::TrackChanged(Some(item)));
         let _ = self.event_sender.send(PlayerEvent::StateChanged(self.state.clone()));
 
-        println!("🚀 INSTANT SEEK playback started at {}s using SymphoniaSource!", offset_seconds);
+        info!("INSTANT SEEK playback started at {}s using SymphoniaSource!", offset_seconds);
+
+        Ok(())
+    }
+
+    // Count loads that kept the listener waiting and nudge towards transcoding when they keep happening
+    fn record_buffering(&mut self, waited: Duration) {
+        if waited < UNDERRUN_THRESHOLD {
+            return;
+        }
+
+        self.state.buffering_underruns += 1;
+        info!("Playback stalled {:.1}s waiting for audio ({} underruns)", waited.as_secs_f64(), self.state.buffering_underruns);
+
+        if self.state.buffering_underruns == UNDERRUN_SUGGESTION_COUNT {
+            let _ = self.event_sender.send(PlayerEvent::SlowConnection(
+                "Your connection seems too slow for this stream. Try enabling transcoding or a lower bitrate.".to_string(),
+            ));
+        }
+    }
+
+    // Remember a failed stream so the tick (or retry_playback) can reload it at the same position
+    fn hold_for_recovery(&mut self, item: &QueueItem, position: f64) {
+        let attempts = self.pending_recovery
+            .as_ref()
+            .filter(|recovery| recovery.item.id == item.id)
+            .map(|recovery| recovery.attempts + 1)
+            .unwrap_or(1);
+
+        info!("Stream for {} failed to load, holding position {:.1}s (attempt {})", item.name, position, attempts);
+        self.state.current_position = position;
+        self.visual_position = position;
+
+        let retry_at = if attempts < MAX_RECOVERY_ATTEMPTS {
+            Some(Instant::now() + RECOVERY_RETRY_INTERVAL)
+        } else {
+            info!("Giving up automatic retries for {}", item.name);
+            None
+        };
+        self.pending_recovery = Some(PendingRecovery {
+            item: item.clone(),
+            position,
+            attempts,
+            retry_at,
+        });
+        let _ = self.event_sender.send(PlayerEvent::Reconnecting(true));
+    }
+
+    async fn retry_playback(&mut self) -> Result<(), String> {
+        let recovery = self.pending_recovery.clone().ok_or("No failed stream to retry")?;
+
+        // Re-resolve from the queue, which may now point at a cached copy
+        let item = self.queue
+            .iter()
+            .find(|item| item.id == recovery.item.id)
+            .cloned()
+            .unwrap_or(recovery.item);
+
+        info!("Retrying {} at {:.1}s", item.name, recovery.position);
+        self.play_item_at(item, recovery.position).await
+    }
+
+    async fn set_queue(&mut self, items: Vec<QueueItem>, start_index: usize) -> Result<(), String> {
+        if items.is_empty() {
+            return Err("Cannot play an empty queue".to_string());
+        }
+
+        let start_index = start_index.min(items.len() - 1);
+        let item = items[start_index].clone();
+
+        self.queue = items.into_iter().collect();
+        self.current_index = Some(start_index);
+        self.unshuffled_queue = None;
+        if self.state.shuffle_mode != ShuffleMode::Off {
+            self.shuffle_queue();
+        }
+
+        info!("Queue set with {} items, starting at {}", self.queue.len(), start_index);
+        self.play_item(item).await
+    }
+
+    fn queue_snapshot(&self) -> QueueSnapshot {
+        QueueSnapshot {
+            items: self.queue.iter().cloned().collect(),
+            current_index: self.current_index,
+            current_position: self.state.current_position,
+            repeat_mode: self.state.repeat_mode.clone(),
+            shuffle_mode: self.state.shuffle_mode,
+        }
+    }
+
+    // Put a saved queue back without loading any audio, so nothing plays until resume
+    fn restore_queue(&mut self, snapshot: QueueSnapshot) -> Result<(), String> {
+        if snapshot.items.is_empty() {
+            return Err("Cannot restore an empty queue".to_string());
+        }
+        if self.state.current_song.is_some() {
+            return Err("Playback has already started".to_string());
+        }
+
+        let index = snapshot.current_index.unwrap_or(0).min(snapshot.items.len() - 1);
+        let item = snapshot.items[index].clone();
+        let duration = item.duration_ticks.map(|ticks| ticks as f64 / 10_000_000.0).unwrap_or(0.0);
+        let position = if snapshot.current_position.is_finite() { snapshot.current_position.max(0.0) } else { 0.0 };
+        let position = if duration > 0.0 { position.min(duration) } else { position };
+
+        self.queue = snapshot.items.into_iter().collect();
+        self.current_index = Some(index);
+        // The saved order is already shuffled; turning shuffle off keeps it rather than guessing the original
+        self.unshuffled_queue = (snapshot.shuffle_mode != ShuffleMode::Off).then(|| self.queue.clone());
+        self.state.shuffle_mode = snapshot.shuffle_mode;
+        self.state.repeat_mode = snapshot.repeat_mode;
+        self.state.is_playing = false;
+        self.state.current_position = position;
+        self.state.duration = duration;
+        self.set_transcoding_state(&item);
+        self.state.current_song = Some(item.clone());
+        self.visual_position = position;
+        self.audio_start_time = None;
+        self.current_started_at = unix_now_secs();
+
+        info!("Restored queue with {} items at {} ({}s)", self.queue.len(), index, position);
+        let _ = self.event_sender.send(PlayerEvent::TrackChanged(Some(item)));
+        let _ = self.event_sender.send(PlayerEvent::StateChanged(self.state.clone()));
+        Ok(())
+    }
+
+    // First resume after a restore: nothing is loaded yet, so start the song where it was left
+    async fn resume_restored(&mut self) {
+        let Some(item) = self.state.current_song.clone() else {
+            return;
+        };
+        let position = self.state.current_position;
+        if let Err(e) = self.play_item_at(item, position).await {
+            error!("Failed to resume the restored queue: {}", e);
+            let _ = self.event_sender.send(PlayerEvent::Error(e));
+        }
+    }
+
+    // Move a queue item without interrupting playback; only the current index pointer follows it
+    fn move_in_queue(&mut self, from: usize, to: usize) -> Result<(), String> {
+        if from >= self.queue.len() || to >= self.queue.len() {
+            return Err(format!("Queue position out of range (queue has {} items)", self.queue.len()));
+        }
+        if from == to {
+            return Ok(());
+        }
+
+        let item = self.queue.remove(from).ok_or("Queue item not found")?;
+        let after = to.checked_sub(1).and_then(|index| self.queue.get(index)).map(|item| item.id.clone());
+        // Same neighbour in the original order, so the move survives turning shuffle off
+        if let Some(unshuffled) = self.unshuffled_queue.as_mut() {
+            move_after(unshuffled, &item.id, after.as_deref());
+        }
+        self.queue.insert(to, item);
+
+        // If the playing track itself moved, only the pointer follows it; the sink keeps running untouched
+        self.current_index = self.current_index.map(|current| index_after_move(current, from, to));
 
+        info!("Moved queue item {} -> {} (current index: {:?})", from, to, self.current_index);
+        self.update_warm_window();
         Ok(())
     }
 
+    fn append_to_queue(&mut self, items: Vec<QueueItem>) {
+        info!("Appending {} items to queue", items.len());
+        if let Some(unshuffled) = self.unshuffled_queue.as_mut() {
+            unshuffled.extend(items.iter().cloned());
+        }
+        self.queue.extend(items);
+        self.update_warm_window();
+    }
+
+    // Transcoding as shown for the URL the item actually plays from, which may be a cached file
+    // rather than the profile's stream
+    fn set_transcoding_state(&mut self, item: &QueueItem) {
+        self.state.is_transcoding = jellyfin::is_transcoding_url(&item.stream_url);
+        self.state.transcode_reason = if self.state.is_transcoding { item.transcode_reason.clone() } else { None };
+    }
+
+    fn apply_resolved_urls(&mut self, urls: &HashMap<String, String>) {
+        for item in self.queue.iter_mut() {
+            if item.stream_url.starts_with("file://") {
+                continue;
+            }
+            if let Some(url) = urls.get(&item.id) {
+                item.remote_stream_url = Some(std::mem::replace(&mut item.stream_url, url.clone()));
+                item.is_transcoding = jellyfin::is_transcoding_url(&item.stream_url);
+            }
+        }
+    }
+
     fn pause(&mut self) {
+        if let Some(fading) = &self.fading_sink {
+            fading.pause();
+        }
         if let Some(sink) = &self.sink {
             sink.pause();
             self.update_position(); // Update position before pausing
@@ -565,30 +2125,49 @@ impl AudioPlayerWorker {
     }
 
     fn resume(&mut self) {
+        if let Some(fading) = &self.fading_sink {
+            fading.play();
+        }
         if let Some(sink) = &self.sink {
             sink.play();
             self.state.is_playing = true;
             // Restart tracking from current visual position
             self.audio_start_time = Some(Instant::now());
             self.visual_position = self.state.current_position;
+            self.played_at_anchor = self.played_seconds();
             let _ = self.event_sender.send(PlayerEvent::StateChanged(self.state.clone()));
         }
     }
 
     fn stop(&mut self) {
+        self.record_history();
         if let Some(sink) = &self.sink {
             sink.stop();
         }
         self.sink = None;
+        if let Some(fading) = self.fading_sink.take() {
+            fading.stop();
+        }
+        self.abandon_stream();
+        self.end_fade = None;
+        self.track_ended = false;
+        self.gapless_next = None;
         self.state.is_playing = false;
         self.state.current_position = 0.0;
         self.state.current_song = None;
+        self.state.is_transcoding = false;
+        self.state.transcode_reason = None;
         self.audio_start_time = None;
         self.visual_position = 0.0;
+        if self.pending_recovery.take().is_some() {
+            let _ = self.event_sender.send(PlayerEvent::Reconnecting(false));
+        }
         
         // Clear audio cache when stopping
         self.cached_audio_data = None;
         self.cached_song_id = None;
+        self.warm_audio.clear();
+        self.playing_stats = None;
         
         let _ = self.event_sender.send(PlayerEvent::StateChanged(self.state.clone()));
         let _ = self.event_sender.send(PlayerEvent::TrackChanged(None));
@@ -596,34 +2175,118 @@ impl AudioPlayerWorker {
 
     fn set_volume(&mut self, volume: f32) {
         let clamped_volume = volume.clamp(0.0, 1.0);
+        self.state.volume = clamped_volume;
         
         if let Some(sink) = &self.sink {
-            sink.set_volume(clamped_volume);
+            sink.set_volume(self.track_volume() * self.end_fade_level());
+        }
+        if let Some(fading) = &self.fading_sink {
+            fading.set_volume(clamped_volume);
         }
         
-        self.state.volume = clamped_volume;
         let _ = self.event_sender.send(PlayerEvent::StateChanged(self.state.clone()));
     }
 
-    fn update_position(&mut self) {
-        if let Some(start_time) = self.audio_start_time {
-            if self.state.is_playing {
-                let elapsed = start_time.elapsed().as_secs_f64();
-                let new_position = self.visual_position + elapsed;
-                
-                // Check if track has finished
-                if self.state.duration > 0.0 && new_position >= self.state.duration {
-                    self.state.current_position = self.state.duration;
+    fn set_balance(&mut self, balance: f32) {
+        let clamped_balance = if balance.is_finite() { balance.clamp(-1.0, 1.0) } else { 0.0 };
+        
+        // Playing sources read this on every sample, so the change is immediate
+        self.processing.balance.store(clamped_balance.to_bits(), Ordering::Relaxed);
+        
+        self.state.balance = clamped_balance;
+        let _ = self.event_sender.send(PlayerEvent::StateChanged(self.state.clone()));
+    }
+
+    fn set_gain(&mut self, gain: f32) {
+        let clamped_gain = if gain.is_finite() { gain.clamp(1.0, MAX_GAIN) } else { 1.0 };
+        
+        self.processing.gain.store(clamped_gain.to_bits(), Ordering::Relaxed);
+        self.processing.clipped.store(false, Ordering::Relaxed);
+        self.clip_warned = false;
+        
+        self.state.gain = clamped_gain;
+        let _ = self.event_sender.send(PlayerEvent::StateChanged(self.state.clone()));
+    }
+
+    fn set_equalizer(&mut self, bands: Vec<f32>) {
+        let mut gains = [0.0; EQ_BAND_COUNT];
+        for (gain, band) in gains.iter_mut().zip(&bands) {
+            *gain = if band.is_finite() { band.clamp(-MAX_BAND_GAIN_DB, MAX_BAND_GAIN_DB) } else { 0.0 };
+        }
+        info!("Equalizer bands: {:?}", gains);
+        
+        if let Ok(mut eq_gains) = self.processing.eq_gains.lock() {
+            *eq_gains = gains;
+        }
+        // Playing sources rebuild their filters when they see the new version
+        self.processing.eq_version.fetch_add(1, Ordering::Relaxed);
+        
+        self.state.equalizer_bands = gains.to_vec();
+        let _ = self.event_sender.send(PlayerEvent::StateChanged(self.state.clone()));
+    }
+
+    fn set_equalizer_enabled(&mut self, enabled: bool) {
+        info!("Equalizer {}", if enabled { "on" } else { "off" });
+        self.processing.eq_enabled.store(enabled, Ordering::Relaxed);
+        self.processing.eq_version.fetch_add(1, Ordering::Relaxed);
+        
+        self.state.equalizer_enabled = enabled;
+        let _ = self.event_sender.send(PlayerEvent::StateChanged(self.state.clone()));
+    }
+
+    // Warn once per gain setting when the boost pushes samples into the soft clipper
+    fn check_clipping(&mut self) {
+        if self.processing.clipped.swap(false, Ordering::Relaxed) && !self.clip_warned {
+            self.clip_warned = true;
+            info!("Gain {:.2} is clipping", self.state.gain);
+            let _ = self.event_sender.send(PlayerEvent::Clipping(self.state.gain));
+        }
+    }
+
+    // Seconds of the playing source heard so far, 0 with no source in the sink
+    fn played_seconds(&self) -> f64 {
+        self.playing_stats.as_ref().map(|stats| stats.played_seconds()).unwrap_or(0.0)
+    }
+
+    fn update_position(&mut self) {
+        if let Some(start_time) = self.audio_start_time {
+            if self.state.is_playing {
+                // Samples are track time, so this already follows any speed change. Only without
+                // a source does the clock stand in, scaled since track time runs faster or slower
+                let elapsed = match self.playing_stats.as_ref() {
+                    Some(stats) => (stats.played_seconds() - self.played_at_anchor).max(0.0),
+                    None => start_time.elapsed().as_secs_f64() * self.effective_speed() as f64,
+                };
+                let new_position = self.visual_position + elapsed;
+                
+                // A drained sink also counts as the end of the track, e.g. when the duration is off
+                let stream_ended = self.sink.as_ref().is_some_and(|sink| sink.empty());
+                // Check if track has finished, ignoring any trailing silence being trimmed.
+                // With a gapless next track queued the sink decides when the handover happens
+                let reached_end = self.gapless_next.is_none()
+                    && self.state.duration > 0.0
+                    && new_position >= self.state.duration - self.trailing_silence;
+                if stream_ended || reached_end {
+                    self.state.current_position = if self.state.duration > 0.0 { self.state.duration } else { new_position };
                     self.state.is_playing = false;
                     self.audio_start_time = None;
                     
-                    // TODO: Auto-advance to next track based on repeat mode
-                    println!("Track finished - would auto-advance here");
+                    // Advanced from the tick, since loading the next track is async
+                    self.track_ended = true;
+                    debug!("Track finished");
                     
                     let _ = self.event_sender.send(PlayerEvent::StateChanged(self.state.clone()));
                 } else {
                     self.state.current_position = new_position;
                     
+                    // Fade the final track out over its last few seconds
+                    let fade = self.state.end_of_queue_fade as f64;
+                    let end = self.state.duration - self.trailing_silence;
+                    let is_last = !self.has_next_track() && !matches!(self.state.repeat_mode, RepeatMode::One);
+                    if fade > 0.0 && self.end_fade.is_none() && is_last && new_position >= end - fade {
+                        self.end_fade = Some(EndFade { start: end - fade, end });
+                    }
+                    
                     // Send position update event (but limit frequency)
                     let now = Instant::now();
                     if now.duration_since(self.last_position_update).as_millis() >= 500 {
@@ -636,161 +2299,1817 @@ impl AudioPlayerWorker {
         }
     }
 
+    // Log the current song as played before it's replaced or stopped
+    fn record_history(&mut self) {
+        self.update_position();
+        let Some(song) = self.state.current_song.as_ref() else {
+            return;
+        };
+
+        let completion = if self.state.duration > 0.0 {
+            (self.state.current_position / self.state.duration).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+
+        self.history.push_back(HistoryEntry {
+            item_id: song.id.clone(),
+            name: song.name.clone(),
+            artist: song.artists.first().cloned(),
+            played_at: self.current_started_at,
+            completion,
+        });
+        if self.history.len() > HISTORY_CAPACITY {
+            self.history.pop_front();
+        }
+    }
+
+    // Kept from when shuffle was on/off: any shuffle turns it off, otherwise shuffle tracks
     fn toggle_shuffle(&mut self) {
-        self.state.is_shuffled = !self.state.is_shuffled;
-        let _ = self.event_sender.send(PlayerEvent::StateChanged(self.state.clone()));
+        let mode = match self.state.shuffle_mode {
+            ShuffleMode::Off => ShuffleMode::Tracks,
+            ShuffleMode::Tracks | ShuffleMode::Albums => ShuffleMode::Off,
+        };
+        self.set_shuffle_mode(mode);
     }
 
-    fn set_repeat_mode(&mut self, mode: RepeatMode) {
-        self.state.repeat_mode = mode;
+    fn set_shuffle_mode(&mut self, mode: ShuffleMode) {
+        if mode == self.state.shuffle_mode {
+            return;
+        }
+
+        // Switching between shuffle modes reshuffles from the original order
+        self.unshuffle_queue();
+        self.state.shuffle_mode = mode;
+        if mode != ShuffleMode::Off {
+            self.shuffle_queue();
+        }
+        self.update_warm_window();
         let _ = self.event_sender.send(PlayerEvent::StateChanged(self.state.clone()));
     }
 
-    async fn next_track(&mut self) {
-        if self.queue.is_empty() {
+    // Randomize the play order for the shuffle mode, keeping the current song current
+    fn shuffle_queue(&mut self) {
+        if self.unshuffled_queue.is_none() {
+            self.unshuffled_queue = Some(self.queue.clone());
+        }
+
+        if self.state.shuffle_mode == ShuffleMode::Albums {
+            self.shuffle_queue_by_album();
             return;
         }
 
-        let next_index = match self.current_index {
-            Some(index) => {
-                if index + 1 >= self.queue.len() {
-                    // At end of queue
-                    match self.state.repeat_mode {
-                        RepeatMode::All => Some(0), // Loop back to start
-                        _ => None,                  // No more tracks
-                    }
-                } else {
-                    Some(index + 1)
+        let queue = std::mem::take(&mut self.queue);
+        let (queue, current_index) = shuffle_tracks(queue, self.current_index, &mut rand::thread_rng());
+        self.queue = queue;
+        self.current_index = current_index;
+        info!("Shuffled queue of {} items", self.queue.len());
+    }
+
+    // Shuffle whole albums, each in disc and track order. The current song's album goes first
+    // and the song keeps its place within it; songs without an album count as albums of one
+    fn shuffle_queue_by_album(&mut self) {
+        let current_id = self.current_index
+            .and_then(|index| self.queue.get(index))
+            .map(|item| item.id.clone());
+
+        let mut albums: Vec<Vec<QueueItem>> = Vec::new();
+        let mut album_index: HashMap<String, usize> = HashMap::new();
+        for item in self.queue.drain(..) {
+            let key = item.album_id.clone().unwrap_or_else(|| item.id.clone());
+            match album_index.get(&key) {
+                Some(&index) => albums[index].push(item),
+                None => {
+                    album_index.insert(key, albums.len());
+                    albums.push(vec![item]);
                 }
             }
-            None => Some(0), // Start from beginning
-        };
+        }
+        for album in albums.iter_mut() {
+            // Stable, so tracks missing numbers keep their queue order
+            album.sort_by_key(|item| (item.disc_number.unwrap_or(1), item.track_number.unwrap_or(i32::MAX)));
+        }
 
-        if let Some(index) = next_index {
-            if let Some(item) = self.queue.get(index).cloned() {
-                self.current_index = Some(index);
-                let _ = self.play_item(item).await;
-            }
+        let album_count = albums.len();
+        let current_album = current_id.as_ref().and_then(|current_id| {
+            albums.iter().position(|album| album.iter().any(|item| &item.id == current_id))
+        });
+        let current_album = current_album.map(|index| albums.remove(index));
+        fisher_yates_shuffle(&mut albums, &mut rand::thread_rng());
+        if let Some(current_album) = current_album {
+            albums.insert(0, current_album);
+        }
+
+        self.queue = albums.into_iter().flatten().collect();
+        if let Some(current_id) = current_id {
+            self.current_index = self.queue.iter().position(|item| item.id == current_id);
         }
+        info!("Shuffled {} albums ({} items)", album_count, self.queue.len());
     }
 
-    async fn previous_track(&mut self) {
-        if self.queue.is_empty() {
+    // Put the queue back in insertion order, keeping the current song current
+    fn unshuffle_queue(&mut self) {
+        let Some(unshuffled) = self.unshuffled_queue.take() else {
             return;
+        };
+
+        let shuffled = std::mem::take(&mut self.queue);
+        self.queue = restore_order(unshuffled, shuffled);
+
+        if self.current_index.is_some() {
+            let current_id = self.state.current_song.as_ref().map(|song| song.id.as_str());
+            self.current_index = self.queue
+                .iter()
+                .position(|item| Some(item.id.as_str()) == current_id);
         }
+        info!("Restored queue order ({} items)", self.queue.len());
+    }
 
-        let prev_index = match self.current_index {
-            Some(index) => {
-                if index == 0 {
-                    // At beginning of queue
-                    match self.state.repeat_mode {
-                        RepeatMode::All => Some(self.queue.len() - 1), // Loop to end
-                        _ => None,                                     // No previous tracks
-                    }
+    fn set_repeat_mode(&mut self, mode: RepeatMode) {
+        self.state.repeat_mode = mode;
+        let _ = self.event_sender.send(PlayerEvent::StateChanged(self.state.clone()));
+    }
+
+    fn set_transition_mode(&mut self, mode: TransitionMode) {
+        self.state.transition_mode = match mode {
+            TransitionMode::Crossfade(seconds) if !seconds.is_finite() || seconds <= 0.0 => TransitionMode::Gapless,
+            TransitionMode::Crossfade(seconds) => TransitionMode::Crossfade(seconds.min(MAX_CROSSFADE_SECONDS)),
+            mode => mode,
+        };
+        self.state.crossfade_seconds = match self.state.transition_mode {
+            TransitionMode::Crossfade(seconds) => seconds,
+            TransitionMode::Smart => SMART_CROSSFADE_SECONDS,
+            TransitionMode::Hard | TransitionMode::Gapless => 0.0,
+        };
+        let _ = self.event_sender.send(PlayerEvent::StateChanged(self.state.clone()));
+    }
+
+    // Resolve the configured transition mode for handing over to `next`
+    fn resolve_transition(&self, next: &QueueItem) -> TransitionMode {
+        match self.state.transition_mode {
+            TransitionMode::Smart => {
+                let same_album = self.state.current_song.as_ref()
+                    .and_then(|current| current.album_id.as_ref())
+                    .is_some_and(|album_id| next.album_id.as_ref() == Some(album_id));
+                if same_album {
+                    TransitionMode::Gapless
                 } else {
-                    Some(index - 1)
+                    TransitionMode::Crossfade(SMART_CROSSFADE_SECONDS)
                 }
             }
-            None => Some(self.queue.len() - 1), // Start from end
-        };
+            mode => mode,
+        }
+    }
 
-        if let Some(index) = prev_index {
-            if let Some(item) = self.queue.get(index).cloned() {
-                self.current_index = Some(index);
-                let _ = self.play_item(item).await;
+    fn set_custom_headers(&mut self, headers: &HashMap<String, String>) {
+        match reqwest::Client::builder()
+            .default_headers(crate::jellyfin::custom_header_map(headers))
+            .build()
+        {
+            Ok(client) => self.http_client = client,
+            Err(e) => warn!("Failed to apply custom headers to player: {}", e),
+        }
+    }
+
+    fn set_favorite(&mut self, item_id: &str, is_favorite: bool) {
+        let items = self.queue.iter_mut().chain(self.state.current_song.as_mut());
+        for item in items.filter(|item| item.id == item_id) {
+            item.is_favorite = is_favorite;
+            if let Some(user_data) = item.user_data.as_mut() {
+                user_data.is_favorite = is_favorite;
             }
         }
+        let _ = self.event_sender.send(PlayerEvent::StateChanged(self.state.clone()));
     }
 
-    async fn seek(&mut self, position: f64) {
-        println!("🚀 INSTANT SEEK to position: {} seconds", position);
-        
-        if let Some(current_song) = self.state.current_song.clone() {
+    fn set_trim_silence(&mut self, enabled: bool) {
+        self.state.trim_silence = enabled;
+        if !enabled {
+            self.trailing_silence = 0.0;
+        }
+        info!("Trim silence at gapless joins: {}", enabled);
+        let _ = self.event_sender.send(PlayerEvent::StateChanged(self.state.clone()));
+    }
+
+    fn set_normalization_enabled(&mut self, enabled: bool) {
+        self.state.normalization_enabled = enabled;
+        if let Some(sink) = &self.sink {
+            sink.set_volume(self.track_volume() * self.end_fade_level());
+        }
+        info!("Volume normalization: {}", enabled);
+        let _ = self.event_sender.send(PlayerEvent::StateChanged(self.state.clone()));
+    }
+
+    // User volume with the track's normalization gain on top, capped at full scale so a boost can't clip
+    fn volume_for(&self, item: Option<&QueueItem>) -> f32 {
+        let gain_db = item
+            .filter(|_| self.state.normalization_enabled)
+            .and_then(|item| item.normalization_gain)
+            .filter(|gain| gain.is_finite())
+            .unwrap_or(0.0);
+        (self.state.volume * 10f32.powf(gain_db / 20.0)).min(1.0) * self.sleep_fade_level()
+    }
+
+    fn track_volume(&self) -> f32 {
+        self.volume_for(self.state.current_song.as_ref())
+    }
+
+    fn set_end_of_queue_fade(&mut self, seconds: f32) {
+        self.state.end_of_queue_fade = if seconds.is_finite() { seconds.clamp(0.0, MAX_END_OF_QUEUE_FADE_SECONDS) } else { 0.0 };
+        if self.state.end_of_queue_fade == 0.0 && self.end_fade.take().is_some() {
+            if let Some(sink) = &self.sink {
+                sink.set_volume(self.track_volume());
+            }
+        }
+        info!("End of queue fade: {:.1}s", self.state.end_of_queue_fade);
+        let _ = self.event_sender.send(PlayerEvent::StateChanged(self.state.clone()));
+    }
+
+    // Speed the source plays at; the listener's choice only while speed control is on
+    fn effective_speed(&self) -> f32 {
+        if self.state.playback_speed_enabled {
+            self.state.playback_speed
+        } else {
+            1.0
+        }
+    }
+
+    fn set_playback_speed(&mut self, speed: f32, enabled: bool) {
+        let speed = if speed.is_finite() { speed.clamp(MIN_PLAYBACK_SPEED, MAX_PLAYBACK_SPEED) } else { 1.0 };
+        let old_speed = self.effective_speed();
+
+        // Settle the position at the old speed before the clock starts counting at the new one
+        self.update_position();
+        self.state.playback_speed = speed;
+        self.state.playback_speed_enabled = enabled;
+        info!("Playback speed: {}x ({})", speed, if enabled { "on" } else { "off" });
+
+        // The speed is baked into the source, so rebuild it where playback is now
+        if self.effective_speed() != old_speed && self.sink.is_some() {
             let was_playing = self.state.is_playing;
-            
-            // INSTANT SEEK: Create new SymphoniaSource at seek position! 🚀
-            if let Some(cached_data) = &self.cached_audio_data {
-                match SymphoniaSource::from_data(cached_data.clone()) {
-                    Ok(mut new_source) => {
-                        // Seek the new source to the desired position
-                        match new_source.seek_to_time(position) {
-                            Ok(_) => {
-                                println!("🚀 INSTANT SEEK: Creating new playback source at {}s", position);
-                                
-                                // Stop current playback
-                                if let Some(sink) = &self.sink {
-                                    sink.stop();
-                                }
-                                
-                                // Create new sink with the sought source
-                                match Sink::try_new(&self.stream_handle) {
-                                    Ok(new_sink) => {
-                                        new_sink.set_volume(self.state.volume);
-                                        new_sink.append(new_source);
-                                        
-                                        // Update stored source for future seeks
-                                        if let Ok(mut seeking_source) = SymphoniaSource::from_data(cached_data.clone()) {
-                                            let _ = seeking_source.seek_to_time(position);
-                                            self.symphonia_source = Some(seeking_source);
-                                        }
-                                        
-                                        // If was paused, pause the new sink
-                                        if !was_playing {
-                                            new_sink.pause();
-                                        }
-                                        
-                                        // Update state
-                                        self.sink = Some(new_sink);
-                                        self.state.current_position = position;
-                                        self.visual_position = position;
-                                        self.state.is_playing = was_playing;
-                                        self.audio_start_time = if was_playing { Some(Instant::now()) } else { None };
-                                        
-                                        println!("🚀 INSTANT SEEK completed! Now playing from {}s", position);
-                                        let _ = self.event_sender.send(PlayerEvent::StateChanged(self.state.clone()));
-                                        return;
-                                    }
-                                    Err(e) => {
-                                        println!("⚠️ Failed to create new sink: {}", e);
-                                    }
-                                }
-                            }
-                            Err(e) => {
-                                println!("⚠️ Symphonia seek failed: {}", e);
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        println!("⚠️ Failed to create new SymphoniaSource: {}", e);
-                    }
-                }
+            if let Err(InstantSeekError::CorruptData(e)) | Err(InstantSeekError::Failed(e)) =
+                self.instant_seek(self.state.current_position, was_playing)
+            {
+                warn!("New speed applies from the next track: {}", e);
             }
-            
-            // Fallback: restart playback method (slower but reliable)
-            println!("🎵 Falling back to restart-based seeking");
-            
-            // Stop current playback
-            if let Some(sink) = &self.sink {
-                sink.stop();
+        }
+        let _ = self.event_sender.send(PlayerEvent::StateChanged(self.state.clone()));
+    }
+
+    fn set_sleep_timer(&mut self, minutes: Option<u32>, mode: SleepTimerMode, fade: bool) {
+        match minutes {
+            Some(minutes) => {
+                info!("Sleep timer: {:?} in {} min", mode, minutes);
+                let duration = Duration::from_secs(minutes as u64 * 60);
+                let sender = self.sleep_timer_sender.clone();
+                let task = tokio::spawn(async move {
+                    tokio::time::sleep(duration).await;
+                    let _ = sender.send(());
+                });
+                if let Some(previous) = self.sleep_timer.replace(SleepTimer {
+                    deadline: Instant::now() + duration,
+                    mode,
+                    fade,
+                    task,
+                }) {
+                    previous.task.abort();
+                }
+                self.state.sleep_timer_remaining_secs = Some(minutes * 60);
+                // Undo any fade from a timer that was about to run out
+                if let Some(sink) = &self.sink {
+                    sink.set_volume(self.track_volume() * self.end_fade_level());
+                }
+                let _ = self.event_sender.send(PlayerEvent::StateChanged(self.state.clone()));
             }
-            self.sink = None;
-            
-            // Restart playback from the new position using cached data
-            if let Err(e) = self.play_item_with_offset(current_song, position).await {
-                println!("Failed to seek: {}", e);
-                return;
+            None => self.cancel_sleep_timer(),
+        }
+    }
+
+    fn cancel_sleep_timer(&mut self) {
+        let Some(timer) = self.sleep_timer.take() else {
+            return;
+        };
+        timer.task.abort();
+        info!("Sleep timer cancelled");
+        self.state.sleep_timer_remaining_secs = None;
+        if let Some(sink) = &self.sink {
+            sink.set_volume(self.track_volume() * self.end_fade_level());
+        }
+        let _ = self.event_sender.send(PlayerEvent::StateChanged(self.state.clone()));
+    }
+
+    fn sleep_fade_level(&self) -> f32 {
+        match &self.sleep_timer {
+            Some(timer) if timer.fade => {
+                let remaining = timer.deadline.saturating_duration_since(Instant::now()).as_secs_f64();
+                (remaining / SLEEP_FADE_SECONDS).clamp(0.0, 1.0) as f32
             }
-            
-            // If it was paused before seeking, pause it again
-            if !was_playing {
+            _ => 1.0,
+        }
+    }
+
+    // Pause or stop playback once the sleep timer's task wakes up
+    fn finish_sleep_timer(&mut self) {
+        // A task aborted just after it sent still lands here, so only act on a timer that's due
+        let Some(timer) = self.sleep_timer.take_if(|timer| timer.deadline <= Instant::now()) else {
+            return;
+        };
+
+        info!("Sleep timer finished");
+        self.state.sleep_timer_remaining_secs = None;
+        match timer.mode {
+            SleepTimerMode::Pause => {
+                self.pause();
+                // Back to full volume for whenever playback resumes
                 if let Some(sink) = &self.sink {
-                    sink.pause();
-                    self.state.is_playing = false;
-                    self.audio_start_time = None;
+                    sink.set_volume(self.track_volume() * self.end_fade_level());
                 }
             }
-            
+            SleepTimerMode::Stop => self.stop(),
+        }
+        let _ = self.event_sender.send(PlayerEvent::StateChanged(self.state.clone()));
+    }
+
+    // Keep the sleep timer's countdown current and ramp the volume down over its final stretch
+    fn step_sleep_fade(&mut self) {
+        let Some(timer) = &self.sleep_timer else {
+            return;
+        };
+        let fade = timer.fade;
+
+        let remaining = timer.deadline.saturating_duration_since(Instant::now());
+        let remaining_secs = remaining.as_secs_f64().ceil() as u32;
+        if self.state.sleep_timer_remaining_secs != Some(remaining_secs) {
+            self.state.sleep_timer_remaining_secs = Some(remaining_secs);
             let _ = self.event_sender.send(PlayerEvent::StateChanged(self.state.clone()));
         }
+        if fade && remaining.as_secs_f64() < SLEEP_FADE_SECONDS {
+            if let Some(sink) = &self.sink {
+                sink.set_volume(self.track_volume() * self.end_fade_level());
+            }
+        }
+    }
+
+    // Fraction of the user volume the sink should play at right now
+    fn end_fade_level(&self) -> f32 {
+        match self.end_fade {
+            Some(fade) if fade.end > fade.start => {
+                ((fade.end - self.state.current_position) / (fade.end - fade.start)).clamp(0.0, 1.0) as f32
+            }
+            Some(_) => 0.0,
+            None => 1.0,
+        }
+    }
+
+    // Advance the fade-out ramp, stopping once it reaches silence
+    fn step_end_fade(&mut self) {
+        let Some(fade) = self.end_fade else {
+            return;
+        };
+
+        if self.state.current_position >= fade.end {
+            info!("Queue finished, fade complete");
+            self.stop();
+            return;
+        }
+
+        if let Some(sink) = &self.sink {
+            sink.set_volume(self.track_volume() * self.end_fade_level());
+        }
+    }
+
+    // Leading silence of a track about to start gaplessly, from audio we already have locally
+    async fn leading_silence_of(&self, item: &QueueItem) -> f64 {
+        let data = match self.warm_audio.get(&item.id) {
+            Some(data) => data.clone(),
+            None => match item.stream_url.strip_prefix("file://") {
+                Some(file_path) => match tokio::fs::read(file_path).await {
+                    Ok(data) => data.into(),
+                    Err(_) => return 0.0,
+                },
+                // Not worth a separate download just to measure silence
+                None => return 0.0,
+            },
+        };
+
+        SymphoniaSource::from_data(data)
+            .map(|mut source| source.leading_silence())
+            .unwrap_or(0.0)
+    }
+
+    // Measure the current track's trailing silence on the blocking pool, if it's going to hand
+    // over gaplessly. Needs the whole file: a track that's still streaming is measured once its
+    // download completes, and one that never finishes downloading plays out in full
+    fn scan_trailing_silence(&mut self) {
+        self.trailing_silence = 0.0;
+        if !self.state.trim_silence {
+            return;
+        }
+
+        let gapless_next = self.following_index()
+            .and_then(|index| self.queue.get(index))
+            .is_some_and(|next| self.resolve_transition(next) == TransitionMode::Gapless);
+        if !gapless_next {
+            return;
+        }
+
+        let Some(song_id) = self.state.current_song.as_ref().map(|song| song.id.clone()) else {
+            return;
+        };
+        if self.cached_song_id.as_ref() != Some(&song_id) {
+            return;
+        }
+        let Some(data) = self.cached_audio_data.clone() else {
+            return;
+        };
+
+        let sender = self.silence_scan_sender.clone();
+        tokio::task::spawn_blocking(move || {
+            let seconds = SymphoniaSource::from_data(data)
+                .map(|mut source| source.trailing_silence())
+                .unwrap_or(0.0);
+            let _ = sender.send((song_id, seconds));
+        });
+    }
+
+    fn finish_silence_scan(&mut self, song_id: String, seconds: f64) {
+        // The track may have changed, or trimming been turned off, while the scan ran
+        let still_current = self.state.current_song.as_ref().is_some_and(|song| song.id == song_id);
+        if !still_current || !self.state.trim_silence {
+            return;
+        }
+
+        self.trailing_silence = seconds;
+        if seconds > 0.0 {
+            info!("Ending {:.2}s early to skip trailing silence", seconds);
+        }
+    }
+
+    // Queue index that plays after the current track, in the current (possibly shuffled) order
+    fn following_index(&self) -> Option<usize> {
+        self.current_index.and_then(|index| {
+            if index + 1 < self.queue.len() {
+                Some(index + 1)
+            } else if matches!(self.state.repeat_mode, RepeatMode::All) {
+                Some(0)
+            } else {
+                None
+            }
+        })
+    }
+
+    // What plays when the current track runs out, honouring repeat-one
+    fn upcoming_index(&self) -> Option<usize> {
+        if matches!(self.state.repeat_mode, RepeatMode::One) {
+            self.current_index
+        } else {
+            self.following_index()
+        }
     }
-} 
\ No newline at end of file
+
+    // Audio of the track that plays next, if it's already local
+    async fn upcoming_audio(&self, item: &QueueItem) -> Option<AudioData> {
+        if self.cached_song_id.as_ref() == Some(&item.id) {
+            self.cached_audio_data.clone()
+        } else if let Some(data) = self.warm_audio.get(&item.id) {
+            Some(data.clone())
+        } else if let Some(file_path) = item.stream_url.strip_prefix("file://") {
+            tokio::fs::read(file_path).await.ok().map(Into::into)
+        } else {
+            None
+        }
+    }
+
+    // Shortly before a track ends, line up the next one so it follows without a reload: appended
+    // to the same sink for gapless, or on a second sink for a crossfade. Only done when its audio
+    // is already local; otherwise the usual reload takes over at the end of the track
+    async fn prepare_transition(&mut self) {
+        if self.gapless_next.is_some() || self.end_fade.is_some() || self.sink.is_none() || self.state.duration <= 0.0 {
+            return;
+        }
+        let Some(index) = self.upcoming_index() else {
+            return;
+        };
+        let Some(item) = self.queue.get(index).cloned() else {
+            return;
+        };
+
+        let remaining = self.state.duration - self.trailing_silence - self.state.current_position;
+        let transition = self.resolve_transition(&item);
+        let crossfade = match transition {
+            TransitionMode::Gapless if self.trailing_silence <= 0.0 && remaining <= GAPLESS_PREPARE_SECONDS => None,
+            // A track shorter than the window gets a shorter fade, so it's still heard on its own
+            TransitionMode::Crossfade(seconds) => {
+                let fade = (seconds as f64).min(self.state.duration / 2.0);
+                if remaining > fade {
+                    return;
+                }
+                Some(remaining.max(MIN_CROSSFADE_SECONDS))
+            }
+            _ => return,
+        };
+
+        let Some(data) = self.upcoming_audio(&item).await else {
+            return;
+        };
+        let mut source = match SymphoniaSource::from_data(data.clone()) {
+            Ok(source) => source,
+            Err(e) => {
+                warn!("Can't prepare {} for a {:?} transition: {}", item.name, transition, e);
+                return;
+            }
+        };
+        let start = if self.state.trim_silence && crossfade.is_none() {
+            match source.skip_leading_silence() {
+                Ok(start) => start,
+                Err(e) => {
+                    warn!("Can't skip the silence at the start of {}: {}", item.name, e);
+                    return;
+                }
+            }
+        } else {
+            0.0
+        };
+        let prepared = PreparedTrack {
+            index,
+            item,
+            start,
+            data,
+            stats: source.stats.clone(),
+        };
+
+        match crossfade {
+            None => {
+                let Some(sink) = &self.sink else {
+                    return;
+                };
+                info!("Queued {} for a gapless handover", prepared.item.name);
+                sink.append(ProcessingSource::new(source.speed(self.effective_speed()), self.processing.clone()));
+                self.gapless_next = Some(prepared);
+            }
+            Some(fade) => self.start_crossfade(source, prepared, fade),
+        }
+    }
+
+    // Fade the current track out on its own sink while the next one fades in on a new sink
+    fn start_crossfade(&mut self, source: SymphoniaSource, next: PreparedTrack, fade: f64) {
+        let new_sink = match self.new_sink() {
+            Ok(sink) => sink,
+            Err(e) => {
+                warn!("Failed to create sink for crossfade: {}", e);
+                return;
+            }
+        };
+        info!("Crossfading into {} over {:.1}s", next.item.name, fade);
+
+        // Both fades run per sample inside the sources, so they stay smooth between ticks
+        if let Some(stats) = &self.playing_stats {
+            let fade_samples = (fade * self.sample_rate_of_playing() as f64) as u64;
+            stats.fade_out_samples.store(fade_samples.max(1), Ordering::Relaxed);
+        }
+        new_sink.set_volume(self.volume_for(Some(&next.item)));
+        let speed = self.effective_speed();
+        let fade_in = source.speed(speed).fade_in(Duration::from_secs_f64(fade / speed as f64));
+        new_sink.append(ProcessingSource::new(fade_in, self.processing.clone()));
+
+        if let Some(outgoing) = self.sink.replace(new_sink) {
+            if let Some(fading) = self.fading_sink.replace(outgoing) {
+                fading.stop();
+            }
+        }
+        self.take_over_track(next);
+    }
+
+    // Samples per second of the playing source, all channels included
+    fn sample_rate_of_playing(&self) -> u32 {
+        self.symphonia_source
+            .as_ref()
+            .map(|source| source.sample_rate * source.channels as u32)
+            .unwrap_or(44100 * 2)
+    }
+
+    // Take over the gapless track once the sink has moved on to it
+    fn check_gapless_handover(&mut self) {
+        let Some(next) = &self.gapless_next else {
+            return;
+        };
+
+        // The queue or repeat mode may have changed since it was appended
+        let still_next = self.upcoming_index() == Some(next.index)
+            && self.queue.get(next.index).map(|item| &item.id) == Some(&next.item.id);
+        if !still_next {
+            info!("Dropping gapless {}, it's no longer next", next.item.name);
+            next.stats.cancelled.store(true, Ordering::Relaxed);
+            self.gapless_next = None;
+            return;
+        }
+
+        let handed_over = self.sink.as_ref().is_some_and(|sink| sink.len() <= 1);
+        if !handed_over {
+            return;
+        }
+        let Some(next) = self.gapless_next.take() else {
+            return;
+        };
+
+        info!("Gapless handover to {}", next.item.name);
+        self.take_over_track(next);
+    }
+
+    // Make a track that's already playing in the sink the current one
+    fn take_over_track(&mut self, next: PreparedTrack) {
+        self.record_history();
+        self.current_started_at = unix_now_secs();
+        self.abandon_stream();
+
+        // Keep the outgoing song warm, like any other change of track
+        if self.cached_song_id.as_ref() != Some(&next.item.id) {
+            if let (Some(song_id), Some(data)) = (self.cached_song_id.take(), self.cached_audio_data.take()) {
+                self.warm_audio.insert(song_id, data);
+            }
+        }
+        self.warm_audio.remove(&next.item.id);
+
+        let mut seeking_source = SymphoniaSource::from_data(next.data.clone()).ok();
+        if let Some(source) = seeking_source.as_mut() {
+            let _ = source.seek_to_time(next.start);
+        }
+        let duration = next.item.duration_ticks
+            .map(|ticks| ticks as f64 / 10_000_000.0)
+            .or_else(|| seeking_source.as_ref()
+                .and_then(|source| source.total_duration())
+                .map(|d| d.as_secs_f64()))
+            .unwrap_or(0.0);
+
+        self.current_index = Some(next.index);
+        self.cached_audio_data = Some(next.data);
+        self.cached_song_id = Some(next.item.id.clone());
+        self.symphonia_source = seeking_source;
+        self.playing_stats = Some(next.stats);
+        self.state.current_song = Some(next.item.clone());
+        self.state.duration = duration;
+        self.state.current_position = next.start;
+        self.set_transcoding_state(&next.item);
+        self.visual_position = next.start;
+        self.played_at_anchor = 0.0;
+        self.audio_start_time = self.state.is_playing.then(Instant::now);
+        self.scan_trailing_silence();
+        self.update_warm_window();
+        // A gapless track shares the sink, so its normalization gain applies from here
+        if let Some(sink) = &self.sink {
+            sink.set_volume(self.track_volume());
+        }
+
+        let _ = self.event_sender.send(PlayerEvent::TrackChanged(Some(next.item)));
+        let _ = self.event_sender.send(PlayerEvent::StateChanged(self.state.clone()));
+    }
+
+    fn set_warm_window(&mut self, window: usize) {
+        self.warm_window = window;
+        info!("Warm window set to {} track(s) each side", window);
+        self.update_warm_window();
+    }
+
+    // Queue items within the warm window around the current track, excluding the current one
+    fn warm_neighbours(&self) -> Vec<QueueItem> {
+        let Some(current) = self.current_index else {
+            return Vec::new();
+        };
+
+        let start = current.saturating_sub(self.warm_window);
+        let end = (current + self.warm_window).min(self.queue.len().saturating_sub(1));
+        (start..=end)
+            .filter(|&index| index != current)
+            .filter_map(|index| self.queue.get(index).cloned())
+            .collect()
+    }
+
+    // Evict warm data outside the window and start loading any neighbours that are missing
+    fn update_warm_window(&mut self) {
+        let neighbours = self.warm_neighbours();
+        let wanted: HashSet<&str> = neighbours.iter().map(|item| item.id.as_str()).collect();
+
+        self.warm_audio.retain(|song_id, _| wanted.contains(song_id.as_str()));
+
+        for item in &neighbours {
+            if self.warm_audio.contains_key(&item.id)
+                || self.warming.contains(&item.id)
+                || self.cached_song_id.as_ref() == Some(&item.id)
+            {
+                continue;
+            }
+
+            self.warming.insert(item.id.clone());
+            let sender = self.warm_sender.clone();
+            let song_id = item.id.clone();
+            let url = item.stream_url.clone();
+            let client = self.http_client.clone();
+            tokio::spawn(async move {
+                let result = load_audio_data(&client, &url).await.map(Into::into);
+                let _ = sender.send((song_id, result));
+            });
+        }
+    }
+
+    fn finish_warming(&mut self, song_id: String, result: Result<AudioData, String>) {
+        self.warming.remove(&song_id);
+
+        // The window may have moved while this was loading
+        let still_wanted = self.warm_neighbours().iter().any(|item| item.id == song_id);
+        match result {
+            Ok(data) if still_wanted => {
+                info!("Warmed audio data for song: {} ({} bytes)", song_id, data.len());
+                self.warm_audio.insert(song_id, data);
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Failed to warm audio for {}: {}", song_id, e),
+        }
+    }
+
+    async fn next_track(&mut self) {
+        if self.queue.is_empty() {
+            return;
+        }
+
+        let next_index = next_queue_index(self.current_index, self.queue.len(), &self.state.repeat_mode);
+
+        if next_index.is_none() {
+            // Out of tracks: fade out from here instead of carrying on, if configured
+            let fade = self.state.end_of_queue_fade as f64;
+            if fade > 0.0 && self.state.is_playing && self.end_fade.is_none() {
+                let start = self.state.current_position;
+                let end = (start + fade).min(self.state.duration - self.trailing_silence);
+                info!("End of queue, fading out over {:.1}s", end - start);
+                self.end_fade = Some(EndFade { start, end });
+            }
+        }
+
+        if let Some(index) = next_index {
+            if let Some(item) = self.queue.get(index).cloned() {
+                let transition = self.resolve_transition(&item);
+                info!("Advancing to {} ({:?} transition)", item.name, transition);
+                let start = if self.state.trim_silence && transition == TransitionMode::Gapless {
+                    self.leading_silence_of(&item).await
+                } else {
+                    0.0
+                };
+                if start > 0.0 {
+                    info!("Skipping {:.2}s of leading silence", start);
+                }
+                self.current_index = Some(index);
+                let _ = self.play_item_at(item, start).await;
+            }
+        }
+    }
+
+    async fn previous_track(&mut self) {
+        if self.queue.is_empty() {
+            return;
+        }
+
+        let prev_index = match self.current_index {
+            Some(index) => {
+                if index == 0 {
+                    // At beginning of queue
+                    match self.state.repeat_mode {
+                        RepeatMode::All => Some(self.queue.len() - 1), // Loop to end
+                        _ => None,                                     // No previous tracks
+                    }
+                } else {
+                    Some(index - 1)
+                }
+            }
+            None => Some(self.queue.len() - 1), // Start from end
+        };
+
+        if let Some(index) = prev_index {
+            if let Some(item) = self.queue.get(index).cloned() {
+                self.current_index = Some(index);
+                let _ = self.play_item(item).await;
+            }
+        }
+    }
+
+    async fn seek_percent(&mut self, percent: f64) {
+        if !percent.is_finite() || self.state.duration <= 0.0 {
+            warn!("Ignoring percent seek: no known duration");
+            return;
+        }
+
+        let position = self.state.duration * percent.clamp(0.0, 100.0) / 100.0;
+        self.seek(position).await;
+    }
+
+    // Chapters for the current track: server chapters, then embedded cue points,
+    // otherwise the whole track as a single chapter
+    fn current_chapters(&self) -> Vec<Chapter> {
+        let mut chapters = self.state.current_song
+            .as_ref()
+            .map(|song| song.chapters.clone())
+            .unwrap_or_default();
+
+        if chapters.is_empty() {
+            chapters = self.symphonia_source
+                .as_ref()
+                .map(|source| source.chapters())
+                .unwrap_or_default();
+        }
+
+        if chapters.is_empty() {
+            chapters.push(Chapter { name: None, start: 0.0 });
+        }
+
+        chapters.sort_by(|a, b| a.start.total_cmp(&b.start));
+        chapters
+    }
+
+    // Index of the chapter containing the current position
+    fn current_chapter_index(&self, chapters: &[Chapter]) -> usize {
+        chapters
+            .iter()
+            .rposition(|chapter| chapter.start <= self.state.current_position + 0.01)
+            .unwrap_or(0)
+    }
+
+    async fn seek_within_chapter(&mut self, offset: f64) {
+        if self.state.current_song.is_none() || !offset.is_finite() {
+            return;
+        }
+
+        self.update_position();
+        let chapters = self.current_chapters();
+        let index = self.current_chapter_index(&chapters);
+        let chapter_start = chapters[index].start;
+        let chapter_end = chapters.get(index + 1).map(|next| next.start).unwrap_or(self.state.duration);
+
+        let target = (self.state.current_position + offset).clamp(chapter_start, chapter_end.max(chapter_start));
+        self.seek(target).await;
+    }
+
+    async fn next_chapter(&mut self) {
+        if self.state.current_song.is_none() {
+            return;
+        }
+
+        self.update_position();
+        let chapters = self.current_chapters();
+        let index = self.current_chapter_index(&chapters);
+        if let Some(next) = chapters.get(index + 1) {
+            info!("Next chapter: {:?} at {}s", next.name, next.start);
+            self.seek(next.start).await;
+        }
+    }
+
+    async fn previous_chapter(&mut self) {
+        if self.state.current_song.is_none() {
+            return;
+        }
+
+        self.update_position();
+        let chapters = self.current_chapters();
+        let index = self.current_chapter_index(&chapters);
+
+        // Like previous track: restart the chapter unless we're right at its start
+        let into_chapter = self.state.current_position - chapters[index].start;
+        let target = if into_chapter > CHAPTER_RESTART_THRESHOLD_SECONDS || index == 0 {
+            &chapters[index]
+        } else {
+            &chapters[index - 1]
+        };
+        info!("Previous chapter: {:?} at {}s", target.name, target.start);
+        let start = target.start;
+        self.seek(start).await;
+    }
+
+    async fn seek(&mut self, position: f64) {
+        let Some(position) = seek_target(position, self.state.duration) else {
+            info!("Seek to {:.2}s reaches the end of the track", position);
+            self.finish_track().await;
+            return;
+        };
+
+        info!("INSTANT SEEK to position: {} seconds", position);
+        
+        if let Some(current_song) = self.state.current_song.clone() {
+            let was_playing = self.state.is_playing;
+            
+            // Seeking past what has arrived so far falls back to waiting for the whole download
+            if self.active_stream.is_some() && !self.stream_covers(position) {
+                if let Err(e) = self.finish_stream().await {
+                    warn!("{}", e);
+                }
+            }
+            
+            // INSTANT SEEK: Create new SymphoniaSource at seek position! 🚀
+            if self.cached_audio_data.is_some() || self.active_stream.is_some() {
+                match self.instant_seek(position, was_playing) {
+                    Ok(()) => return,
+                    Err(InstantSeekError::CorruptData(e)) => {
+                        // The cached bytes no longer decode (e.g. a truncated stream got cached),
+                        // so every seek would fail the same way. Re-download them and retry once.
+                        warn!("Cached audio data is unusable ({}), refreshing from stream", e);
+                        self.cached_audio_data = None;
+                        self.cached_song_id = None;
+                        // It would fail the same way next time it plays. Whoever owns the file decides
+                        // what to do with it: offline downloads aren't the player's to delete
+                        if let Some(file_path) = current_song.stream_url.strip_prefix("file://") {
+                            let _ = self.event_sender.send(PlayerEvent::LocalFileUnusable {
+                                item_id: current_song.id.clone(),
+                                path: file_path.to_string(),
+                            });
+                        }
+                        
+                        match self.refresh_cached_audio(&current_song).await {
+                            Ok(()) => {
+                                let _ = self.event_sender.send(PlayerEvent::AudioCacheRefreshed(current_song.id.clone()));
+                                match self.instant_seek(position, was_playing) {
+                                    Ok(()) => return,
+                                    Err(InstantSeekError::CorruptData(e)) | Err(InstantSeekError::Failed(e)) => {
+                                        warn!("Seek still failing after cache refresh: {}", e);
+                                    }
+                                }
+                            }
+                            Err(e) => {
+                                warn!("Failed to refresh cached audio: {}", e);
+                                let _ = self.event_sender.send(PlayerEvent::Error(e));
+                            }
+                        }
+                    }
+                    Err(InstantSeekError::Failed(e)) => {
+                        warn!("{}", e);
+                    }
+                }
+            }
+            
+            // Fallback: restart playback method (slower but reliable)
+            info!("Falling back to restart-based seeking");
+            
+            // Stop current playback
+            if let Some(sink) = &self.sink {
+                sink.stop();
+            }
+            self.sink = None;
+            
+            // Restart playback from the new position using cached data
+            if let Err(e) = self.play_item_with_offset(current_song, position).await {
+                warn!("Failed to seek: {}", e);
+                return;
+            }
+            
+            // If it was paused before seeking, pause it again
+            if !was_playing {
+                if let Some(sink) = &self.sink {
+                    sink.pause();
+                    self.state.is_playing = false;
+                    self.audio_start_time = None;
+                }
+            }
+            
+            let _ = self.event_sender.send(PlayerEvent::StateChanged(self.state.clone()));
+        }
+    }
+
+    fn has_next_track(&self) -> bool {
+        self.current_index.is_some() && next_queue_index(self.current_index, self.queue.len(), &self.state.repeat_mode).is_some()
+    }
+
+    // Handle reaching the end of the current track according to the repeat mode
+    async fn finish_track(&mut self) {
+        let Some(current_song) = self.state.current_song.clone() else {
+            return;
+        };
+
+        let has_next = self.has_next_track();
+
+        if matches!(self.state.repeat_mode, RepeatMode::One) {
+            let _ = self.play_item_at(current_song, 0.0).await;
+        } else if has_next {
+            self.next_track().await;
+        } else {
+            // Real end of the queue
+            info!("End of queue");
+            self.stop();
+        }
+    }
+
+    fn set_output_device(&mut self, name: Option<String>) -> Result<(), String> {
+        let (stream, stream_handle) = open_output(name.as_deref())?;
+        info!("Output device: {}", name.as_deref().unwrap_or("system default"));
+        self.switch_output(stream, stream_handle);
+        self.state.output_device = name;
+        let _ = self.event_sender.send(PlayerEvent::StateChanged(self.state.clone()));
+        Ok(())
+    }
+
+    // Move playback onto a new output stream, resuming the current track where it was
+    fn switch_output(&mut self, stream: OutputStream, stream_handle: OutputStreamHandle) {
+        self.update_position();
+        let was_playing = self.state.is_playing;
+        if let Some(fading_sink) = self.fading_sink.take() {
+            fading_sink.stop();
+        }
+        self.output = Some((stream, stream_handle));
+        
+        // Sinks are tied to the stream they were made on, so the current one has to be rebuilt
+        if self.sink.is_some() {
+            if let Err(InstantSeekError::CorruptData(e)) | Err(InstantSeekError::Failed(e)) =
+                self.instant_seek(self.state.current_position, was_playing)
+            {
+                error!("Failed to resume on the new output: {}", e);
+                if let Some(sink) = self.sink.take() {
+                    sink.stop();
+                }
+                self.state.is_playing = false;
+                self.audio_start_time = None;
+                let _ = self.event_sender.send(PlayerEvent::Error(format!("Failed to resume playback on the new output: {}", e)));
+            }
+        }
+    }
+
+    // Look for the chosen output device off the worker, so enumerating devices never stalls playback
+    fn check_output_device(&mut self) {
+        let Some(name) = self.state.output_device.clone() else {
+            return;
+        };
+        if self.device_check_pending || self.last_device_check.elapsed() < OUTPUT_DEVICE_CHECK_INTERVAL {
+            return;
+        }
+        self.last_device_check = Instant::now();
+        self.device_check_pending = true;
+        
+        let sender = self.device_check_sender.clone();
+        tokio::task::spawn_blocking(move || {
+            let found = matches!(find_output_device(&name), Ok(Some(_)));
+            let _ = sender.send((name, found));
+        });
+    }
+
+    // Fall back to the default output when the chosen device has been unplugged
+    fn finish_device_check(&mut self, name: String, found: bool) {
+        self.device_check_pending = false;
+        // The listener may have picked another output while the lookup ran
+        if found || self.state.output_device.as_deref() != Some(name.as_str()) {
+            return;
+        }
+        
+        info!("Output device {} disappeared, switching to the default", name);
+        match open_output(None) {
+            Ok((stream, stream_handle)) => {
+                self.switch_output(stream, stream_handle);
+                self.state.output_device = None;
+                let _ = self.event_sender.send(PlayerEvent::Error(format!(
+                    "Audio output \"{}\" was disconnected; playing through the default output", name
+                )));
+                let _ = self.event_sender.send(PlayerEvent::StateChanged(self.state.clone()));
+            }
+            Err(e) => {
+                let _ = self.event_sender.send(PlayerEvent::Error(e));
+            }
+        }
+    }
+
+    // Rebuild the sink from the in-memory audio data (or the part of it downloaded so far) at the requested position
+    fn instant_seek(&mut self, position: f64, was_playing: bool) -> Result<(), InstantSeekError> {
+        if self.cached_audio_data.is_none() && self.active_stream.is_none() {
+            return Err(InstantSeekError::Failed("No cached audio data".to_string()));
+        }
+        
+        let mut new_source = self.open_current_source()
+            .map_err(InstantSeekError::CorruptData)?;
+        
+        // Seek the new source to the desired position, reporting where it actually landed
+        let position = new_source.seek_to_time(position)
+            .map_err(|e| InstantSeekError::Failed(format!("Symphonia seek failed: {}", e)))?;
+        
+        info!("INSTANT SEEK: Creating new playback source at {}s", position);
+        
+        // Stop current playback
+        if let Some(sink) = &self.sink {
+            sink.stop();
+        }
+        
+        // Create new sink with the sought source
+        let new_sink = self.new_sink()
+            .map_err(|e| InstantSeekError::Failed(format!("Failed to create new sink: {}", e)))?;
+        new_sink.set_volume(self.track_volume());
+        self.playing_stats = Some(new_source.stats.clone());
+        new_sink.append(ProcessingSource::new(new_source.speed(self.effective_speed()), self.processing.clone()));
+        
+        // Update stored source for future seeks
+        if let Ok(mut seeking_source) = self.open_current_source() {
+            let _ = seeking_source.seek_to_time(position);
+            self.symphonia_source = Some(seeking_source);
+        }
+        
+        // If was paused, pause the new sink
+        if !was_playing {
+            new_sink.pause();
+        }
+        
+        // Update state
+        self.sink = Some(new_sink);
+        self.end_fade = None;
+        self.track_ended = false;
+        self.gapless_next = None;
+        self.state.current_position = position;
+        self.visual_position = position;
+        self.played_at_anchor = 0.0;
+        self.state.is_playing = was_playing;
+        self.audio_start_time = if was_playing { Some(Instant::now()) } else { None };
+        
+        info!("INSTANT SEEK completed! Now playing from {}s", position);
+        let _ = self.event_sender.send(PlayerEvent::StateChanged(self.state.clone()));
+        Ok(())
+    }
+
+    // Decoder over the current song's bytes: the full data when we have it, else the live download
+    fn open_current_source(&self) -> Result<SymphoniaSource, String> {
+        match (&self.cached_audio_data, &self.active_stream) {
+            (Some(data), _) => SymphoniaSource::from_data(data.clone()),
+            (None, Some(stream)) => SymphoniaSource::from_stream(stream.buffer.clone()),
+            (None, None) => Err("No cached audio data".to_string()),
+        }
+    }
+
+    // Start a song from the network, returning a source that plays while the rest downloads
+    async fn start_progressive(&mut self, item: &QueueItem) -> Result<SymphoniaSource, String> {
+        info!("Streaming audio progressively");
+        let _ = self.event_sender.send(PlayerEvent::Buffering(true));
+        let download_started = Instant::now();
+        let opened = open_stream(&self.http_client, &item.stream_url).await;
+        let probed = match &opened {
+            Ok(buffer) => {
+                let probe_buffer = buffer.clone();
+                let probed = tokio::task::spawn_blocking(move || SymphoniaSource::from_stream(probe_buffer)).await
+                    .unwrap_or_else(|e| Err(format!("Failed to start audio stream: {}", e)));
+                if probed.is_err() {
+                    buffer.abandon();
+                }
+                probed
+            }
+            Err(e) => Err(e.clone()),
+        };
+        let _ = self.event_sender.send(PlayerEvent::Buffering(false));
+        self.record_buffering(download_started.elapsed());
+
+        let buffer = match opened {
+            Ok(buffer) => buffer,
+            Err(e) => {
+                self.hold_for_recovery(item, 0.0);
+                return Err(e);
+            }
+        };
+        let source = probed?;
+        if self.pending_recovery.take().is_some() {
+            info!("Stream reloaded, playback recovered");
+            let _ = self.event_sender.send(PlayerEvent::Reconnecting(false));
+        }
+        self.active_stream = Some(ActiveStream {
+            song_id: item.id.clone(),
+            url: item.stream_url.clone(),
+            buffer,
+        });
+        Ok(source)
+    }
+
+    // Rough check that the bytes for a position have arrived, assuming a constant bitrate
+    fn stream_covers(&self, position: f64) -> bool {
+        let Some(stream) = &self.active_stream else {
+            return false;
+        };
+        if position <= 0.0 {
+            return true;
+        }
+        match stream.buffer.total_len {
+            Some(total) if self.state.duration > 0.0 => {
+                let needed = (position / self.state.duration * total as f64) as u64 + STREAM_SEEK_MARGIN_BYTES;
+                stream.buffer.downloaded() >= needed.min(total)
+            }
+            _ => false,
+        }
+    }
+
+    // Drop a progressive download for another song, or finish this song's first when
+    // playback has to start past the bytes that have arrived
+    async fn settle_stream(&mut self, song_id: &str, position: f64) {
+        match &self.active_stream {
+            Some(stream) if stream.song_id != song_id => self.abandon_stream(),
+            Some(_) if !self.stream_covers(position) => {
+                if let Err(e) = self.finish_stream().await {
+                    warn!("{}", e);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Wait for the rest of the progressive download
+    async fn finish_stream(&mut self) -> Result<(), String> {
+        let Some(buffer) = self.active_stream.as_ref().map(|stream| stream.buffer.clone()) else {
+            return Ok(());
+        };
+
+        let _ = self.event_sender.send(PlayerEvent::Buffering(true));
+        let outcome = loop {
+            if let Some(outcome) = buffer.outcome() {
+                break outcome;
+            }
+            tokio::time::sleep(STREAM_POLL_INTERVAL).await;
+        };
+        let _ = self.event_sender.send(PlayerEvent::Buffering(false));
+        self.absorb_stream(outcome)
+    }
+
+    // Once the progressive download is done, keep its bytes for instant seeking like any other load
+    fn absorb_stream(&mut self, outcome: Result<Vec<u8>, String>) -> Result<(), String> {
+        let Some(stream) = self.active_stream.take() else {
+            return Ok(());
+        };
+        let data: AudioData = outcome?.into();
+
+        info!("Finished streaming {} ({} bytes)", stream.song_id, data.len());
+        self.announce_download(&stream.song_id, &stream.url, &data);
+        self.cached_audio_data = Some(data);
+        self.cached_song_id = Some(stream.song_id);
+        // Only now is the whole file here to look for silence at its end
+        self.scan_trailing_silence();
+        Ok(())
+    }
+
+    // Offer a full download to the audio cache rather than have it fetch the song again
+    fn announce_download(&self, item_id: &str, url: &str, data: &AudioData) {
+        let _ = self.event_sender.send(PlayerEvent::AudioDownloaded {
+            item_id: item_id.to_string(),
+            url: url.to_string(),
+            data: data.clone(),
+        });
+    }
+
+    fn poll_stream(&mut self) {
+        let Some(outcome) = self.active_stream.as_ref().and_then(|stream| stream.buffer.outcome()) else {
+            return;
+        };
+        if let Err(e) = self.absorb_stream(outcome) {
+            warn!("{}", e);
+            // Playback ran dry where the bytes stopped; reload from there like any failed stream
+            if let Some(current_song) = self.state.current_song.clone() {
+                if let Some(sink) = self.sink.take() {
+                    sink.stop();
+                }
+                self.state.is_playing = false;
+                self.audio_start_time = None;
+                self.hold_for_recovery(&current_song, self.state.current_position);
+                let _ = self.event_sender.send(PlayerEvent::StateChanged(self.state.clone()));
+            }
+        }
+    }
+
+    fn abandon_stream(&mut self) {
+        if let Some(stream) = self.active_stream.take() {
+            stream.buffer.abandon();
+        }
+    }
+
+    // Re-fetch the current song's bytes, preferring the remote stream over a possibly corrupt cached file
+    async fn refresh_cached_audio(&mut self, item: &QueueItem) -> Result<(), String> {
+        let url = item.remote_stream_url.as_ref().unwrap_or(&item.stream_url);
+        let data = load_audio_data(&self.http_client, url).await?;
+        
+        self.abandon_stream();
+        self.cached_audio_data = Some(data.into());
+        self.cached_song_id = Some(item.id.clone());
+        info!("Refreshed cached audio data for song: {}", item.id);
+        Ok(())
+    }
+}
+
+// Names of the output devices on the default audio host
+pub fn list_output_devices() -> Result<Vec<String>, String> {
+    let devices = rodio::cpal::default_host()
+        .output_devices()
+        .map_err(|e| format!("Failed to list audio outputs: {}", e))?;
+    Ok(devices.filter_map(|device| device.name().ok()).collect())
+}
+
+fn find_output_device(name: &str) -> Result<Option<rodio::Device>, String> {
+    let mut devices = rodio::cpal::default_host()
+        .output_devices()
+        .map_err(|e| format!("Failed to list audio outputs: {}", e))?;
+    Ok(devices.find(|device| device.name().is_ok_and(|device_name| device_name == name)))
+}
+
+// Output stream on the named device, or the system default for None
+fn open_output(name: Option<&str>) -> Result<(OutputStream, OutputStreamHandle), String> {
+    let Some(name) = name else {
+        return OutputStream::try_default().map_err(|e| format!("Failed to open the default audio output: {}", e));
+    };
+    let device = find_output_device(name)?.ok_or_else(|| format!("Audio output not found: {}", name))?;
+    OutputStream::try_from_device(&device).map_err(|e| format!("Failed to open audio output {}: {}", name, e))
+}
+
+// Start downloading a song, handing back its buffer as soon as the response headers are in
+async fn open_stream(client: &reqwest::Client, url: &str) -> Result<Arc<StreamBuffer>, String> {
+    let response = client.get(url).send().await
+        .map_err(|e| format!("Failed to download audio: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("Failed to download audio: {}", response.status()));
+    }
+
+    let buffer = Arc::new(StreamBuffer::new(response.content_length()));
+    let writer = buffer.clone();
+    tokio::spawn(async move {
+        let mut body = response.bytes_stream();
+        while let Some(chunk) = body.next().await {
+            if writer.is_abandoned() {
+                return;
+            }
+            match chunk {
+                Ok(bytes) => writer.update(|state| state.data.extend_from_slice(&bytes)),
+                Err(e) => {
+                    writer.update(|state| state.error = Some(format!("Audio stream failed: {}", e)));
+                    return;
+                }
+            }
+        }
+
+        let expected = writer.total_len;
+        writer.update(|state| match expected {
+            Some(expected) if state.data.len() as u64 != expected => {
+                state.error = Some(format!("Audio stream ended early: got {} of {} bytes", state.data.len(), expected));
+            }
+            _ => state.complete = true,
+        });
+    });
+
+    Ok(buffer)
+}
+
+// Read a song's full audio data from a local file:// URL or an HTTP stream
+async fn load_audio_data(client: &reqwest::Client, url: &str) -> Result<Vec<u8>, String> {
+    if let Some(file_path) = url.strip_prefix("file://") {
+        tokio::fs::read(file_path).await
+            .map_err(|e| format!("Failed to read cached audio file: {}", e))
+    } else {
+        let response = client.get(url).send().await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| format!("Failed to download audio: {}", e))?;
+        let bytes = response.bytes().await
+            .map_err(|e| format!("Failed to read audio bytes: {}", e))?;
+        Ok(bytes.to_vec())
+    }
+}
+
+// Queue index played after current, None at the end of the queue unless repeating all.
+// Nothing playing yet starts from the beginning
+fn next_queue_index(current: Option<usize>, len: usize, repeat_mode: &RepeatMode) -> Option<usize> {
+    if len == 0 {
+        return None;
+    }
+    match current {
+        Some(index) if index + 1 < len => Some(index + 1),
+        Some(_) => match repeat_mode {
+            RepeatMode::All => Some(0), // Loop back to start
+            _ => None,                  // No more tracks
+        },
+        None => Some(0),
+    }
+}
+
+// Requested seek position clamped into the track, or None when it lands on the very end,
+// where playing a sliver of audio is pointless and the track should finish instead.
+// A duration of 0 is unknown, so only the lower bound applies
+fn seek_target(position: f64, duration: f64) -> Option<f64> {
+    let position = if position.is_finite() { position.max(0.0) } else { 0.0 };
+    if duration <= 0.0 {
+        return Some(position);
+    }
+    if position >= duration - SEEK_END_EPSILON_SECONDS {
+        return None;
+    }
+    Some(position)
+}
+
+// Where the item at index ends up after the queue item at from is moved to to
+fn index_after_move(index: usize, from: usize, to: usize) -> usize {
+    if index == from {
+        to
+    } else if from < index && to >= index {
+        index - 1
+    } else if from > index && to <= index {
+        index + 1
+    } else {
+        index
+    }
+}
+
+// Tracks in random order with the current song moved to the front, and its new index
+fn shuffle_tracks(
+    mut queue: VecDeque<QueueItem>,
+    current_index: Option<usize>,
+    rng: &mut impl rand::Rng,
+) -> (VecDeque<QueueItem>, Option<usize>) {
+    let current = current_index.and_then(|index| queue.remove(index));
+    let mut items: Vec<QueueItem> = queue.into();
+    fisher_yates_shuffle(&mut items, rng);
+    let mut queue: VecDeque<QueueItem> = items.into();
+
+    match current {
+        Some(current) => {
+            queue.push_front(current);
+            (queue, Some(0))
+        }
+        None => (queue, None),
+    }
+}
+
+// Insertion order back, taking the shuffled copies, which carry any resolved URLs or favorite
+// changes made meanwhile
+fn restore_order(unshuffled: VecDeque<QueueItem>, shuffled: VecDeque<QueueItem>) -> VecDeque<QueueItem> {
+    let mut latest: HashMap<String, QueueItem> = shuffled
+        .into_iter()
+        .map(|item| (item.id.clone(), item))
+        .collect();
+    unshuffled
+        .into_iter()
+        .map(|item| latest.remove(&item.id).unwrap_or(item))
+        .collect()
+}
+
+// Move the item with this id to just after another, or to the front
+fn move_after(queue: &mut VecDeque<QueueItem>, id: &str, after: Option<&str>) {
+    let Some(item) = queue.iter().position(|item| item.id == id).and_then(|index| queue.remove(index)) else {
+        return;
+    };
+    let index = match after {
+        Some(after) => queue.iter().position(|item| item.id == after).map_or(queue.len(), |index| index + 1),
+        None => 0,
+    };
+    queue.insert(index, item);
+}
+
+// In-place Fisher–Yates: every permutation is equally likely
+fn fisher_yates_shuffle<T>(items: &mut [T], rng: &mut impl rand::Rng) {
+    for i in (1..items.len()).rev() {
+        let j = rng.gen_range(0..=i);
+        items.swap(i, j);
+    }
+}
+
+fn unix_now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeks_are_clamped_to_the_start() {
+        assert_eq!(seek_target(-5.0, 180.0), Some(0.0));
+        assert_eq!(seek_target(f64::NAN, 180.0), Some(0.0));
+        assert_eq!(seek_target(0.0, 180.0), Some(0.0));
+        assert_eq!(seek_target(42.5, 180.0), Some(42.5));
+    }
+
+    #[test]
+    fn seeks_onto_the_end_finish_the_track() {
+        let duration = 180.0;
+        assert_eq!(seek_target(duration, duration), None);
+        assert_eq!(seek_target(duration + 30.0, duration), None);
+        assert_eq!(seek_target(duration - SEEK_END_EPSILON_SECONDS, duration), None);
+
+        let just_before = duration - SEEK_END_EPSILON_SECONDS - 0.01;
+        assert_eq!(seek_target(just_before, duration), Some(just_before));
+    }
+
+    #[test]
+    fn seeks_without_a_known_duration_only_clamp_the_start() {
+        assert_eq!(seek_target(500.0, 0.0), Some(500.0));
+        assert_eq!(seek_target(-1.0, 0.0), Some(0.0));
+    }
+
+    // Indices the queue plays through from nothing playing, following next_queue_index
+    fn walk(len: usize, repeat_mode: RepeatMode, steps: usize) -> Vec<usize> {
+        let mut played = Vec::new();
+        let mut current = None;
+        for _ in 0..steps {
+            let Some(next) = next_queue_index(current, len, &repeat_mode) else {
+                break;
+            };
+            played.push(next);
+            current = Some(next);
+        }
+        played
+    }
+
+    #[test]
+    fn auto_advance_walks_the_whole_queue_then_stops() {
+        assert_eq!(walk(3, RepeatMode::None, 10), vec![0, 1, 2]);
+        assert_eq!(next_queue_index(Some(2), 3, &RepeatMode::None), None);
+    }
+
+    #[test]
+    fn repeat_all_loops_back_to_the_start() {
+        assert_eq!(walk(3, RepeatMode::All, 7), vec![0, 1, 2, 0, 1, 2, 0]);
+    }
+
+    #[test]
+    fn an_empty_queue_has_nothing_next() {
+        assert_eq!(next_queue_index(None, 0, &RepeatMode::All), None);
+    }
+
+    fn queue_of(ids: &[&str]) -> VecDeque<QueueItem> {
+        ids.iter()
+            .map(|id| serde_json::from_value(serde_json::json!({
+                "id": id,
+                "name": id,
+                "artists": [],
+                "artist_ids": null,
+                "album": null,
+                "duration_ticks": null,
+                "stream_url": format!("https://example.com/{}", id),
+            })).unwrap())
+            .collect()
+    }
+
+    fn ids(queue: &VecDeque<QueueItem>) -> Vec<&str> {
+        queue.iter().map(|item| item.id.as_str()).collect()
+    }
+
+    #[test]
+    fn shuffling_keeps_the_current_song_current() {
+        let original = queue_of(&["a", "b", "c", "d", "e", "f"]);
+        let mut rng = rand::thread_rng();
+        for current in 0..original.len() {
+            let (shuffled, index) = shuffle_tracks(original.clone(), Some(current), &mut rng);
+            assert_eq!(index, Some(0));
+            assert_eq!(shuffled[0].id, original[current].id);
+
+            let mut played = ids(&shuffled);
+            played.sort();
+            assert_eq!(played, ids(&original));
+        }
+    }
+
+    #[test]
+    fn unshuffling_restores_insertion_order() {
+        let original = queue_of(&["a", "b", "c", "d", "e", "f"]);
+        let (mut shuffled, index) = shuffle_tracks(original.clone(), Some(3), &mut rand::thread_rng());
+        shuffled[1].is_favorite = true;
+        let changed = shuffled[1].id.clone();
+
+        let restored = restore_order(original.clone(), shuffled.clone());
+        assert_eq!(ids(&restored), ids(&original));
+        // Changes made while shuffled survive the unshuffle
+        assert!(restored.iter().find(|item| item.id == changed).unwrap().is_favorite);
+
+        let current_id = &shuffled[index.unwrap()].id;
+        assert_eq!(restored.iter().position(|item| &item.id == current_id), Some(3));
+    }
+
+    // A 16-bit PCM WAV of a quiet tone, built in memory
+    fn wav(sample_rate: u32, channels: u16, seconds: u32) -> Vec<u8> {
+        let frames = sample_rate * seconds;
+        let data_len = frames * channels as u32 * 2;
+        let mut wav = Vec::new();
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes());
+        wav.extend_from_slice(&channels.to_le_bytes());
+        wav.extend_from_slice(&sample_rate.to_le_bytes());
+        wav.extend_from_slice(&(sample_rate * channels as u32 * 2).to_le_bytes());
+        wav.extend_from_slice(&(channels * 2).to_le_bytes());
+        wav.extend_from_slice(&16u16.to_le_bytes());
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&data_len.to_le_bytes());
+        for frame in 0..frames {
+            let sample = ((frame as f32 * 0.05).sin() * 8000.0) as i16;
+            for _ in 0..channels {
+                wav.extend_from_slice(&sample.to_le_bytes());
+            }
+        }
+        wav
+    }
+
+    #[test]
+    fn played_seconds_counts_the_samples_the_sink_pulls() {
+        let mut source = SymphoniaSource::from_data(wav(8000, 2, 2).into()).unwrap();
+        let stats = source.stats.clone();
+        assert_eq!(source.total_duration, Some(Duration::from_secs(2)));
+        assert_eq!(stats.played_seconds(), 0.0);
+
+        // Half a second of stereo at 8 kHz
+        assert_eq!(source.by_ref().take(8000).count(), 8000);
+        assert!((stats.played_seconds() - 0.5).abs() < 1e-9, "{}", stats.played_seconds());
+
+        // Nothing is counted while the sink isn't pulling, as when paused or stalled
+        std::thread::sleep(Duration::from_millis(50));
+        assert!((stats.played_seconds() - 0.5).abs() < 1e-9);
+
+        // Playing to the end reports the whole track, not more
+        source.by_ref().for_each(drop);
+        assert!((stats.played_seconds() - 2.0).abs() < 0.01, "{}", stats.played_seconds());
+    }
+
+    // Empty directory of its own for each test
+    fn test_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("bloodin-player-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    // A worker without an audio device, and the events it sends
+    fn worker() -> (AudioPlayerWorker, broadcast::Receiver<PlayerEvent>) {
+        let (_, command_receiver) = mpsc::unbounded_channel();
+        let (event_sender, events) = broadcast::channel(100);
+        (AudioPlayerWorker::new(None, command_receiver, event_sender), events)
+    }
+
+    #[tokio::test]
+    async fn a_corrupt_seek_on_a_downloaded_track_leaves_the_file_in_place() {
+        let dir = test_dir("corrupt-seek");
+        // Laid out like an offline download, which the player has no business deleting
+        let downloaded = dir.join("song.audio");
+        std::fs::write(&downloaded, b"not audio").unwrap();
+        let remote = dir.join("remote.wav");
+        std::fs::write(&remote, wav(8000, 1, 4)).unwrap();
+
+        let (mut worker, mut events) = worker();
+        let mut item = queue_of(&["song"]).pop_front().unwrap();
+        item.stream_url = format!("file://{}", downloaded.display());
+        item.remote_stream_url = Some(format!("file://{}", remote.display()));
+        worker.state.current_song = Some(item);
+        worker.cached_audio_data = Some(b"not audio".to_vec().into());
+        worker.cached_song_id = Some("song".to_string());
+
+        worker.seek(1.0).await;
+
+        assert_eq!(std::fs::read(&downloaded).unwrap(), b"not audio");
+        // Playback carried on from the server's copy, at the start of the packet holding 1s
+        let position = worker.state.current_position;
+        assert!(position > 0.5 && position <= 1.0, "{}", position);
+        let mut reported = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            if let PlayerEvent::LocalFileUnusable { path, .. } = event {
+                reported.push(path);
+            }
+        }
+        assert_eq!(reported, vec![downloaded.display().to_string()]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn skipping_leading_silence_starts_at_the_first_audible_sample() {
+        let mut data = wav(8000, 1, 3);
+        // The first second is silent
+        data[44..44 + 16000].fill(0);
+        let mut source = SymphoniaSource::from_data(data.into()).unwrap();
+        let stats = source.stats.clone();
+
+        let start = source.skip_leading_silence().unwrap();
+
+        // Seeks land on a packet boundary at or before the sound
+        assert!(start > 0.5 && start <= 0.98, "{}", start);
+        // Measuring isn't counted as played
+        assert_eq!(stats.played_seconds(), 0.0);
+        assert!(source.by_ref().take(4000).any(|sample| sample.abs() > SILENCE_THRESHOLD));
+    }
+
+    #[tokio::test]
+    async fn a_streamed_track_is_scanned_for_trailing_silence_once_it_has_downloaded() {
+        let (mut worker, _events) = worker();
+        worker.state.trim_silence = true;
+        worker.state.transition_mode = TransitionMode::Gapless;
+        worker.queue = queue_of(&["streamed", "next"]);
+        worker.current_index = Some(0);
+        worker.state.current_song = worker.queue.front().cloned();
+        worker.active_stream = Some(ActiveStream {
+            song_id: "streamed".to_string(),
+            url: "https://example.com/streamed".to_string(),
+            buffer: Arc::new(StreamBuffer::new(None)),
+        });
+
+        // While it streams there's nothing to scan yet
+        worker.scan_trailing_silence();
+        assert!(worker.silence_scan_receiver.try_recv().is_err());
+
+        let mut data = wav(8000, 1, 6);
+        // The last second is silent
+        let len = data.len();
+        data[len - 16000..].fill(0);
+        worker.absorb_stream(Ok(data)).unwrap();
+
+        let (song_id, seconds) = tokio::time::timeout(Duration::from_secs(5), worker.silence_scan_receiver.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(song_id, "streamed");
+        worker.finish_silence_scan(song_id, seconds);
+        assert!((worker.trailing_silence - 0.98).abs() < 0.01, "{}", worker.trailing_silence);
+
+        // A scan finishing after the track changed doesn't apply to the new one
+        worker.state.current_song = worker.queue.get(1).cloned();
+        worker.trailing_silence = 0.0;
+        worker.finish_silence_scan("streamed".to_string(), 1.5);
+        assert_eq!(worker.trailing_silence, 0.0);
+    }
+
+    #[test]
+    fn moving_queue_items_keeps_the_current_index_on_the_playing_track() {
+        let (mut worker, _events) = worker();
+        // No neighbours to load in the background
+        worker.warm_window = 0;
+        worker.queue = queue_of(&["a", "b", "c", "d", "e"]);
+        worker.current_index = Some(2);
+        worker.state.current_song = worker.queue.get(2).cloned();
+
+        // The playing track moving, then another one moving past it
+        worker.move_in_queue(2, 0).unwrap();
+        assert_eq!(ids(&worker.queue), ["c", "a", "b", "d", "e"]);
+        assert_eq!(worker.current_index, Some(0));
+        worker.move_in_queue(4, 0).unwrap();
+        assert_eq!(ids(&worker.queue), ["e", "c", "a", "b", "d"]);
+        assert_eq!(worker.current_index, Some(1));
+        assert!(worker.unshuffled_queue.is_none());
+
+        assert!(worker.move_in_queue(0, 5).is_err());
+        assert_eq!(ids(&worker.queue), ["e", "c", "a", "b", "d"]);
+    }
+
+    #[test]
+    fn moving_queue_items_while_shuffled_carries_over_to_the_original_order() {
+        let (mut worker, _events) = worker();
+        // No neighbours to load in the background
+        worker.warm_window = 0;
+        worker.queue = queue_of(&["d", "b", "e", "a", "c"]);
+        worker.unshuffled_queue = Some(queue_of(&["a", "b", "c", "d", "e"]));
+        worker.state.shuffle_mode = ShuffleMode::Tracks;
+        worker.current_index = Some(1);
+        worker.state.current_song = worker.queue.get(1).cloned();
+
+        // "a" now plays right after the current song, "b"
+        worker.move_in_queue(3, 2).unwrap();
+        assert_eq!(ids(&worker.queue), ["d", "b", "a", "e", "c"]);
+        assert_eq!(ids(worker.unshuffled_queue.as_ref().unwrap()), ["b", "a", "c", "d", "e"]);
+        assert_eq!(worker.current_index, Some(1));
+
+        // Moving to the front moves to the front of the original order too
+        worker.move_in_queue(4, 0).unwrap();
+        assert_eq!(ids(&worker.queue), ["c", "d", "b", "a", "e"]);
+        assert_eq!(ids(worker.unshuffled_queue.as_ref().unwrap()), ["c", "b", "a", "d", "e"]);
+        assert_eq!(worker.current_index, Some(2));
+
+        worker.set_shuffle_mode(ShuffleMode::Off);
+        assert_eq!(ids(&worker.queue), ["c", "b", "a", "d", "e"]);
+        assert_eq!(worker.current_index, Some(1));
+        assert!(worker.unshuffled_queue.is_none());
+    }
+
+    #[tokio::test]
+    async fn songs_ending_walk_the_queue_then_stop_at_its_end() {
+        let dir = test_dir("song-end");
+        let mut queue = queue_of(&["a", "b", "c"]);
+        for item in queue.iter_mut() {
+            let path = dir.join(format!("{}.wav", item.id));
+            std::fs::write(&path, wav(8000, 1, 1)).unwrap();
+            item.stream_url = format!("file://{}", path.display());
+        }
+
+        let (mut worker, mut events) = worker();
+        worker.warm_window = 0;
+        worker.queue = queue.clone();
+        worker.current_index = Some(0);
+        worker.play_item(queue[0].clone()).await.unwrap();
+
+        // Each end of a song is what the position tick hands to finish_track
+        for _ in 0..3 {
+            worker.finish_track().await;
+        }
+
+        let mut tracks = Vec::new();
+        let mut last_state = None;
+        while let Ok(event) = events.try_recv() {
+            match event {
+                PlayerEvent::TrackChanged(item) => tracks.push(item.map(|item| item.id)),
+                PlayerEvent::StateChanged(state) => last_state = Some(state),
+                _ => {}
+            }
+        }
+        assert_eq!(tracks, [Some("a".to_string()), Some("b".to_string()), Some("c".to_string()), None]);
+        let last_state = last_state.unwrap();
+        assert!(!last_state.is_playing);
+        assert!(last_state.current_song.is_none());
+        assert_eq!(worker.current_index, Some(2));
+
+        // Nothing more happens once the queue has run out
+        worker.finish_track().await;
+        assert!(events.try_recv().is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
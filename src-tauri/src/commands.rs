@@ -1,35 +1,151 @@
-use crate::audio_player::{AudioPlayer, PlaybackState, QueueItem, RepeatMode};
-use crate::jellyfin::{JellyfinClient, ServerInfo, UserProfile, MusicItem};
-use crate::storage;
-use crate::audio_cache::AudioCache;
+use crate::audio_player::{self, AudioInfo, AudioPlayer, BufferDiagnostics, PlaybackState, PlayerEvent, QueueItem, RepeatMode, ShuffleMode, SleepTimerMode, TransitionMode};
+use crate::jellyfin::{self, SessionEvent, SessionRenewal, AuthHeaderDebug, JellyfinClient, JellyfinConfig, JellyfinError, Lyrics, QuickConnectSession, ScheduledTask, ServerInfo, SystemInfo, UserProfile, MusicItem, StreamingProfile, ExternalUrl};
+use crate::storage::{self, PlayerSettings, RecentSearch};
+use crate::audio_cache::{AudioCache, CacheStats, CacheStatus, CacheUsage, DownloadQuality};
+use crate::search_index::{IndexEntry, SearchIndex};
+use crate::event_log;
+use crate::logging;
+use crate::discord_presence::PresenceMessage;
+use crate::downloads::{self, DownloadJob, DownloadManager, DownloadState};
+use crate::image_cache::ImageCache;
+use crate::scrobble::{LastfmClient, LastfmSession};
+use crate::equalizer::{self, EqualizerPreset, EQ_BAND_COUNT, MAX_BAND_GAIN_DB};
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Mutex};
 use tokio::sync::Mutex as TokioMutex;
-use tauri::State;
+use tauri::{Emitter, Manager, State};
+use log::{error, info, warn};
 
 pub struct AppState {
     pub jellyfin_client: Arc<Mutex<JellyfinClient>>,
     pub audio_player: Arc<Mutex<AudioPlayer>>,
     pub audio_cache: Arc<TokioMutex<AudioCache>>,
+    pub streaming_profile: Arc<Mutex<Option<StreamingProfile>>>,
+    // Overrides the negotiated profile unless Original
+    pub streaming_quality: Arc<Mutex<DownloadQuality>>,
+    // Loaded lazily from disk by local_search
+    pub search_index: Arc<Mutex<Option<SearchIndex>>>,
+    pub download_quality: Arc<Mutex<DownloadQuality>>,
+    // Version kept when songs are cached during playback
+    pub cache_quality: Arc<Mutex<DownloadQuality>>,
+    // Unlocks diagnostic commands meant for bug reports
+    pub debug_mode: Arc<Mutex<bool>>,
+    // Used by listing commands when the caller passes no limit
+    pub default_page_size: Arc<Mutex<Option<i32>>>,
+    // Running while opt-in event logging is enabled
+    pub event_logger: Arc<Mutex<Option<tauri::async_runtime::JoinHandle<()>>>>,
+    // Background stream URL resolution for the current queue; replaced with the queue
+    pub queue_resolver: Arc<Mutex<Option<tauri::async_runtime::JoinHandle<()>>>>,
+    // Seconds between background token checks, None when disabled
+    pub token_revalidation_interval: Arc<Mutex<Option<u64>>>,
+    pub token_revalidator: Arc<Mutex<Option<tauri::async_runtime::JoinHandle<()>>>>,
+    // "For You" shelves, built once per session, with the user they were built for
+    pub recommendations: Arc<Mutex<Option<(String, Recommendations)>>>,
+    // Quick Connect login waiting for approval
+    pub quick_connect: Arc<Mutex<Option<PendingQuickConnect>>>,
+    // Scrobbling happens only while this is set
+    pub lastfm_session: Arc<Mutex<Option<LastfmSession>>>,
+    // Feeds the Discord Rich Presence thread; None when the build has no Discord application
+    pub discord_presence: Arc<Mutex<Option<std::sync::mpsc::Sender<PresenceMessage>>>>,
+    // Offline downloads, opened at startup; None if the downloads folder is unusable
+    pub downloads: Arc<Mutex<Option<DownloadManager>>>,
+    pub download_queue: Arc<Mutex<Option<tokio::sync::mpsc::UnboundedSender<DownloadJob>>>>,
+    // Images handed to the webview by fetch_image
+    pub image_cache: Arc<Mutex<ImageCache>>,
+    // Held across each read-modify-write of the saved player settings
+    pub player_settings_lock: Arc<TokioMutex<()>>,
+    // Waiting to save the volume until the slider settles
+    pub volume_saver: Arc<Mutex<Option<tauri::async_runtime::JoinHandle<()>>>>,
+}
+
+#[derive(Clone)]
+pub struct PendingQuickConnect {
+    session: QuickConnectSession,
+    server_name: String,
+    custom_headers: HashMap<String, String>,
 }
 
 impl AppState {
     pub fn new() -> Self {
         let audio_player = AudioPlayer::new().expect("Failed to initialize audio player");
-        let audio_cache = AudioCache::new().expect("Failed to initialize audio cache");
+        let audio_cache = AudioCache::new();
         Self {
             jellyfin_client: Arc::new(Mutex::new(JellyfinClient::new())),
             audio_player: Arc::new(Mutex::new(audio_player)),
             audio_cache: Arc::new(TokioMutex::new(audio_cache)),
+            streaming_profile: Arc::new(Mutex::new(None)),
+            streaming_quality: Arc::new(Mutex::new(DownloadQuality::Original)),
+            search_index: Arc::new(Mutex::new(None)),
+            download_quality: Arc::new(Mutex::new(DownloadQuality::Original)),
+            cache_quality: Arc::new(Mutex::new(DownloadQuality::Original)),
+            debug_mode: Arc::new(Mutex::new(false)),
+            default_page_size: Arc::new(Mutex::new(None)),
+            event_logger: Arc::new(Mutex::new(None)),
+            queue_resolver: Arc::new(Mutex::new(None)),
+            token_revalidation_interval: Arc::new(Mutex::new(Some(DEFAULT_TOKEN_REVALIDATION_SECS))),
+            token_revalidator: Arc::new(Mutex::new(None)),
+            recommendations: Arc::new(Mutex::new(None)),
+            quick_connect: Arc::new(Mutex::new(None)),
+            lastfm_session: Arc::new(Mutex::new(None)),
+            discord_presence: Arc::new(Mutex::new(None)),
+            downloads: Arc::new(Mutex::new(None)),
+            download_queue: Arc::new(Mutex::new(None)),
+            image_cache: Arc::new(Mutex::new(ImageCache::new())),
+            player_settings_lock: Arc::new(TokioMutex::new(())),
+            volume_saver: Arc::new(Mutex::new(None)),
         }
     }
 }
 
+// What a command rejects with, shaped like a serialized JellyfinError so the UI branches on
+// kind the same way for both; failures that didn't come from the server are kind "other"
+#[derive(Debug, serde::Serialize)]
+pub struct CommandError {
+    pub kind: &'static str,
+    pub message: String,
+    pub status: Option<u16>,
+}
+
+impl std::fmt::Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl From<JellyfinError> for CommandError {
+    fn from(e: JellyfinError) -> Self {
+        let status = match &e {
+            JellyfinError::Server { status, .. } => Some(*status),
+            _ => None,
+        };
+        Self { kind: e.kind(), message: e.to_string(), status }
+    }
+}
+
+impl From<String> for CommandError {
+    fn from(message: String) -> Self {
+        Self { kind: "other", message, status: None }
+    }
+}
+
+impl From<&str> for CommandError {
+    fn from(message: &str) -> Self {
+        message.to_string().into()
+    }
+}
+
+// Prefix a server failure with what was being attempted, keeping its kind
+fn failed(action: impl std::fmt::Display) -> impl FnOnce(JellyfinError) -> CommandError {
+    move |e| CommandError { message: format!("{}: {}", action, e), ..e.into() }
+}
+
 #[derive(serde::Serialize)]
 pub struct ConnectResult {
     pub success: bool,
     pub message: String,
     pub user_name: Option<String>,
     pub server_name: Option<String>,
+    pub error: Option<JellyfinError>,
 }
 
 #[derive(serde::Serialize)]
@@ -37,6 +153,7 @@ pub struct ServerInfoResult {
     pub success: bool,
     pub message: String,
     pub server_info: Option<ServerInfo>,
+    pub error: Option<JellyfinError>,
 }
 
 #[derive(serde::Serialize)]
@@ -44,6 +161,7 @@ pub struct UserProfileResult {
     pub success: bool,
     pub message: String,
     pub user_profile: Option<UserProfile>,
+    pub error: Option<JellyfinError>,
 }
 
 #[derive(serde::Serialize)]
@@ -60,6 +178,10 @@ pub struct MusicLibraryResult {
     pub message: String,
     pub items: Option<Vec<MusicItem>>,
     pub total_count: Option<i32>,
+    // Where this page starts, and whether another page follows it
+    pub start_index: Option<i32>,
+    pub has_more: bool,
+    pub error: Option<JellyfinError>,
 }
 
 #[derive(serde::Serialize)]
@@ -67,6 +189,81 @@ pub struct ItemResult {
     pub success: bool,
     pub message: String,
     pub item: Option<MusicItem>,
+    pub error: Option<JellyfinError>,
+}
+
+#[derive(serde::Serialize)]
+pub struct FavoriteUpdateResult {
+    pub item_id: String,
+    pub success: bool,
+    pub message: String,
+    pub error: Option<JellyfinError>,
+}
+
+#[derive(serde::Serialize)]
+pub struct OfflineReadiness {
+    pub item_id: String,
+    pub ready: bool,
+    pub message: String,
+}
+
+#[derive(serde::Serialize)]
+pub struct ImageBytes {
+    pub mime_type: String,
+    pub data: Vec<u8>,
+}
+
+#[derive(serde::Serialize)]
+pub struct LocalSearchResult {
+    pub success: bool,
+    pub message: String,
+    pub from_index: bool,
+    pub items: Vec<IndexEntry>,
+}
+
+#[derive(serde::Serialize)]
+pub struct DuplicateGroup {
+    pub name: String,
+    pub album: Option<String>,
+    pub runtime_ticks: Option<i64>,
+    pub item_ids: Vec<String>,
+}
+
+#[derive(Clone, serde::Serialize)]
+pub struct CacheProgress {
+    pub item_id: String,
+    pub downloaded: u64,
+    pub total: Option<u64>, // from Content-Length, None when the server doesn't send one
+}
+
+#[derive(serde::Serialize)]
+pub struct QueueItemCacheStatus {
+    pub item_id: String,
+    pub status: CacheStatus,
+}
+
+#[derive(Clone, serde::Serialize)]
+pub struct QueueResolveProgress {
+    pub checked: usize,
+    pub total: usize,
+    pub ready: bool,
+}
+
+// Make the audio player, cache and offline downloads send the profile's custom headers too
+async fn apply_custom_headers(state: &State<'_, AppState>, headers: &HashMap<String, String>) -> Result<(), String> {
+    {
+        let audio_player = state.audio_player.lock().map_err(|e| e.to_string())?;
+        audio_player.set_custom_headers(headers.clone())?;
+    }
+    {
+        let mut downloads = state.downloads.lock().map_err(|e| e.to_string())?;
+        if let Some(downloads) = downloads.as_mut() {
+            downloads.set_custom_headers(headers).map_err(|e| e.to_string())?;
+        }
+    }
+
+    let mut cache = state.audio_cache.lock().await;
+    cache.set_custom_headers(headers).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
@@ -74,11 +271,13 @@ pub async fn connect_to_jellyfin(
     server_url: String,
     username: String,
     password: String,
+    custom_headers: Option<HashMap<String, String>>,
     state: State<'_, AppState>,
     app_handle: tauri::AppHandle,
-) -> Result<ConnectResult, String> {
-    // Create a new client for this operation
-    let mut client = JellyfinClient::new();
+) -> Result<ConnectResult, CommandError> {
+    // Create a new client for this operation, with any proxy headers needed to reach the server
+    let custom_headers = custom_headers.unwrap_or_default();
+    let mut client = JellyfinClient::with_custom_headers(&custom_headers);
     
     // First, get server info to validate the URL
     let server_info = match client.get_server_info(&server_url).await {
@@ -89,12 +288,13 @@ pub async fn connect_to_jellyfin(
                 message: format!("Failed to connect to server: {}", e),
                 user_name: None,
                 server_name: None,
+                error: Some(e),
             });
         }
     };
 
     // Attempt authentication
-    let config = match client.authenticate(&server_url, &username, &password).await {
+    let mut config = match client.authenticate(&server_url, &username, &password).await {
         Ok(config) => config,
         Err(e) => {
             return Ok(ConnectResult {
@@ -102,34 +302,181 @@ pub async fn connect_to_jellyfin(
                 message: format!("Authentication failed: {}", e),
                 user_name: None,
                 server_name: Some(server_info.server_name),
+                error: Some(e),
             });
         }
     };
 
+    // Headers are stored with the profile
+    config.custom_headers = custom_headers;
+    attach_session_renewal(&mut config, Some(password), &app_handle);
+
+    complete_login(config, server_info.server_name, &state, &app_handle).await
+}
+
+// Let requests recover from a token the server stops accepting. With the password from a login
+// made this session they log in again; otherwise, or if that fails, "auth-expired" tells the UI
+// to ask for a login
+fn attach_session_renewal(config: &mut JellyfinConfig, password: Option<String>, app_handle: &tauri::AppHandle) {
+    let app_handle = app_handle.clone();
+    let server_url = config.server_url.clone();
+    let user_id = config.user_id.clone();
+    let expiry_reported = std::sync::atomic::AtomicBool::new(false);
+
+    config.renewal = Some(Arc::new(SessionRenewal::new(password, move |event| match event {
+        SessionEvent::Renewed(token) => {
+            // Keep the active session (and the saved one) on the new token, unless the user has moved on
+            let renewed_config = {
+                let state = app_handle.state::<AppState>();
+                let Ok(mut client) = state.jellyfin_client.lock() else {
+                    return;
+                };
+                let Some(mut config) = client.get_config().cloned() else {
+                    return;
+                };
+                if config.server_url != server_url || config.user_id != user_id {
+                    return;
+                }
+                config.access_token = token;
+                client.set_config(config.clone());
+                config
+            };
+            let app_handle = app_handle.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = storage::save_jellyfin_config(&app_handle, &renewed_config).await {
+                    error!("Failed to save renewed credentials: {}", e);
+                }
+            });
+        }
+        SessionEvent::Expired => {
+            // Every failing request ends up here; the UI only needs telling once
+            if !expiry_reported.swap(true, std::sync::atomic::Ordering::Relaxed) {
+                info!("Session expired and couldn't be renewed");
+                let _ = app_handle.emit("auth-expired", ());
+            }
+        }
+    })));
+}
+
+// Make a freshly authenticated config the active session and persist it
+async fn complete_login(
+    config: JellyfinConfig,
+    server_name: String,
+    state: &State<'_, AppState>,
+    app_handle: &tauri::AppHandle,
+) -> Result<ConnectResult, CommandError> {
     // Update the shared state
     {
         let mut shared_client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
         shared_client.set_config(config.clone());
     }
+    apply_custom_headers(state, &config.custom_headers).await?;
 
     // Save credentials securely
-    if let Err(e) = storage::save_jellyfin_config(&app_handle, &config).await {
-        eprintln!("Failed to save credentials: {}", e);
+    if let Err(e) = storage::save_jellyfin_config(app_handle, &config).await {
+        error!("Failed to save credentials: {}", e);
     }
+    start_token_revalidation(state, app_handle)?;
 
     Ok(ConnectResult {
         success: true,
         message: "Successfully connected to Jellyfin".to_string(),
         user_name: Some(config.username),
-        server_name: Some(server_info.server_name),
+        server_name: Some(server_name),
+        error: None,
     })
 }
 
+// Begin a Quick Connect login and return the code for the user to approve on another client
+#[tauri::command]
+pub async fn start_quick_connect(
+    server_url: String,
+    custom_headers: Option<HashMap<String, String>>,
+    state: State<'_, AppState>,
+) -> Result<String, CommandError> {
+    let custom_headers = custom_headers.unwrap_or_default();
+    let client = JellyfinClient::with_custom_headers(&custom_headers);
+
+    let server_info = client.get_server_info(&server_url).await
+        .map_err(failed("Failed to connect to server"))?;
+    let session = client.initiate_quick_connect(&server_url).await?;
+
+    let code = session.code.clone();
+    info!("Quick Connect code issued for {}", server_info.server_name);
+    let mut quick_connect = state.quick_connect.lock().map_err(|e| e.to_string())?;
+    *quick_connect = Some(PendingQuickConnect {
+        session,
+        server_name: server_info.server_name,
+        custom_headers,
+    });
+    Ok(code)
+}
+
+// None while the code is still waiting for approval; logs in once it's approved
+#[tauri::command]
+pub async fn poll_quick_connect(
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<Option<ConnectResult>, CommandError> {
+    let pending = {
+        let quick_connect = state.quick_connect.lock().map_err(|e| e.to_string())?;
+        quick_connect.clone()
+    };
+    let pending = pending.ok_or("No Quick Connect login in progress")?;
+
+    let mut client = JellyfinClient::with_custom_headers(&pending.custom_headers);
+    let approved = client.poll_quick_connect(&pending.session).await?;
+    if !approved {
+        return Ok(None);
+    }
+
+    let mut config = client.authenticate_with_quick_connect(&pending.session).await?;
+    config.custom_headers = pending.custom_headers;
+    attach_session_renewal(&mut config, None, &app_handle);
+
+    {
+        let mut quick_connect = state.quick_connect.lock().map_err(|e| e.to_string())?;
+        *quick_connect = None;
+    }
+
+    info!("Quick Connect approved for {}", config.username);
+    complete_login(config, pending.server_name, &state, &app_handle).await.map(Some)
+}
+
+#[tauri::command]
+pub fn cancel_quick_connect(state: State<'_, AppState>) -> Result<bool, CommandError> {
+    let mut quick_connect = state.quick_connect.lock().map_err(|e| e.to_string())?;
+    Ok(quick_connect.take().is_some())
+}
+
+#[tauri::command]
+pub async fn set_custom_headers(
+    headers: HashMap<String, String>,
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<bool, CommandError> {
+    let config = {
+        let mut shared_client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+        let mut config = shared_client.get_config().cloned().ok_or("Not authenticated")?;
+        config.custom_headers = headers;
+        shared_client.set_config(config.clone());
+        config
+    };
+    apply_custom_headers(&state, &config.custom_headers).await?;
+
+    if let Err(e) = storage::save_jellyfin_config(&app_handle, &config).await {
+        error!("Failed to save custom headers: {}", e);
+    }
+
+    info!("Applied {} custom request headers", config.custom_headers.len());
+    Ok(true)
+}
+
 #[tauri::command]
 pub async fn get_server_info(
     server_url: String,
     _state: State<'_, AppState>,
-) -> Result<ServerInfoResult, String> {
+) -> Result<ServerInfoResult, CommandError> {
     // Create a new client for this operation
     let client = JellyfinClient::new();
     
@@ -138,19 +485,102 @@ pub async fn get_server_info(
             success: true,
             message: "Server info retrieved successfully".to_string(),
             server_info: Some(server_info),
+            error: None,
         }),
         Err(e) => Ok(ServerInfoResult {
             success: false,
             message: format!("Failed to get server info: {}", e),
             server_info: None,
+            error: Some(e),
         }),
     }
 }
 
+#[tauri::command]
+pub async fn get_system_info(state: State<'_, AppState>) -> Result<SystemInfo, CommandError> {
+    let client_config = {
+        let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+        client.get_config().cloned()
+    };
+
+    let config = match client_config {
+        Some(config) => config,
+        None => {
+            return Err(JellyfinError::NotAuthenticated.into());
+        }
+    };
+
+    let mut client = JellyfinClient::new();
+    client.set_config(config);
+
+    client
+        .get_system_info_authenticated()
+        .await
+        .map_err(failed("Failed to get system info"))
+}
+
+// None for non-admin users, so the UI can hide the panel rather than show an error
+#[tauri::command]
+pub async fn get_scheduled_tasks(state: State<'_, AppState>) -> Result<Option<Vec<ScheduledTask>>, CommandError> {
+    let client_config = {
+        let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+        client.get_config().cloned()
+    };
+
+    let config = match client_config {
+        Some(config) => config,
+        None => {
+            return Err(JellyfinError::NotAuthenticated.into());
+        }
+    };
+
+    let mut client = JellyfinClient::new();
+    client.set_config(config);
+
+    client
+        .get_scheduled_tasks()
+        .await
+        .map_err(failed("Failed to get scheduled tasks"))
+}
+
+// Returns false for non-admin users instead of failing
+#[tauri::command]
+pub async fn trigger_scheduled_task(
+    task_id: String,
+    state: State<'_, AppState>,
+) -> Result<bool, CommandError> {
+    let client_config = {
+        let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+        client.get_config().cloned()
+    };
+
+    let config = match client_config {
+        Some(config) => config,
+        None => {
+            return Err(JellyfinError::NotAuthenticated.into());
+        }
+    };
+
+    let mut client = JellyfinClient::new();
+    client.set_config(config);
+
+    let started = client
+        .trigger_scheduled_task(&task_id)
+        .await
+        .map_err(failed("Failed to start scheduled task"))?;
+    if started {
+        info!("Started scheduled task {}", task_id);
+        // A library scan may add items, so don't serve stale listings afterwards
+        let mut shared_client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+        shared_client.invalidate_cache();
+    }
+    Ok(started)
+}
+
 #[tauri::command]
 pub async fn get_user_profile(
     state: State<'_, AppState>,
-) -> Result<UserProfileResult, String> {
+) -> Result<UserProfileResult, CommandError> {
     // Get the client config from shared state
     let client_config = {
         let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
@@ -164,6 +594,7 @@ pub async fn get_user_profile(
                 success: false,
                 message: "Not authenticated".to_string(),
                 user_profile: None,
+                error: Some(JellyfinError::NotAuthenticated),
             });
         }
     };
@@ -177,11 +608,13 @@ pub async fn get_user_profile(
             success: true,
             message: "User profile retrieved successfully".to_string(),
             user_profile: Some(user_profile),
+            error: None,
         }),
         Err(e) => Ok(UserProfileResult {
             success: false,
             message: format!("Failed to get user profile: {}", e),
             user_profile: None,
+            error: Some(e),
         }),
     }
 }
@@ -190,12 +623,12 @@ pub async fn get_user_profile(
 pub async fn check_authentication(
     state: State<'_, AppState>,
     app_handle: tauri::AppHandle,
-) -> Result<AuthCheckResult, String> {
+) -> Result<AuthCheckResult, CommandError> {
     // Try to load saved credentials
     let saved_config = match storage::load_jellyfin_config(&app_handle).await {
         Ok(config) => config,
         Err(e) => {
-            eprintln!("Failed to load saved config: {}", e);
+            error!("Failed to load saved config: {}", e);
             return Ok(AuthCheckResult {
                 is_authenticated: false,
                 user_name: None,
@@ -205,7 +638,7 @@ pub async fn check_authentication(
         }
     };
 
-    let config = match saved_config {
+    let mut config = match saved_config {
         Some(config) => config,
         None => {
             return Ok(AuthCheckResult {
@@ -217,6 +650,9 @@ pub async fn check_authentication(
         }
     };
 
+    // Restored sessions have no password to log in again with, but can still report expiry
+    attach_session_renewal(&mut config, None, &app_handle);
+
     // Create a new client and validate the token
     let mut client = JellyfinClient::new();
     client.set_config(config.clone());
@@ -238,6 +674,8 @@ pub async fn check_authentication(
             let mut shared_client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
             shared_client.set_config(config.clone());
         }
+        apply_custom_headers(&state, &config.custom_headers).await?;
+        start_token_revalidation(&state, &app_handle)?;
         
         Ok(AuthCheckResult {
             is_authenticated: true,
@@ -248,7 +686,7 @@ pub async fn check_authentication(
     } else {
         // Clear invalid credentials
         if let Err(e) = storage::clear_jellyfin_config(&app_handle).await {
-            eprintln!("Failed to clear invalid credentials: {}", e);
+            error!("Failed to clear invalid credentials: {}", e);
         }
         
         Ok(AuthCheckResult {
@@ -264,111 +702,237 @@ pub async fn check_authentication(
 pub async fn logout(
     state: State<'_, AppState>,
     app_handle: tauri::AppHandle,
-) -> Result<bool, String> {
+) -> Result<bool, CommandError> {
     // Clear saved credentials
     if let Err(e) = storage::clear_jellyfin_config(&app_handle).await {
-        eprintln!("Failed to clear credentials: {}", e);
+        error!("Failed to clear credentials: {}", e);
         return Ok(false);
     }
 
-    // Clear client config
+    stop_token_revalidation(&state)?;
+
+    {
+        let mut image_cache = state.image_cache.lock().map_err(|e| e.to_string())?;
+        image_cache.clear();
+    }
+
+    // Clear client config, keeping the cache lifetime setting
     let mut client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+    let cache_ttl = client.cache_ttl();
     *client = JellyfinClient::new();
+    client.set_cache_ttl(cache_ttl);
 
     Ok(true)
 }
 
+// Sign in as another user on the same server, keeping the server profile and its headers
 #[tauri::command]
-pub async fn get_songs(
-    limit: Option<i32>,
-    start_index: Option<i32>,
+pub async fn switch_user(
+    username: String,
+    password: String,
     state: State<'_, AppState>,
-) -> Result<MusicLibraryResult, String> {
-    println!("🔧 get_songs called with limit: {:?}, start_index: {:?}", limit, start_index);
-    let client_config = {
+    app_handle: tauri::AppHandle,
+) -> Result<ConnectResult, CommandError> {
+    let current_config = {
         let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
         client.get_config().cloned()
     };
 
-    let config = match client_config {
+    let current_config = match current_config {
         Some(config) => config,
         None => {
-            return Ok(MusicLibraryResult {
+            return Err("Not connected to a server".into());
+        }
+    };
+
+    let mut client = JellyfinClient::with_custom_headers(&current_config.custom_headers);
+    let mut config = match client.authenticate(&current_config.server_url, &username, &password).await {
+        Ok(config) => config,
+        Err(e) => {
+            return Ok(ConnectResult {
                 success: false,
-                message: "Not authenticated".to_string(),
-                items: None,
-                total_count: None,
+                message: format!("Authentication failed: {}", e),
+                user_name: None,
+                server_name: None,
+                error: Some(e),
             });
         }
     };
+    config.custom_headers = current_config.custom_headers;
+    attach_session_renewal(&mut config, Some(password), &app_handle);
 
-    let mut client = JellyfinClient::new();
-    client.set_config(config);
+    // Swap the active user and drop everything cached for the previous one
+    {
+        let mut shared_client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+        shared_client.set_config(config.clone());
+        shared_client.invalidate_cache();
+    }
+    {
+        let mut search_index = state.search_index.lock().map_err(|e| e.to_string())?;
+        *search_index = None;
+    }
+    {
+        let mut recommendations = state.recommendations.lock().map_err(|e| e.to_string())?;
+        *recommendations = None;
+    }
 
-    match client.get_songs(limit, start_index).await {
-        Ok(response) => Ok(MusicLibraryResult {
-            success: true,
-            message: "Songs retrieved successfully".to_string(),
-            items: Some(response.items),
-            total_count: Some(response.total_record_count),
-        }),
-        Err(e) => Ok(MusicLibraryResult {
-            success: false,
-            message: format!("Failed to get songs: {}", e),
-            items: None,
-            total_count: None,
-        }),
+    if let Err(e) = storage::save_jellyfin_config(&app_handle, &config).await {
+        error!("Failed to save credentials: {}", e);
     }
-}
+    start_token_revalidation(&state, &app_handle)?;
 
-#[tauri::command]
-pub async fn get_albums(
-    limit: Option<i32>,
-    start_index: Option<i32>,
-    state: State<'_, AppState>,
-) -> Result<MusicLibraryResult, String> {
-    let client_config = {
-        let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
-        client.get_config().cloned()
-    };
+    info!("Switched user from {} to {}", current_config.username, config.username);
 
-    let config = match client_config {
-        Some(config) => config,
-        None => {
-            return Ok(MusicLibraryResult {
-                success: false,
-                message: "Not authenticated".to_string(),
-                items: None,
-                total_count: None,
-            });
-        }
-    };
+    Ok(ConnectResult {
+        success: true,
+        message: "Switched user successfully".to_string(),
+        user_name: Some(config.username),
+        server_name: None,
+        error: None,
+    })
+}
 
-    let mut client = JellyfinClient::new();
-    client.set_config(config);
+const DEFAULT_TOKEN_REVALIDATION_SECS: u64 = 15 * 60;
+const MIN_TOKEN_REVALIDATION_SECS: u64 = 60;
 
-    match client.get_albums(limit, start_index).await {
-        Ok(response) => Ok(MusicLibraryResult {
-            success: true,
-            message: "Albums retrieved successfully".to_string(),
-            items: Some(response.items),
-            total_count: Some(response.total_record_count),
-        }),
-        Err(e) => Ok(MusicLibraryResult {
-            success: false,
-            message: format!("Failed to get albums: {}", e),
-            items: None,
-            total_count: None,
-        }),
+fn stop_token_revalidation(state: &State<'_, AppState>) -> Result<(), String> {
+    let mut token_revalidator = state.token_revalidator.lock().map_err(|e| e.to_string())?;
+    if let Some(handle) = token_revalidator.take() {
+        handle.abort();
     }
+    Ok(())
 }
 
-#[tauri::command]
-pub async fn get_artists(
-    limit: Option<i32>,
+// (Re)start the periodic token check, if enabled, replacing any running one
+fn start_token_revalidation(state: &State<'_, AppState>, app_handle: &tauri::AppHandle) -> Result<(), String> {
+    stop_token_revalidation(state)?;
+
+    let interval = {
+        let interval = state.token_revalidation_interval.lock().map_err(|e| e.to_string())?;
+        match *interval {
+            Some(interval) => interval,
+            None => return Ok(()),
+        }
+    };
+
+    let jellyfin_client = state.jellyfin_client.clone();
+    let app_handle = app_handle.clone();
+    let handle = tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(std::time::Duration::from_secs(interval)).await;
+
+            let client_config = match jellyfin_client.lock() {
+                Ok(client) => client.get_config().cloned(),
+                Err(_) => break,
+            };
+            let Some(config) = client_config else {
+                break;
+            };
+
+            let mut client = JellyfinClient::new();
+            client.set_config(config);
+
+            match client.validate_token().await.map_err(|e| e.to_string()) {
+                Ok(true) => {}
+                Ok(false) => {
+                    info!("Access token no longer valid, ending session");
+                    if let Ok(mut shared_client) = jellyfin_client.lock() {
+                        *shared_client = JellyfinClient::new();
+                    }
+                    let _ = app_handle.emit("session-expired", ());
+                    break;
+                }
+                // Being offline isn't an expired session; check again next time
+                Err(e) => warn!("Token revalidation failed: {}", e),
+            }
+        }
+    });
+
+    let mut token_revalidator = state.token_revalidator.lock().map_err(|e| e.to_string())?;
+    *token_revalidator = Some(handle);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn set_token_revalidation(
+    enabled: bool,
+    interval_seconds: Option<u64>,
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<bool, CommandError> {
+    let interval = interval_seconds.unwrap_or(DEFAULT_TOKEN_REVALIDATION_SECS);
+    if enabled && interval < MIN_TOKEN_REVALIDATION_SECS {
+        return Err(format!("Revalidation interval must be at least {} seconds", MIN_TOKEN_REVALIDATION_SECS).into());
+    }
+
+    {
+        let mut revalidation_interval = state.token_revalidation_interval.lock().map_err(|e| e.to_string())?;
+        *revalidation_interval = enabled.then_some(interval);
+    }
+
+    let is_authenticated = {
+        let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+        client.get_config().is_some()
+    };
+    if is_authenticated {
+        start_token_revalidation(&state, &app_handle)?;
+    } else {
+        stop_token_revalidation(&state)?;
+    }
+
+    Ok(true)
+}
+
+pub const MIN_PAGE_SIZE: i32 = 20;
+pub const MAX_PAGE_SIZE: i32 = 500;
+
+// The caller's limit, or the app-wide default page size when none was given
+fn resolve_page_limit(state: &State<'_, AppState>, limit: Option<i32>) -> Result<Option<i32>, String> {
+    if limit.is_some() {
+        return Ok(limit);
+    }
+    let default_page_size = state.default_page_size.lock().map_err(|e| e.to_string())?;
+    Ok(*default_page_size)
+}
+
+// Whether anything lies past this page; a last page that is exactly full has nothing after it
+fn has_more_pages(response: &jellyfin::ItemsResponse) -> bool {
+    (response.start_index as i64 + response.items.len() as i64) < response.total_record_count as i64
+}
+
+#[tauri::command]
+pub async fn set_default_page_size(
+    page_size: i32,
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<bool, CommandError> {
+    if !(MIN_PAGE_SIZE..=MAX_PAGE_SIZE).contains(&page_size) {
+        return Err(format!("Page size must be between {} and {}", MIN_PAGE_SIZE, MAX_PAGE_SIZE).into());
+    }
+
+    {
+        let mut default_page_size = state.default_page_size.lock().map_err(|e| e.to_string())?;
+        *default_page_size = Some(page_size);
+    }
+
+    if let Err(e) = storage::save_default_page_size(&app_handle, page_size).await {
+        error!("Failed to save default page size: {}", e);
+    }
+
+    Ok(true)
+}
+
+#[tauri::command]
+pub async fn get_songs(
+    limit: Option<i32>,
     start_index: Option<i32>,
+    sort_by: Option<String>,
+    sort_order: Option<String>,
     state: State<'_, AppState>,
-) -> Result<MusicLibraryResult, String> {
+) -> Result<MusicLibraryResult, CommandError> {
+    let limit = resolve_page_limit(&state, limit)?;
+    let (sort_by, sort_order) = jellyfin::validate_sort(sort_by.as_deref(), sort_order.as_deref())?;
+    info!("get_songs called with limit: {:?}, start_index: {:?}", limit, start_index);
     let client_config = {
         let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
         client.get_config().cloned()
@@ -380,8 +944,11 @@ pub async fn get_artists(
             return Ok(MusicLibraryResult {
                 success: false,
                 message: "Not authenticated".to_string(),
+                start_index: None,
+                has_more: false,
                 items: None,
                 total_count: None,
+                error: Some(JellyfinError::NotAuthenticated),
             });
         }
     };
@@ -389,18 +956,155 @@ pub async fn get_artists(
     let mut client = JellyfinClient::new();
     client.set_config(config);
 
-    match client.get_artists(limit, start_index).await {
+    match client.get_songs(limit, start_index, sort_by, sort_order).await {
+        Ok(response) => Ok(MusicLibraryResult {
+            success: true,
+            message: "Songs retrieved successfully".to_string(),
+            start_index: Some(response.start_index),
+            has_more: has_more_pages(&response),
+            items: Some(response.items),
+            total_count: Some(response.total_record_count),
+            error: None,
+        }),
+        Err(e) => Ok(MusicLibraryResult {
+            success: false,
+            message: format!("Failed to get songs: {}", e),
+            start_index: None,
+            has_more: false,
+            items: None,
+            total_count: None,
+            error: Some(e),
+        }),
+    }
+}
+
+// Page of the song list by number (counting from 0) rather than by offset
+#[tauri::command]
+pub async fn get_songs_page(
+    page: i32,
+    page_size: i32,
+    sort_by: Option<String>,
+    sort_order: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<MusicLibraryResult, CommandError> {
+    if page < 0 {
+        return Err("Page must not be negative".into());
+    }
+    if !(MIN_PAGE_SIZE..=MAX_PAGE_SIZE).contains(&page_size) {
+        return Err(format!("Page size must be between {} and {}", MIN_PAGE_SIZE, MAX_PAGE_SIZE).into());
+    }
+    let start_index = page.checked_mul(page_size).ok_or("Page is out of range")?;
+    get_songs(Some(page_size), Some(start_index), sort_by, sort_order, state).await
+}
+
+#[tauri::command]
+pub async fn get_albums(
+    limit: Option<i32>,
+    start_index: Option<i32>,
+    sort_by: Option<String>,
+    sort_order: Option<String>,
+    force: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<MusicLibraryResult, CommandError> {
+    let limit = resolve_page_limit(&state, limit)?;
+    let (sort_by, sort_order) = jellyfin::validate_sort(sort_by.as_deref(), sort_order.as_deref())?;
+    // The app's client, cloned so the listing cache carries over between calls
+    let mut client = {
+        let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+        client.clone()
+    };
+
+    if client.get_config().is_none() {
+        return Ok(MusicLibraryResult {
+            success: false,
+            message: "Not authenticated".to_string(),
+            start_index: None,
+            has_more: false,
+            items: None,
+            total_count: None,
+            error: Some(JellyfinError::NotAuthenticated),
+        });
+    }
+
+    // Forcing drops the cached pages of this listing, so the fetch goes to the server
+    if force.unwrap_or(false) {
+        client.invalidate_cache_for("MusicAlbum");
+    }
+
+    match client.get_albums(limit, start_index, sort_by, sort_order).await {
+        Ok(response) => Ok(MusicLibraryResult {
+            success: true,
+            message: "Albums retrieved successfully".to_string(),
+            start_index: Some(response.start_index),
+            has_more: has_more_pages(&response),
+            items: Some(response.items),
+            total_count: Some(response.total_record_count),
+            error: None,
+        }),
+        Err(e) => Ok(MusicLibraryResult {
+            success: false,
+            message: format!("Failed to get albums: {}", e),
+            start_index: None,
+            has_more: false,
+            items: None,
+            total_count: None,
+            error: Some(e),
+        }),
+    }
+}
+
+#[tauri::command]
+pub async fn get_artists(
+    limit: Option<i32>,
+    start_index: Option<i32>,
+    sort_by: Option<String>,
+    sort_order: Option<String>,
+    force: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<MusicLibraryResult, CommandError> {
+    let limit = resolve_page_limit(&state, limit)?;
+    let (sort_by, sort_order) = jellyfin::validate_sort(sort_by.as_deref(), sort_order.as_deref())?;
+    // The app's client, cloned so the listing cache carries over between calls
+    let mut client = {
+        let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+        client.clone()
+    };
+
+    if client.get_config().is_none() {
+        return Ok(MusicLibraryResult {
+            success: false,
+            message: "Not authenticated".to_string(),
+            start_index: None,
+            has_more: false,
+            items: None,
+            total_count: None,
+            error: Some(JellyfinError::NotAuthenticated),
+        });
+    }
+
+    // Forcing drops the cached pages of this listing, so the fetch goes to the server
+    if force.unwrap_or(false) {
+        client.invalidate_cache_for("MusicArtist");
+    }
+
+    match client.get_artists(limit, start_index, sort_by, sort_order).await {
         Ok(response) => Ok(MusicLibraryResult {
             success: true,
             message: "Artists retrieved successfully".to_string(),
+            start_index: Some(response.start_index),
+            has_more: has_more_pages(&response),
             items: Some(response.items),
             total_count: Some(response.total_record_count),
+            error: None,
         }),
         Err(e) => Ok(MusicLibraryResult {
             success: false,
             message: format!("Failed to get artists: {}", e),
+            start_index: None,
+            has_more: false,
             items: None,
             total_count: None,
+            error: Some(e),
         }),
     }
 }
@@ -409,40 +1113,54 @@ pub async fn get_artists(
 pub async fn get_playlists(
     limit: Option<i32>,
     start_index: Option<i32>,
+    sort_by: Option<String>,
+    sort_order: Option<String>,
+    force: Option<bool>,
     state: State<'_, AppState>,
-) -> Result<MusicLibraryResult, String> {
-    let client_config = {
+) -> Result<MusicLibraryResult, CommandError> {
+    let limit = resolve_page_limit(&state, limit)?;
+    let (sort_by, sort_order) = jellyfin::validate_sort(sort_by.as_deref(), sort_order.as_deref())?;
+    // The app's client, cloned so the listing cache carries over between calls
+    let mut client = {
         let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
-        client.get_config().cloned()
+        client.clone()
     };
 
-    let config = match client_config {
-        Some(config) => config,
-        None => {
-            return Ok(MusicLibraryResult {
-                success: false,
-                message: "Not authenticated".to_string(),
-                items: None,
-                total_count: None,
-            });
-        }
-    };
+    if client.get_config().is_none() {
+        return Ok(MusicLibraryResult {
+            success: false,
+            message: "Not authenticated".to_string(),
+            start_index: None,
+            has_more: false,
+            items: None,
+            total_count: None,
+            error: Some(JellyfinError::NotAuthenticated),
+        });
+    }
 
-    let mut client = JellyfinClient::new();
-    client.set_config(config);
+    // Forcing drops the cached pages of this listing, so the fetch goes to the server
+    if force.unwrap_or(false) {
+        client.invalidate_cache_for("Playlist");
+    }
 
-    match client.get_playlists(limit, start_index).await {
+    match client.get_playlists(limit, start_index, sort_by, sort_order).await {
         Ok(response) => Ok(MusicLibraryResult {
             success: true,
             message: "Playlists retrieved successfully".to_string(),
+            start_index: Some(response.start_index),
+            has_more: has_more_pages(&response),
             items: Some(response.items),
             total_count: Some(response.total_record_count),
+            error: None,
         }),
         Err(e) => Ok(MusicLibraryResult {
             success: false,
             message: format!("Failed to get playlists: {}", e),
+            start_index: None,
+            has_more: false,
             items: None,
             total_count: None,
+            error: Some(e),
         }),
     }
 }
@@ -452,7 +1170,9 @@ pub async fn search_music(
     query: String,
     limit: Option<i32>,
     state: State<'_, AppState>,
-) -> Result<MusicLibraryResult, String> {
+    app_handle: tauri::AppHandle,
+) -> Result<MusicLibraryResult, CommandError> {
+    let limit = resolve_page_limit(&state, limit)?;
     let client_config = {
         let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
         client.get_config().cloned()
@@ -464,8 +1184,11 @@ pub async fn search_music(
             return Ok(MusicLibraryResult {
                 success: false,
                 message: "Not authenticated".to_string(),
+                start_index: None,
+                has_more: false,
                 items: None,
                 total_count: None,
+                error: Some(JellyfinError::NotAuthenticated),
             });
         }
     };
@@ -474,27 +1197,65 @@ pub async fn search_music(
     client.set_config(config);
 
     match client.search(&query, limit).await {
-        Ok(response) => Ok(MusicLibraryResult {
-            success: true,
-            message: "Search completed successfully".to_string(),
-            items: Some(response.items),
-            total_count: Some(response.total_record_count),
-        }),
+        Ok(response) => {
+            if let Err(e) = storage::record_recent_search(&app_handle, &query).await {
+                error!("Failed to save recent search: {}", e);
+            }
+
+            Ok(MusicLibraryResult {
+                success: true,
+                message: "Search completed successfully".to_string(),
+                start_index: Some(response.start_index),
+                has_more: has_more_pages(&response),
+                items: Some(response.items),
+                total_count: Some(response.total_record_count),
+                error: None,
+            })
+        }
         Err(e) => Ok(MusicLibraryResult {
             success: false,
             message: format!("Search failed: {}", e),
+            start_index: None,
+            has_more: false,
             items: None,
             total_count: None,
+            error: Some(e),
         }),
     }
 }
 
 #[tauri::command]
-pub async fn get_image_url(
-    item_id: String,
-    image_type: String,
+pub async fn get_recent_searches(app_handle: tauri::AppHandle) -> Result<Vec<RecentSearch>, CommandError> {
+    let searches = storage::load_recent_searches(&app_handle)
+        .await
+        .map_err(|e| format!("Failed to load recent searches: {}", e))?;
+    Ok(searches)
+}
+
+#[tauri::command]
+pub async fn clear_recent_searches(app_handle: tauri::AppHandle) -> Result<bool, CommandError> {
+    storage::clear_recent_searches(&app_handle)
+        .await
+        .map_err(|e| format!("Failed to clear recent searches: {}", e))?;
+    Ok(true)
+}
+
+const SEARCH_INDEX_PAGE_SIZE: i32 = 500;
+const SEARCH_INDEX_ITEM_TYPES: [&str; 3] = ["Audio", "MusicAlbum", "MusicArtist"];
+
+fn search_index_path(app_handle: &tauri::AppHandle) -> Result<std::path::PathBuf, String> {
+    app_handle
+        .path()
+        .app_data_dir()
+        .map(|dir| dir.join("search_index.json"))
+        .map_err(|e| format!("Failed to resolve app data directory: {}", e))
+}
+
+#[tauri::command]
+pub async fn build_search_index(
+    app_handle: tauri::AppHandle,
     state: State<'_, AppState>,
-) -> Result<String, String> {
+) -> Result<usize, CommandError> {
     let client_config = {
         let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
         client.get_config().cloned()
@@ -503,24 +1264,54 @@ pub async fn get_image_url(
     let config = match client_config {
         Some(config) => config,
         None => {
-            return Err("Not authenticated".to_string());
+            return Err(JellyfinError::NotAuthenticated.into());
         }
     };
 
     let mut client = JellyfinClient::new();
-    client.set_config(config);
+    client.set_config(config.clone());
+
+    let mut entries = Vec::new();
+    for item_type in SEARCH_INDEX_ITEM_TYPES {
+        let mut start_index = 0;
+        loop {
+            let page = client
+                .get_items(item_type, Some(SEARCH_INDEX_PAGE_SIZE), Some(start_index))
+                .await
+                .map_err(failed(format!("Failed to fetch {} items", item_type)))?;
 
-    match client.get_image_url(&item_id, &image_type) {
-        Ok(url) => Ok(url),
-        Err(e) => Err(format!("Failed to get image URL: {}", e)),
+            let page_len = page.items.len() as i32;
+            entries.extend(page.items.iter().map(IndexEntry::from));
+            start_index += page_len;
+
+            if page_len == 0 || start_index >= page.total_record_count {
+                break;
+            }
+        }
+        // Pages are only needed once; don't keep them in the response cache
+        client.invalidate_cache();
     }
+
+    let index = SearchIndex::new(config.server_url, config.user_id, entries);
+    index.save(&search_index_path(&app_handle)?)
+        .map_err(|e| format!("Failed to save search index: {}", e))?;
+
+    let count = index.entries.len();
+    info!("Built search index with {} items", count);
+
+    let mut search_index = state.search_index.lock().map_err(|e| e.to_string())?;
+    *search_index = Some(index);
+    Ok(count)
 }
 
 #[tauri::command]
-pub async fn get_stream_url(
-    item_id: String,
+pub async fn local_search(
+    query: String,
+    limit: Option<usize>,
+    app_handle: tauri::AppHandle,
     state: State<'_, AppState>,
-) -> Result<String, String> {
+) -> Result<LocalSearchResult, CommandError> {
+    let limit = limit.unwrap_or(50);
     let client_config = {
         let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
         client.get_config().cloned()
@@ -529,251 +1320,3357 @@ pub async fn get_stream_url(
     let config = match client_config {
         Some(config) => config,
         None => {
-            return Err("Not authenticated".to_string());
+            return Ok(LocalSearchResult {
+                success: false,
+                message: "Not authenticated".to_string(),
+                from_index: false,
+                items: Vec::new(),
+            });
+        }
+    };
+
+    let index_matches = {
+        let mut search_index = state.search_index.lock().map_err(|e| e.to_string())?;
+        if search_index.is_none() {
+            *search_index = SearchIndex::load(&search_index_path(&app_handle)?);
         }
+        search_index
+            .as_ref()
+            .filter(|index| !index.is_stale(&config.server_url, &config.user_id))
+            .map(|index| index.search(&query, limit))
     };
 
+    if let Some(items) = index_matches {
+        return Ok(LocalSearchResult {
+            success: true,
+            message: "Search completed from local index".to_string(),
+            from_index: true,
+            items,
+        });
+    }
+
+    // No usable index, so ask the server instead
+    info!("Search index missing or stale, falling back to server search");
     let mut client = JellyfinClient::new();
     client.set_config(config);
 
-    match client.get_stream_url(&item_id) {
-        Ok(url) => Ok(url),
-        Err(e) => Err(format!("Failed to get stream URL: {}", e)),
+    let server_result = client.search(&query, Some(limit as i32)).await.map_err(|e| e.to_string());
+    match server_result {
+        Ok(response) => Ok(LocalSearchResult {
+            success: true,
+            message: "Search completed on server".to_string(),
+            from_index: false,
+            items: response.items.iter().map(IndexEntry::from).collect(),
+        }),
+        Err(e) => Ok(LocalSearchResult {
+            success: false,
+            message: format!("Search failed: {}", e),
+            from_index: false,
+            items: Vec::new(),
+        }),
     }
 }
 
-// Audio Player Commands
-
+// Whichever image the item actually has as a data URL, or None when it has no art at all
 #[tauri::command]
-pub async fn play_song(
+pub async fn fetch_best_image(
     item_id: String,
+    max_width: Option<u32>,
     state: State<'_, AppState>,
-) -> Result<bool, String> {
-    // Get Jellyfin client config
-    let jellyfin_config = {
+) -> Result<Option<String>, CommandError> {
+    let client_config = {
         let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
         client.get_config().cloned()
     };
 
-    let config = match jellyfin_config {
+    let config = match client_config {
         Some(config) => config,
         None => {
-            return Err("Not authenticated with Jellyfin".to_string());
+            return Err(JellyfinError::NotAuthenticated.into());
         }
     };
 
-    // Create temporary client to get song details and stream URL
-    let mut jellyfin_client = JellyfinClient::new();
-    jellyfin_client.set_config(config);
+    let mut client = JellyfinClient::new();
+    client.set_config(config);
 
-    // Get stream URL
-    let stream_url = match jellyfin_client.get_stream_url(&item_id) {
-        Ok(url) => url,
-        Err(e) => {
-            return Err(format!("Failed to get stream URL: {}", e));
-        }
+    let item = client.get_item_details(&item_id).await
+        .map_err(failed("Failed to get item details"))?;
+
+    match item.best_image_type() {
+        Some(image_type) => fetch_image(item_id, image_type.to_string(), max_width, state).await,
+        None => Ok(None),
+    }
+}
+
+#[tauri::command]
+pub async fn get_image_bytes(
+    item_id: String,
+    image_type: String,
+    state: State<'_, AppState>,
+) -> Result<Option<ImageBytes>, CommandError> {
+    let client_config = {
+        let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+        client.get_config().cloned()
     };
 
-    // Try to get cached audio file or cache it
-    let cached_url = {
-        // First, check if already cached
-        let cached_path = {
-            let mut cache = state.audio_cache.lock().await;
-            cache.get_cached_path(&item_id)
-        };
-        
-        if let Some(cached_path) = cached_path {
-            format!("file://{}", cached_path.to_string_lossy())
-        } else {
-            // Cache the audio file
-            let cache_result = {
-                let mut cache = state.audio_cache.lock().await;
-                cache.cache_audio(&item_id, &stream_url).await
-            };
-            
-            match cache_result {
-                Ok(cached_path) => {
-                    println!("✅ Successfully cached audio for song: {}", item_id);
-                    format!("file://{}", cached_path.to_string_lossy())
-                },
-                Err(e) => {
-                    println!("⚠️ Failed to cache audio for song {}: {}", item_id, e);
-                    // Fall back to direct streaming
-                    stream_url.clone()
-                }
-            }
+    if let Some(config) = client_config {
+        let mut client = JellyfinClient::new();
+        client.set_config(config);
+
+        let fetched = client
+            .get_image_bytes(&item_id, &image_type, None)
+            .await
+            .map_err(|e| e.to_string());
+        match fetched {
+            Ok((mime_type, data)) => return Ok(Some(ImageBytes { mime_type, data })),
+            Err(e) => warn!("Image fetch failed for {}, trying cached cover art: {}", item_id, e),
         }
-    };
+    }
 
-    // Get song details from Jellyfin
-    let song_details = match jellyfin_client.get_item_details(&item_id).await {
-        Ok(item) => item,
-        Err(e) => {
-            return Err(format!("Failed to get song details: {}", e));
+    // Offline: fall back to cover art extracted from the cached audio file
+    let cache = state.audio_cache.lock().await;
+    Ok(cache
+        .get_cover_art(&item_id)
+        .map(|(mime_type, data)| ImageBytes { mime_type, data }))
+}
+
+// An item's image as a data URL, fetched with the auth header so the access token never ends up
+// in an img src. None when the item has no image of that type
+#[tauri::command]
+pub async fn fetch_image(
+    item_id: String,
+    image_type: String,
+    max_width: Option<u32>,
+    state: State<'_, AppState>,
+) -> Result<Option<String>, CommandError> {
+    use base64::Engine;
+
+    let key = ImageCache::key(&item_id, &image_type, max_width);
+    {
+        let mut image_cache = state.image_cache.lock().map_err(|e| e.to_string())?;
+        if let Some(data_url) = image_cache.get(&key) {
+            return Ok(Some(data_url));
         }
+    }
+
+    let client_config = {
+        let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+        client.get_config().cloned()
     };
 
-    // Extract artist names
-    let artists = if let Some(ref artists_vec) = song_details.artists {
-        if !artists_vec.is_empty() {
-            artists_vec.clone()
-        } else if let Some(ref album_artist) = song_details.album_artist {
-            vec![album_artist.clone()]
-        } else {
-            vec!["Unknown Artist".to_string()]
+    let config = match client_config {
+        Some(config) => config,
+        None => {
+            return Err(JellyfinError::NotAuthenticated.into());
         }
-    } else if let Some(ref album_artist) = song_details.album_artist {
-        vec![album_artist.clone()]
-    } else {
-        vec!["Unknown Artist".to_string()]
     };
 
-    // Extract artist IDs
-    let artist_ids = if let Some(ref artist_items) = song_details.artist_items {
-        Some(artist_items.iter().map(|item| item.id.clone()).collect())
-    } else {
-        None
+    let mut client = JellyfinClient::new();
+    client.set_config(config);
+
+    let (mime_type, data) = match client.get_image_bytes(&item_id, &image_type, max_width).await {
+        Ok(image) => image,
+        Err(JellyfinError::NotFound(_)) => return Ok(None),
+        Err(e) => return Err(failed("Failed to fetch image")(e)),
     };
+    let data_url = format!("data:{};base64,{}", mime_type, base64::engine::general_purpose::STANDARD.encode(data));
 
-    // Create queue item with real song data (use cached URL if available)
-    let queue_item = QueueItem {
-        id: item_id.clone(),
-        name: song_details.name.clone(),
-        artists: artists.clone(),
-        artist_ids: artist_ids.clone(),
-        album: song_details.album.clone(),
-        duration_ticks: song_details.runtime_ticks,
-        stream_url: cached_url.clone(),
+    let mut image_cache = state.image_cache.lock().map_err(|e| e.to_string())?;
+    image_cache.insert(key, data_url.clone());
+    Ok(Some(data_url))
+}
+
+// Listing types kept in the response cache
+const CACHED_LIBRARY_TYPES: [&str; 3] = ["MusicAlbum", "MusicArtist", "Playlist"];
+
+// Drop cached listings (of one item type, or all of them) and fetch each first page again,
+// so newly added items show up without waiting out the cache. Returns each type's fresh total
+#[tauri::command]
+pub async fn refresh_library(
+    item_type: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<HashMap<String, i32>, CommandError> {
+    let mut client = {
+        let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+        client.clone()
     };
+    if client.get_config().is_none() {
+        return Err(JellyfinError::NotAuthenticated.into());
+    }
 
-    // Play the song - clone the AudioPlayer to avoid holding the lock
+    let item_types = match item_type.as_deref() {
+        Some(item_type) => {
+            let item_type = CACHED_LIBRARY_TYPES
+                .into_iter()
+                .find(|cached_type| *cached_type == item_type)
+                .ok_or_else(|| format!("Unknown library type: {}", item_type))?;
+            client.invalidate_cache_for(item_type);
+            vec![item_type]
+        }
+        None => {
+            client.invalidate_cache();
+            CACHED_LIBRARY_TYPES.to_vec()
+        }
+    };
+
+    let page_size = resolve_page_limit(&state, None)?;
+    let mut totals = HashMap::new();
+    for item_type in item_types {
+        let response = client.get_items(item_type, page_size, None).await
+            .map_err(failed(format!("Failed to refresh {}", item_type)))?;
+        totals.insert(item_type.to_string(), response.total_record_count);
+    }
+
+    info!("Refreshed library listings: {:?}", totals);
+    Ok(totals)
+}
+
+// Longest a listing may be served from the cache
+pub const MAX_CACHE_TTL_SECONDS: u64 = 24 * 60 * 60;
+
+// How long library listings are served from the cache; 0 turns caching off
+#[tauri::command]
+pub async fn set_cache_ttl(
+    seconds: u64,
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<bool, CommandError> {
+    if seconds > MAX_CACHE_TTL_SECONDS {
+        return Err(format!("Cache lifetime must be at most {} seconds", MAX_CACHE_TTL_SECONDS).into());
+    }
+
+    {
+        let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+        client.set_cache_ttl(seconds);
+    }
+
+    if let Err(e) = storage::save_cache_ttl(&app_handle, seconds).await {
+        error!("Failed to save cache lifetime: {}", e);
+    }
+
+    Ok(true)
+}
+
+#[tauri::command]
+pub fn prune_expired_cache(state: State<'_, AppState>) -> Result<usize, CommandError> {
+    let mut client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+    Ok(client.prune_expired_cache())
+}
+
+#[tauri::command]
+pub async fn get_share_link(
+    item_id: String,
+    state: State<'_, AppState>,
+) -> Result<String, CommandError> {
+    let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+    client
+        .get_share_link(&item_id)
+        .map_err(failed("Failed to get share link"))
+}
+
+#[tauri::command]
+pub async fn get_stream_url(
+    item_id: String,
+    state: State<'_, AppState>,
+) -> Result<String, CommandError> {
+    let client_config = {
+        let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+        client.get_config().cloned()
+    };
+
+    let config = match client_config {
+        Some(config) => config,
+        None => {
+            return Err(JellyfinError::NotAuthenticated.into());
+        }
+    };
+
+    let mut client = JellyfinClient::new();
+    client.set_config(config);
+
+    client.get_stream_url(&item_id).map_err(failed("Failed to get stream URL"))
+}
+
+#[tauri::command]
+pub async fn get_recommended_profile(
+    item_id: String,
+    network_type: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<StreamingProfile, CommandError> {
+    let network_type = network_type.unwrap_or_else(|| "unknown".to_string());
+
+    // Reuse the cached profile until the network type changes
+    {
+        let profile = state.streaming_profile.lock().map_err(|e| e.to_string())?;
+        if let Some(profile) = profile.as_ref() {
+            if profile.network_type == network_type {
+                return Ok(profile.clone());
+            }
+        }
+    }
+
+    let client_config = {
+        let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+        client.get_config().cloned()
+    };
+
+    let config = match client_config {
+        Some(config) => config,
+        None => {
+            return Err(JellyfinError::NotAuthenticated.into());
+        }
+    };
+
+    let mut client = JellyfinClient::new();
+    client.set_config(config);
+
+    // Be conservative with bandwidth on metered connections
+    let max_bitrate = match network_type.as_str() {
+        "cellular" => 320_000,
+        _ => 140_000_000,
+    };
+
+    let playback_info = client.get_playback_info(&item_id, max_bitrate).await
+        .map_err(failed("Failed to get playback info"))?;
+
+    let profile = StreamingProfile::from_playback_info(&playback_info, max_bitrate, &network_type);
+    info!("Negotiated streaming profile: {:?}", profile);
+
+    {
+        let mut cached = state.streaming_profile.lock().map_err(|e| e.to_string())?;
+        *cached = Some(profile.clone());
+    }
+
+    Ok(profile)
+}
+
+fn emit_cache_progress(app_handle: &tauri::AppHandle, item_id: &str, downloaded: u64, total: Option<u64>) {
+    let _ = app_handle.emit("cache-progress", CacheProgress {
+        item_id: item_id.to_string(),
+        downloaded,
+        total,
+    });
+}
+
+// Server URL for downloading a song at the chosen quality
+fn resolve_download_url(client: &JellyfinClient, quality: &DownloadQuality, item_id: &str) -> Result<String, CommandError> {
+    let url = match quality {
+        DownloadQuality::Original => client.get_stream_url(item_id),
+        DownloadQuality::Transcoded(max_bitrate) => {
+            let profile = StreamingProfile::transcoding(*max_bitrate, "download", None);
+            client.get_profile_stream_url(item_id, &profile)
+        }
+    };
+    url.map_err(failed("Failed to get download URL"))
+}
+
+// Download a song into the cache at the chosen quality, replacing a copy downloaded at another quality
+async fn download_to_cache(
+    state: &State<'_, AppState>,
+    app_handle: &tauri::AppHandle,
+    client: &JellyfinClient,
+    quality: &DownloadQuality,
+    item_id: &str,
+) -> Result<std::path::PathBuf, CommandError> {
+    let download_url = resolve_download_url(client, quality, item_id)?;
+    let cache_key = AudioCache::cache_key(item_id, quality);
+
+    // Explicit downloads at another quality get replaced, passing their pin on
+    let (replaced, was_pinned) = {
+        let cache = state.audio_cache.lock().await;
+        let replaced: Vec<String> = cache.song_keys(item_id)
+            .into_iter()
+            .filter(|key| *key != cache_key && cache.get_quality(key).is_some())
+            .collect();
+        let was_pinned = replaced.iter().any(|key| cache.is_pinned(key));
+        (replaced, was_pinned)
+    };
+
+    let path = AudioCache::cache_audio(&state.audio_cache, &cache_key, &download_url, |downloaded_bytes, total_bytes| {
+        emit_cache_progress(app_handle, item_id, downloaded_bytes, total_bytes);
+    }).await
+        .map_err(|e| e.to_string())?;
+
+    let mut cache = state.audio_cache.lock().await;
+    for key in replaced {
+        info!("Replacing download {} with {:?} quality", key, quality);
+        cache.evict(&key);
+    }
+    cache.set_quality(&cache_key, quality.clone());
+    if was_pinned {
+        cache.pin(&cache_key);
+    }
+    Ok(path)
+}
+
+fn parse_quality(mode: &str, bitrate: Option<i64>) -> Result<DownloadQuality, String> {
+    match mode {
+        "original" => Ok(DownloadQuality::Original),
+        "transcoded" => Ok(DownloadQuality::Transcoded(bitrate.ok_or("Transcoded quality requires a bitrate")?)),
+        _ => Err("Invalid quality".to_string()),
+    }
+}
+
+#[tauri::command]
+pub async fn set_download_quality(
+    mode: String,
+    bitrate: Option<i64>,
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<bool, CommandError> {
+    let quality = parse_quality(&mode, bitrate)?;
+
+    {
+        let mut download_quality = state.download_quality.lock().map_err(|e| e.to_string())?;
+        *download_quality = quality.clone();
+    }
+
+    if let Err(e) = storage::save_download_quality(&app_handle, &quality).await {
+        error!("Failed to save download quality: {}", e);
+    }
+
+    Ok(true)
+}
+
+// Same modes as set_download_quality; anything but original transcodes while streaming
+#[tauri::command]
+pub async fn set_streaming_quality(
+    mode: String,
+    bitrate: Option<i64>,
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<bool, CommandError> {
+    let quality = parse_quality(&mode, bitrate)?;
+
+    {
+        let mut streaming_quality = state.streaming_quality.lock().map_err(|e| e.to_string())?;
+        *streaming_quality = quality.clone();
+    }
+
+    save_player_setting(&app_handle, |settings| settings.streaming_quality = quality).await;
+    Ok(true)
+}
+
+// How often the cache budget is re-checked in the background
+const CACHE_ENFORCE_INTERVAL_SECS: u64 = 30;
+// Smallest disk budget accepted for the audio cache
+pub const MIN_CACHE_BYTES: u64 = 100 * 1024 * 1024;
+
+// Keep the audio cache inside its budget even when bursts of downloads overshoot it,
+// reporting evicted cache keys through a "cache-evicted" event
+pub fn spawn_cache_enforcer(app_handle: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(std::time::Duration::from_secs(CACHE_ENFORCE_INTERVAL_SECS)).await;
+
+            let state = app_handle.state::<AppState>();
+            let audio_player = match state.audio_player.lock() {
+                Ok(ap) => ap.clone(),
+                Err(_) => break,
+            };
+            let audio_cache = state.audio_cache.clone();
+
+            // Never pull the file out from under the song that's playing
+            let in_use = playing_song_ids(&audio_player).await;
+
+            let evicted = {
+                let mut cache = audio_cache.lock().await;
+                cache.set_in_use(in_use);
+                cache.enforce_budget();
+                cache.take_evicted()
+            };
+            if !evicted.is_empty() {
+                let _ = app_handle.emit("cache-evicted", evicted);
+            }
+        }
+    });
+}
+
+async fn playing_song_ids(audio_player: &AudioPlayer) -> HashSet<String> {
+    audio_player.get_state().await
+        .ok()
+        .and_then(|playback| playback.current_song)
+        .map(|song| song.id)
+        .into_iter()
+        .collect()
+}
+
+// Progress is reported at most this often while a track plays uninterrupted
+const PLAYBACK_REPORT_INTERVAL_SECS: u64 = 10;
+// A position jump bigger than this between player events is reported as a seek
+const SEEK_REPORT_THRESHOLD_SECS: f64 = 3.0;
+
+// What the server was last told about the song that's playing
+struct ReportedPlayback {
+    item_id: String,
+    position: f64,
+    is_paused: bool,
+    updated_at: std::time::Instant,
+    reported_at: std::time::Instant,
+    // Report on the next event regardless of the interval
+    report_due: bool,
+}
+
+fn seconds_to_ticks(seconds: f64) -> i64 {
+    (seconds * 10_000_000.0) as i64
+}
+
+fn reporting_client(app_handle: &tauri::AppHandle) -> Option<JellyfinClient> {
+    let state = app_handle.state::<AppState>();
+    let config = state.jellyfin_client.lock().ok()?.get_config().cloned()?;
+    let mut client = JellyfinClient::new();
+    client.set_config(config);
+    Some(client)
+}
+
+// Mirror the player's lifecycle to the server's Sessions/Playing endpoints, so the
+// dashboard and other clients see what's playing and where it was left off
+pub fn spawn_playback_reporter(app_handle: tauri::AppHandle) {
+    let mut receiver = {
+        let state = app_handle.state::<AppState>();
+        let Ok(audio_player) = state.audio_player.lock() else {
+            return;
+        };
+        audio_player.subscribe_to_events()
+    };
+
+    tauri::async_runtime::spawn(async move {
+        use tokio::sync::broadcast::error::RecvError;
+
+        let mut current: Option<ReportedPlayback> = None;
+        loop {
+            let event = match receiver.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            };
+
+            let Some(client) = reporting_client(&app_handle) else {
+                current = None;
+                continue;
+            };
+
+            match event {
+                // Reloading the same song (a network seek) is progress, not a new play
+                PlayerEvent::TrackChanged(Some(item)) if current.as_ref().is_some_and(|playing| playing.item_id == item.id) => {
+                    if let Some(playing) = current.as_mut() {
+                        playing.report_due = true;
+                    }
+                }
+                PlayerEvent::TrackChanged(song) => {
+                    if let Some(previous) = current.take() {
+                        // Write the final position back as progress so it becomes the song's resume point
+                        let ticks = seconds_to_ticks(previous.position);
+                        let progress = client.report_playback_progress(&previous.item_id, ticks, true).await.map_err(|e| e.to_string());
+                        let stopped = client.report_playback_stopped(&previous.item_id, ticks).await.map_err(|e| e.to_string());
+                        if let Err(e) = progress.and(stopped) {
+                            warn!("{}", e);
+                        }
+                    }
+
+                    if let Some(item) = song {
+                        let result = client.report_playback_start(&item.id).await.map_err(|e| e.to_string());
+                        if let Err(e) = result {
+                            warn!("{}", e);
+                        }
+                        let now = std::time::Instant::now();
+                        current = Some(ReportedPlayback {
+                            item_id: item.id,
+                            position: 0.0,
+                            is_paused: false,
+                            updated_at: now,
+                            reported_at: now,
+                            report_due: false,
+                        });
+                    }
+                    continue;
+                }
+                PlayerEvent::StateChanged(playback) => {
+                    let Some(playing) = current.as_mut() else {
+                        continue;
+                    };
+                    // Stop and track switches briefly describe a different song
+                    if playback.current_song.as_ref().map(|song| song.id.as_str()) != Some(playing.item_id.as_str()) {
+                        continue;
+                    }
+
+                    let expected = if playing.is_paused {
+                        playing.position
+                    } else {
+                        playing.position + playing.updated_at.elapsed().as_secs_f64()
+                    };
+                    let seeked = (playback.current_position - expected).abs() > SEEK_REPORT_THRESHOLD_SECS;
+                    let pause_changed = playing.is_paused == playback.is_playing;
+
+                    playing.position = playback.current_position;
+                    playing.is_paused = !playback.is_playing;
+                    playing.updated_at = std::time::Instant::now();
+                    if seeked || pause_changed {
+                        playing.report_due = true;
+                    }
+                }
+                PlayerEvent::PositionUpdate(position) => {
+                    if let Some(playing) = current.as_mut() {
+                        playing.position = position;
+                        playing.updated_at = std::time::Instant::now();
+                    }
+                }
+                _ => continue,
+            }
+
+            let Some(playing) = current.as_mut() else {
+                continue;
+            };
+            if playing.report_due || playing.reported_at.elapsed().as_secs() >= PLAYBACK_REPORT_INTERVAL_SECS {
+                playing.reported_at = std::time::Instant::now();
+                playing.report_due = false;
+                let result = client
+                    .report_playback_progress(&playing.item_id, seconds_to_ticks(playing.position), playing.is_paused)
+                    .await
+                    .map_err(|e| e.to_string());
+                if let Err(e) = result {
+                    warn!("{}", e);
+                }
+            }
+        }
+    });
+}
+
+// PlayerEvent as pushed to the webview on "player-event", e.g. {"type": "PositionUpdate", "payload": 12.5}
+#[derive(Clone, serde::Serialize)]
+#[serde(tag = "type", content = "payload")]
+pub enum FrontendPlayerEvent {
+    StateChanged(PlaybackState),
+    TrackChanged(Option<QueueItem>),
+    PositionUpdate(f64),
+    AudioCacheRefreshed(String),
+    Clipping(f32),
+    Buffering(bool),
+    Reconnecting(bool),
+    SlowConnection(String),
+    Error(String),
+}
+
+// Fails for events that only matter to the backend
+impl TryFrom<PlayerEvent> for FrontendPlayerEvent {
+    type Error = ();
+
+    fn try_from(event: PlayerEvent) -> Result<Self, ()> {
+        Ok(match event {
+            PlayerEvent::StateChanged(state) => FrontendPlayerEvent::StateChanged(state),
+            PlayerEvent::TrackChanged(item) => FrontendPlayerEvent::TrackChanged(item),
+            PlayerEvent::PositionUpdate(position) => FrontendPlayerEvent::PositionUpdate(position),
+            PlayerEvent::AudioCacheRefreshed(song_id) => FrontendPlayerEvent::AudioCacheRefreshed(song_id),
+            PlayerEvent::Clipping(gain) => FrontendPlayerEvent::Clipping(gain),
+            PlayerEvent::Buffering(buffering) => FrontendPlayerEvent::Buffering(buffering),
+            PlayerEvent::Reconnecting(reconnecting) => FrontendPlayerEvent::Reconnecting(reconnecting),
+            PlayerEvent::SlowConnection(message) => FrontendPlayerEvent::SlowConnection(message),
+            PlayerEvent::Error(message) => FrontendPlayerEvent::Error(message),
+            PlayerEvent::AudioDownloaded { .. } | PlayerEvent::LocalFileUnusable { .. } => return Err(()),
+        })
+    }
+}
+
+// Sign in to Last.fm so listens get scrobbled; only the session key is kept, never the password.
+// Returns the Last.fm user name
+#[tauri::command]
+pub async fn connect_lastfm(
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+    username: String,
+    password: String,
+) -> Result<String, CommandError> {
+    let lastfm = LastfmClient::new()?;
+    let session = lastfm.get_mobile_session(username.trim(), &password).await?;
+
+    if let Err(e) = storage::save_lastfm_session(&app_handle, Some(&session)).await {
+        error!("Failed to save Last.fm session: {}", e);
+    }
+    info!("Connected to Last.fm as {}", session.username);
+    let username = session.username.clone();
+    let mut lastfm_session = state.lastfm_session.lock().map_err(|e| e.to_string())?;
+    *lastfm_session = Some(session);
+
+    Ok(username)
+}
+
+#[tauri::command]
+pub async fn disconnect_lastfm(state: State<'_, AppState>, app_handle: tauri::AppHandle) -> Result<bool, CommandError> {
+    {
+        let mut lastfm_session = state.lastfm_session.lock().map_err(|e| e.to_string())?;
+        *lastfm_session = None;
+    }
+    storage::save_lastfm_session(&app_handle, None).await.map_err(|e| e.to_string())?;
+    Ok(true)
+}
+
+// Show the playing song on the user's Discord profile; off by default
+#[tauri::command]
+pub async fn set_discord_presence_enabled(
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+    enabled: bool,
+) -> Result<bool, CommandError> {
+    {
+        let discord_presence = state.discord_presence.lock().map_err(|e| e.to_string())?;
+        let sender = discord_presence.as_ref().ok_or("This build has no Discord application ID")?;
+        sender.send(PresenceMessage::Enabled(enabled)).map_err(|_| "Discord presence is not running".to_string())?;
+    }
+
+    if let Err(e) = storage::save_discord_presence_enabled(&app_handle, enabled).await {
+        error!("Failed to save Discord presence setting: {}", e);
+    }
+    Ok(true)
+}
+
+// How much goes to stdout and the log file: "off", "error", "warn", "info", "debug" or "trace"
+#[tauri::command]
+pub async fn set_log_level(app_handle: tauri::AppHandle, level: String) -> Result<bool, CommandError> {
+    let level = level.to_lowercase();
+    let filter = logging::parse_level(&level).ok_or("Invalid log level")?;
+    logging::set_level(filter);
+    info!("Log level set to {}", filter);
+
+    if let Err(e) = storage::save_log_level(&app_handle, &level).await {
+        error!("Failed to save log level: {}", e);
+    }
+    Ok(true)
+}
+
+// Shortest gap between two queue saves; position ticks alone mark the queue dirty
+const QUEUE_SAVE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+// Save the queue to queue.json whenever the player changes, at most once per interval
+pub fn spawn_queue_persister(app_handle: tauri::AppHandle) {
     let audio_player = {
-        let ap = state.audio_player.lock().map_err(|e| e.to_string())?;
-        ap.clone()  // AudioPlayer is designed to be cloneable for this purpose
+        let state = app_handle.state::<AppState>();
+        let Ok(audio_player) = state.audio_player.lock() else {
+            return;
+        };
+        audio_player.clone()
     };
-    
-    // Try to play with cached URL first, fallback to original stream URL if it fails
-    match audio_player.play_item(queue_item).await {
-        Ok(_) => {
-            println!("✅ Successfully played song using cached/stream URL");
-            Ok(true)
-        },
-        Err(e) => {
-            // If cached file failed and we were using a cached URL, try original stream URL
-            if cached_url != stream_url {
-                println!("⚠️ Cached file failed ({}), trying original stream URL", e);
-                
-                let fallback_queue_item = QueueItem {
-                    id: item_id.clone(),
-                    name: song_details.name.clone(),
-                    artists: artists.clone(),
-                    artist_ids: artist_ids.clone(),
-                    album: song_details.album.clone(),
-                    duration_ticks: song_details.runtime_ticks,
-                    stream_url: stream_url,
-                };
-                
-                match audio_player.play_item(fallback_queue_item).await {
-                    Ok(_) => {
-                        println!("✅ Successfully played song using fallback stream URL");
-                        Ok(true)
-                    },
-                    Err(fallback_e) => {
-                        Err(format!("Failed to play song with both cached file and stream URL. Cached error: {}. Stream error: {}", e, fallback_e))
+    let mut receiver = audio_player.subscribe_to_events();
+
+    tauri::async_runtime::spawn(async move {
+        use tokio::sync::broadcast::error::RecvError;
+
+        let mut save_interval = tokio::time::interval(QUEUE_SAVE_INTERVAL);
+        let mut dirty = false;
+        // An empty queue at startup means nothing was played yet, not that the saved one should go
+        let mut saved_any = false;
+        loop {
+            tokio::select! {
+                event = receiver.recv() => match event {
+                    Ok(_) | Err(RecvError::Lagged(_)) => dirty = true,
+                    Err(RecvError::Closed) => break,
+                },
+                _ = save_interval.tick() => {
+                    if !std::mem::take(&mut dirty) {
+                        continue;
+                    }
+                    let snapshot = match audio_player.get_queue_snapshot().await {
+                        Ok(snapshot) => snapshot,
+                        Err(_) => continue,
+                    };
+                    if snapshot.items.is_empty() && !saved_any {
+                        continue;
+                    }
+                    match storage::save_queue(&app_handle, &snapshot).await {
+                        Ok(()) => saved_any = true,
+                        Err(e) => error!("Failed to save queue: {}", e),
                     }
                 }
-            } else {
-                Err(format!("Failed to play song: {}", e))
             }
         }
+    });
+}
+
+// Ids per request when looking up a saved queue, keeping the URL a sane length
+const RESTORE_QUEUE_BATCH_SIZE: usize = 100;
+
+// Bring back the queue saved by the last session, paused where it was left.
+// Songs no longer on the server are dropped; returns how many songs were restored
+#[tauri::command]
+pub async fn restore_queue(
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<usize, CommandError> {
+    let client = {
+        let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+        let config = client.get_config().cloned().ok_or("Not authenticated with Jellyfin")?;
+        let mut client = JellyfinClient::new();
+        client.set_config(config);
+        client
+    };
+    restore_saved_queue(&state, &app_handle, &client).await
+}
+
+// Restore the saved queue at startup, signed in with the saved login since the frontend
+// won't have checked it yet. Anything the user queues first wins
+pub fn spawn_queue_restore(app_handle: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let config = match storage::load_jellyfin_config(&app_handle).await {
+            Ok(Some(config)) => config,
+            Ok(None) => return,
+            Err(e) => {
+                warn!("Not restoring the queue, the saved login can't be read: {}", e);
+                return;
+            }
+        };
+        let mut client = JellyfinClient::new();
+        client.set_config(config);
+
+        let state = app_handle.state::<AppState>();
+        let audio_player = match state.audio_player.lock() {
+            Ok(audio_player) => audio_player.clone(),
+            Err(_) => return,
+        };
+        match audio_player.get_queue_snapshot().await {
+            Ok(current) if current.items.is_empty() => {}
+            _ => return,
+        }
+
+        match restore_saved_queue(&state, &app_handle, &client).await {
+            Ok(0) => {}
+            Ok(restored) => info!("Restored {} songs from the last session's queue", restored),
+            Err(e) => warn!("Failed to restore the queue: {}", e),
+        }
+    });
+}
+
+async fn restore_saved_queue(
+    state: &State<'_, AppState>,
+    app_handle: &tauri::AppHandle,
+    client: &JellyfinClient,
+) -> Result<usize, CommandError> {
+    let Some(mut snapshot) = storage::load_queue(app_handle).await.map_err(|e| e.to_string())? else {
+        return Ok(0);
+    };
+    if snapshot.items.is_empty() {
+        return Ok(0);
+    }
+
+    // Fetch the songs again so stale entries drop out and stream URLs carry the current token
+    let mut found: HashMap<String, MusicItem> = HashMap::new();
+    let ids: Vec<String> = snapshot.items.iter().map(|item| item.id.clone()).collect();
+    for chunk in ids.chunks(RESTORE_QUEUE_BATCH_SIZE) {
+        let items = client.get_items_by_ids(chunk).await
+            .map_err(failed("Failed to look up the saved queue"))?;
+        found.extend(items.into_iter().map(|item| (item.id.clone(), item)));
+    }
+
+    let streaming_profile = active_streaming_profile(state)?;
+
+    let saved_index = snapshot.current_index.unwrap_or(0);
+    let mut current_index = None;
+    let mut queue = Vec::with_capacity(found.len());
+    for (index, saved) in snapshot.items.iter().enumerate() {
+        let Some(item) = found.get(&saved.id) else {
+            warn!("Skipping queue item no longer on the server: {}", saved.name);
+            continue;
+        };
+        if index == saved_index {
+            current_index = Some(queue.len());
+        }
+        let queue_item = stream_queue_item(client, streaming_profile.as_ref(), item)?;
+        queue.push(queue_item);
+    }
+
+    if queue.is_empty() {
+        return Ok(0);
+    }
+    // When the current song itself is gone, pick up with whatever followed it, from the start
+    if current_index.is_none() {
+        current_index = Some(snapshot.items[..saved_index.min(snapshot.items.len())]
+            .iter()
+            .filter(|saved| found.contains_key(&saved.id))
+            .count()
+            .min(queue.len() - 1));
+        snapshot.current_position = 0.0;
+    }
+
+    let queue_len = queue.len();
+    snapshot.items = queue;
+    snapshot.current_index = current_index;
+
+    let audio_player = {
+        let ap = state.audio_player.lock().map_err(|e| e.to_string())?;
+        ap.clone()
+    };
+    audio_player.restore_queue(snapshot).await?;
+    start_queue_resolution(state, app_handle, audio_player)?;
+
+    Ok(queue_len)
+}
+
+// Push player events to the frontend so it doesn't have to poll get_playback_state
+pub fn spawn_player_event_forwarder(app_handle: tauri::AppHandle) {
+    let audio_player = {
+        let state = app_handle.state::<AppState>();
+        let Ok(audio_player) = state.audio_player.lock() else {
+            return;
+        };
+        audio_player.clone()
+    };
+    let mut receiver = audio_player.subscribe_to_events();
+
+    tauri::async_runtime::spawn(async move {
+        use tokio::sync::broadcast::error::RecvError;
+
+        loop {
+            let event = match receiver.recv().await {
+                Ok(event) => match FrontendPlayerEvent::try_from(event) {
+                    Ok(event) => event,
+                    Err(()) => continue,
+                },
+                // Skipped events can't be replayed, so resync the UI with a fresh state instead
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Player event forwarding lagged, {} events skipped", skipped);
+                    match audio_player.get_state().await {
+                        Ok(state) => FrontendPlayerEvent::StateChanged(state),
+                        Err(_) => continue,
+                    }
+                }
+                Err(RecvError::Closed) => break,
+            };
+
+            if let Err(e) = app_handle.emit("player-event", event) {
+                warn!("Failed to forward player event: {}", e);
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub async fn get_cache_stats(state: State<'_, AppState>) -> Result<CacheStats, CommandError> {
+    let cache = state.audio_cache.lock().await;
+    Ok(cache.get_cache_stats())
+}
+
+// Delete cached audio, keeping the file of the song that's playing
+#[tauri::command]
+pub async fn clear_audio_cache(
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<bool, CommandError> {
+    let audio_player = {
+        let audio_player = state.audio_player.lock().map_err(|e| e.to_string())?;
+        audio_player.clone()
+    };
+    let in_use = playing_song_ids(&audio_player).await;
+
+    let evicted = {
+        let mut cache = state.audio_cache.lock().await;
+        cache.set_in_use(in_use);
+        cache.clear_cache().map_err(|e| e.to_string())?;
+        cache.take_evicted()
+    };
+    if !evicted.is_empty() {
+        let _ = app_handle.emit("cache-evicted", evicted);
+    }
+
+    Ok(true)
+}
+
+#[tauri::command]
+pub async fn get_cache_usage(state: State<'_, AppState>) -> Result<CacheUsage, CommandError> {
+    let cache = state.audio_cache.lock().await;
+    Ok(cache.usage())
+}
+
+// Set both the file count and byte limits, evicting least recently used files until they hold
+#[tauri::command]
+pub async fn set_cache_limits(
+    max_entries: usize,
+    max_bytes: u64,
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<bool, CommandError> {
+    if max_entries == 0 {
+        return Err("Cache must allow at least one file".into());
+    }
+    if max_bytes < MIN_CACHE_BYTES {
+        return Err(format!("Cache limit must be at least {} bytes", MIN_CACHE_BYTES).into());
+    }
+
+    let audio_player = {
+        let ap = state.audio_player.lock().map_err(|e| e.to_string())?;
+        ap.clone()
+    };
+    // Refresh what's playing first, the enforcer may not have run since the song changed
+    let in_use = playing_song_ids(&audio_player).await;
+
+    let evicted = {
+        let mut cache = state.audio_cache.lock().await;
+        cache.set_in_use(in_use);
+        cache.set_cache_limits(max_entries, max_bytes);
+        cache.take_evicted()
+    };
+    if !evicted.is_empty() {
+        let _ = app_handle.emit("cache-evicted", evicted);
+    }
+
+    if let Err(e) = storage::save_cache_limits(&app_handle, max_entries, max_bytes).await {
+        error!("Failed to save cache limits: {}", e);
+    }
+
+    Ok(true)
+}
+
+// Move the audio cache to a user-chosen directory. If it can't be used, the cache falls back
+// to the default temp directory and the error says why
+#[tauri::command]
+pub async fn set_cache_directory(
+    path: String,
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<bool, CommandError> {
+    let path = path.trim();
+    if path.is_empty() {
+        return Err("Cache directory cannot be empty".into());
+    }
+
+    let result = {
+        let mut cache = state.audio_cache.lock().await;
+        match cache.set_cache_dir(std::path::PathBuf::from(path)) {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                let message = e.to_string();
+                if let Err(e) = cache.set_cache_dir(AudioCache::default_dir()) {
+                    warn!("Failed to fall back to the default cache directory: {}", e);
+                }
+                Err(format!("{}; using the default cache directory instead", message))
+            }
+        }
+    };
+
+    let saved_dir = result.is_ok().then_some(path);
+    if let Err(e) = storage::save_cache_directory(&app_handle, saved_dir).await {
+        error!("Failed to save cache directory: {}", e);
+    }
+
+    result?;
+    Ok(true)
+}
+
+// Choose whether songs cached while playing are originals or space-saving transcodes
+#[tauri::command]
+pub async fn set_cache_quality(
+    mode: String,
+    bitrate: Option<i64>,
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<bool, CommandError> {
+    let quality = parse_quality(&mode, bitrate)?;
+
+    {
+        let mut cache_quality = state.cache_quality.lock().map_err(|e| e.to_string())?;
+        *cache_quality = quality.clone();
+    }
+
+    if let Err(e) = storage::save_cache_quality(&app_handle, &quality).await {
+        error!("Failed to save cache quality: {}", e);
+    }
+
+    Ok(true)
+}
+
+#[tauri::command]
+pub async fn cache_song(
+    item_id: String,
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<bool, CommandError> {
+    let client_config = {
+        let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+        client.get_config().cloned()
+    };
+
+    let config = match client_config {
+        Some(config) => config,
+        None => {
+            return Err(JellyfinError::NotAuthenticated.into());
+        }
+    };
+
+    let mut client = JellyfinClient::new();
+    client.set_config(config);
+
+    let quality = {
+        let download_quality = state.download_quality.lock().map_err(|e| e.to_string())?;
+        download_quality.clone()
+    };
+
+    download_to_cache(&state, &app_handle, &client, &quality, &item_id).await
+        .map_err(|e| format!("Failed to cache song: {}", e))?;
+
+    Ok(true)
+}
+
+const OFFLINE_PROBE_CONCURRENCY: usize = 4;
+
+#[tauri::command]
+pub async fn prepare_offline(
+    item_ids: Vec<String>,
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<OfflineReadiness>, CommandError> {
+    use futures_util::StreamExt;
+
+    let client_config = {
+        let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+        client.get_config().cloned()
+    };
+
+    let config = match client_config {
+        Some(config) => config,
+        None => {
+            return Err(JellyfinError::NotAuthenticated.into());
+        }
+    };
+
+    let mut client = JellyfinClient::new();
+    client.set_config(config);
+
+    let quality = {
+        let download_quality = state.download_quality.lock().map_err(|e| e.to_string())?;
+        download_quality.clone()
+    };
+
+    // Make sure every item is downloaded and pinned
+    let mut report = Vec::with_capacity(item_ids.len());
+    let mut to_probe = Vec::new();
+    for item_id in item_ids {
+        let cache_result = download_to_cache(&state, &app_handle, &client, &quality, &item_id).await;
+        if cache_result.is_ok() {
+            let mut cache = state.audio_cache.lock().await;
+            cache.pin(&AudioCache::cache_key(&item_id, &quality));
+        }
+
+        match cache_result {
+            Ok(path) => to_probe.push((item_id, path)),
+            Err(e) => report.push(OfflineReadiness {
+                item_id,
+                ready: false,
+                message: format!("Download failed: {}", e),
+            }),
+        }
+    }
+
+    // Verify each file actually decodes, off the async runtime
+    let probed: Vec<OfflineReadiness> = futures_util::stream::iter(to_probe)
+        .map(|(item_id, path)| async move {
+            let probe = tokio::task::spawn_blocking(move || crate::audio_player::probe_audio_file(&path)).await;
+            match probe {
+                Ok(Ok(info)) => OfflineReadiness {
+                    item_id,
+                    ready: true,
+                    message: format!("Ready ({}, {} Hz)", info.codec, info.sample_rate),
+                },
+                Ok(Err(e)) => OfflineReadiness {
+                    item_id,
+                    ready: false,
+                    message: format!("File does not decode: {}", e),
+                },
+                Err(e) => OfflineReadiness {
+                    item_id,
+                    ready: false,
+                    message: format!("Probe task failed: {}", e),
+                },
+            }
+        })
+        .buffer_unordered(OFFLINE_PROBE_CONCURRENCY)
+        .collect()
+        .await;
+    report.extend(probed);
+
+    let ready = report.iter().filter(|r| r.ready).count();
+    info!("Offline preparation: {}/{} items ready", ready, report.len());
+
+    Ok(report)
+}
+
+// Queue songs for offline download at the download quality, returning those newly queued
+fn queue_downloads(state: &State<'_, AppState>, app_handle: &tauri::AppHandle, client: &JellyfinClient, songs: &[MusicItem]) -> Result<Vec<DownloadState>, CommandError> {
+    let quality = {
+        let download_quality = state.download_quality.lock().map_err(|e| e.to_string())?;
+        download_quality.clone()
+    };
+    let download_queue = state.download_queue.lock().map_err(|e| e.to_string())?;
+    let download_queue = download_queue.as_ref().ok_or("Offline downloads are unavailable")?;
+
+    let mut queued = Vec::new();
+    for song in songs {
+        let job = DownloadJob {
+            item_id: song.id.clone(),
+            name: song.name.clone(),
+            artists: song.artists.clone().unwrap_or_default(),
+            album: song.album.clone(),
+            url: resolve_download_url(client, &quality, &song.id)?,
+        };
+        let download_state = {
+            let mut downloads = state.downloads.lock().map_err(|e| e.to_string())?;
+            let downloads = downloads.as_mut().ok_or("Offline downloads are unavailable")?;
+            downloads.enqueue(&job)
+        };
+        // Already downloaded or on its way
+        let Some(download_state) = download_state else {
+            continue;
+        };
+        download_queue.send(job).map_err(|_| "Download worker has stopped".to_string())?;
+        downloads::emit_state(app_handle, &download_state);
+        queued.push(download_state);
+    }
+    Ok(queued)
+}
+
+// Keep a song on disk for offline playback; progress arrives as "download-state" events
+#[tauri::command]
+pub async fn download_song(
+    item_id: String,
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<DownloadState>, CommandError> {
+    let client_config = {
+        let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+        client.get_config().cloned()
+    };
+
+    let config = match client_config {
+        Some(config) => config,
+        None => {
+            return Err(JellyfinError::NotAuthenticated.into());
+        }
+    };
+
+    let mut client = JellyfinClient::new();
+    client.set_config(config);
+
+    let song = client.get_item_details(&item_id).await
+        .map_err(failed("Failed to get song details"))?;
+    queue_downloads(&state, &app_handle, &client, &[song])
+}
+
+#[tauri::command]
+pub async fn download_album(
+    album_id: String,
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<DownloadState>, CommandError> {
+    let client_config = {
+        let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+        client.get_config().cloned()
+    };
+
+    let config = match client_config {
+        Some(config) => config,
+        None => {
+            return Err(JellyfinError::NotAuthenticated.into());
+        }
+    };
+
+    let mut client = JellyfinClient::new();
+    client.set_config(config);
+
+    let songs = client.get_album_songs(&album_id).await
+        .map_err(failed("Failed to get album songs"))?;
+    let queued = queue_downloads(&state, &app_handle, &client, &songs.items)?;
+    info!("Queued {} songs from album {} for download", queued.len(), album_id);
+    Ok(queued)
+}
+
+#[tauri::command]
+pub fn list_downloads(state: State<'_, AppState>) -> Result<Vec<DownloadState>, CommandError> {
+    let downloads = state.downloads.lock().map_err(|e| e.to_string())?;
+    let downloads = downloads.as_ref().ok_or("Offline downloads are unavailable")?;
+    Ok(downloads.list())
+}
+
+// Remove a downloaded song, or cancel one that's still queued or downloading
+#[tauri::command]
+pub fn delete_download(item_id: String, state: State<'_, AppState>) -> Result<bool, CommandError> {
+    let mut downloads = state.downloads.lock().map_err(|e| e.to_string())?;
+    let downloads = downloads.as_mut().ok_or("Offline downloads are unavailable")?;
+    Ok(downloads.delete(&item_id))
+}
+
+// Audio Player Commands
+
+// The profile songs are streamed with: the chosen streaming quality, or else the negotiated profile if any
+fn active_streaming_profile(state: &State<'_, AppState>) -> Result<Option<StreamingProfile>, String> {
+    let quality = {
+        let streaming_quality = state.streaming_quality.lock().map_err(|e| e.to_string())?;
+        streaming_quality.clone()
+    };
+    if let DownloadQuality::Transcoded(max_bitrate) = quality {
+        let reason = format!("{} kbps streaming quality", max_bitrate / 1000);
+        return Ok(Some(StreamingProfile::transcoding(max_bitrate, "any", Some(reason))));
+    }
+
+    let profile = state.streaming_profile.lock().map_err(|e| e.to_string())?;
+    Ok(profile.clone())
+}
+
+// Build the stream URL for an item, honoring the negotiated streaming profile if any
+fn resolve_stream_url(
+    client: &JellyfinClient,
+    profile: Option<&StreamingProfile>,
+    item_id: &str,
+) -> Result<String, CommandError> {
+    let result = match profile {
+        Some(profile) => client.get_profile_stream_url(item_id, profile),
+        None => client.get_stream_url(item_id),
+    };
+    result.map_err(failed("Failed to get stream URL"))
+}
+
+// A queue entry streaming the item with the profile. Transcoded entries also carry the
+// original file's URL, which the player falls back to if the transcode doesn't decode
+fn stream_queue_item(
+    client: &JellyfinClient,
+    profile: Option<&StreamingProfile>,
+    item: &MusicItem,
+) -> Result<QueueItem, CommandError> {
+    let stream_url = resolve_stream_url(client, profile, &item.id)?;
+    let mut queue_item = QueueItem::from_music_item(item, stream_url);
+    queue_item.apply_streaming_profile(profile);
+    if queue_item.is_transcoding {
+        queue_item.original_stream_url = Some(resolve_stream_url(client, None, &item.id)?);
+    }
+    Ok(queue_item)
+}
+
+// Tracks after the current one that get downloaded ahead of time by the queue resolver
+const QUEUE_PREFETCH_COUNT: usize = 2;
+
+// Resolve the queue against the audio cache in the background, cancelling any earlier run
+fn start_queue_resolution(state: &State<'_, AppState>, app_handle: &tauri::AppHandle, audio_player: AudioPlayer) -> Result<(), String> {
+    let mut queue_resolver = state.queue_resolver.lock().map_err(|e| e.to_string())?;
+    if let Some(handle) = queue_resolver.take() {
+        handle.abort();
+    }
+
+    let prefetch_client = {
+        let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+        client.get_config().cloned().map(|config| {
+            let mut client = JellyfinClient::new();
+            client.set_config(config);
+            client
+        })
+    };
+    let cache_quality = {
+        let cache_quality = state.cache_quality.lock().map_err(|e| e.to_string())?;
+        cache_quality.clone()
+    };
+
+    let audio_cache = state.audio_cache.clone();
+    let app_handle = app_handle.clone();
+    *queue_resolver = Some(tauri::async_runtime::spawn(async move {
+        resolve_queue(audio_player, audio_cache, prefetch_client, cache_quality, app_handle).await;
+    }));
+    Ok(())
+}
+
+async fn resolve_queue(
+    audio_player: AudioPlayer,
+    audio_cache: Arc<TokioMutex<AudioCache>>,
+    prefetch_client: Option<JellyfinClient>,
+    cache_quality: DownloadQuality,
+    app_handle: tauri::AppHandle,
+) {
+    let queue = match audio_player.get_queue().await {
+        Ok(queue) => queue,
+        Err(e) => {
+            warn!("Failed to read queue for resolution: {}", e);
+            return;
+        }
+    };
+    let total = queue.len();
+
+    // Already-cached items can switch to their local files straight away
+    let mut resolved = HashMap::new();
+    for (index, item) in queue.iter().enumerate() {
+        if !item.stream_url.starts_with("file://") {
+            let mut cached_path = downloads::downloaded_path(&app_handle, &item.id);
+            if cached_path.is_none() {
+                let mut cache = audio_cache.lock().await;
+                cached_path = cache.get_cached_path_for_quality(&item.id, &cache_quality);
+            }
+            if let Some(cached_path) = cached_path {
+                resolved.insert(item.id.clone(), format!("file://{}", cached_path.to_string_lossy()));
+            }
+        }
+        let _ = app_handle.emit("queue-resolve-progress", QueueResolveProgress {
+            checked: index + 1,
+            total,
+            ready: false,
+        });
+    }
+    let resolved_count = resolved.len();
+    if let Err(e) = audio_player.apply_resolved_urls(resolved.clone()) {
+        warn!("{}", e);
+        return;
+    }
+
+    // Download the next few tracks, one at a time
+    let current_id = audio_player.get_state().await.ok().and_then(|state| state.current_song).map(|song| song.id);
+    let start = current_id
+        .and_then(|id| queue.iter().position(|item| item.id == id))
+        .map(|index| index + 1)
+        .unwrap_or(0);
+    let upcoming = queue
+        .iter()
+        .skip(start)
+        .filter(|item| !item.stream_url.starts_with("file://") && !resolved.contains_key(&item.id))
+        .take(QUEUE_PREFETCH_COUNT);
+
+    // Prefetching needs a signed-in client to build the cache-quality URL
+    if let Some(client) = prefetch_client.as_ref() {
+        for item in upcoming {
+            let cache_url = match resolve_download_url(client, &cache_quality, &item.id) {
+                Ok(url) => url,
+                Err(e) => {
+                    warn!("Failed to prefetch {}: {}", item.name, e);
+                    continue;
+                }
+            };
+
+            let cache_key = AudioCache::cache_key(&item.id, &cache_quality);
+            let cache_result = AudioCache::cache_audio(&audio_cache, &cache_key, &cache_url, |downloaded_bytes, total_bytes| {
+                emit_cache_progress(&app_handle, &item.id, downloaded_bytes, total_bytes);
+            }).await;
+
+            match cache_result {
+                Ok(cached_path) => {
+                    let url = format!("file://{}", cached_path.to_string_lossy());
+                    let _ = audio_player.apply_resolved_urls(HashMap::from([(item.id.clone(), url)]));
+                }
+                Err(e) => warn!("Failed to prefetch {}: {}", item.name, e),
+            }
+        }
+    }
+
+    info!("Queue resolved: {}/{} items already cached", resolved_count, total);
+    let _ = app_handle.emit("queue-resolve-progress", QueueResolveProgress {
+        checked: total,
+        total,
+        ready: true,
+    });
+}
+
+// Cache/download status of every queue item, in queue order
+#[tauri::command]
+pub async fn get_queue_cache_status(state: State<'_, AppState>) -> Result<Vec<QueueItemCacheStatus>, CommandError> {
+    let audio_player = {
+        let ap = state.audio_player.lock().map_err(|e| e.to_string())?;
+        ap.clone()
+    };
+    let queue = audio_player.get_queue().await?;
+
+    let cache = state.audio_cache.lock().await;
+    Ok(queue
+        .into_iter()
+        .map(|item| QueueItemCacheStatus {
+            status: cache.cache_status(&item.id),
+            item_id: item.id,
+        })
+        .collect())
+}
+
+// Re-run resolution for the current queue, e.g. after the cache was filled elsewhere
+#[tauri::command]
+pub fn resolve_queue_urls(state: State<'_, AppState>, app_handle: tauri::AppHandle) -> Result<bool, CommandError> {
+    let audio_player = {
+        let ap = state.audio_player.lock().map_err(|e| e.to_string())?;
+        ap.clone()
+    };
+    start_queue_resolution(&state, &app_handle, audio_player)?;
+    Ok(true)
+}
+
+#[tauri::command]
+pub async fn play_song(
+    item_id: String,
+    resume: Option<bool>,
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<bool, CommandError> {
+    // Resuming starts from the position the server saved when the song was last stopped
+    let position = if resume.unwrap_or(false) { None } else { Some(0.0) };
+    play_song_at(item_id, position, &state, &app_handle).await
+}
+
+// Play a song at a timestamp in one step, for deep links and OS "resume" actions
+#[tauri::command]
+pub async fn play_at(
+    item_id: String,
+    position_seconds: f64,
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<bool, CommandError> {
+    play_song_at(item_id, Some(position_seconds), &state, &app_handle).await
+}
+
+// Cache a song while it streams, pointing the queue at the cached file once it's written
+fn spawn_background_cache(
+    app_handle: &tauri::AppHandle,
+    item_id: String,
+    cache_quality: DownloadQuality,
+    cache_url: String,
+) {
+    let app_handle = app_handle.clone();
+    tauri::async_runtime::spawn(async move {
+        let state = app_handle.state::<AppState>();
+        let cache_key = AudioCache::cache_key(&item_id, &cache_quality);
+        let cache_result = AudioCache::cache_audio(&state.audio_cache, &cache_key, &cache_url, |downloaded_bytes, total_bytes| {
+            emit_cache_progress(&app_handle, &item_id, downloaded_bytes, total_bytes);
+        }).await
+            .map_err(|e| e.to_string());
+
+        match cache_result {
+            Ok(cached_path) => {
+                info!("Successfully cached audio for song: {}", item_id);
+                let audio_player = state.audio_player.lock().map(|ap| ap.clone());
+                if let Ok(audio_player) = audio_player {
+                    let url = format!("file://{}", cached_path.to_string_lossy());
+                    let _ = audio_player.apply_resolved_urls(HashMap::from([(item_id, url)]));
+                }
+            }
+            Err(e) => warn!("Failed to cache audio for song {}: {}", item_id, e),
+        }
+    });
+}
+
+// Cache songs the player downloaded in full at the cache quality, instead of fetching them twice,
+// and drop cached files the player found unusable
+pub fn spawn_played_audio_cacher(app_handle: tauri::AppHandle) {
+    let audio_player = {
+        let state = app_handle.state::<AppState>();
+        let Ok(audio_player) = state.audio_player.lock() else {
+            return;
+        };
+        audio_player.clone()
+    };
+    let mut receiver = audio_player.subscribe_to_events();
+
+    tauri::async_runtime::spawn(async move {
+        use tokio::sync::broadcast::error::RecvError;
+
+        loop {
+            match receiver.recv().await {
+                Ok(PlayerEvent::AudioDownloaded { item_id, url, data }) => {
+                    cache_played_audio(&app_handle, &item_id, &url, &data).await;
+                }
+                Ok(PlayerEvent::LocalFileUnusable { item_id, path }) => {
+                    evict_unusable_file(&app_handle, &item_id, &path).await;
+                }
+                Ok(_) | Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+// Drop a cached file the player couldn't decode. Files outside the cache, like offline
+// downloads, belong to someone else and are left alone
+async fn evict_unusable_file(app_handle: &tauri::AppHandle, item_id: &str, path: &str) {
+    let state = app_handle.state::<AppState>();
+    let evicted = {
+        let mut cache = state.audio_cache.lock().await;
+        if !cache.evict_file(std::path::Path::new(path)) {
+            return;
+        }
+        cache.take_evicted()
+    };
+    warn!("Evicted unusable cached audio for song {}", item_id);
+    let _ = app_handle.emit("cache-evicted", evicted);
+}
+
+async fn cache_played_audio(app_handle: &tauri::AppHandle, item_id: &str, url: &str, data: &[u8]) {
+    let state = app_handle.state::<AppState>();
+    let Ok(cache_quality) = state.cache_quality.lock().map(|quality| quality.clone()) else {
+        return;
+    };
+    let client = {
+        let Ok(client) = state.jellyfin_client.lock() else {
+            return;
+        };
+        let Some(config) = client.get_config().cloned() else {
+            return;
+        };
+        let mut client = JellyfinClient::new();
+        client.set_config(config);
+        client
+    };
+
+    // Only bytes fetched at the cache quality can stand in for the cached copy
+    if resolve_download_url(&client, &cache_quality, item_id).ok().as_deref() != Some(url) {
+        return;
+    }
+
+    let cache_result = {
+        let mut cache = state.audio_cache.lock().await;
+        cache.store_audio(&AudioCache::cache_key(item_id, &cache_quality), data).await
+            .map_err(|e| e.to_string())
+    };
+    match cache_result {
+        Ok(cached_path) => {
+            info!("Cached streamed audio for song: {}", item_id);
+            let audio_player = state.audio_player.lock().map(|ap| ap.clone());
+            if let Ok(audio_player) = audio_player {
+                let url = format!("file://{}", cached_path.to_string_lossy());
+                let _ = audio_player.apply_resolved_urls(HashMap::from([(item_id.to_string(), url)]));
+            }
+        }
+        Err(e) => warn!("Failed to cache streamed audio for song {}: {}", item_id, e),
+    }
+}
+
+// A position of None resumes from the song's saved playback position
+async fn play_song_at(
+    item_id: String,
+    position: Option<f64>,
+    state: &State<'_, AppState>,
+    app_handle: &tauri::AppHandle,
+) -> Result<bool, CommandError> {
+    // Get Jellyfin client config
+    let jellyfin_config = {
+        let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+        client.get_config().cloned()
+    };
+
+    let config = match jellyfin_config {
+        Some(config) => config,
+        None => {
+            return Err(JellyfinError::NotAuthenticated.into());
+        }
+    };
+
+    // Create temporary client to get song details and stream URL
+    let mut jellyfin_client = JellyfinClient::new();
+    jellyfin_client.set_config(config);
+
+    // Use the streaming quality or negotiated profile if we have one, otherwise direct play
+    let streaming_profile = active_streaming_profile(state)?;
+
+    let stream_url = resolve_stream_url(&jellyfin_client, streaming_profile.as_ref(), &item_id)?;
+
+    // The cached copy follows the cache quality setting, not the playback profile
+    let cache_quality = {
+        let cache_quality = state.cache_quality.lock().map_err(|e| e.to_string())?;
+        cache_quality.clone()
+    };
+    let cache_url = resolve_download_url(&jellyfin_client, &cache_quality, &item_id)?;
+
+    // Try to get cached audio file or cache it
+    let cached_url = {
+        // First, check for an offline download, then the cache
+        let mut cached_path = downloads::downloaded_path(app_handle, &item_id);
+        if cached_path.is_none() {
+            let mut cache = state.audio_cache.lock().await;
+            cached_path = cache.get_cached_path_for_quality(&item_id, &cache_quality);
+        }
+
+        if let Some(cached_path) = cached_path {
+            format!("file://{}", cached_path.to_string_lossy())
+        } else {
+            // Start playing from the stream right away instead of waiting for the download.
+            // When the stream is the cached version, the played bytes get cached once it finishes
+            if cache_url != stream_url {
+                spawn_background_cache(app_handle, item_id.clone(), cache_quality, cache_url);
+            }
+            stream_url.clone()
+        }
+    };
+
+    // Get song details from Jellyfin
+    let song_details = jellyfin_client.get_item_details(&item_id).await
+        .map_err(failed("Failed to get song details"))?;
+
+    let position = position.unwrap_or_else(|| song_details.resume_position_seconds());
+    if position > 0.0 {
+        info!("Starting {} at {:.1}s", item_id, position);
+    }
+
+    // Create queue item with real song data (use cached URL if available)
+    let stream_item = stream_queue_item(&jellyfin_client, streaming_profile.as_ref(), &song_details)?;
+    let mut queue_item = stream_item.clone();
+    if cached_url != stream_url {
+        queue_item.stream_url = cached_url.clone();
+        queue_item.remote_stream_url = Some(stream_url.clone());
+        queue_item.apply_streaming_profile(streaming_profile.as_ref());
+    }
+
+    // Play the song - clone the AudioPlayer to avoid holding the lock
+    let audio_player = {
+        let ap = state.audio_player.lock().map_err(|e| e.to_string())?;
+        ap.clone()  // AudioPlayer is designed to be cloneable for this purpose
+    };
+    
+    // Try to play with cached URL first, fallback to the stream if it fails; the player itself
+    // falls back from a transcode that doesn't decode to the original file
+    match audio_player.play_item_at(queue_item, position).await {
+        Ok(_) => {
+            info!("Successfully played song using cached/stream URL");
+            Ok(true)
+        },
+        Err(e) if cached_url != stream_url => {
+            warn!("Playback failed ({}), trying the stream", e);
+            match audio_player.play_item_at(stream_item, position).await {
+                Ok(_) => {
+                    info!("Successfully played song using the stream");
+                    Ok(true)
+                },
+                Err(fallback_e) => Err(format!("Failed to play song: {}. Fallback error: {}", e, fallback_e).into()),
+            }
+        }
+        Err(e) => Err(format!("Failed to play song: {}", e).into()),
+    }
+}
+
+#[tauri::command]
+pub fn pause_playback(state: State<'_, AppState>) -> Result<bool, CommandError> {
+    let audio_player = state.audio_player.lock().map_err(|e| e.to_string())?;
+    audio_player.pause()?;
+    Ok(true)
+}
+
+#[tauri::command]
+pub fn resume_playback(state: State<'_, AppState>) -> Result<bool, CommandError> {
+    let audio_player = state.audio_player.lock().map_err(|e| e.to_string())?;
+    audio_player.resume()?;
+    Ok(true)
+}
+
+#[tauri::command]
+pub fn stop_playback(state: State<'_, AppState>) -> Result<bool, CommandError> {
+    let audio_player = state.audio_player.lock().map_err(|e| e.to_string())?;
+    audio_player.stop()?;
+    Ok(true)
+}
+
+// Change one saved player setting, keeping the rest; a failed save only costs it on the next launch.
+// Saves take turns so concurrent changes can't overwrite each other
+async fn save_player_setting(app_handle: &tauri::AppHandle, update: impl FnOnce(&mut PlayerSettings)) {
+    let settings_lock = app_handle.state::<AppState>().player_settings_lock.clone();
+    let _guard = settings_lock.lock().await;
+    let mut settings = storage::load_player_settings(app_handle).await.unwrap_or_default();
+    update(&mut settings);
+    if let Err(e) = storage::save_player_settings(app_handle, &settings).await {
+        error!("Failed to save player settings: {}", e);
+    }
+}
+
+// Quiet time after a volume change before it's saved
+const VOLUME_SAVE_DELAY_MS: u64 = 500;
+
+#[tauri::command]
+pub async fn set_volume(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    volume: f32,
+) -> Result<bool, CommandError> {
+    {
+        let audio_player = state.audio_player.lock().map_err(|e| e.to_string())?;
+        audio_player.set_volume(volume)?;
+    }
+
+    // Dragging the slider sends a stream of changes; only the one it stops at gets written
+    let volume = volume.clamp(0.0, 1.0);
+    let mut volume_saver = state.volume_saver.lock().map_err(|e| e.to_string())?;
+    if let Some(pending) = volume_saver.take() {
+        pending.abort();
+    }
+    *volume_saver = Some(tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(std::time::Duration::from_millis(VOLUME_SAVE_DELAY_MS)).await;
+        save_player_setting(&app_handle, |settings| settings.volume = volume).await;
+    }));
+    Ok(true)
+}
+
+#[tauri::command]
+pub async fn set_balance(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    balance: f32,
+) -> Result<bool, CommandError> {
+    {
+        let audio_player = state.audio_player.lock().map_err(|e| e.to_string())?;
+        audio_player.set_balance(balance)?;
+    }
+
+    // Remember the balance for the next session
+    if let Err(e) = storage::save_balance(&app_handle, balance.clamp(-1.0, 1.0)).await {
+        error!("Failed to save balance: {}", e);
+    }
+
+    Ok(true)
+}
+
+#[tauri::command]
+pub fn set_gain(state: State<'_, AppState>, gain: f32) -> Result<bool, CommandError> {
+    let audio_player = state.audio_player.lock().map_err(|e| e.to_string())?;
+    audio_player.set_gain(gain)?;
+    Ok(true)
+}
+
+// Band gains in dB, lowest frequency first; they apply once the equalizer is enabled
+#[tauri::command]
+pub fn set_equalizer(state: State<'_, AppState>, bands: Vec<f32>) -> Result<bool, CommandError> {
+    if bands.len() != EQ_BAND_COUNT {
+        return Err(format!("The equalizer has {} bands, got {}", EQ_BAND_COUNT, bands.len()).into());
+    }
+    if bands.iter().any(|band| !band.is_finite() || band.abs() > MAX_BAND_GAIN_DB) {
+        return Err(format!("Equalizer band gains must be between -{} and {} dB", MAX_BAND_GAIN_DB, MAX_BAND_GAIN_DB).into());
+    }
+
+    let audio_player = state.audio_player.lock().map_err(|e| e.to_string())?;
+    audio_player.set_equalizer(bands)?;
+    Ok(true)
+}
+
+#[tauri::command]
+pub fn set_equalizer_enabled(state: State<'_, AppState>, enabled: bool) -> Result<bool, CommandError> {
+    let audio_player = state.audio_player.lock().map_err(|e| e.to_string())?;
+    audio_player.set_equalizer_enabled(enabled)?;
+    Ok(true)
+}
+
+#[tauri::command]
+pub fn get_equalizer_presets() -> Vec<EqualizerPreset> {
+    equalizer::presets()
+}
+
+#[tauri::command]
+pub fn seek_to(state: State<'_, AppState>, position: f64) -> Result<bool, CommandError> {
+    let audio_player = state.audio_player.lock().map_err(|e| e.to_string())?;
+    audio_player.seek(position)?;
+    Ok(true)
+}
+
+#[tauri::command]
+pub fn seek_percent(state: State<'_, AppState>, percent: f64) -> Result<bool, CommandError> {
+    let audio_player = state.audio_player.lock().map_err(|e| e.to_string())?;
+    audio_player.seek_percent(percent)?;
+    Ok(true)
+}
+
+#[tauri::command]
+pub fn seek_within_chapter(state: State<'_, AppState>, offset: f64) -> Result<bool, CommandError> {
+    let audio_player = state.audio_player.lock().map_err(|e| e.to_string())?;
+    audio_player.seek_within_chapter(offset)?;
+    Ok(true)
+}
+
+#[tauri::command]
+pub fn next_chapter(state: State<'_, AppState>) -> Result<bool, CommandError> {
+    let audio_player = state.audio_player.lock().map_err(|e| e.to_string())?;
+    audio_player.next_chapter()?;
+    Ok(true)
+}
+
+#[tauri::command]
+pub fn previous_chapter(state: State<'_, AppState>) -> Result<bool, CommandError> {
+    let audio_player = state.audio_player.lock().map_err(|e| e.to_string())?;
+    audio_player.previous_chapter()?;
+    Ok(true)
+}
+
+#[tauri::command]
+pub async fn toggle_shuffle(app_handle: tauri::AppHandle, state: State<'_, AppState>) -> Result<bool, CommandError> {
+    let audio_player = {
+        let ap = state.audio_player.lock().map_err(|e| e.to_string())?;
+        ap.clone()
+    };
+    audio_player.toggle_shuffle()?;
+
+    // Commands run in order, so this state already reflects the toggle
+    let shuffle_mode = audio_player.get_state().await?.shuffle_mode;
+    save_player_setting(&app_handle, |settings| settings.shuffle_mode = shuffle_mode).await;
+    Ok(true)
+}
+
+#[tauri::command]
+pub async fn set_shuffle_mode(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    mode: String,
+) -> Result<bool, CommandError> {
+    let shuffle_mode = match mode.as_str() {
+        "off" => ShuffleMode::Off,
+        "tracks" => ShuffleMode::Tracks,
+        "albums" => ShuffleMode::Albums,
+        _ => return Err("Invalid shuffle mode".into()),
+    };
+
+    {
+        let audio_player = state.audio_player.lock().map_err(|e| e.to_string())?;
+        audio_player.set_shuffle_mode(shuffle_mode)?;
+    }
+
+    save_player_setting(&app_handle, |settings| settings.shuffle_mode = shuffle_mode).await;
+    Ok(true)
+}
+
+#[tauri::command]
+pub async fn set_repeat_mode(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    mode: String,
+) -> Result<bool, CommandError> {
+    let repeat_mode = match mode.as_str() {
+        "none" => RepeatMode::None,
+        "one" => RepeatMode::One,
+        "all" => RepeatMode::All,
+        _ => return Err("Invalid repeat mode".into()),
+    };
+
+    {
+        let audio_player = state.audio_player.lock().map_err(|e| e.to_string())?;
+        audio_player.set_repeat_mode(repeat_mode.clone())?;
+    }
+
+    save_player_setting(&app_handle, |settings| settings.repeat_mode = repeat_mode).await;
+    Ok(true)
+}
+
+#[tauri::command]
+pub fn set_transition_mode(
+    state: State<'_, AppState>,
+    mode: String,
+    seconds: Option<f32>,
+) -> Result<bool, CommandError> {
+    let transition_mode = match mode.as_str() {
+        "hard" => TransitionMode::Hard,
+        "gapless" => TransitionMode::Gapless,
+        "crossfade" => TransitionMode::Crossfade(seconds.ok_or("Crossfade requires a duration in seconds")?),
+        "smart" => TransitionMode::Smart,
+        _ => return Err("Invalid transition mode".into()),
+    };
+
+    let audio_player = state.audio_player.lock().map_err(|e| e.to_string())?;
+    audio_player.set_transition_mode(transition_mode)?;
+    Ok(true)
+}
+
+// Overlap consecutive tracks by this many seconds; 0 turns crossfading off in favour of gapless
+#[tauri::command]
+pub fn set_crossfade_seconds(state: State<'_, AppState>, seconds: f32) -> Result<bool, CommandError> {
+    if !seconds.is_finite() || seconds < 0.0 {
+        return Err("Crossfade must be a non-negative number of seconds".into());
+    }
+
+    let transition_mode = if seconds > 0.0 {
+        TransitionMode::Crossfade(seconds)
+    } else {
+        TransitionMode::Gapless
+    };
+
+    let audio_player = state.audio_player.lock().map_err(|e| e.to_string())?;
+    audio_player.set_transition_mode(transition_mode)?;
+    Ok(true)
+}
+
+// Trim silence baked into rips where tracks meet gaplessly
+#[tauri::command]
+pub fn trim_silence(state: State<'_, AppState>, enabled: bool) -> Result<bool, CommandError> {
+    let audio_player = state.audio_player.lock().map_err(|e| e.to_string())?;
+    audio_player.set_trim_silence(enabled)?;
+    Ok(true)
+}
+
+// Even out loudness between tracks using the server's normalization gain, where it has one
+#[tauri::command]
+pub async fn set_normalization_enabled(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    enabled: bool,
+) -> Result<bool, CommandError> {
+    {
+        let audio_player = state.audio_player.lock().map_err(|e| e.to_string())?;
+        audio_player.set_normalization_enabled(enabled)?;
+    }
+
+    save_player_setting(&app_handle, |settings| settings.normalization_enabled = enabled).await;
+    Ok(true)
+}
+
+// Play faster or slower (0.5x to 2x). This resamples, so the pitch shifts with the speed;
+// pitch-preserving time-stretching isn't supported. enabled=false goes back to normal playback
+#[tauri::command]
+pub fn set_playback_speed(state: State<'_, AppState>, speed: f32, enabled: Option<bool>) -> Result<bool, CommandError> {
+    if !(0.5..=2.0).contains(&speed) {
+        return Err("Playback speed must be between 0.5 and 2.0".into());
+    }
+
+    let audio_player = state.audio_player.lock().map_err(|e| e.to_string())?;
+    audio_player.set_playback_speed(speed, enabled.unwrap_or(true))?;
+    Ok(true)
+}
+
+// Names of the audio outputs that set_output_device accepts
+#[tauri::command]
+pub fn list_output_devices() -> Result<Vec<String>, CommandError> {
+    Ok(audio_player::list_output_devices()?)
+}
+
+// Route playback to the named output, or back to the system default with no name.
+// If the device is later unplugged, playback falls back to the default and an error event is emitted
+#[tauri::command]
+pub async fn set_output_device(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    name: Option<String>,
+) -> Result<bool, CommandError> {
+    let audio_player = {
+        let ap = state.audio_player.lock().map_err(|e| e.to_string())?;
+        ap.clone()
+    };
+    audio_player.set_output_device(name.clone()).await?;
+
+    save_player_setting(&app_handle, |settings| settings.output_device = name).await;
+    Ok(true)
+}
+
+// Longest sleep timer accepted, in minutes
+const MAX_SLEEP_TIMER_MINUTES: u32 = 24 * 60;
+
+// Pause (or stop) playback after a number of minutes; no minutes cancels the timer.
+// Starting a song explicitly also cancels it
+#[tauri::command]
+pub fn set_sleep_timer(
+    state: State<'_, AppState>,
+    minutes: Option<u32>,
+    mode: Option<String>,
+    fade: Option<bool>,
+) -> Result<bool, CommandError> {
+    if minutes.is_some_and(|minutes| minutes == 0 || minutes > MAX_SLEEP_TIMER_MINUTES) {
+        return Err(format!("Sleep timer must be between 1 and {} minutes", MAX_SLEEP_TIMER_MINUTES).into());
+    }
+    let mode = match mode.as_deref() {
+        None | Some("pause") => SleepTimerMode::Pause,
+        Some("stop") => SleepTimerMode::Stop,
+        Some(_) => return Err("Invalid sleep timer mode".into()),
+    };
+
+    let audio_player = state.audio_player.lock().map_err(|e| e.to_string())?;
+    audio_player.set_sleep_timer(minutes, mode, fade.unwrap_or(true))?;
+    Ok(true)
+}
+
+// Fade the last track out when the queue runs out (0 stops abruptly)
+#[tauri::command]
+pub fn end_of_queue_fade(state: State<'_, AppState>, seconds: f32) -> Result<bool, CommandError> {
+    let audio_player = state.audio_player.lock().map_err(|e| e.to_string())?;
+    audio_player.set_end_of_queue_fade(seconds)?;
+    Ok(true)
+}
+
+#[tauri::command]
+pub fn set_warm_window(state: State<'_, AppState>, window: usize) -> Result<bool, CommandError> {
+    let audio_player = state.audio_player.lock().map_err(|e| e.to_string())?;
+    audio_player.set_warm_window(window)?;
+    Ok(true)
+}
+
+#[tauri::command]
+pub async fn move_in_queue(state: State<'_, AppState>, from: usize, to: usize) -> Result<bool, CommandError> {
+    let audio_player = {
+        let ap = state.audio_player.lock().map_err(|e| e.to_string())?;
+        ap.clone()
+    };
+    audio_player.move_in_queue(from, to).await?;
+    Ok(true)
+}
+
+#[tauri::command]
+pub async fn get_playback_state(state: State<'_, AppState>) -> Result<PlaybackState, CommandError> {
+    let audio_player = {
+        let ap = state.audio_player.lock().map_err(|e| e.to_string())?;
+        ap.clone()
+    };
+    Ok(audio_player.get_state().await?)
+}
+
+#[tauri::command]
+pub async fn get_audio_info(state: State<'_, AppState>) -> Result<Option<AudioInfo>, CommandError> {
+    let audio_player = {
+        let ap = state.audio_player.lock().map_err(|e| e.to_string())?;
+        ap.clone()
+    };
+    Ok(audio_player.get_audio_info().await?)
+}
+
+#[tauri::command]
+pub fn set_debug_mode(state: State<'_, AppState>, enabled: bool) -> Result<bool, CommandError> {
+    let mut debug_mode = state.debug_mode.lock().map_err(|e| e.to_string())?;
+    *debug_mode = enabled;
+    Ok(true)
+}
+
+fn require_debug_mode(state: &State<'_, AppState>) -> Result<(), String> {
+    let debug_mode = state.debug_mode.lock().map_err(|e| e.to_string())?;
+    if *debug_mode {
+        Ok(())
+    } else {
+        Err("Debug mode is not enabled".to_string())
+    }
+}
+
+#[tauri::command]
+pub async fn get_buffer_diagnostics(state: State<'_, AppState>) -> Result<Option<BufferDiagnostics>, CommandError> {
+    require_debug_mode(&state)?;
+
+    let audio_player = {
+        let ap = state.audio_player.lock().map_err(|e| e.to_string())?;
+        ap.clone()
+    };
+    Ok(audio_player.get_buffer_diagnostics().await?)
+}
+
+#[tauri::command]
+pub fn get_auth_header_debug(state: State<'_, AppState>) -> Result<AuthHeaderDebug, CommandError> {
+    require_debug_mode(&state)?;
+
+    let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+    client
+        .get_auth_header_debug()
+        .map_err(failed("Failed to build auth header"))
+}
+
+fn event_log_path(app_handle: &tauri::AppHandle) -> Result<std::path::PathBuf, String> {
+    app_handle
+        .path()
+        .app_data_dir()
+        .map(|dir| event_log::event_log_path(&dir))
+        .map_err(|e| format!("Failed to resolve app data directory: {}", e))
+}
+
+#[tauri::command]
+pub fn set_event_logging(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    enabled: bool,
+) -> Result<bool, CommandError> {
+    let mut event_logger = state.event_logger.lock().map_err(|e| e.to_string())?;
+
+    if let Some(handle) = event_logger.take() {
+        handle.abort();
+    }
+
+    if enabled {
+        let log_path = event_log_path(&app_handle)?;
+        let audio_player = state.audio_player.lock().map_err(|e| e.to_string())?;
+        *event_logger = Some(event_log::spawn_event_logger(&audio_player, log_path));
+    }
+
+    Ok(true)
+}
+
+#[tauri::command]
+pub fn get_event_log_path(app_handle: tauri::AppHandle) -> Result<String, CommandError> {
+    Ok(event_log_path(&app_handle)?.to_string_lossy().to_string())
+}
+
+#[tauri::command]
+pub async fn retry_playback(state: State<'_, AppState>) -> Result<bool, CommandError> {
+    let audio_player = {
+        let ap = state.audio_player.lock().map_err(|e| e.to_string())?;
+        ap.clone()
+    };
+    audio_player.retry_playback().await?;
+    Ok(true)
+}
+
+// Write the listening history to `path` as "json" or "csv"; an empty history still yields a valid file
+#[tauri::command]
+pub async fn export_history(
+    format: String,
+    path: String,
+    state: State<'_, AppState>,
+) -> Result<usize, CommandError> {
+    let audio_player = {
+        let ap = state.audio_player.lock().map_err(|e| e.to_string())?;
+        ap.clone()
+    };
+    let history = audio_player.get_history().await?;
+
+    let contents = match format.to_lowercase().as_str() {
+        "json" => serde_json::to_string_pretty(&history)
+            .map_err(|e| format!("Failed to serialize history: {}", e))?,
+        "csv" => {
+            let mut csv = String::from("item_id,name,artist,played_at,completion\n");
+            for entry in &history {
+                csv.push_str(&format!(
+                    "{},{},{},{},{:.3}\n",
+                    csv_field(&entry.item_id),
+                    csv_field(&entry.name),
+                    csv_field(entry.artist.as_deref().unwrap_or("")),
+                    entry.played_at,
+                    entry.completion,
+                ));
+            }
+            csv
+        }
+        other => return Err(format!("Unsupported export format: {}", other).into()),
+    };
+
+    tokio::fs::write(&path, contents).await
+        .map_err(|e| format!("Failed to write history to {}: {}", path, e))?;
+
+    info!("Exported {} history entries to {}", history.len(), path);
+    Ok(history.len())
+}
+
+// Quote a CSV field when it contains a delimiter, quote or newline
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[tauri::command]
+pub fn next_track(state: State<'_, AppState>) -> Result<bool, CommandError> {
+    let audio_player = state.audio_player.lock().map_err(|e| e.to_string())?;
+    audio_player.next_track()?;
+    Ok(true)
+}
+
+#[tauri::command]
+pub fn previous_track(state: State<'_, AppState>) -> Result<bool, CommandError> {
+    let audio_player = state.audio_player.lock().map_err(|e| e.to_string())?;
+    audio_player.previous_track()?;
+    Ok(true)
+}
+
+// How many songs the play history views show when the caller gives no limit and there's no default page size
+const DEFAULT_PLAY_STATS_LIMIT: i32 = 50;
+
+#[tauri::command]
+pub async fn get_recently_played(
+    limit: Option<i32>,
+    state: State<'_, AppState>,
+) -> Result<MusicLibraryResult, CommandError> {
+    let limit = resolve_page_limit(&state, limit)?.unwrap_or(DEFAULT_PLAY_STATS_LIMIT);
+    info!("get_recently_played command called with limit: {}", limit);
+    let client_config = {
+        let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+        client.get_config().cloned()
+    };
+
+    let config = match client_config {
+        Some(config) => config,
+        None => {
+            return Ok(MusicLibraryResult {
+                success: false,
+                message: "Not authenticated".to_string(),
+                start_index: None,
+                has_more: false,
+                items: None,
+                total_count: None,
+                error: Some(JellyfinError::NotAuthenticated),
+            });
+        }
+    };
+
+    let mut client = JellyfinClient::new();
+    client.set_config(config);
+
+    match client.get_recently_played(limit).await {
+        Ok(response) => Ok(MusicLibraryResult {
+            success: true,
+            message: "Recently played songs retrieved successfully".to_string(),
+            start_index: Some(response.start_index),
+            has_more: has_more_pages(&response),
+            items: Some(response.items),
+            total_count: Some(response.total_record_count),
+            error: None,
+        }),
+        Err(e) => Ok(MusicLibraryResult {
+            success: false,
+            message: format!("Failed to get recently played songs: {}", e),
+            start_index: None,
+            has_more: false,
+            items: None,
+            total_count: None,
+            error: Some(e),
+        }),
+    }
+}
+
+#[tauri::command]
+pub async fn get_most_played(
+    limit: Option<i32>,
+    state: State<'_, AppState>,
+) -> Result<MusicLibraryResult, CommandError> {
+    let limit = resolve_page_limit(&state, limit)?.unwrap_or(DEFAULT_PLAY_STATS_LIMIT);
+    info!("get_most_played command called with limit: {}", limit);
+    let client_config = {
+        let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+        client.get_config().cloned()
+    };
+
+    let config = match client_config {
+        Some(config) => config,
+        None => {
+            return Ok(MusicLibraryResult {
+                success: false,
+                message: "Not authenticated".to_string(),
+                start_index: None,
+                has_more: false,
+                items: None,
+                total_count: None,
+                error: Some(JellyfinError::NotAuthenticated),
+            });
+        }
+    };
+
+    let mut client = JellyfinClient::new();
+    client.set_config(config);
+
+    match client.get_most_played(limit).await {
+        Ok(response) => Ok(MusicLibraryResult {
+            success: true,
+            message: "Most played songs retrieved successfully".to_string(),
+            start_index: Some(response.start_index),
+            has_more: has_more_pages(&response),
+            items: Some(response.items),
+            total_count: Some(response.total_record_count),
+            error: None,
+        }),
+        Err(e) => Ok(MusicLibraryResult {
+            success: false,
+            message: format!("Failed to get most played songs: {}", e),
+            start_index: None,
+            has_more: false,
+            items: None,
+            total_count: None,
+            error: Some(e),
+        }),
+    }
+}
+
+#[tauri::command]
+pub async fn get_random_songs(
+    limit: Option<i32>,
+    force: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<MusicLibraryResult, CommandError> {
+    let limit = resolve_page_limit(&state, limit)?;
+    info!("get_random_songs command called with limit: {:?}", limit);
+    // The app's client, cloned so the listing cache carries over between calls
+    let mut client = {
+        let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+        client.clone()
+    };
+
+    if client.get_config().is_none() {
+        return Ok(MusicLibraryResult {
+            success: false,
+            message: "Not authenticated".to_string(),
+            start_index: None,
+            has_more: false,
+            items: None,
+            total_count: None,
+            error: Some(JellyfinError::NotAuthenticated),
+        });
+    }
+
+    // Forcing drops the cached pages of this listing, so the fetch goes to the server
+    if force.unwrap_or(false) {
+        client.invalidate_cache_for("Audio");
+    }
+
+    match client.get_random_songs(limit).await {
+        Ok(response) => Ok(MusicLibraryResult {
+            success: true,
+            message: "Random songs retrieved successfully".to_string(),
+            start_index: Some(response.start_index),
+            has_more: has_more_pages(&response),
+            items: Some(response.items),
+            total_count: Some(response.total_record_count),
+            error: None,
+        }),
+        Err(e) => Ok(MusicLibraryResult {
+            success: false,
+            message: format!("Failed to get random songs: {}", e),
+            start_index: None,
+            has_more: false,
+            items: None,
+            total_count: None,
+            error: Some(e),
+        }),
+    }
+}
+
+#[tauri::command]
+pub async fn get_recent_albums(
+    limit: Option<i32>,
+    start_index: Option<i32>,
+    force: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<MusicLibraryResult, CommandError> {
+    let limit = resolve_page_limit(&state, limit)?;
+    info!("get_recent_albums command called with limit: {:?}, start_index: {:?}", limit, start_index);
+    // The app's client, cloned so the listing cache carries over between calls
+    let mut client = {
+        let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+        client.clone()
+    };
+
+    if client.get_config().is_none() {
+        return Ok(MusicLibraryResult {
+            success: false,
+            message: "Not authenticated".to_string(),
+            start_index: None,
+            has_more: false,
+            items: None,
+            total_count: None,
+            error: Some(JellyfinError::NotAuthenticated),
+        });
+    }
+
+    // Forcing drops the cached pages of this listing, so the fetch goes to the server
+    if force.unwrap_or(false) {
+        client.invalidate_cache_for("MusicAlbum");
+    }
+
+    match client.get_recent_albums(limit, start_index).await {
+        Ok(response) => Ok(MusicLibraryResult {
+            success: true,
+            message: "Recent albums retrieved successfully".to_string(),
+            start_index: Some(response.start_index),
+            has_more: has_more_pages(&response),
+            items: Some(response.items),
+            total_count: Some(response.total_record_count),
+            error: None,
+        }),
+        Err(e) => Ok(MusicLibraryResult {
+            success: false,
+            message: format!("Failed to get recent albums: {}", e),
+            start_index: None,
+            has_more: false,
+            items: None,
+            total_count: None,
+            error: Some(e),
+        }),
+    }
+}
+
+#[tauri::command]
+pub async fn get_album_songs(
+    album_id: String,
+    state: State<'_, AppState>,
+) -> Result<MusicLibraryResult, CommandError> {
+    let client_config = {
+        let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+        client.get_config().cloned()
+    };
+
+    let config = match client_config {
+        Some(config) => config,
+        None => {
+            return Ok(MusicLibraryResult {
+                success: false,
+                message: "Not authenticated".to_string(),
+                start_index: None,
+                has_more: false,
+                items: None,
+                total_count: None,
+                error: Some(JellyfinError::NotAuthenticated),
+            });
+        }
+    };
+
+    let mut client = JellyfinClient::new();
+    client.set_config(config);
+
+    match client.get_album_songs(&album_id).await {
+        Ok(response) => Ok(MusicLibraryResult {
+            success: true,
+            message: "Album songs retrieved successfully".to_string(),
+            start_index: Some(response.start_index),
+            has_more: has_more_pages(&response),
+            items: Some(response.items),
+            total_count: Some(response.total_record_count),
+            error: None,
+        }),
+        Err(e) => Ok(MusicLibraryResult {
+            success: false,
+            message: format!("Failed to get album songs: {}", e),
+            start_index: None,
+            has_more: false,
+            items: None,
+            total_count: None,
+            error: Some(e),
+        }),
+    }
+}
+
+#[tauri::command]
+pub async fn get_artist_songs(
+    artist_id: String,
+    state: State<'_, AppState>,
+) -> Result<MusicLibraryResult, CommandError> {
+    let client_config = {
+        let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+        client.get_config().cloned()
+    };
+
+    let config = match client_config {
+        Some(config) => config,
+        None => {
+            return Ok(MusicLibraryResult {
+                success: false,
+                message: "Not authenticated".to_string(),
+                start_index: None,
+                has_more: false,
+                items: None,
+                total_count: None,
+                error: Some(JellyfinError::NotAuthenticated),
+            });
+        }
+    };
+
+    let mut client = JellyfinClient::new();
+    client.set_config(config);
+
+    match client.get_artist_songs(&artist_id).await {
+        Ok(response) => Ok(MusicLibraryResult {
+            success: true,
+            message: "Artist songs retrieved successfully".to_string(),
+            start_index: Some(response.start_index),
+            has_more: has_more_pages(&response),
+            items: Some(response.items),
+            total_count: Some(response.total_record_count),
+            error: None,
+        }),
+        Err(e) => Ok(MusicLibraryResult {
+            success: false,
+            message: format!("Failed to get artist songs: {}", e),
+            start_index: None,
+            has_more: false,
+            items: None,
+            total_count: None,
+            error: Some(e),
+        }),
+    }
+}
+
+#[tauri::command]
+pub async fn get_playlist_songs(
+    playlist_id: String,
+    limit: Option<i32>,
+    start_index: Option<i32>,
+    state: State<'_, AppState>,
+) -> Result<MusicLibraryResult, CommandError> {
+    let limit = resolve_page_limit(&state, limit)?;
+    let client_config = {
+        let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+        client.get_config().cloned()
+    };
+
+    let config = match client_config {
+        Some(config) => config,
+        None => {
+            return Ok(MusicLibraryResult {
+                success: false,
+                message: "Not authenticated".to_string(),
+                start_index: None,
+                has_more: false,
+                items: None,
+                total_count: None,
+                error: Some(JellyfinError::NotAuthenticated),
+            });
+        }
+    };
+
+    let mut client = JellyfinClient::new();
+    client.set_config(config);
+
+    match client.get_playlist_songs(&playlist_id, limit, start_index).await {
+        Ok(response) => Ok(MusicLibraryResult {
+            success: true,
+            message: "Playlist songs retrieved successfully".to_string(),
+            start_index: Some(response.start_index),
+            has_more: has_more_pages(&response),
+            items: Some(response.items),
+            total_count: Some(response.total_record_count),
+            error: None,
+        }),
+        Err(e) => Ok(MusicLibraryResult {
+            success: false,
+            message: format!("Failed to get playlist songs: {}", e),
+            start_index: None,
+            has_more: false,
+            items: None,
+            total_count: None,
+            error: Some(e),
+        }),
+    }
+}
+
+// A client for the signed-in user
+fn playlist_client(state: &State<'_, AppState>) -> Result<JellyfinClient, String> {
+    let shared_client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+    let config = shared_client.get_config().cloned().ok_or_else(|| "Not authenticated".to_string())?;
+
+    let mut client = JellyfinClient::new();
+    client.set_config(config);
+    Ok(client)
+}
+
+// Drop cached listings once a playlist change has gone through, so the next listing shows it.
+// Dropping them before would let a listing fetched during the change cache the old state
+fn invalidate_playlist_listings(state: &State<'_, AppState>) -> Result<(), String> {
+    let mut shared_client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+    shared_client.invalidate_cache();
+    Ok(())
+}
+
+// Returns the new playlist's id
+#[tauri::command]
+pub async fn create_playlist(
+    name: String,
+    item_ids: Vec<String>,
+    state: State<'_, AppState>,
+) -> Result<String, CommandError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Playlist name must not be empty".into());
+    }
+
+    let client = playlist_client(&state)?;
+    let playlist_id = client.create_playlist(name, &item_ids).await
+        .map_err(failed("Failed to create playlist"))?;
+    invalidate_playlist_listings(&state)?;
+    info!("Created playlist {} with {} songs", name, item_ids.len());
+    Ok(playlist_id)
+}
+
+#[tauri::command]
+pub async fn add_to_playlist(
+    playlist_id: String,
+    item_ids: Vec<String>,
+    state: State<'_, AppState>,
+) -> Result<bool, CommandError> {
+    if item_ids.is_empty() {
+        return Ok(true);
+    }
+
+    let client = playlist_client(&state)?;
+    client.add_to_playlist(&playlist_id, &item_ids).await
+        .map_err(failed("Failed to add to playlist"))?;
+    invalidate_playlist_listings(&state)?;
+    Ok(true)
+}
+
+// entry_ids are the playlist_item_id of songs from get_playlist_songs, not their item ids
+#[tauri::command]
+pub async fn remove_from_playlist(
+    playlist_id: String,
+    entry_ids: Vec<String>,
+    state: State<'_, AppState>,
+) -> Result<bool, CommandError> {
+    if entry_ids.is_empty() {
+        return Ok(true);
+    }
+
+    let client = playlist_client(&state)?;
+    client.remove_from_playlist(&playlist_id, &entry_ids).await
+        .map_err(failed("Failed to remove from playlist"))?;
+    invalidate_playlist_listings(&state)?;
+    Ok(true)
+}
+
+#[tauri::command]
+pub async fn reorder_playlist_item(
+    playlist_id: String,
+    entry_id: String,
+    new_index: u32,
+    state: State<'_, AppState>,
+) -> Result<bool, CommandError> {
+    let client = playlist_client(&state)?;
+    client.reorder_playlist_item(&playlist_id, &entry_id, new_index).await
+        .map_err(failed("Failed to move playlist item"))?;
+    invalidate_playlist_listings(&state)?;
+    Ok(true)
+}
+
+#[tauri::command]
+pub async fn get_item(
+    item_id: String,
+    state: State<'_, AppState>,
+) -> Result<ItemResult, CommandError> {
+    let client_config = {
+        let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+        client.get_config().cloned()
+    };
+
+    let config = match client_config {
+        Some(config) => config,
+        None => {
+            return Ok(ItemResult {
+                success: false,
+                message: "Not authenticated".to_string(),
+                item: None,
+                error: Some(JellyfinError::NotAuthenticated),
+            });
+        }
+    };
+
+    let mut client = JellyfinClient::new();
+    client.set_config(config);
+
+    match client.get_item(&item_id).await {
+        Ok(item) => Ok(ItemResult {
+            success: true,
+            message: "Item retrieved successfully".to_string(),
+            item: Some(item),
+            error: None,
+        }),
+        Err(e) => Ok(ItemResult {
+            success: false,
+            message: format!("Failed to get item: {}", e),
+            item: None,
+            error: Some(e),
+        }),
+    }
+}
+
+// None when the song has no lyrics on the server
+#[tauri::command]
+pub async fn get_lyrics(item_id: String, state: State<'_, AppState>) -> Result<Option<Lyrics>, CommandError> {
+    let client_config = {
+        let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+        client.get_config().cloned()
+    };
+
+    let config = match client_config {
+        Some(config) => config,
+        None => {
+            return Err(JellyfinError::NotAuthenticated.into());
+        }
+    };
+
+    let mut client = JellyfinClient::new();
+    client.set_config(config);
+
+    client
+        .get_lyrics(&item_id)
+        .await
+        .map_err(failed("Failed to get lyrics"))
+}
+
+// The other tracks on an item's album, in disc/track order, for "more from this album"
+#[tauri::command]
+pub async fn get_album_siblings(
+    item_id: String,
+    state: State<'_, AppState>,
+) -> Result<MusicLibraryResult, CommandError> {
+    let client_config = {
+        let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+        client.get_config().cloned()
+    };
+
+    let config = match client_config {
+        Some(config) => config,
+        None => {
+            return Ok(MusicLibraryResult {
+                success: false,
+                message: "Not authenticated".to_string(),
+                start_index: None,
+                has_more: false,
+                items: None,
+                total_count: None,
+                error: Some(JellyfinError::NotAuthenticated),
+            });
+        }
+    };
+
+    let mut client = JellyfinClient::new();
+    client.set_config(config);
+
+    let album_id = match client.get_item(&item_id).await {
+        Ok(item) => item.album_id,
+        Err(e) => {
+            return Ok(MusicLibraryResult {
+                success: false,
+                message: format!("Failed to get item: {}", e),
+                start_index: None,
+                has_more: false,
+                items: None,
+                total_count: None,
+                error: Some(e),
+            });
+        }
+    };
+
+    let Some(album_id) = album_id else {
+        return Ok(MusicLibraryResult {
+            success: true,
+            message: "Item has no album".to_string(),
+            start_index: Some(0),
+            has_more: false,
+            items: Some(Vec::new()),
+            total_count: Some(0),
+            error: None,
+        });
+    };
+
+    match client.get_album_songs(&album_id).await {
+        Ok(response) => {
+            let items: Vec<MusicItem> = response.items
+                .into_iter()
+                .filter(|song| song.id != item_id)
+                .collect();
+            let total_count = items.len() as i32;
+            Ok(MusicLibraryResult {
+                success: true,
+                message: "Album siblings retrieved successfully".to_string(),
+                start_index: Some(0),
+                has_more: false,
+                items: Some(items),
+                total_count: Some(total_count),
+                error: None,
+            })
+        }
+        Err(e) => Ok(MusicLibraryResult {
+            success: false,
+            message: format!("Failed to get album songs: {}", e),
+            start_index: None,
+            has_more: false,
+            items: None,
+            total_count: None,
+            error: Some(e),
+        }),
+    }
+}
+
+#[tauri::command]
+pub async fn get_current_album(state: State<'_, AppState>) -> Result<Option<MusicItem>, CommandError> {
+    let audio_player = {
+        let ap = state.audio_player.lock().map_err(|e| e.to_string())?;
+        ap.clone()
+    };
+
+    // Singles and items without album context have nothing to go to
+    let album_id = match audio_player.get_state().await?.current_song.and_then(|song| song.album_id) {
+        Some(album_id) => album_id,
+        None => return Ok(None),
+    };
+
+    let client_config = {
+        let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+        client.get_config().cloned()
+    };
+
+    let config = match client_config {
+        Some(config) => config,
+        None => {
+            return Err(JellyfinError::NotAuthenticated.into());
+        }
+    };
+
+    let mut client = JellyfinClient::new();
+    client.set_config(config);
+
+    client.get_item(&album_id).await
+        .map(Some)
+        .map_err(failed("Failed to get album"))
+}
+
+#[tauri::command]
+pub async fn get_external_links(
+    item_id: String,
+    state: State<'_, AppState>,
+) -> Result<Vec<ExternalUrl>, CommandError> {
+    let client_config = {
+        let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+        client.get_config().cloned()
+    };
+
+    let config = match client_config {
+        Some(config) => config,
+        None => {
+            return Err(JellyfinError::NotAuthenticated.into());
+        }
+    };
+
+    let mut client = JellyfinClient::new();
+    client.set_config(config);
+
+    let item = client.get_item_details(&item_id).await
+        .map_err(failed("Failed to get item"))?;
+
+    Ok(item.external_urls.unwrap_or_default())
+}
+
+#[tauri::command]
+pub async fn get_favorites(
+    item_type: Option<String>,
+    limit: Option<i32>,
+    start_index: Option<i32>,
+    state: State<'_, AppState>,
+) -> Result<MusicLibraryResult, CommandError> {
+    let limit = resolve_page_limit(&state, limit)?;
+    let client_config = {
+        let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+        client.get_config().cloned()
+    };
+
+    let config = match client_config {
+        Some(config) => config,
+        None => {
+            return Ok(MusicLibraryResult {
+                success: false,
+                message: "Not authenticated".to_string(),
+                start_index: None,
+                has_more: false,
+                items: None,
+                total_count: None,
+                error: Some(JellyfinError::NotAuthenticated),
+            });
+        }
+    };
+
+    let mut client = JellyfinClient::new();
+    client.set_config(config);
+
+    let item_type = item_type.unwrap_or_else(|| "Audio".to_string());
+    match client.get_favorites(&item_type, limit, start_index).await {
+        Ok(response) => Ok(MusicLibraryResult {
+            success: true,
+            message: "Favorites retrieved successfully".to_string(),
+            start_index: Some(response.start_index),
+            has_more: has_more_pages(&response),
+            items: Some(response.items),
+            total_count: Some(response.total_record_count),
+            error: None,
+        }),
+        Err(e) => Ok(MusicLibraryResult {
+            success: false,
+            message: format!("Failed to get favorites: {}", e),
+            start_index: None,
+            has_more: false,
+            items: None,
+            total_count: None,
+            error: Some(e),
+        }),
+    }
+}
+
+#[tauri::command]
+pub async fn get_songs_by_tag(
+    tag: String,
+    genre: Option<String>,
+    limit: Option<i32>,
+    start_index: Option<i32>,
+    state: State<'_, AppState>,
+) -> Result<MusicLibraryResult, CommandError> {
+    let limit = resolve_page_limit(&state, limit)?;
+    let client_config = {
+        let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+        client.get_config().cloned()
+    };
+
+    let config = match client_config {
+        Some(config) => config,
+        None => {
+            return Ok(MusicLibraryResult {
+                success: false,
+                message: "Not authenticated".to_string(),
+                start_index: None,
+                has_more: false,
+                items: None,
+                total_count: None,
+                error: Some(JellyfinError::NotAuthenticated),
+            });
+        }
+    };
+
+    let mut client = JellyfinClient::new();
+    client.set_config(config);
+
+    match client.get_songs_by_tag(&tag, genre.as_deref(), limit, start_index).await {
+        Ok(response) => Ok(MusicLibraryResult {
+            success: true,
+            message: "Tagged songs retrieved successfully".to_string(),
+            start_index: Some(response.start_index),
+            has_more: has_more_pages(&response),
+            items: Some(response.items),
+            total_count: Some(response.total_record_count),
+            error: None,
+        }),
+        Err(e) => Ok(MusicLibraryResult {
+            success: false,
+            message: format!("Failed to get songs by tag: {}", e),
+            start_index: None,
+            has_more: false,
+            items: None,
+            total_count: None,
+            error: Some(e),
+        }),
+    }
+}
+
+const FAVORITE_UPDATE_CONCURRENCY: usize = 4;
+
+#[tauri::command]
+pub async fn set_favorites(
+    item_ids: Vec<String>,
+    is_favorite: bool,
+    state: State<'_, AppState>,
+) -> Result<Vec<FavoriteUpdateResult>, CommandError> {
+    use futures_util::StreamExt;
+
+    let client_config = {
+        let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+        client.get_config().cloned()
+    };
+
+    let config = match client_config {
+        Some(config) => config,
+        None => {
+            return Err(JellyfinError::NotAuthenticated.into());
+        }
+    };
+
+    let mut client = JellyfinClient::new();
+    client.set_config(config);
+
+    let client = &client;
+    let results: Vec<FavoriteUpdateResult> = futures_util::stream::iter(item_ids)
+        .map(|item_id| async move {
+            match client.set_favorite(&item_id, is_favorite).await {
+                Ok(()) => FavoriteUpdateResult {
+                    item_id,
+                    success: true,
+                    message: "Favorite updated".to_string(),
+                    error: None,
+                },
+                Err(e) => FavoriteUpdateResult {
+                    item_id,
+                    success: false,
+                    message: format!("Failed to update favorite: {}", e),
+                    error: Some(e),
+                },
+            }
+        })
+        .buffer_unordered(FAVORITE_UPDATE_CONCURRENCY)
+        .collect()
+        .await;
+
+    // Invalidate cached listings once for the whole batch
+    {
+        let mut shared_client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+        shared_client.invalidate_cache();
+    }
+
+    let updated = results.iter().filter(|r| r.success).count();
+    info!("Updated favorites for {}/{} items", updated, results.len());
+
+    Ok(results)
+}
+
+#[tauri::command]
+pub async fn toggle_current_favorite(state: State<'_, AppState>) -> Result<bool, CommandError> {
+    let audio_player = {
+        let ap = state.audio_player.lock().map_err(|e| e.to_string())?;
+        ap.clone()
+    };
+
+    let current_song = audio_player.get_state().await?
+        .current_song
+        .ok_or_else(|| "Nothing is playing".to_string())?;
+
+    let client_config = {
+        let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+        client.get_config().cloned()
+    };
+
+    let config = client_config.ok_or_else(|| "Not authenticated".to_string())?;
+    let mut client = JellyfinClient::new();
+    client.set_config(config);
+
+    let is_favorite = toggle_favorite_on_server(&client, &current_song.id).await?;
+
+    {
+        let mut shared_client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+        shared_client.invalidate_cache();
+    }
+
+    audio_player.set_favorite(current_song.id, is_favorite)?;
+    Ok(is_favorite)
+}
+
+#[tauri::command]
+pub async fn toggle_favorite(item_id: String, state: State<'_, AppState>) -> Result<bool, CommandError> {
+    let client_config = {
+        let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+        client.get_config().cloned()
+    };
+
+    let config = client_config.ok_or_else(|| "Not authenticated".to_string())?;
+    let mut client = JellyfinClient::new();
+    client.set_config(config);
+
+    let is_favorite = toggle_favorite_on_server(&client, &item_id).await?;
+
+    {
+        let mut shared_client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+        shared_client.invalidate_cache();
+    }
+
+    // Keep any queued copies of the item in step
+    let audio_player = state.audio_player.lock().map_err(|e| e.to_string())?;
+    audio_player.set_favorite(item_id, is_favorite)?;
+    Ok(is_favorite)
+}
+
+// Flip the favorite flag starting from the server's UserData rather than our copy, since
+// the item may have been (un)favorited on another client, and return the state the server reports
+async fn toggle_favorite_on_server(client: &JellyfinClient, item_id: &str) -> Result<bool, CommandError> {
+    let is_favorite = |item: &MusicItem| item.user_data.as_ref().is_some_and(|data| data.is_favorite);
+
+    let item = client.get_item_details(item_id).await
+        .map_err(failed("Failed to get item"))?;
+    client.set_favorite(item_id, !is_favorite(&item)).await
+        .map_err(failed("Failed to update favorite"))?;
+
+    let item = client.get_item_details(item_id).await
+        .map_err(failed("Failed to refresh item"))?;
+    Ok(is_favorite(&item))
+}
+
+const FAVORITES_PAGE_SIZE: i32 = 200;
+
+#[tauri::command]
+pub async fn play_favorites(
+    shuffle: bool,
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<usize, CommandError> {
+    let client_config = {
+        let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+        client.get_config().cloned()
+    };
+
+    let config = match client_config {
+        Some(config) => config,
+        None => {
+            return Err(JellyfinError::NotAuthenticated.into());
+        }
+    };
+
+    let mut client = JellyfinClient::new();
+    client.set_config(config);
+
+    // Page through all favorited songs so large collections aren't truncated
+    let mut favorites: Vec<MusicItem> = Vec::new();
+    loop {
+        let page = client.get_favorites("Audio", Some(FAVORITES_PAGE_SIZE), Some(favorites.len() as i32)).await
+            .map_err(failed("Failed to get favorites"))?;
+
+        let fetched = page.items.len();
+        favorites.extend(page.items);
+
+        if fetched == 0 || favorites.len() >= page.total_record_count as usize {
+            break;
+        }
+    }
+
+    if favorites.is_empty() {
+        return Ok(0);
+    }
+
+    if shuffle {
+        use rand::seq::SliceRandom;
+        favorites.shuffle(&mut rand::thread_rng());
+    }
+
+    let streaming_profile = active_streaming_profile(&state)?;
+
+    let mut queue = Vec::with_capacity(favorites.len());
+    for item in &favorites {
+        let queue_item = stream_queue_item(&client, streaming_profile.as_ref(), item)?;
+        queue.push(queue_item);
+    }
+
+    let queue_len = queue.len();
+    info!("Playing {} favorite songs (shuffle: {})", queue_len, shuffle);
+
+    let audio_player = {
+        let ap = state.audio_player.lock().map_err(|e| e.to_string())?;
+        ap.clone()
+    };
+    audio_player.set_queue(queue, 0).await?;
+    start_queue_resolution(&state, &app_handle, audio_player)?;
+
+    Ok(queue_len)
+}
+
+#[derive(Clone, serde::Serialize)]
+pub struct Recommendations {
+    pub albums: Vec<MusicItem>,
+    pub artists: Vec<MusicItem>,
+}
+
+const RECOMMENDATION_SHELF_SIZE: usize = 20;
+// Favorites of each type whose similar items fill out thin shelves
+const RECOMMENDATION_SEED_COUNT: i32 = 5;
+const SIMILAR_ITEMS_PER_SEED: i32 = 10;
+
+// Add items to a shelf, skipping duplicates and anything the user already favorited
+fn extend_shelf(shelf: &mut Vec<MusicItem>, seen: &mut std::collections::HashSet<String>, items: Vec<MusicItem>) {
+    for item in items {
+        if shelf.len() >= RECOMMENDATION_SHELF_SIZE {
+            break;
+        }
+        let is_favorite = item.user_data.as_ref().is_some_and(|user_data| user_data.is_favorite);
+        if !is_favorite && seen.insert(item.id.clone()) {
+            shelf.push(item);
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn recommendations(
+    refresh: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<Recommendations, CommandError> {
+    let client_config = {
+        let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
+        client.get_config().cloned()
+    };
+
+    let config = match client_config {
+        Some(config) => config,
+        None => {
+            return Err(JellyfinError::NotAuthenticated.into());
+        }
+    };
+
+    if !refresh.unwrap_or(false) {
+        let cached = state.recommendations.lock().map_err(|e| e.to_string())?;
+        if let Some((user_id, recommendations)) = cached.as_ref() {
+            if *user_id == config.user_id {
+                return Ok(recommendations.clone());
+            }
+        }
+    }
+
+    let user_id = config.user_id.clone();
+    let mut client = JellyfinClient::new();
+    client.set_config(config);
+
+    let mut seen = std::collections::HashSet::new();
+    let mut albums = Vec::new();
+    let mut artists = Vec::new();
+
+    let suggestions = client.get_suggestions("MusicAlbum,MusicArtist", (RECOMMENDATION_SHELF_SIZE * 2) as i32).await
+        .map_err(|e| e.to_string());
+    match suggestions {
+        Ok(Some(response)) => {
+            let (suggested_albums, suggested_artists): (Vec<MusicItem>, Vec<MusicItem>) = response.items
+                .into_iter()
+                .filter(|item| item.item_type == "MusicAlbum" || item.item_type == "MusicArtist")
+                .partition(|item| item.item_type == "MusicAlbum");
+            extend_shelf(&mut albums, &mut seen, suggested_albums);
+            extend_shelf(&mut artists, &mut seen, suggested_artists);
+        }
+        Ok(None) => info!("Server has no suggestions endpoint, using favorites instead"),
+        Err(e) => warn!("Failed to get suggestions: {}", e),
     }
-}
 
-#[tauri::command]
-pub fn pause_playback(state: State<'_, AppState>) -> Result<bool, String> {
-    let audio_player = state.audio_player.lock().map_err(|e| e.to_string())?;
-    audio_player.pause()?;
-    Ok(true)
-}
+    // Top up thin shelves with items similar to the user's favorites
+    for (item_type, shelf) in [("MusicAlbum", &mut albums), ("MusicArtist", &mut artists)] {
+        if shelf.len() >= RECOMMENDATION_SHELF_SIZE {
+            continue;
+        }
 
-#[tauri::command]
-pub fn resume_playback(state: State<'_, AppState>) -> Result<bool, String> {
-    let audio_player = state.audio_player.lock().map_err(|e| e.to_string())?;
-    audio_player.resume()?;
-    Ok(true)
-}
+        let favorites = match client.get_favorites(item_type, Some(RECOMMENDATION_SEED_COUNT), None).await {
+            Ok(response) => response.items,
+            Err(e) => {
+                warn!("Failed to get favorite {} items for recommendations: {}", item_type, e);
+                continue;
+            }
+        };
 
-#[tauri::command]
-pub fn stop_playback(state: State<'_, AppState>) -> Result<bool, String> {
-    let audio_player = state.audio_player.lock().map_err(|e| e.to_string())?;
-    audio_player.stop()?;
-    Ok(true)
-}
+        for favorite in &favorites {
+            if shelf.len() >= RECOMMENDATION_SHELF_SIZE {
+                break;
+            }
+            match client.get_similar_items(&favorite.id, SIMILAR_ITEMS_PER_SEED).await {
+                Ok(response) => extend_shelf(shelf, &mut seen, response.items),
+                Err(e) => warn!("Failed to get items similar to {}: {}", favorite.name, e),
+            }
+        }
+    }
 
-#[tauri::command]
-pub fn set_volume(state: State<'_, AppState>, volume: f32) -> Result<bool, String> {
-    let audio_player = state.audio_player.lock().map_err(|e| e.to_string())?;
-    audio_player.set_volume(volume)?;
-    Ok(true)
-}
+    info!("Built recommendations: {} albums, {} artists", albums.len(), artists.len());
 
-#[tauri::command]
-pub fn seek_to(state: State<'_, AppState>, position: f64) -> Result<bool, String> {
-    let audio_player = state.audio_player.lock().map_err(|e| e.to_string())?;
-    audio_player.seek(position)?;
-    Ok(true)
-}
+    let recommendations = Recommendations { albums, artists };
+    {
+        let mut cached = state.recommendations.lock().map_err(|e| e.to_string())?;
+        *cached = Some((user_id, recommendations.clone()));
+    }
 
-#[tauri::command]
-pub fn toggle_shuffle(state: State<'_, AppState>) -> Result<bool, String> {
-    let audio_player = state.audio_player.lock().map_err(|e| e.to_string())?;
-    audio_player.toggle_shuffle()?;
-    Ok(true)
+    Ok(recommendations)
 }
 
-#[tauri::command]
-pub fn set_repeat_mode(state: State<'_, AppState>, mode: String) -> Result<bool, String> {
-    let repeat_mode = match mode.as_str() {
-        "none" => RepeatMode::None,
-        "one" => RepeatMode::One,
-        "all" => RepeatMode::All,
-        _ => return Err("Invalid repeat mode".to_string()),
-    };
+const DAILY_MIX_SIZE: usize = 50;
+const DAILY_MIX_GENRES: usize = 5;
+// Most played and favorite songs sampled to work out the user's top genres
+const TASTE_SAMPLE_SIZE: i32 = 200;
+// A favorite counts as this many plays towards its genres
+const FAVORITE_GENRE_WEIGHT: i64 = 5;
 
-    let audio_player = state.audio_player.lock().map_err(|e| e.to_string())?;
-    audio_player.set_repeat_mode(repeat_mode)?;
-    Ok(true)
+// The day's mix, kept on disk so it stays the same across restarts until the date changes
+#[derive(serde::Serialize, serde::Deserialize)]
+struct DailyMix {
+    day: u64, // days since the unix epoch (UTC)
+    user_id: String,
+    items: Vec<MusicItem>,
 }
 
-#[tauri::command]
-pub async fn get_playback_state(state: State<'_, AppState>) -> Result<PlaybackState, String> {
-    let audio_player = {
-        let ap = state.audio_player.lock().map_err(|e| e.to_string())?;
-        ap.clone()
-    };
-    audio_player.get_state().await
+fn daily_mix_path(app_handle: &tauri::AppHandle) -> Result<std::path::PathBuf, String> {
+    app_handle
+        .path()
+        .app_data_dir()
+        .map(|dir| dir.join("daily_mix.json"))
+        .map_err(|e| format!("Failed to resolve app data directory: {}", e))
 }
 
-#[tauri::command]
-pub fn next_track(state: State<'_, AppState>) -> Result<bool, String> {
-    let audio_player = state.audio_player.lock().map_err(|e| e.to_string())?;
-    audio_player.next_track()?;
-    Ok(true)
-}
+// Weight each genre by how often its songs were played plus how many were favorited
+fn top_genres(most_played: &[MusicItem], favorites: &[MusicItem]) -> Vec<String> {
+    let mut scores: HashMap<&str, i64> = HashMap::new();
+    let weighted = most_played
+        .iter()
+        .map(|song| {
+            let plays = song.user_data.as_ref().and_then(|user_data| user_data.play_count).unwrap_or(0);
+            (song, i64::from(plays))
+        })
+        .chain(favorites.iter().map(|song| (song, FAVORITE_GENRE_WEIGHT)));
 
-#[tauri::command]
-pub fn previous_track(state: State<'_, AppState>) -> Result<bool, String> {
-    let audio_player = state.audio_player.lock().map_err(|e| e.to_string())?;
-    audio_player.previous_track()?;
-    Ok(true)
+    for (song, weight) in weighted {
+        for genre in song.genres.iter().flatten() {
+            *scores.entry(genre.as_str()).or_insert(0) += weight;
+        }
+    }
+
+    let mut genres: Vec<(&str, i64)> = scores.into_iter().filter(|(_, score)| *score > 0).collect();
+    genres.sort_by(|(name_a, score_a), (name_b, score_b)| score_b.cmp(score_a).then_with(|| name_a.cmp(name_b)));
+    genres.into_iter().take(DAILY_MIX_GENRES).map(|(genre, _)| genre.to_string()).collect()
 }
 
 #[tauri::command]
-pub async fn get_random_songs(
-    limit: Option<i32>,
+pub async fn get_daily_mix(
     state: State<'_, AppState>,
-) -> Result<MusicLibraryResult, String> {
-    println!("🎲 get_random_songs command called with limit: {:?}", limit);
+    app_handle: tauri::AppHandle,
+) -> Result<MusicLibraryResult, CommandError> {
     let client_config = {
         let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
         client.get_config().cloned()
@@ -785,38 +4682,127 @@ pub async fn get_random_songs(
             return Ok(MusicLibraryResult {
                 success: false,
                 message: "Not authenticated".to_string(),
+                start_index: None,
+                has_more: false,
                 items: None,
                 total_count: None,
+                error: Some(JellyfinError::NotAuthenticated),
             });
         }
     };
 
+    let today = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() / (24 * 60 * 60);
+
+    let mix_path = daily_mix_path(&app_handle)?;
+    let cached_mix = std::fs::read_to_string(&mix_path)
+        .ok()
+        .and_then(|content| serde_json::from_str::<DailyMix>(&content).ok())
+        .filter(|mix| mix.day == today && mix.user_id == config.user_id);
+    if let Some(mix) = cached_mix {
+        let total = mix.items.len() as i32;
+        return Ok(MusicLibraryResult {
+            success: true,
+            message: "Daily mix retrieved successfully".to_string(),
+            start_index: Some(0),
+            has_more: false,
+            items: Some(mix.items),
+            total_count: Some(total),
+            error: None,
+        });
+    }
+
+    let user_id = config.user_id.clone();
     let mut client = JellyfinClient::new();
     client.set_config(config);
 
-    match client.get_random_songs(limit).await {
-        Ok(response) => Ok(MusicLibraryResult {
+    let most_played = client.get_most_played(TASTE_SAMPLE_SIZE).await
+        .map_err(failed("Failed to get most played songs"))?
+        .items;
+    let favorites = client.get_favorites("Audio", Some(TASTE_SAMPLE_SIZE), None).await
+        .map_err(failed("Failed to get favorites"))?
+        .items;
+
+    let genres = top_genres(&most_played, &favorites);
+    if genres.is_empty() {
+        return Ok(MusicLibraryResult {
             success: true,
-            message: "Random songs retrieved successfully".to_string(),
-            items: Some(response.items),
-            total_count: Some(response.total_record_count),
-        }),
-        Err(e) => Ok(MusicLibraryResult {
-            success: false,
-            message: format!("Failed to get random songs: {}", e),
-            items: None,
-            total_count: None,
-        }),
+            message: "Not enough listening history for a daily mix yet".to_string(),
+            start_index: Some(0),
+            has_more: false,
+            items: Some(Vec::new()),
+            total_count: Some(0),
+            error: None,
+        });
+    }
+
+    // An even share per genre, over-fetched a little to survive de-duplication
+    let per_genre = DAILY_MIX_SIZE.div_ceil(genres.len());
+    let mut seen = std::collections::HashSet::new();
+    let mut mix = Vec::with_capacity(DAILY_MIX_SIZE);
+    for genre in &genres {
+        let sample = match client.get_random_genre_songs(genre, (per_genre * 2) as i32).await {
+            Ok(response) => response.items,
+            Err(e) => {
+                warn!("Failed to sample genre {}: {}", genre, e);
+                continue;
+            }
+        };
+        mix.extend(
+            sample
+                .into_iter()
+                .filter(|song| seen.insert(song.id.clone()))
+                .take(per_genre),
+        );
+    }
+
+    // Seeded by the date so the order only changes from one day to the next
+    {
+        use rand::{seq::SliceRandom, SeedableRng};
+        mix.shuffle(&mut rand::rngs::StdRng::seed_from_u64(today));
+    }
+    mix.truncate(DAILY_MIX_SIZE);
+
+    info!("Built daily mix of {} songs from genres: {}", mix.len(), genres.join(", "));
+
+    let daily_mix = DailyMix { day: today, user_id, items: mix };
+    match serde_json::to_string(&daily_mix) {
+        Ok(content) => {
+            if let Some(parent) = mix_path.parent() {
+                let _ = std::fs::create_dir_all(parent);
+            }
+            if let Err(e) = std::fs::write(&mix_path, content) {
+                warn!("Failed to cache daily mix: {}", e);
+            }
+        }
+        Err(e) => warn!("Failed to serialize daily mix: {}", e),
     }
+
+    let total = daily_mix.items.len() as i32;
+    Ok(MusicLibraryResult {
+        success: true,
+        message: "Daily mix retrieved successfully".to_string(),
+        start_index: Some(0),
+        has_more: false,
+        items: Some(daily_mix.items),
+        total_count: Some(total),
+        error: None,
+    })
 }
 
+// Most queue items used to seed an instant mix, to cap server calls
+const MIX_SEED_LIMIT: usize = 5;
+const DEFAULT_INSTANT_MIX_LIMIT: i32 = 50;
+
+// Songs similar to a song, album or artist, for radio-style playback
 #[tauri::command]
-pub async fn get_recent_albums(
+pub async fn get_instant_mix(
+    item_id: String,
     limit: Option<i32>,
-    start_index: Option<i32>,
     state: State<'_, AppState>,
-) -> Result<MusicLibraryResult, String> {
-    println!("📅 get_recent_albums command called with limit: {:?}, start_index: {:?}", limit, start_index);
+) -> Result<MusicLibraryResult, CommandError> {
     let client_config = {
         let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
         client.get_config().cloned()
@@ -828,8 +4814,11 @@ pub async fn get_recent_albums(
             return Ok(MusicLibraryResult {
                 success: false,
                 message: "Not authenticated".to_string(),
+                start_index: None,
+                has_more: false,
                 items: None,
                 total_count: None,
+                error: Some(JellyfinError::NotAuthenticated),
             });
         }
     };
@@ -837,27 +4826,36 @@ pub async fn get_recent_albums(
     let mut client = JellyfinClient::new();
     client.set_config(config);
 
-    match client.get_recent_albums(limit, start_index).await {
+    match client.get_instant_mix(&item_id, limit.unwrap_or(DEFAULT_INSTANT_MIX_LIMIT)).await {
         Ok(response) => Ok(MusicLibraryResult {
             success: true,
-            message: "Recent albums retrieved successfully".to_string(),
+            message: "Instant mix retrieved successfully".to_string(),
+            start_index: Some(response.start_index),
+            has_more: has_more_pages(&response),
             items: Some(response.items),
             total_count: Some(response.total_record_count),
+            error: None,
         }),
         Err(e) => Ok(MusicLibraryResult {
             success: false,
-            message: format!("Failed to get recent albums: {}", e),
+            message: format!("Failed to get instant mix: {}", e),
+            start_index: None,
+            has_more: false,
             items: None,
             total_count: None,
+            error: Some(e),
         }),
     }
 }
 
+// Replace the queue with an instant mix seeded from the item, in the server's order
 #[tauri::command]
-pub async fn get_album_songs(
-    album_id: String,
+pub async fn play_instant_mix(
+    item_id: String,
+    limit: Option<i32>,
     state: State<'_, AppState>,
-) -> Result<MusicLibraryResult, String> {
+    app_handle: tauri::AppHandle,
+) -> Result<usize, CommandError> {
     let client_config = {
         let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
         client.get_config().cloned()
@@ -866,39 +4864,49 @@ pub async fn get_album_songs(
     let config = match client_config {
         Some(config) => config,
         None => {
-            return Ok(MusicLibraryResult {
-                success: false,
-                message: "Not authenticated".to_string(),
-                items: None,
-                total_count: None,
-            });
+            return Err(JellyfinError::NotAuthenticated.into());
         }
     };
 
     let mut client = JellyfinClient::new();
     client.set_config(config);
 
-    match client.get_album_songs(&album_id).await {
-        Ok(response) => Ok(MusicLibraryResult {
-            success: true,
-            message: "Album songs retrieved successfully".to_string(),
-            items: Some(response.items),
-            total_count: Some(response.total_record_count),
-        }),
-        Err(e) => Ok(MusicLibraryResult {
-            success: false,
-            message: format!("Failed to get album songs: {}", e),
-            items: None,
-            total_count: None,
-        }),
+    let mix = client.get_instant_mix(&item_id, limit.unwrap_or(DEFAULT_INSTANT_MIX_LIMIT)).await
+        .map_err(failed("Failed to get instant mix"))?;
+    if mix.items.is_empty() {
+        return Ok(0);
+    }
+
+    let streaming_profile = active_streaming_profile(&state)?;
+
+    let mut queue = Vec::with_capacity(mix.items.len());
+    for item in &mix.items {
+        let queue_item = stream_queue_item(&client, streaming_profile.as_ref(), item)?;
+        queue.push(queue_item);
     }
+
+    let queue_len = queue.len();
+    info!("Playing instant mix of {} songs seeded from {}", queue_len, item_id);
+
+    let audio_player = {
+        let ap = state.audio_player.lock().map_err(|e| e.to_string())?;
+        ap.clone()
+    };
+    audio_player.set_queue(queue, 0).await?;
+    start_queue_resolution(&state, &app_handle, audio_player)?;
+
+    Ok(queue_len)
 }
 
 #[tauri::command]
-pub async fn get_artist_songs(
-    artist_id: String,
+pub async fn extend_queue_with_mix(
+    count: usize,
     state: State<'_, AppState>,
-) -> Result<MusicLibraryResult, String> {
+) -> Result<usize, CommandError> {
+    if count == 0 {
+        return Ok(0);
+    }
+
     let client_config = {
         let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
         client.get_config().cloned()
@@ -907,41 +4915,75 @@ pub async fn get_artist_songs(
     let config = match client_config {
         Some(config) => config,
         None => {
-            return Ok(MusicLibraryResult {
-                success: false,
-                message: "Not authenticated".to_string(),
-                items: None,
-                total_count: None,
-            });
+            return Err(JellyfinError::NotAuthenticated.into());
         }
     };
 
     let mut client = JellyfinClient::new();
     client.set_config(config);
 
-    match client.get_artist_songs(&artist_id).await {
-        Ok(response) => Ok(MusicLibraryResult {
-            success: true,
-            message: "Artist songs retrieved successfully".to_string(),
-            items: Some(response.items),
-            total_count: Some(response.total_record_count),
-        }),
-        Err(e) => Ok(MusicLibraryResult {
-            success: false,
-            message: format!("Failed to get artist songs: {}", e),
-            items: None,
-            total_count: None,
-        }),
+    let audio_player = {
+        let ap = state.audio_player.lock().map_err(|e| e.to_string())?;
+        ap.clone()
+    };
+
+    // Everything in the queue, played or upcoming, is excluded from the mix
+    let queue = audio_player.get_queue().await?;
+    let mut seen: std::collections::HashSet<String> = queue.iter().map(|item| item.id.clone()).collect();
+    if seen.is_empty() {
+        return Err("Queue is empty, nothing to seed a mix from".into());
     }
+
+    let seeds: Vec<String> = {
+        use rand::seq::IteratorRandom;
+        seen.iter().cloned().choose_multiple(&mut rand::thread_rng(), MIX_SEED_LIMIT)
+    };
+
+    let mut mix: Vec<MusicItem> = Vec::new();
+    for seed in &seeds {
+        let result = client.get_instant_mix(seed, count as i32).await
+            .map_err(|e| e.to_string());
+        match result {
+            Ok(response) => {
+                for item in response.items {
+                    if seen.insert(item.id.clone()) {
+                        mix.push(item);
+                    }
+                }
+            }
+            Err(e) => warn!("Instant mix failed for seed {}: {}", seed, e),
+        }
+    }
+
+    // Interleave the seeds' contributions instead of favouring the first one
+    {
+        use rand::seq::SliceRandom;
+        mix.shuffle(&mut rand::thread_rng());
+    }
+    mix.truncate(count);
+
+    let streaming_profile = active_streaming_profile(&state)?;
+
+    let mut additions = Vec::with_capacity(mix.len());
+    for item in &mix {
+        let queue_item = stream_queue_item(&client, streaming_profile.as_ref(), item)?;
+        additions.push(queue_item);
+    }
+
+    let added = additions.len();
+    info!("Extending queue with {} mix tracks from {} seeds", added, seeds.len());
+    audio_player.append_to_queue(additions)?;
+
+    Ok(added)
 }
 
+// Queue an album starting at its first unplayed track, or from the top if everything's been heard
 #[tauri::command]
-pub async fn get_playlist_songs(
-    playlist_id: String,
-    limit: Option<i32>,
-    start_index: Option<i32>,
+pub async fn resume_album(
+    album_id: String,
     state: State<'_, AppState>,
-) -> Result<MusicLibraryResult, String> {
+    app_handle: tauri::AppHandle,
+) -> Result<usize, CommandError> {
     let client_config = {
         let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
         client.get_config().cloned()
@@ -950,39 +4992,55 @@ pub async fn get_playlist_songs(
     let config = match client_config {
         Some(config) => config,
         None => {
-            return Ok(MusicLibraryResult {
-                success: false,
-                message: "Not authenticated".to_string(),
-                items: None,
-                total_count: None,
-            });
+            return Err(JellyfinError::NotAuthenticated.into());
         }
     };
 
     let mut client = JellyfinClient::new();
     client.set_config(config);
 
-    match client.get_playlist_songs(&playlist_id, limit, start_index).await {
-        Ok(response) => Ok(MusicLibraryResult {
-            success: true,
-            message: "Playlist songs retrieved successfully".to_string(),
-            items: Some(response.items),
-            total_count: Some(response.total_record_count),
-        }),
-        Err(e) => Ok(MusicLibraryResult {
-            success: false,
-            message: format!("Failed to get playlist songs: {}", e),
-            items: None,
-            total_count: None,
-        }),
+    let songs = client.get_album_songs(&album_id).await
+        .map_err(failed("Failed to get album songs"))?
+        .items;
+    if songs.is_empty() {
+        return Err("Album has no songs".into());
+    }
+
+    let start_index = songs
+        .iter()
+        .position(|song| {
+            song.user_data
+                .as_ref()
+                .and_then(|user_data| user_data.play_count)
+                .unwrap_or(0) == 0
+        })
+        .unwrap_or(0);
+
+    let streaming_profile = active_streaming_profile(&state)?;
+
+    let mut queue = Vec::with_capacity(songs.len());
+    for song in &songs {
+        let queue_item = stream_queue_item(&client, streaming_profile.as_ref(), song)?;
+        queue.push(queue_item);
     }
+
+    info!("Resuming album {} at track {} of {}", album_id, start_index + 1, queue.len());
+
+    let audio_player = {
+        let ap = state.audio_player.lock().map_err(|e| e.to_string())?;
+        ap.clone()
+    };
+    audio_player.set_queue(queue, start_index).await?;
+    start_queue_resolution(&state, &app_handle, audio_player)?;
+
+    Ok(start_index)
 }
 
+const DUPLICATE_SCAN_PAGE_SIZE: i32 = 500;
+
+// Read-only scan for songs that look imported twice: same name, album and runtime
 #[tauri::command]
-pub async fn get_item(
-    item_id: String,
-    state: State<'_, AppState>,
-) -> Result<ItemResult, String> {
+pub async fn find_duplicate_tracks(state: State<'_, AppState>) -> Result<Vec<DuplicateGroup>, CommandError> {
     let client_config = {
         let client = state.jellyfin_client.lock().map_err(|e| e.to_string())?;
         client.get_config().cloned()
@@ -991,35 +5049,60 @@ pub async fn get_item(
     let config = match client_config {
         Some(config) => config,
         None => {
-            return Ok(ItemResult {
-                success: false,
-                message: "Not authenticated".to_string(),
-                item: None,
-            });
+            return Err(JellyfinError::NotAuthenticated.into());
         }
     };
 
     let mut client = JellyfinClient::new();
     client.set_config(config);
 
-    match client.get_item(&item_id).await {
-        Ok(item) => Ok(ItemResult {
-            success: true,
-            message: "Item retrieved successfully".to_string(),
-            item: Some(item),
-        }),
-        Err(e) => Ok(ItemResult {
-            success: false,
-            message: format!("Failed to get item: {}", e),
-            item: None,
-        }),
+    // Only the grouping key and ids are kept per song, pages are dropped as we go
+    type DuplicateKey = (String, Option<String>, Option<i64>);
+    let mut groups: HashMap<DuplicateKey, DuplicateGroup> = HashMap::new();
+    let mut start_index = 0;
+    loop {
+        let page = client.get_songs(Some(DUPLICATE_SCAN_PAGE_SIZE), Some(start_index), "SortName", "Ascending").await
+            .map_err(failed("Failed to get songs"))?;
+
+        let page_len = page.items.len() as i32;
+        for item in page.items {
+            let key = (
+                item.name.trim().to_lowercase(),
+                item.album.as_ref().map(|album| album.trim().to_lowercase()),
+                item.runtime_ticks,
+            );
+            groups
+                .entry(key)
+                .or_insert_with(|| DuplicateGroup {
+                    name: item.name.clone(),
+                    album: item.album.clone(),
+                    runtime_ticks: item.runtime_ticks,
+                    item_ids: Vec::new(),
+                })
+                .item_ids
+                .push(item.id);
+        }
+
+        start_index += page_len;
+        if page_len == 0 || start_index >= page.total_record_count {
+            break;
+        }
     }
-} 
+
+    let mut duplicates: Vec<DuplicateGroup> = groups
+        .into_values()
+        .filter(|group| group.item_ids.len() > 1)
+        .collect();
+    duplicates.sort_by(|a, b| a.name.cmp(&b.name));
+
+    info!("Scanned {} songs, found {} duplicate groups", start_index, duplicates.len());
+    Ok(duplicates)
+}
 
 use std::process::Command;
 
 #[tauri::command]
-pub async fn open_link(url: String) -> Result<(), String> {
+pub async fn open_link(url: String) -> Result<(), CommandError> {
     #[cfg(target_os = "windows")]
     {
         use std::os::windows::process::CommandExt;
@@ -1056,7 +5139,7 @@ pub async fn open_link(url: String) -> Result<(), String> {
     // This part might need more refinement depending on your target platforms.
     #[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
     {
-        eprintln!("Warning: open_link not explicitly supported on this OS.");
+        warn!("open_link not explicitly supported on this OS.");
         // You might want to return an error or try a very generic command
         // that might not work everywhere.
         return Err("Unsupported operating system for opening links.".to_string());
@@ -0,0 +1,211 @@
+use crate::audio_player::{PlayerEvent, QueueItem};
+use crate::commands::AppState;
+use discord_rich_presence::{activity, DiscordIpc, DiscordIpcClient};
+use std::sync::mpsc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::Manager;
+use tokio::sync::broadcast::error::RecvError;
+use log::{info, warn};
+
+// Discord application the presence is shown under, supplied at build time
+const DISCORD_CLIENT_ID: Option<&str> = option_env!("DISCORD_CLIENT_ID");
+// Art asset uploaded to the Discord application, shown next to the song
+const LARGE_IMAGE_KEY: &str = "logo";
+// How long to wait before looking for Discord again after it wasn't running
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(15);
+// Position drift beyond this (a seek) moves the elapsed/remaining timestamps
+const SEEK_TOLERANCE_SECS: f64 = 2.0;
+
+pub enum PresenceMessage {
+    Enabled(bool),
+    Playback { song: Option<Box<QueueItem>>, is_playing: bool, position: f64 },
+}
+
+// What Discord is currently showing, to skip sending the same activity again
+struct ShownActivity {
+    song_id: String,
+    is_playing: bool,
+    started_at: f64, // unix seconds the song would have started at without pauses
+}
+
+struct Presence {
+    enabled: bool,
+    song: Option<QueueItem>,
+    is_playing: bool,
+    started_at: f64,
+    client_id: &'static str,
+    client: Option<DiscordIpcClient>,
+    last_connect_attempt: Option<Instant>,
+    shown: Option<ShownActivity>,
+}
+
+impl Presence {
+    fn apply(&mut self, message: PresenceMessage) {
+        match message {
+            PresenceMessage::Enabled(enabled) => self.enabled = enabled,
+            PresenceMessage::Playback { song, is_playing, position } => {
+                self.song = song.map(|song| *song);
+                self.is_playing = is_playing;
+                self.started_at = unix_now_secs() - position;
+            }
+        }
+    }
+
+    // Bring Discord in line with the player, connecting (at most every RECONNECT_INTERVAL) when needed
+    fn sync(&mut self) {
+        if !self.enabled {
+            if let Some(mut client) = self.client.take() {
+                let _ = client.clear_activity();
+                let _ = client.close();
+            }
+            self.shown = None;
+            return;
+        }
+
+        let Some(song) = self.song.clone() else {
+            if self.shown.take().is_some() {
+                if let Some(client) = self.client.as_mut() {
+                    let _ = client.clear_activity();
+                }
+            }
+            return;
+        };
+
+        let wanted = ShownActivity {
+            song_id: song.id.clone(),
+            is_playing: self.is_playing,
+            started_at: self.started_at,
+        };
+        if self.shown.as_ref().is_some_and(|shown| {
+            shown.song_id == wanted.song_id
+                && shown.is_playing == wanted.is_playing
+                && (!wanted.is_playing || (shown.started_at - wanted.started_at).abs() < SEEK_TOLERANCE_SECS)
+        }) {
+            return;
+        }
+
+        if self.client.is_none() && !self.connect() {
+            return;
+        }
+        let Some(client) = self.client.as_mut() else {
+            return;
+        };
+
+        let details = format!("{} — {}", song.name, song.artists.join(", "));
+        let album = song.album.clone().unwrap_or_default();
+        let mut assets = activity::Assets::new().large_image(LARGE_IMAGE_KEY);
+        if !album.is_empty() {
+            assets = assets.large_text(&album);
+        }
+        let mut presence = activity::Activity::new().details(&details).assets(assets);
+        if self.is_playing {
+            let mut timestamps = activity::Timestamps::new().start(self.started_at as i64);
+            if let Some(ticks) = song.duration_ticks {
+                timestamps = timestamps.end((self.started_at + ticks as f64 / 10_000_000.0) as i64);
+            }
+            presence = presence.timestamps(timestamps);
+        } else {
+            presence = presence.state("Paused");
+        }
+
+        match client.set_activity(presence) {
+            Ok(()) => self.shown = Some(wanted),
+            Err(e) => {
+                // Discord was closed; try again once it's back
+                warn!("Discord presence lost: {}", e);
+                self.client = None;
+                self.shown = None;
+            }
+        }
+    }
+
+    fn connect(&mut self) -> bool {
+        if self.last_connect_attempt.is_some_and(|attempt| attempt.elapsed() < RECONNECT_INTERVAL) {
+            return false;
+        }
+        self.last_connect_attempt = Some(Instant::now());
+
+        // Not running Discord is normal, so failures here stay quiet
+        let Ok(mut client) = DiscordIpcClient::new(self.client_id) else {
+            return false;
+        };
+        if client.connect().is_err() {
+            return false;
+        }
+        info!("Connected to Discord");
+        self.client = Some(client);
+        true
+    }
+}
+
+// Mirror the playing song into Discord Rich Presence while enabled. The IPC client blocks,
+// so it lives on its own thread fed by a task that follows the player's events
+pub fn spawn_discord_presence(app_handle: tauri::AppHandle, enabled: bool) {
+    let Some(client_id) = DISCORD_CLIENT_ID else {
+        return;
+    };
+    let (sender, receiver) = mpsc::channel();
+    let mut events = {
+        let state = app_handle.state::<AppState>();
+        let Ok(audio_player) = state.audio_player.lock() else {
+            return;
+        };
+        let Ok(mut presence_sender) = state.discord_presence.lock() else {
+            return;
+        };
+        *presence_sender = Some(sender.clone());
+        audio_player.subscribe_to_events()
+    };
+
+    std::thread::spawn(move || {
+        let mut presence = Presence {
+            enabled,
+            song: None,
+            is_playing: false,
+            started_at: 0.0,
+            client_id,
+            client: None,
+            last_connect_attempt: None,
+            shown: None,
+        };
+        loop {
+            match receiver.recv_timeout(RECONNECT_INTERVAL) {
+                Ok(message) => {
+                    presence.apply(message);
+                    // Take everything already waiting so a burst of events sends one update
+                    while let Ok(message) = receiver.try_recv() {
+                        presence.apply(message);
+                    }
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            }
+            presence.sync();
+        }
+    });
+
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let state = match events.recv().await {
+                Ok(PlayerEvent::StateChanged(state)) => state,
+                Ok(_) | Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            };
+            let message = PresenceMessage::Playback {
+                song: state.current_song.map(Box::new),
+                is_playing: state.is_playing,
+                position: state.current_position,
+            };
+            if sender.send(message).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+fn unix_now_secs() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs_f64())
+        .unwrap_or(0.0)
+}
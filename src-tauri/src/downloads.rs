@@ -0,0 +1,376 @@
+use crate::commands::AppState;
+use futures_util::StreamExt;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::{Emitter, Manager};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+use log::{info, warn};
+
+// Completed downloads, kept next to the files so the folder can be moved as a whole
+const INDEX_FILE: &str = "downloads.json";
+// Progress is only broadcast when it has moved by at least this many percentage points
+const PROGRESS_STEP_PERCENT: f32 = 1.0;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum DownloadStatus {
+    Queued,
+    Downloading { progress: Option<f32> }, // percent, None while the size is unknown
+    Done,
+    Failed { message: String },
+}
+
+// Where one song's download stands, as listed by list_downloads and sent in "download-state" events
+#[derive(Debug, Clone, Serialize)]
+pub struct DownloadState {
+    pub item_id: String,
+    pub name: String,
+    pub artists: Vec<String>,
+    pub album: Option<String>,
+    pub status: DownloadStatus,
+    pub size_bytes: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct DownloadedSong {
+    name: String,
+    artists: Vec<String>,
+    album: Option<String>,
+    file_name: String,
+    size_bytes: u64,
+    downloaded_at: u64, // unix seconds
+}
+
+// A song waiting for the download worker
+#[derive(Debug, Clone)]
+pub struct DownloadJob {
+    pub item_id: String,
+    pub name: String,
+    pub artists: Vec<String>,
+    pub album: Option<String>,
+    pub url: String,
+}
+
+// Songs explicitly downloaded for offline listening. They live in the app data directory,
+// apart from the audio cache, so cache limits and clearing never touch them
+pub struct DownloadManager {
+    dir: PathBuf,
+    downloaded: HashMap<String, DownloadedSong>,
+    // Queued, running and failed downloads; removing an entry cancels it
+    pending: HashMap<String, DownloadState>,
+    client: Client,
+}
+
+impl DownloadManager {
+    pub fn new(dir: PathBuf) -> Result<Self, Box<dyn std::error::Error>> {
+        fs::create_dir_all(&dir)?;
+        let mut manager = Self {
+            dir,
+            downloaded: HashMap::new(),
+            pending: HashMap::new(),
+            client: Self::build_client(&HashMap::new())?,
+        };
+        manager.load_index();
+        Ok(manager)
+    }
+
+    fn build_client(custom_headers: &HashMap<String, String>) -> Result<Client, Box<dyn std::error::Error>> {
+        Ok(Client::builder()
+            .timeout(std::time::Duration::from_secs(600)) // whole albums of lossless files take a while
+            .default_headers(crate::jellyfin::custom_header_map(custom_headers))
+            .build()?)
+    }
+
+    // Downloads need the same proxy headers as API requests
+    pub fn set_custom_headers(&mut self, custom_headers: &HashMap<String, String>) -> Result<(), Box<dyn std::error::Error>> {
+        self.client = Self::build_client(custom_headers)?;
+        Ok(())
+    }
+
+    fn index_file(&self) -> PathBuf {
+        self.dir.join(INDEX_FILE)
+    }
+
+    fn load_index(&mut self) {
+        let Ok(contents) = fs::read_to_string(self.index_file()) else {
+            return;
+        };
+        if let Ok(downloaded) = serde_json::from_str::<HashMap<String, DownloadedSong>>(&contents) {
+            // Files deleted by hand are forgotten rather than offered for playback
+            self.downloaded = downloaded
+                .into_iter()
+                .filter(|(_, song)| self.dir.join(&song.file_name).exists())
+                .collect();
+        }
+        info!("Loaded {} offline downloads", self.downloaded.len());
+    }
+
+    fn save_index(&self) {
+        match serde_json::to_string(&self.downloaded) {
+            Ok(contents) => {
+                if let Err(e) = fs::write(self.index_file(), contents) {
+                    warn!("Failed to save download index: {}", e);
+                }
+            }
+            Err(e) => warn!("Failed to serialize download index: {}", e),
+        }
+    }
+
+    // The downloaded file for a song, if it's still on disk
+    pub fn downloaded_path(&self, item_id: &str) -> Option<PathBuf> {
+        let song = self.downloaded.get(item_id)?;
+        let path = self.dir.join(&song.file_name);
+        path.exists().then_some(path)
+    }
+
+    // Mark a song as queued, returning its state; None when it's already downloaded or on its way
+    pub fn enqueue(&mut self, job: &DownloadJob) -> Option<DownloadState> {
+        if self.downloaded_path(&job.item_id).is_some() {
+            return None;
+        }
+        if self.pending.get(&job.item_id).is_some_and(|state| !matches!(state.status, DownloadStatus::Failed { .. })) {
+            return None;
+        }
+
+        let state = DownloadState {
+            item_id: job.item_id.clone(),
+            name: job.name.clone(),
+            artists: job.artists.clone(),
+            album: job.album.clone(),
+            status: DownloadStatus::Queued,
+            size_bytes: None,
+        };
+        self.pending.insert(job.item_id.clone(), state.clone());
+        Some(state)
+    }
+
+    // Update a pending download, returning the new state; None if it was cancelled meanwhile
+    fn set_status(&mut self, item_id: &str, status: DownloadStatus) -> Option<DownloadState> {
+        let state = self.pending.get_mut(item_id)?;
+        state.status = status;
+        Some(state.clone())
+    }
+
+    fn is_pending(&self, item_id: &str) -> bool {
+        self.pending.contains_key(item_id)
+    }
+
+    fn complete(&mut self, job: &DownloadJob, file_name: String, size_bytes: u64) -> DownloadState {
+        self.pending.remove(&job.item_id);
+        self.downloaded.insert(job.item_id.clone(), DownloadedSong {
+            name: job.name.clone(),
+            artists: job.artists.clone(),
+            album: job.album.clone(),
+            file_name,
+            size_bytes,
+            downloaded_at: unix_now_secs(),
+        });
+        self.save_index();
+
+        DownloadState {
+            item_id: job.item_id.clone(),
+            name: job.name.clone(),
+            artists: job.artists.clone(),
+            album: job.album.clone(),
+            status: DownloadStatus::Done,
+            size_bytes: Some(size_bytes),
+        }
+    }
+
+    // Every finished download, newest first, after anything queued, running or failed
+    pub fn list(&self) -> Vec<DownloadState> {
+        let mut downloaded: Vec<(&String, &DownloadedSong)> = self.downloaded.iter().collect();
+        downloaded.sort_by_key(|(_, song)| std::cmp::Reverse(song.downloaded_at));
+
+        self.pending
+            .values()
+            .cloned()
+            .chain(downloaded.into_iter().map(|(item_id, song)| DownloadState {
+                item_id: item_id.clone(),
+                name: song.name.clone(),
+                artists: song.artists.clone(),
+                album: song.album.clone(),
+                status: DownloadStatus::Done,
+                size_bytes: Some(song.size_bytes),
+            }))
+            .collect()
+    }
+
+    // Delete a downloaded song, or cancel it if it hasn't finished; false if there was nothing to remove
+    pub fn delete(&mut self, item_id: &str) -> bool {
+        let cancelled = self.pending.remove(item_id).is_some();
+        let Some(song) = self.downloaded.remove(item_id) else {
+            return cancelled;
+        };
+
+        if let Err(e) = fs::remove_file(self.dir.join(&song.file_name)) {
+            warn!("Failed to delete download {}: {}", song.file_name, e);
+        }
+        self.save_index();
+        true
+    }
+}
+
+// Open the downloads folder and start the worker that works through queued downloads one at a time
+pub fn spawn_download_worker(app_handle: tauri::AppHandle) {
+    let dir = match app_handle.path().app_data_dir() {
+        Ok(dir) => dir.join("downloads"),
+        Err(e) => {
+            warn!("Offline downloads unavailable: {}", e);
+            return;
+        }
+    };
+    let manager = match DownloadManager::new(dir) {
+        Ok(manager) => manager,
+        Err(e) => {
+            warn!("Offline downloads unavailable: {}", e);
+            return;
+        }
+    };
+
+    let (sender, mut receiver) = mpsc::unbounded_channel::<DownloadJob>();
+    {
+        let state = app_handle.state::<AppState>();
+        let Ok(mut downloads) = state.downloads.lock() else {
+            return;
+        };
+        let Ok(mut download_queue) = state.download_queue.lock() else {
+            return;
+        };
+        *downloads = Some(manager);
+        *download_queue = Some(sender);
+    }
+
+    tauri::async_runtime::spawn(async move {
+        while let Some(job) = receiver.recv().await {
+            run_download(&app_handle, job).await;
+        }
+    });
+}
+
+async fn run_download(app_handle: &tauri::AppHandle, job: DownloadJob) {
+    let state = app_handle.state::<AppState>();
+    let started = with_manager(app_handle, |manager| {
+        let status = manager.set_status(&job.item_id, DownloadStatus::Downloading { progress: Some(0.0) })?;
+        Some((status, manager.dir.clone(), manager.client.clone()))
+    });
+    // Cancelled while it was queued
+    let Some((status, dir, client)) = started.flatten() else {
+        return;
+    };
+    emit_state(app_handle, &status);
+    info!("Downloading {} for offline use", job.name);
+
+    let file_name = format!("{}.audio", job.item_id);
+    let part_path = dir.join(format!("{}.part", job.item_id));
+    let result = fetch_to_file(app_handle, &client, &job, &part_path).await;
+
+    let finished = match result {
+        Ok(size_bytes) => match tokio::fs::rename(&part_path, dir.join(&file_name)).await {
+            Ok(()) => Ok(size_bytes),
+            Err(e) => Err(format!("Failed to save download: {}", e)),
+        },
+        Err(e) => Err(e),
+    };
+    if finished.is_err() {
+        let _ = tokio::fs::remove_file(&part_path).await;
+    }
+
+    let Ok(mut downloads) = state.downloads.lock() else {
+        return;
+    };
+    let Some(manager) = downloads.as_mut() else {
+        return;
+    };
+    let status = match finished {
+        Ok(size_bytes) => {
+            info!("Downloaded {} ({} bytes)", job.name, size_bytes);
+            Some(manager.complete(&job, file_name, size_bytes))
+        }
+        Err(e) => {
+            warn!("Download of {} failed: {}", job.name, e);
+            manager.set_status(&job.item_id, DownloadStatus::Failed { message: e })
+        }
+    };
+    drop(downloads);
+    if let Some(status) = status {
+        emit_state(app_handle, &status);
+    }
+}
+
+// Stream a download to disk, reporting progress; stops early if the download is cancelled
+async fn fetch_to_file(app_handle: &tauri::AppHandle, client: &Client, job: &DownloadJob, path: &Path) -> Result<u64, String> {
+    let response = client.get(&job.url).send().await
+        .map_err(|e| format!("Download request failed: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("Server returned error {}", response.status()));
+    }
+
+    let total_bytes = response.content_length();
+    let mut file = tokio::fs::File::create(path).await
+        .map_err(|e| format!("Failed to create file: {}", e))?;
+    let mut stream = response.bytes_stream();
+    let mut downloaded: u64 = 0;
+    let mut last_progress = 0.0;
+
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| format!("Download interrupted: {}", e))?;
+        file.write_all(&chunk).await
+            .map_err(|e| format!("Failed to write file: {}", e))?;
+        downloaded += chunk.len() as u64;
+
+        let progress = total_bytes.map(|total| (downloaded as f32 / total.max(1) as f32 * 100.0).min(100.0));
+        if progress.is_some_and(|progress| progress - last_progress < PROGRESS_STEP_PERCENT) {
+            continue;
+        }
+        last_progress = progress.unwrap_or(0.0);
+
+        let status = with_manager(app_handle, |manager| {
+            manager.set_status(&job.item_id, DownloadStatus::Downloading { progress })
+        });
+        match status.flatten() {
+            Some(status) => emit_state(app_handle, &status),
+            None => return Err("Download cancelled".to_string()),
+        }
+    }
+    file.flush().await.map_err(|e| format!("Failed to write file: {}", e))?;
+
+    if let Some(expected) = total_bytes {
+        if downloaded != expected {
+            return Err(format!("Incomplete download: got {} of {} bytes", downloaded, expected));
+        }
+    }
+    // Deleted in the moments after the last chunk
+    if !with_manager(app_handle, |manager| manager.is_pending(&job.item_id)).unwrap_or(false) {
+        return Err("Download cancelled".to_string());
+    }
+    Ok(downloaded)
+}
+
+// Run f against the download manager; None when downloads aren't available
+fn with_manager<T>(app_handle: &tauri::AppHandle, f: impl FnOnce(&mut DownloadManager) -> T) -> Option<T> {
+    let state = app_handle.state::<AppState>();
+    let mut downloads = state.downloads.lock().ok()?;
+    downloads.as_mut().map(f)
+}
+
+// The offline copy of a song, preferred over both the stream and the audio cache
+pub fn downloaded_path(app_handle: &tauri::AppHandle, item_id: &str) -> Option<PathBuf> {
+    with_manager(app_handle, |manager| manager.downloaded_path(item_id)).flatten()
+}
+
+pub fn emit_state(app_handle: &tauri::AppHandle, state: &DownloadState) {
+    let _ = app_handle.emit("download-state", state);
+}
+
+fn unix_now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+}
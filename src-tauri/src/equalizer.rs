@@ -0,0 +1,177 @@
+use serde::Serialize;
+use std::f32::consts::PI;
+
+// Centre frequencies of the graphic equalizer's bands, an octave apart
+pub const EQ_BAND_FREQUENCIES: [f32; EQ_BAND_COUNT] = [
+    31.0, 62.0, 125.0, 250.0, 500.0, 1000.0, 2000.0, 4000.0, 8000.0, 16000.0,
+];
+pub const EQ_BAND_COUNT: usize = 10;
+// Largest boost or cut per band, in dB
+pub const MAX_BAND_GAIN_DB: f32 = 12.0;
+// Bandwidth of roughly one octave, so neighbouring bands overlap smoothly
+const BAND_Q: f32 = 1.41;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EqualizerPreset {
+    pub name: String,
+    pub bands: Vec<f32>, // dB per band, lowest frequency first
+}
+
+pub fn presets() -> Vec<EqualizerPreset> {
+    let preset = |name: &str, bands: [f32; EQ_BAND_COUNT]| EqualizerPreset {
+        name: name.to_string(),
+        bands: bands.to_vec(),
+    };
+    vec![
+        preset("Flat", [0.0; EQ_BAND_COUNT]),
+        preset("Bass Boost", [6.0, 5.0, 4.0, 2.5, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0]),
+        preset("Vocal", [-2.0, -2.0, -1.0, 1.0, 3.0, 4.0, 3.5, 2.0, 0.0, -1.0]),
+        preset("Treble", [0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 2.5, 4.0, 5.0, 6.0]),
+    ]
+}
+
+// Peaking filter coefficients from the RBJ audio EQ cookbook, normalized so a0 is 1
+#[derive(Debug, Clone, Copy)]
+struct BiquadCoefficients {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+}
+
+impl BiquadCoefficients {
+    fn peaking(frequency: f32, gain_db: f32, q: f32, sample_rate: f32) -> Self {
+        let amplitude = 10f32.powf(gain_db / 40.0);
+        let omega = 2.0 * PI * frequency / sample_rate;
+        let alpha = omega.sin() / (2.0 * q);
+        let cos_omega = omega.cos();
+
+        let a0 = 1.0 + alpha / amplitude;
+        BiquadCoefficients {
+            b0: (1.0 + alpha * amplitude) / a0,
+            b1: (-2.0 * cos_omega) / a0,
+            b2: (1.0 - alpha * amplitude) / a0,
+            a1: (-2.0 * cos_omega) / a0,
+            a2: (1.0 - alpha / amplitude) / a0,
+        }
+    }
+}
+
+// Direct form I history of one filter on one channel
+#[derive(Debug, Clone, Copy, Default)]
+struct BiquadState {
+    x1: f32,
+    x2: f32,
+    y1: f32,
+    y2: f32,
+}
+
+impl BiquadState {
+    fn process(&mut self, c: &BiquadCoefficients, x: f32) -> f32 {
+        let y = c.b0 * x + c.b1 * self.x1 + c.b2 * self.x2 - c.a1 * self.y1 - c.a2 * self.y2;
+        self.x2 = self.x1;
+        self.x1 = x;
+        self.y2 = self.y1;
+        self.y1 = y;
+        y
+    }
+}
+
+// Chain of peaking filters over an interleaved stream, one filter history per channel.
+// Bands left at 0 dB (or above Nyquist) are skipped, so a flat EQ costs nothing
+pub struct Equalizer {
+    filters: Vec<BiquadCoefficients>,
+    states: Vec<Vec<BiquadState>>,
+    boosted: bool,
+}
+
+impl Equalizer {
+    pub fn new(gains: &[f32], sample_rate: u32, channels: u16) -> Self {
+        let sample_rate = sample_rate.max(1) as f32;
+        let filters: Vec<BiquadCoefficients> = EQ_BAND_FREQUENCIES
+            .iter()
+            .zip(gains)
+            .filter(|(frequency, gain)| **gain != 0.0 && **frequency < sample_rate / 2.0)
+            .map(|(frequency, gain)| BiquadCoefficients::peaking(*frequency, *gain, BAND_Q, sample_rate))
+            .collect();
+
+        Equalizer {
+            states: vec![vec![BiquadState::default(); filters.len()]; channels.max(1) as usize],
+            filters,
+            boosted: gains.iter().any(|gain| *gain > 0.0),
+        }
+    }
+
+    pub fn is_flat(&self) -> bool {
+        self.filters.is_empty()
+    }
+
+    // Whether any band boosts, and so may push samples past full scale
+    pub fn is_boosted(&self) -> bool {
+        self.boosted
+    }
+
+    pub fn process(&mut self, sample: f32, channel: usize) -> f32 {
+        let Some(states) = self.states.get_mut(channel) else {
+            return sample;
+        };
+        self.filters
+            .iter()
+            .zip(states.iter_mut())
+            .fold(sample, |sample, (coefficients, state)| state.process(coefficients, sample))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Magnitude of the filter's response at frequency, in dB
+    fn response_db(c: &BiquadCoefficients, frequency: f32, sample_rate: f32) -> f32 {
+        let omega = 2.0 * PI * frequency / sample_rate;
+        // z^-1 = e^(-j omega)
+        let (re1, im1) = (omega.cos(), -omega.sin());
+        let (re2, im2) = ((2.0 * omega).cos(), -(2.0 * omega).sin());
+        let numerator = (c.b0 + c.b1 * re1 + c.b2 * re2, c.b1 * im1 + c.b2 * im2);
+        let denominator = (1.0 + c.a1 * re1 + c.a2 * re2, c.a1 * im1 + c.a2 * im2);
+        let magnitude = numerator.0.hypot(numerator.1) / denominator.0.hypot(denominator.1);
+        20.0 * magnitude.log10()
+    }
+
+    #[test]
+    fn a_flat_band_has_identity_coefficients() {
+        for frequency in EQ_BAND_FREQUENCIES {
+            let c = BiquadCoefficients::peaking(frequency, 0.0, BAND_Q, 44100.0);
+            assert!((c.b0 - 1.0).abs() < 1e-6);
+            assert!((c.b1 - c.a1).abs() < 1e-6);
+            assert!((c.b2 - c.a2).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn a_flat_equalizer_passes_samples_through() {
+        let mut equalizer = Equalizer::new(&[0.0; EQ_BAND_COUNT], 44100, 2);
+        assert!(equalizer.is_flat());
+        for sample in [0.0, 0.5, -1.0, 0.25] {
+            assert_eq!(equalizer.process(sample, 0), sample);
+            assert_eq!(equalizer.process(sample, 1), sample);
+        }
+    }
+
+    #[test]
+    fn peaking_bands_apply_their_gain_at_the_centre_frequency() {
+        for sample_rate in [44100.0, 48000.0] {
+            for frequency in EQ_BAND_FREQUENCIES {
+                for gain_db in [-MAX_BAND_GAIN_DB, -3.0, 6.0, MAX_BAND_GAIN_DB] {
+                    let c = BiquadCoefficients::peaking(frequency, gain_db, BAND_Q, sample_rate);
+                    let response = response_db(&c, frequency, sample_rate);
+                    assert!(
+                        (response - gain_db).abs() < 0.05,
+                        "{} Hz at {} dB gave {} dB", frequency, gain_db, response
+                    );
+                }
+            }
+        }
+    }
+}
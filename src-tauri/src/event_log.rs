@@ -0,0 +1,54 @@
+use crate::audio_player::{AudioPlayer, PlayerEvent};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast::error::RecvError;
+use log::{info, warn};
+
+// Rotate once the log grows past this, keeping a single previous file as .log.1
+const MAX_LOG_BYTES: u64 = 1024 * 1024;
+
+pub fn event_log_path(log_dir: &Path) -> PathBuf {
+    log_dir.join("player-events.log")
+}
+
+// Append every player event to the log until the player shuts down or the task is aborted
+pub fn spawn_event_logger(audio_player: &AudioPlayer, log_path: PathBuf) -> tauri::async_runtime::JoinHandle<()> {
+    let mut receiver = audio_player.subscribe_to_events();
+
+    tauri::async_runtime::spawn(async move {
+        info!("Logging player events to {}", log_path.display());
+        loop {
+            let line = match receiver.recv().await {
+                // Position ticks arrive several times a second and say nothing about failures
+                Ok(PlayerEvent::PositionUpdate(_)) => continue,
+                Ok(event) => format!("{:?}", event),
+                Err(RecvError::Lagged(skipped)) => format!("Lagged: {} events skipped", skipped),
+                Err(RecvError::Closed) => break,
+            };
+
+            if let Err(e) = append_line(&log_path, &line) {
+                warn!("Failed to write event log: {}", e);
+            }
+        }
+    })
+}
+
+fn append_line(log_path: &Path, line: &str) -> std::io::Result<()> {
+    if let Some(parent) = log_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    if fs::metadata(log_path).map(|meta| meta.len() >= MAX_LOG_BYTES).unwrap_or(false) {
+        fs::rename(log_path, log_path.with_extension("log.1"))?;
+    }
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+
+    let mut file = OpenOptions::new().create(true).append(true).open(log_path)?;
+    writeln!(file, "[{}] {}", timestamp, line)
+}
@@ -0,0 +1,132 @@
+use crate::audio_player::QueueItem;
+use crate::commands::AppState;
+use crate::jellyfin::JellyfinClient;
+use log::warn;
+use std::collections::{HashMap, VecDeque};
+use tauri::Manager;
+
+// Memory budget for fetched images, counted in data URL bytes
+const MAX_IMAGE_CACHE_BYTES: usize = 64 * 1024 * 1024;
+
+// Images fetched through the backend as data URLs, so the webview never sees a tokened URL
+// and scrolling back over art doesn't download it again. Least recently used goes first
+pub struct ImageCache {
+    entries: HashMap<String, String>,
+    order: VecDeque<String>,
+    total_bytes: usize,
+}
+
+impl ImageCache {
+    pub fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+            total_bytes: 0,
+        }
+    }
+
+    pub fn key(item_id: &str, image_type: &str, max_width: Option<u32>) -> String {
+        match max_width {
+            Some(max_width) => format!("{}:{}:{}", item_id, image_type, max_width),
+            None => format!("{}:{}:full", item_id, image_type),
+        }
+    }
+
+    pub fn get(&mut self, key: &str) -> Option<String> {
+        let data_url = self.entries.get(key)?.clone();
+        self.touch(key);
+        Some(data_url)
+    }
+
+    pub fn insert(&mut self, key: String, data_url: String) {
+        // Never worth evicting everything else for
+        if data_url.len() > MAX_IMAGE_CACHE_BYTES / 4 {
+            return;
+        }
+
+        self.total_bytes += data_url.len();
+        if let Some(previous) = self.entries.insert(key.clone(), data_url) {
+            self.total_bytes -= previous.len();
+        }
+        self.touch(&key);
+
+        while self.total_bytes > MAX_IMAGE_CACHE_BYTES {
+            let Some(oldest) = self.order.pop_front() else {
+                break;
+            };
+            if let Some(evicted) = self.entries.remove(&oldest) {
+                self.total_bytes -= evicted.len();
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+        self.total_bytes = 0;
+    }
+
+    fn touch(&mut self, key: &str) {
+        self.order.retain(|existing| existing != key);
+        self.order.push_back(key.to_string());
+    }
+}
+
+// Width art is saved at for the OS media controls
+const ART_FILE_MAX_WIDTH: u32 = 512;
+
+// A song's cover art saved under the app cache dir, as a file:// URL for the OS media controls.
+// Handing them a server URL would publish the access token to every process that can read them
+pub async fn local_art_url(app_handle: &tauri::AppHandle, song: &QueueItem) -> Option<String> {
+    let image_item = song.album_id.as_deref().unwrap_or(&song.id);
+    let dir = app_handle.path().app_cache_dir().ok()?.join("art");
+
+    let existing = ART_FILE_EXTENSIONS
+        .iter()
+        .map(|(_, extension)| dir.join(format!("{}.{}", image_item, extension)))
+        .find(|path| path.exists());
+    let path = match existing {
+        Some(path) => path,
+        None => {
+            let (mime_type, data) = fetch_art(app_handle, image_item, &song.id).await?;
+            let extension = ART_FILE_EXTENSIONS
+                .iter()
+                .find(|(known, _)| *known == mime_type)
+                .map(|(_, extension)| *extension)
+                .unwrap_or("jpg");
+            let path = dir.join(format!("{}.{}", image_item, extension));
+            if let Err(e) = write_art(&path, &data).await {
+                warn!("Failed to save cover art: {}", e);
+                return None;
+            }
+            path
+        }
+    };
+    tauri::Url::from_file_path(&path).ok().map(|url| url.to_string())
+}
+
+const ART_FILE_EXTENSIONS: [(&str, &str); 3] = [("image/jpeg", "jpg"), ("image/png", "png"), ("image/webp", "webp")];
+
+// From the server, or offline from the art embedded in the cached audio file
+async fn fetch_art(app_handle: &tauri::AppHandle, image_item: &str, song_id: &str) -> Option<(String, Vec<u8>)> {
+    let state = app_handle.state::<AppState>();
+    let config = state.jellyfin_client.lock().ok()?.get_config().cloned();
+    if let Some(config) = config {
+        let mut client = JellyfinClient::new();
+        client.set_config(config);
+        match client.get_image_bytes(image_item, "Primary", Some(ART_FILE_MAX_WIDTH)).await {
+            Ok(art) => return Some(art),
+            Err(e) => warn!("Cover art fetch failed for {}, trying cached art: {}", image_item, e),
+        }
+    }
+
+    let cache = state.audio_cache.lock().await;
+    cache.get_cover_art(song_id)
+}
+
+async fn write_art(path: &std::path::Path, data: &[u8]) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+    tokio::fs::write(path, data).await
+}
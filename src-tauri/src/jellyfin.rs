@@ -1,8 +1,12 @@
 use reqwest::Client;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::{SystemTime, UNIX_EPOCH};
+use log::{debug, info, warn};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JellyfinConfig {
@@ -11,6 +15,74 @@ pub struct JellyfinConfig {
     pub user_id: String,
     pub access_token: String,
     pub device_id: String,
+    // Extra headers sent on every request, e.g. for Cloudflare Access or Authelia
+    #[serde(default)]
+    pub custom_headers: HashMap<String, String>,
+    // Shared by every copy of this session's config; never written to disk
+    #[serde(skip)]
+    pub renewal: Option<Arc<SessionRenewal>>,
+}
+
+impl JellyfinConfig {
+    // The token to send: a renewed one once the session has been logged in again
+    pub fn current_token(&self) -> String {
+        self.renewal
+            .as_ref()
+            .and_then(|renewal| renewal.renewed_token.lock().ok().and_then(|token| token.clone()))
+            .unwrap_or_else(|| self.access_token.clone())
+    }
+}
+
+pub enum SessionEvent {
+    // Logged in again with the remembered password; carries the new token
+    Renewed(String),
+    // The token was rejected and couldn't be renewed
+    Expired,
+}
+
+// Lets a session recover when the server rejects its token mid-session. The password is
+// only known for logins made this session, so restored sessions can just report expiry
+pub struct SessionRenewal {
+    password: Option<String>,
+    renewed_token: Mutex<Option<String>>,
+    // Held while logging in again, so requests rejected together renew the session only once
+    renewing: tokio::sync::Mutex<()>,
+    on_event: Box<dyn Fn(SessionEvent) + Send + Sync>,
+}
+
+impl SessionRenewal {
+    pub fn new(password: Option<String>, on_event: impl Fn(SessionEvent) + Send + Sync + 'static) -> Self {
+        SessionRenewal {
+            password,
+            renewed_token: Mutex::new(None),
+            renewing: tokio::sync::Mutex::new(()),
+            on_event: Box::new(on_event),
+        }
+    }
+}
+
+impl std::fmt::Debug for SessionRenewal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SessionRenewal")
+            .field("can_reauthenticate", &self.password.is_some())
+            .finish()
+    }
+}
+
+// Convert configured custom headers into a HeaderMap, skipping any that aren't valid HTTP
+pub fn custom_header_map(headers: &HashMap<String, String>) -> reqwest::header::HeaderMap {
+    use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+
+    let mut header_map = HeaderMap::new();
+    for (name, value) in headers {
+        match (HeaderName::from_bytes(name.as_bytes()), HeaderValue::from_str(value)) {
+            (Ok(name), Ok(value)) => {
+                header_map.insert(name, value);
+            }
+            _ => warn!("Ignoring invalid custom header: {}", name),
+        }
+    }
+    header_map
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -22,6 +94,118 @@ pub struct ServerInfo {
     pub id: String,
 }
 
+// A server maintenance task, e.g. the library scan (admin only)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ScheduledTask {
+    #[serde(rename = "Id")]
+    pub id: String,
+    #[serde(rename = "Name")]
+    pub name: String,
+    #[serde(rename = "State")]
+    pub state: String, // Idle, Running or Cancelling
+    #[serde(rename = "Category", default)]
+    pub category: Option<String>,
+    #[serde(rename = "Key", default)]
+    pub key: Option<String>,
+    #[serde(rename = "CurrentProgressPercentage", default)]
+    pub current_progress_percentage: Option<f64>,
+    #[serde(rename = "LastExecutionResult", default)]
+    pub last_execution_result: Option<TaskResult>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TaskResult {
+    #[serde(rename = "StartTimeUtc", default)]
+    pub start_time_utc: Option<String>,
+    #[serde(rename = "EndTimeUtc", default)]
+    pub end_time_utc: Option<String>,
+    #[serde(rename = "Status", default)]
+    pub status: Option<String>,
+}
+
+// Song lyrics (Jellyfin 10.9+); synced lyrics carry a start time on every line
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Lyrics {
+    #[serde(rename = "Metadata", default)]
+    pub metadata: LyricMetadata,
+    #[serde(rename = "Lyrics", default)]
+    pub lines: Vec<LyricLine>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct LyricMetadata {
+    #[serde(rename = "Artist", default)]
+    pub artist: Option<String>,
+    #[serde(rename = "Album", default)]
+    pub album: Option<String>,
+    #[serde(rename = "Title", default)]
+    pub title: Option<String>,
+    #[serde(rename = "Offset", default)]
+    pub offset: Option<i64>, // ticks
+    #[serde(rename = "IsSynced", default)]
+    pub is_synced: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LyricLine {
+    #[serde(rename = "Start", default)]
+    pub start_ticks: Option<i64>, // None for unsynced lyrics
+    #[serde(rename = "Text", default)]
+    pub text: String,
+}
+
+// Detailed server info from the authenticated /System/Info endpoint
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct SystemInfo {
+    #[serde(rename = "ServerName")]
+    pub server_name: String,
+    #[serde(rename = "Version")]
+    pub version: String,
+    #[serde(rename = "ProductName", default)]
+    pub product_name: Option<String>,
+    #[serde(rename = "OperatingSystem", default)]
+    pub operating_system: Option<String>,
+    #[serde(rename = "OperatingSystemDisplayName", default)]
+    pub operating_system_display_name: Option<String>,
+    #[serde(rename = "Id")]
+    pub id: String,
+    #[serde(rename = "SystemArchitecture", default)]
+    pub system_architecture: Option<String>,
+    #[serde(rename = "HasPendingRestart", default)]
+    pub has_pending_restart: Option<bool>,
+    #[serde(rename = "HasUpdateAvailable", default)]
+    pub has_update_available: Option<bool>,
+    #[serde(rename = "IsShuttingDown", default)]
+    pub is_shutting_down: Option<bool>,
+    #[serde(rename = "ProgramDataPath", default)]
+    pub program_data_path: Option<String>,
+    #[serde(rename = "CachePath", default)]
+    pub cache_path: Option<String>,
+    #[serde(rename = "LogPath", default)]
+    pub log_path: Option<String>,
+    #[serde(rename = "TranscodingTempPath", default)]
+    pub transcoding_temp_path: Option<String>,
+    #[serde(rename = "EncoderLocation", default)]
+    pub encoder_location: Option<String>,
+    // False when only the public info could be fetched
+    #[serde(rename = "IsDetailed", default)]
+    pub is_detailed: bool,
+}
+
+impl From<ServerInfo> for SystemInfo {
+    fn from(info: ServerInfo) -> Self {
+        SystemInfo {
+            server_name: info.server_name,
+            version: info.version,
+            product_name: Some(info.product_name),
+            operating_system: Some(info.operating_system),
+            id: info.id,
+            is_detailed: false,
+            ..Default::default()
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UserProfile {
     pub name: String,
@@ -63,8 +247,77 @@ pub struct MusicItem {
     pub image_tags: Option<std::collections::HashMap<String, String>>,
     #[serde(rename = "BackdropImageTags")]
     pub backdrop_image_tags: Option<Vec<String>>,
+    // Image type -> image tag -> blurhash; the server sends these alongside image tags
+    #[serde(rename = "ImageBlurHashes", default)]
+    pub image_blur_hashes: Option<HashMap<String, HashMap<String, String>>>,
     #[serde(rename = "ChildCount")]
     pub child_count: Option<i32>,
+    #[serde(rename = "ExternalUrls")]
+    pub external_urls: Option<Vec<ExternalUrl>>,
+    #[serde(rename = "Chapters", default)]
+    pub chapters: Option<Vec<ChapterInfo>>,
+    #[serde(rename = "Genres", default)]
+    pub genres: Option<Vec<String>>,
+    #[serde(rename = "NormalizationGain", default)]
+    pub normalization_gain: Option<f32>, // dB, from the server's loudness scan
+    // Only on playlist listings: this entry's id within the playlist, which removing and moving use
+    #[serde(rename = "PlaylistItemId", default)]
+    pub playlist_item_id: Option<String>,
+}
+
+// Image types in order of preference when an item has no primary image
+const IMAGE_TYPE_PREFERENCE: [&str; 4] = ["Primary", "Thumb", "Art", "Logo"];
+// Saved positions past this fraction of the runtime count as finished
+const RESUME_MAX_FRACTION: f64 = 0.95;
+
+impl MusicItem {
+    // Best image type the server actually has for this item, if any
+    pub fn best_image_type(&self) -> Option<&'static str> {
+        IMAGE_TYPE_PREFERENCE
+            .into_iter()
+            .chain(["Backdrop"])
+            .find(|image_type| self.image_tag(image_type).is_some())
+    }
+
+    // Blurhash of the primary image, for a placeholder while the art loads
+    pub fn primary_blurhash(&self) -> Option<&str> {
+        let hashes = self.image_blur_hashes.as_ref()?.get("Primary")?;
+        // Prefer the hash for the current image; there can be stale ones for replaced art
+        self.image_tag("Primary")
+            .and_then(|tag| hashes.get(tag))
+            .or_else(|| hashes.values().next())
+            .map(String::as_str)
+    }
+
+    // The item's tag for an image type (the first backdrop's for Backdrop), None when it has no such image
+    pub fn image_tag(&self, image_type: &str) -> Option<&str> {
+        if image_type == "Backdrop" {
+            return self.backdrop_image_tags.as_ref()?.first().map(String::as_str);
+        }
+        self.image_tags.as_ref()?.get(image_type).map(String::as_str)
+    }
+
+    // Where to resume from the saved position, or 0 when it's unset, nearly finished or past the end
+    pub fn resume_position_seconds(&self) -> f64 {
+        let saved_ticks = self.user_data.as_ref().and_then(|data| data.playback_position_ticks).unwrap_or(0);
+        let Some(runtime_ticks) = self.runtime_ticks.filter(|ticks| *ticks > 0) else {
+            return 0.0;
+        };
+
+        if saved_ticks > 0 && (saved_ticks as f64) < runtime_ticks as f64 * RESUME_MAX_FRACTION {
+            saved_ticks as f64 / 10_000_000.0
+        } else {
+            0.0
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ChapterInfo {
+    #[serde(rename = "Name", default)]
+    pub name: Option<String>,
+    #[serde(rename = "StartPositionTicks")]
+    pub start_position_ticks: i64,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -87,6 +340,14 @@ pub struct NameIdPair {
     pub id: String,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExternalUrl {
+    #[serde(rename = "Name")]
+    pub name: String,
+    #[serde(rename = "Url")]
+    pub url: String,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ItemsResponse {
     #[serde(rename = "Items")]
@@ -105,6 +366,32 @@ pub struct AuthResponse {
     pub user: UserInfo,
 }
 
+// Pending Quick Connect login; the device id must match across every call so the
+// server can tie the approval to this client
+#[derive(Debug, Clone)]
+pub struct QuickConnectSession {
+    pub server_url: String,
+    pub device_id: String,
+    pub secret: String,
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct QuickConnectResult {
+    #[serde(rename = "Authenticated", default)]
+    authenticated: bool,
+    #[serde(rename = "Secret")]
+    secret: String,
+    #[serde(rename = "Code")]
+    code: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlaylistCreationResult {
+    #[serde(rename = "Id")]
+    id: String,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UserInfo {
     #[serde(rename = "Name")]
@@ -127,6 +414,201 @@ struct AuthRequest {
     password: String,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MediaSourceInfo {
+    #[serde(rename = "Id")]
+    pub id: String,
+    #[serde(rename = "Container")]
+    pub container: Option<String>,
+    #[serde(rename = "Bitrate")]
+    pub bitrate: Option<i64>,
+    #[serde(rename = "SupportsDirectPlay", default)]
+    pub supports_direct_play: bool,
+    #[serde(rename = "SupportsDirectStream", default)]
+    pub supports_direct_stream: bool,
+    #[serde(rename = "SupportsTranscoding", default)]
+    pub supports_transcoding: bool,
+    #[serde(rename = "TranscodingUrl")]
+    pub transcoding_url: Option<String>,
+    #[serde(rename = "TranscodingContainer")]
+    pub transcoding_container: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PlaybackInfoResponse {
+    #[serde(rename = "MediaSources", default)]
+    pub media_sources: Vec<MediaSourceInfo>,
+    #[serde(rename = "PlaySessionId")]
+    pub play_session_id: Option<String>,
+}
+
+// mp3 decodes everywhere SymphoniaSource does and carries no container quirks over progressive streams
+const TRANSCODE_CONTAINER: &str = "mp3";
+const TRANSCODE_AUDIO_CODEC: &str = "mp3";
+
+// Streaming settings negotiated with the server, reused across play_song calls
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StreamingProfile {
+    pub direct_play: bool,
+    pub container: String,
+    // Codec asked of the server when transcoding into the container
+    pub audio_codec: String,
+    pub max_bitrate: i64,
+    pub network_type: String,
+    pub transcode_reason: Option<String>,
+}
+
+impl StreamingProfile {
+    // Transcode to mp3 at up to max_bitrate, whatever the server would have picked
+    pub fn transcoding(max_bitrate: i64, network_type: &str, transcode_reason: Option<String>) -> Self {
+        Self {
+            direct_play: false,
+            container: TRANSCODE_CONTAINER.to_string(),
+            audio_codec: TRANSCODE_AUDIO_CODEC.to_string(),
+            max_bitrate,
+            network_type: network_type.to_string(),
+            transcode_reason,
+        }
+    }
+
+    pub fn from_playback_info(info: &PlaybackInfoResponse, max_bitrate: i64, network_type: &str) -> Self {
+        let source = info.media_sources.first();
+        let direct_play = source
+            .map(|s| s.supports_direct_play || s.supports_direct_stream)
+            .unwrap_or(true);
+
+        let container = source
+            .and_then(|s| {
+                if direct_play {
+                    s.container.clone()
+                } else {
+                    s.transcoding_container.clone()
+                }
+            })
+            .unwrap_or_else(|| "mp3".to_string());
+
+        // Never ask for more than the source actually has
+        let max_bitrate = source
+            .and_then(|s| s.bitrate)
+            .map(|bitrate| bitrate.min(max_bitrate))
+            .unwrap_or(max_bitrate);
+
+        // The server only explains itself through the TranscodeReasons query parameter
+        let transcoding_url = source.filter(|_| !direct_play).and_then(|s| s.transcoding_url.as_deref());
+        let transcode_reason = transcoding_url.and_then(|url| query_param(url, "TranscodeReasons"));
+
+        // The server's URL names the codec it picked, possibly as a list of acceptable ones
+        let audio_codec = transcoding_url
+            .and_then(|url| query_param(url, "AudioCodec"))
+            .and_then(|codecs| codecs.split(',').next().map(str::to_string))
+            .filter(|codec| !codec.is_empty())
+            .unwrap_or_else(|| audio_codec_for_container(&container).to_string());
+
+        Self {
+            direct_play,
+            container,
+            audio_codec,
+            max_bitrate,
+            network_type: network_type.to_string(),
+            transcode_reason,
+        }
+    }
+}
+
+// A query parameter of a URL, percent-decoded
+fn query_param(url: &str, name: &str) -> Option<String> {
+    let (_, query) = url.split_once('?')?;
+    let value = query.split('&').find_map(|pair| pair.strip_prefix(name)?.strip_prefix('='))?;
+    Some(urlencoding::decode(value).map(|decoded| decoded.into_owned()).unwrap_or_else(|_| value.to_string()))
+}
+
+// The usual audio codec inside a container, for when the server doesn't say
+fn audio_codec_for_container(container: &str) -> &str {
+    match container {
+        "m4a" | "mp4" | "ts" => "aac",
+        "ogg" => "vorbis",
+        "webm" => "opus",
+        container => container,
+    }
+}
+
+// How this app identifies itself in the MediaBrowser auth header
+const CLIENT_NAME: &str = "Jelly Player";
+const DEVICE_NAME: &str = "Desktop";
+const CLIENT_VERSION: &str = "0.1.0";
+
+// Header for requests made before there's a token (login and Quick Connect)
+fn format_device_auth_header(device_id: &str) -> String {
+    format!(
+        "MediaBrowser Client=\"{}\", Device=\"{}\", DeviceId=\"{}\", Version=\"{}\"",
+        CLIENT_NAME, DEVICE_NAME, device_id, CLIENT_VERSION
+    )
+}
+
+fn format_auth_header(device_id: &str, token: &str) -> String {
+    format!(
+        "MediaBrowser Client=\"{}\", Device=\"{}\", DeviceId=\"{}\", Version=\"{}\", Token=\"{}\"",
+        CLIENT_NAME, DEVICE_NAME, device_id, CLIENT_VERSION, token
+    )
+}
+
+// Keep just enough of a token to tell two apart; short tokens are hidden entirely
+fn redact_token(token: &str) -> String {
+    let chars: Vec<char> = token.chars().collect();
+    if chars.len() <= 12 {
+        return "*".repeat(chars.len());
+    }
+    let head: String = chars[..4].iter().collect();
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("{}…{}", head, tail)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuthHeaderDebug {
+    pub header: String,
+    pub client_name: String,
+    pub device_name: String,
+    pub device_id: String,
+    pub client_version: String,
+}
+
+// How long a cached items response stays fresh unless set_cache_ttl says otherwise
+const DEFAULT_CACHE_TTL_SECONDS: u64 = 600; // 10 minutes
+// Past this many cached responses the oldest are evicted, so long sessions don't keep every page
+const MAX_CACHED_RESPONSES: usize = 200;
+
+// Sort keys the library commands accept; anything else would be passed straight into the query
+pub const ALLOWED_SORT_FIELDS: [&str; 8] = [
+    "SortName",
+    "DateCreated",
+    "Album",
+    "Artist",
+    "PlayCount",
+    "Random",
+    "CommunityRating",
+    "ProductionYear",
+];
+pub const ALLOWED_SORT_ORDERS: [&str; 2] = ["Ascending", "Descending"];
+
+// Map optional user-supplied sort options onto the allowlists, defaulting to SortName/Ascending
+pub fn validate_sort(sort_by: Option<&str>, sort_order: Option<&str>) -> Result<(&'static str, &'static str), String> {
+    let sort_by = match sort_by {
+        None => "SortName",
+        Some(key) => ALLOWED_SORT_FIELDS
+            .into_iter()
+            .find(|allowed| allowed.eq_ignore_ascii_case(key))
+            .ok_or_else(|| format!("Unsupported sort key '{}' (expected one of: {})", key, ALLOWED_SORT_FIELDS.join(", ")))?,
+    };
+    let sort_order = match sort_order {
+        None => "Ascending",
+        Some(order) => ALLOWED_SORT_ORDERS
+            .into_iter()
+            .find(|allowed| allowed.eq_ignore_ascii_case(order))
+            .ok_or_else(|| format!("Unsupported sort order '{}' (expected Ascending or Descending)", order))?,
+    };
+    Ok((sort_by, sort_order))
+}
+
 #[derive(Debug, Clone)]
 struct CachedResponse {
     response: ItemsResponse,
@@ -142,39 +624,149 @@ impl CachedResponse {
         Self { response, timestamp }
     }
 
+    // Everything is expired with a TTL of 0, which turns caching off
     fn is_expired(&self, ttl_seconds: u64) -> bool {
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
             .as_secs();
-        now - self.timestamp > ttl_seconds
+        ttl_seconds == 0 || now.saturating_sub(self.timestamp) > ttl_seconds
     }
 }
 
+// What went wrong talking to the server. Serialized as { kind, message, status } so the UI can
+// tell a dropped connection from a rejected password without parsing the message; command
+// results carry it as `error` beside the message they show
+#[derive(Debug, Clone)]
+pub enum JellyfinError {
+    // The server couldn't be reached, or the connection failed mid-request
+    Network(String),
+    // Credentials or the access token were rejected, or the user isn't allowed
+    Auth(String),
+    NotFound(String),
+    Server { status: u16, message: String },
+    // The server answered with something we couldn't read
+    Parse(String),
+    NotAuthenticated,
+}
+
+impl JellyfinError {
+    // Classify an unsuccessful response by its status
+    fn from_status(status: reqwest::StatusCode, message: String) -> Self {
+        match status {
+            reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN => JellyfinError::Auth(message),
+            reqwest::StatusCode::NOT_FOUND => JellyfinError::NotFound(message),
+            status => JellyfinError::Server { status: status.as_u16(), message },
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            JellyfinError::Network(_) => "network",
+            JellyfinError::Auth(_) => "auth",
+            JellyfinError::NotFound(_) => "not_found",
+            JellyfinError::Server { .. } => "server",
+            JellyfinError::Parse(_) => "parse",
+            JellyfinError::NotAuthenticated => "not_authenticated",
+        }
+    }
+}
+
+impl std::fmt::Display for JellyfinError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JellyfinError::Network(message)
+            | JellyfinError::Auth(message)
+            | JellyfinError::NotFound(message)
+            | JellyfinError::Server { message, .. }
+            | JellyfinError::Parse(message) => write!(f, "{}", message),
+            JellyfinError::NotAuthenticated => write!(f, "Not authenticated"),
+        }
+    }
+}
+
+impl std::error::Error for JellyfinError {}
+
+impl Serialize for JellyfinError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut payload = serializer.serialize_struct("JellyfinError", 3)?;
+        payload.serialize_field("kind", self.kind())?;
+        payload.serialize_field("message", &self.to_string())?;
+        let status = match self {
+            JellyfinError::Server { status, .. } => Some(*status),
+            _ => None,
+        };
+        payload.serialize_field("status", &status)?;
+        payload.end()
+    }
+}
+
+impl From<reqwest::Error> for JellyfinError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_decode() {
+            JellyfinError::Parse(format!("Failed to parse server response: {}", e))
+        } else {
+            JellyfinError::Network(format!("Request failed: {}", e))
+        }
+    }
+}
+
+// Clones share the listing cache, so a clone taken for one request still fills and hits it
+#[derive(Clone)]
 pub struct JellyfinClient {
     client: Client,
     config: Option<JellyfinConfig>,
-    cache: HashMap<String, CachedResponse>,
+    cache: Arc<Mutex<HashMap<String, CachedResponse>>>,
+    cache_ttl_seconds: Arc<AtomicU64>,
 }
 
 impl JellyfinClient {
     pub fn new() -> Self {
+        Self::with_custom_headers(&HashMap::new())
+    }
+
+    // Client whose requests all carry the given extra headers (needed before login behind a proxy)
+    pub fn with_custom_headers(custom_headers: &HashMap<String, String>) -> Self {
+        Self {
+            client: Self::build_http_client(custom_headers),
+            config: None,
+            cache: Arc::new(Mutex::new(HashMap::new())),
+            cache_ttl_seconds: Arc::new(AtomicU64::new(DEFAULT_CACHE_TTL_SECONDS)),
+        }
+    }
+
+    // Applies to this client and every clone of it, including responses already cached.
+    // 0 turns the cache off
+    pub fn set_cache_ttl(&self, seconds: u64) {
+        self.cache_ttl_seconds.store(seconds, Ordering::Relaxed);
+        if seconds == 0 {
+            self.cache().clear();
+        }
+    }
+
+    pub fn cache_ttl(&self) -> u64 {
+        self.cache_ttl_seconds.load(Ordering::Relaxed)
+    }
+
+    fn cache(&self) -> std::sync::MutexGuard<'_, HashMap<String, CachedResponse>> {
+        // A panic elsewhere can't leave the map itself inconsistent
+        self.cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn build_http_client(custom_headers: &HashMap<String, String>) -> Client {
         // Create a more robust HTTP client with proper configuration
-        let client = Client::builder()
+        Client::builder()
             .user_agent("Bloodin/0.1.0")
             .timeout(std::time::Duration::from_secs(30))
             .danger_accept_invalid_certs(true) // Accept self-signed certificates for testing
+            .default_headers(custom_header_map(custom_headers))
             .build()
-            .unwrap_or_else(|_| Client::new()); // Fallback to default client
-
-        Self {
-            client,
-            config: None,
-            cache: HashMap::new(),
-        }
+            .unwrap_or_else(|_| Client::new()) // Fallback to default client
     }
 
     pub fn set_config(&mut self, config: JellyfinConfig) {
+        self.client = Self::build_http_client(&config.custom_headers);
         self.config = Some(config);
     }
 
@@ -182,9 +774,9 @@ impl JellyfinClient {
         self.config.as_ref()
     }
 
-    pub async fn get_server_info(&self, server_url: &str) -> Result<ServerInfo, Box<dyn std::error::Error>> {
+    pub async fn get_server_info(&self, server_url: &str) -> Result<ServerInfo, JellyfinError> {
         let url = format!("{}/System/Info/Public", server_url.trim_end_matches('/'));
-        println!("Attempting to connect to: {}", url);
+        debug!("Attempting to connect to: {}", url);
         
         let response = match self.client
             .get(&url)
@@ -193,27 +785,27 @@ impl JellyfinClient {
             .await {
                 Ok(response) => response,
                 Err(e) => {
-                    println!("Request failed: {}", e);
-                    return Err(format!("Connection failed to {}: {}", url, e).into());
+                    warn!("Request failed: {}", e);
+                    return Err(JellyfinError::Network(format!("Connection failed to {}: {}", url, e)));
                 }
             };
 
-        println!("Response status: {}", response.status());
+        debug!("Response status: {}", response.status());
 
         if !response.status().is_success() {
             let status = response.status();
             let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(format!("Server returned error {}: {}", status, error_text).into());
+            return Err(JellyfinError::from_status(status, format!("Server returned error {}: {}", status, error_text)));
         }
 
         let server_info: serde_json::Value = match response.json().await {
             Ok(json) => json,
             Err(e) => {
-                return Err(format!("Failed to parse server response: {}", e).into());
+                return Err(JellyfinError::Parse(format!("Failed to parse server response: {}", e)));
             }
         };
         
-        println!("Server info received: {:?}", server_info);
+        debug!("Server info received: {:?}", server_info);
         
         Ok(ServerInfo {
             server_name: server_info["ServerName"].as_str().unwrap_or("Unknown").to_string(),
@@ -224,10 +816,10 @@ impl JellyfinClient {
         })
     }
 
-    pub async fn authenticate(&mut self, server_url: &str, username: &str, password: &str) -> Result<JellyfinConfig, Box<dyn std::error::Error>> {
+    pub async fn authenticate(&mut self, server_url: &str, username: &str, password: &str) -> Result<JellyfinConfig, JellyfinError> {
         let device_id = Uuid::new_v4().to_string();
         let url = format!("{}/Users/AuthenticateByName", server_url.trim_end_matches('/'));
-        println!("Attempting authentication to: {}", url);
+        debug!("Attempting authentication to: {}", url);
         
         let auth_request = AuthRequest {
             username: username.to_string(),
@@ -238,33 +830,30 @@ impl JellyfinClient {
             .post(&url)
             .header("Accept", "application/json")
             .header("Content-Type", "application/json")
-            .header("Authorization", format!(
-                "MediaBrowser Client=\"Jelly Player\", Device=\"Desktop\", DeviceId=\"{}\", Version=\"0.1.0\"", 
-                device_id
-            ))
+            .header("Authorization", format_device_auth_header(&device_id))
             .json(&auth_request)
             .send()
             .await {
                 Ok(response) => response,
                 Err(e) => {
-                    println!("Authentication request failed: {}", e);
-                    return Err(format!("Authentication connection failed to {}: {}", url, e).into());
+                    warn!("Authentication request failed: {}", e);
+                    return Err(JellyfinError::Network(format!("Authentication connection failed to {}: {}", url, e)));
                 }
             };
 
-        println!("Authentication response status: {}", response.status());
+        debug!("Authentication response status: {}", response.status());
 
         if !response.status().is_success() {
             let status = response.status();
             let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-            println!("Authentication failed with status {}: {}", status, error_text);
-            return Err(format!("Authentication failed: {} - {}", status, error_text).into());
+            warn!("Authentication failed with status {}: {}", status, error_text);
+            return Err(JellyfinError::from_status(status, format!("Authentication failed: {} - {}", status, error_text)));
         }
 
         let auth_response: AuthResponse = match response.json().await {
             Ok(response) => response,
             Err(e) => {
-                return Err(format!("Failed to parse authentication response: {}", e).into());
+                return Err(JellyfinError::Parse(format!("Failed to parse authentication response: {}", e)));
             }
         };
         
@@ -274,32 +863,104 @@ impl JellyfinClient {
             user_id: auth_response.user.id,
             access_token: auth_response.access_token,
             device_id,
+            custom_headers: HashMap::new(),
+            renewal: None,
         };
 
         self.config = Some(config.clone());
         Ok(config)
     }
 
-    pub async fn get_user_profile(&self) -> Result<UserProfile, Box<dyn std::error::Error>> {
-        let config = self.config.as_ref().ok_or("Not authenticated")?;
-        let url = format!("{}/Users/{}", config.server_url.trim_end_matches('/'), config.user_id);
-        
+    // Start a Quick Connect login; the user approves the returned code from a signed-in client
+    pub async fn initiate_quick_connect(&self, server_url: &str) -> Result<QuickConnectSession, JellyfinError> {
+        let device_id = Uuid::new_v4().to_string();
+        let url = format!("{}/QuickConnect/Initiate", server_url.trim_end_matches('/'));
+
+        let response = self.client
+            .post(&url)
+            .header("Accept", "application/json")
+            .header("Authorization", format_device_auth_header(&device_id))
+            .send()
+            .await?;
+
+        if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+            return Err(JellyfinError::Auth("Quick Connect is disabled on this server".to_string()));
+        }
+        if !response.status().is_success() {
+            let status = response.status();
+            return Err(JellyfinError::from_status(status, format!("Failed to start Quick Connect: {}", status)));
+        }
+
+        let result: QuickConnectResult = response.json().await?;
+        Ok(QuickConnectSession {
+            server_url: server_url.to_string(),
+            device_id,
+            secret: result.secret,
+            code: result.code,
+        })
+    }
+
+    // Whether the code has been approved yet
+    pub async fn poll_quick_connect(&self, session: &QuickConnectSession) -> Result<bool, JellyfinError> {
+        let url = format!(
+            "{}/QuickConnect/Connect?Secret={}",
+            session.server_url.trim_end_matches('/'),
+            session.secret
+        );
+
         let response = self.client
             .get(&url)
             .header("Accept", "application/json")
-            .header("Authorization", format!(
-                "MediaBrowser Client=\"Jelly Player\", Device=\"Desktop\", DeviceId=\"{}\", Version=\"0.1.0\", Token=\"{}\"", 
-                config.device_id, config.access_token
-            ))
+            .header("Authorization", format_device_auth_header(&session.device_id))
             .send()
             .await?;
 
         if !response.status().is_success() {
-            return Err(format!("Failed to get user profile: {}", response.status()).into());
+            let status = response.status();
+            return Err(JellyfinError::from_status(status, format!("Failed to check Quick Connect: {}", status)));
         }
 
-        let user_info: UserInfo = response.json().await?;
-        
+        let result: QuickConnectResult = response.json().await?;
+        Ok(result.authenticated)
+    }
+
+    // Exchange an approved Quick Connect secret for an access token
+    pub async fn authenticate_with_quick_connect(&mut self, session: &QuickConnectSession) -> Result<JellyfinConfig, JellyfinError> {
+        let url = format!("{}/Users/AuthenticateWithQuickConnect", session.server_url.trim_end_matches('/'));
+
+        let response = self.client
+            .post(&url)
+            .header("Accept", "application/json")
+            .header("Content-Type", "application/json")
+            .header("Authorization", format_device_auth_header(&session.device_id))
+            .json(&serde_json::json!({ "Secret": session.secret }))
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            return Err(JellyfinError::from_status(status, format!("Quick Connect authentication failed: {}", status)));
+        }
+
+        let auth_response: AuthResponse = response.json().await?;
+        let config = JellyfinConfig {
+            server_url: session.server_url.clone(),
+            username: auth_response.user.name,
+            user_id: auth_response.user.id,
+            access_token: auth_response.access_token,
+            device_id: session.device_id.clone(),
+            custom_headers: HashMap::new(),
+            renewal: None,
+        };
+
+        self.config = Some(config.clone());
+        Ok(config)
+    }
+
+    pub async fn get_user_profile(&self) -> Result<UserProfile, JellyfinError> {
+        let config = self.config.as_ref().ok_or(JellyfinError::NotAuthenticated)?;
+        let user_info: UserInfo = self.authed_get(&format!("Users/{}", config.user_id), &[]).await?;
+
         Ok(UserProfile {
             name: user_info.name,
             id: user_info.id,
@@ -309,30 +970,196 @@ impl JellyfinClient {
         })
     }
 
-    pub async fn validate_token(&self) -> Result<bool, Box<dyn std::error::Error>> {
-        match self.get_user_profile().await {
-            Ok(_) => Ok(true),
-            Err(_) => Ok(false),
+    // Ok(false) only when the server rejects the token; network failures are errors
+    pub async fn validate_token(&self) -> Result<bool, JellyfinError> {
+        let config = self.config.as_ref().ok_or(JellyfinError::NotAuthenticated)?;
+        // Sent without renewal, since the answer is whether this exact token still works
+        let path = format!("Users/{}", config.user_id);
+        let response = self.send_request(reqwest::Method::GET, &path, &[], None, &config.current_token()).await?;
+
+        match response.status() {
+            status if status.is_success() => Ok(true),
+            reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN => Ok(false),
+            status => Err(JellyfinError::from_status(status, format!("Failed to validate token: {}", status))),
         }
     }
 
-    // Get authorization header for authenticated requests
-    fn get_auth_header(&self) -> Result<String, Box<dyn std::error::Error>> {
-        let config = self.config.as_ref().ok_or("Not authenticated")?;
-        Ok(format!(
-            "MediaBrowser Client=\"Jelly Player\", Device=\"Desktop\", DeviceId=\"{}\", Version=\"0.1.0\", Token=\"{}\"",
-            config.device_id, config.access_token
-        ))
+    // Send one request to an API path on the server, authorized with the given token
+    async fn send_request(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        query: &[(&str, &str)],
+        body: Option<&serde_json::Value>,
+        token: &str,
+    ) -> Result<reqwest::Response, JellyfinError> {
+        let config = self.config.as_ref().ok_or(JellyfinError::NotAuthenticated)?;
+        let url = format!("{}/{}", config.server_url.trim_end_matches('/'), path);
+
+        let started = std::time::Instant::now();
+        let mut request = self.client
+            .request(method.clone(), &url)
+            .header("Accept", "application/json")
+            .header("Authorization", format_auth_header(&config.device_id, token))
+            .query(query);
+        if let Some(body) = body {
+            request = request.json(body);
+        }
+
+        match request.send().await {
+            Ok(response) => {
+                debug!("{} {} -> {} in {} ms", method, path, response.status(), started.elapsed().as_millis());
+                Ok(response)
+            }
+            Err(e) => {
+                warn!("{} {} failed after {} ms: {}", method, path, started.elapsed().as_millis(), e);
+                Err(JellyfinError::Network(format!("Connection failed to {}: {}", config.server_url, e)))
+            }
+        }
+    }
+
+    // Authenticated request that survives an expired token: on a 401 the session logs in again
+    // (once, and only if it knows the password) and the request is retried with the new token.
+    // A 401 that can't be recovered from is returned as-is after the session reports expiry
+    async fn send_authed(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        query: &[(&str, &str)],
+        body: Option<&serde_json::Value>,
+    ) -> Result<reqwest::Response, JellyfinError> {
+        let config = self.config.as_ref().ok_or(JellyfinError::NotAuthenticated)?;
+        let sent_token = config.current_token();
+        let response = self.send_request(method.clone(), path, query, body, &sent_token).await?;
+
+        if response.status() != reqwest::StatusCode::UNAUTHORIZED {
+            return Ok(response);
+        }
+        let Some(renewal) = config.renewal.clone() else {
+            return Ok(response);
+        };
+
+        // Another request may have renewed the session already, or be renewing it now
+        let token = {
+            let _renewing = renewal.renewing.lock().await;
+            let token = config.current_token();
+            if token != sent_token {
+                Some(token)
+            } else {
+                self.renew_session(config, &renewal).await
+            }
+        };
+        let Some(token) = token else {
+            (renewal.on_event)(SessionEvent::Expired);
+            return Ok(response);
+        };
+
+        let retried = self.send_request(method, path, query, body, &token).await?;
+        if retried.status() == reqwest::StatusCode::UNAUTHORIZED {
+            (renewal.on_event)(SessionEvent::Expired);
+        }
+        Ok(retried)
+    }
+
+    // Pass successful responses through and turn the rest into an error saying what went wrong,
+    // so every endpoint words 401/404/5xx the same way
+    async fn check_response(response: reqwest::Response, path: &str) -> Result<reqwest::Response, JellyfinError> {
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+
+        let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+        let message = match status {
+            reqwest::StatusCode::UNAUTHORIZED => "Not authorized: the server rejected the access token".to_string(),
+            reqwest::StatusCode::FORBIDDEN => format!("Not allowed to access {}", path),
+            reqwest::StatusCode::NOT_FOUND => format!("Not found: {}", path),
+            status if status.is_server_error() => format!("Server error {}: {}", status, error_text),
+            status => format!("Server returned error {}: {}", status, error_text),
+        };
+        Err(JellyfinError::from_status(status, message))
+    }
+
+    async fn parse_response<T: DeserializeOwned>(response: reqwest::Response, path: &str) -> Result<T, JellyfinError> {
+        match response.json().await {
+            Ok(parsed) => Ok(parsed),
+            Err(e) => Err(JellyfinError::Parse(format!("Failed to parse response from {}: {}", path, e))),
+        }
+    }
+
+    // Authenticated GET of a JSON endpoint
+    async fn authed_get<T: DeserializeOwned>(&self, path: &str, query: &[(&str, &str)]) -> Result<T, JellyfinError> {
+        let response = self.send_authed(reqwest::Method::GET, path, query, None).await?;
+        let response = Self::check_response(response, path).await?;
+        Self::parse_response(response, path).await
+    }
+
+    // Like authed_get, for endpoints where a 404 means the server simply has nothing
+    async fn authed_get_optional<T: DeserializeOwned>(&self, path: &str, query: &[(&str, &str)]) -> Result<Option<T>, JellyfinError> {
+        let response = self.send_authed(reqwest::Method::GET, path, query, None).await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let response = Self::check_response(response, path).await?;
+        Ok(Some(Self::parse_response(response, path).await?))
+    }
+
+    // Authenticated POST of a JSON body to a JSON endpoint
+    async fn authed_post<T: DeserializeOwned>(&self, path: &str, query: &[(&str, &str)], body: &serde_json::Value) -> Result<T, JellyfinError> {
+        let response = self.send_authed(reqwest::Method::POST, path, query, Some(body)).await?;
+        let response = Self::check_response(response, path).await?;
+        Self::parse_response(response, path).await
+    }
+
+    // Authenticated request whose response has nothing worth reading
+    async fn authed_send(&self, method: reqwest::Method, path: &str, query: &[(&str, &str)], body: Option<&serde_json::Value>) -> Result<(), JellyfinError> {
+        let response = self.send_authed(method, path, query, body).await?;
+        Self::check_response(response, path).await?;
+        Ok(())
+    }
+
+    // Log in again with the session's password, returning the new token
+    async fn renew_session(&self, config: &JellyfinConfig, renewal: &SessionRenewal) -> Option<String> {
+        let password = renewal.password.as_ref()?;
+        info!("Access token rejected, logging in again as {}", config.username);
+
+        let mut client = JellyfinClient::with_custom_headers(&config.custom_headers);
+        let renewed = match client.authenticate(&config.server_url, &config.username, password).await {
+            Ok(renewed) if renewed.user_id == config.user_id => renewed,
+            Ok(_) => return None,
+            Err(e) => {
+                warn!("Re-authentication failed: {}", e);
+                return None;
+            }
+        };
+
+        if let Ok(mut renewed_token) = renewal.renewed_token.lock() {
+            *renewed_token = Some(renewed.access_token.clone());
+        }
+        (renewal.on_event)(SessionEvent::Renewed(renewed.access_token.clone()));
+        Some(renewed.access_token)
+    }
+
+    // The auth header as sent, but with the token redacted, for diagnosing rejected auth
+    pub fn get_auth_header_debug(&self) -> Result<AuthHeaderDebug, JellyfinError> {
+        let config = self.config.as_ref().ok_or(JellyfinError::NotAuthenticated)?;
+        Ok(AuthHeaderDebug {
+            header: format_auth_header(&config.device_id, &redact_token(&config.access_token)),
+            client_name: CLIENT_NAME.to_string(),
+            device_name: DEVICE_NAME.to_string(),
+            device_id: config.device_id.clone(),
+            client_version: CLIENT_VERSION.to_string(),
+        })
     }
 
     // Get music library items with filters
-    pub async fn get_items(&mut self, item_type: &str, limit: Option<i32>, start_index: Option<i32>) -> Result<ItemsResponse, Box<dyn std::error::Error>> {
+    pub async fn get_items(&mut self, item_type: &str, limit: Option<i32>, start_index: Option<i32>) -> Result<ItemsResponse, JellyfinError> {
         self.get_items_with_sort(item_type, limit, start_index, "SortName", "Ascending").await
     }
 
     // Get music library items with custom sorting (with caching)
-    pub async fn get_items_with_sort(&mut self, item_type: &str, limit: Option<i32>, start_index: Option<i32>, sort_by: &str, sort_order: &str) -> Result<ItemsResponse, Box<dyn std::error::Error>> {
-        println!("📊 get_items_with_sort called with item_type: {}, limit: {:?}, start_index: {:?}, sort: {} {}", item_type, limit, start_index, sort_by, sort_order);
+    pub async fn get_items_with_sort(&mut self, item_type: &str, limit: Option<i32>, start_index: Option<i32>, sort_by: &str, sort_order: &str) -> Result<ItemsResponse, JellyfinError> {
+        info!("get_items_with_sort called with item_type: {}, limit: {:?}, start_index: {:?}, sort: {} {}", item_type, limit, start_index, sort_by, sort_order);
         
         // Create cache key from request parameters
         let cache_key = format!("{}:{}:{}:{}:{}", 
@@ -343,211 +1170,419 @@ impl JellyfinClient {
             sort_order
         );
         
-        // Check cache first (10 minutes TTL)
-        if let Some(cached) = self.cache.get(&cache_key) {
-            if !cached.is_expired(600) { // 10 minutes = 600 seconds
-                println!("📦 Cache hit for key: {}", cache_key);
-                return Ok(cached.response.clone());
-            } else {
-                println!("🕒 Cache expired for key: {}", cache_key);
-                self.cache.remove(&cache_key);
+        // Check cache first
+        let ttl = self.cache_ttl();
+        if ttl > 0 {
+            let mut cache = self.cache();
+            if let Some(cached) = cache.get(&cache_key) {
+                if !cached.is_expired(ttl) {
+                    info!("Cache hit for key: {}", cache_key);
+                    return Ok(cached.response.clone());
+                } else {
+                    info!("Cache expired for key: {}", cache_key);
+                    cache.remove(&cache_key);
+                }
             }
         }
         
-        println!("🌐 Cache miss, fetching from server for key: {}", cache_key);
+        info!("Cache miss, fetching from server for key: {}", cache_key);
         
-        let config = self.config.as_ref().ok_or("Not authenticated")?;
-        
-        let mut url = format!(
-            "{}/Users/{}/Items?IncludeItemTypes={}&Recursive=true&Fields=BasicSyncInfo,CanDelete,PrimaryImageAspectRatio,ProductionYear&SortBy={}&SortOrder={}",
-            config.server_url.trim_end_matches('/'),
-            config.user_id,
-            item_type,
-            sort_by,
-            sort_order
-        );
-
-        if let Some(limit) = limit {
-            url.push_str(&format!("&Limit={}", limit));
+        let config = self.config.as_ref().ok_or(JellyfinError::NotAuthenticated)?;
+        let path = format!("Users/{}/Items", config.user_id);
+        let limit = limit.map(|limit| limit.to_string());
+        let start_index = start_index.map(|start_index| start_index.to_string());
+        let mut query = vec![
+            ("IncludeItemTypes", item_type),
+            ("Recursive", "true"),
+            ("Fields", "BasicSyncInfo,CanDelete,PrimaryImageAspectRatio,ProductionYear"),
+            ("SortBy", sort_by),
+            ("SortOrder", sort_order),
+        ];
+        if let Some(limit) = &limit {
+            query.push(("Limit", limit));
         }
-        if let Some(start_index) = start_index {
-            url.push_str(&format!("&StartIndex={}", start_index));
+        if let Some(start_index) = &start_index {
+            query.push(("StartIndex", start_index));
         }
 
-        println!("Fetching items: {}", url);
-
-        let auth_header = self.get_auth_header()?;
-        let response = match self.client
-            .get(&url)
-            .header("Accept", "application/json")
-            .header("Authorization", auth_header)
-            .send()
-            .await {
-                Ok(response) => response,
-                Err(e) => {
-                    println!("Request failed: {}", e);
-                    return Err(format!("Failed to fetch items: {}", e).into());
-                }
-            };
-
-        println!("Response status: {}", response.status());
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(format!("Server returned error {}: {}", status, error_text).into());
-        }
+        debug!("Fetching items: {} {:?}", path, query);
 
-        let items_response: ItemsResponse = match response.json().await {
-            Ok(response) => response,
-            Err(e) => {
-                return Err(format!("Failed to parse items response: {}", e).into());
-            }
-        };
+        let items_response: ItemsResponse = self.authed_get(&path, &query).await?;
 
-        println!("Fetched {} items of type {}", items_response.items.len(), item_type);
+        debug!("Fetched {} items of type {}", items_response.items.len(), item_type);
         
-        // Store in cache
-        self.cache.insert(cache_key.clone(), CachedResponse::new(items_response.clone()));
-        println!("💾 Cached response for key: {}", cache_key);
+        self.store_cached(cache_key, CachedResponse::new(items_response.clone()));
         
         Ok(items_response)
     }
 
     // Get random songs
-    pub async fn get_random_songs(&mut self, limit: Option<i32>) -> Result<ItemsResponse, Box<dyn std::error::Error>> {
-        println!("🎲 get_random_songs called with limit: {:?}", limit);
+    pub async fn get_random_songs(&mut self, limit: Option<i32>) -> Result<ItemsResponse, JellyfinError> {
+        info!("get_random_songs called with limit: {:?}", limit);
         self.get_items_with_sort("Audio", limit, None, "Random", "Ascending").await
     }
 
     // Get recently added albums
-    pub async fn get_recent_albums(&mut self, limit: Option<i32>, start_index: Option<i32>) -> Result<ItemsResponse, Box<dyn std::error::Error>> {
-        println!("📅 get_recent_albums called with limit: {:?}, start_index: {:?}", limit, start_index);
+    pub async fn get_recent_albums(&mut self, limit: Option<i32>, start_index: Option<i32>) -> Result<ItemsResponse, JellyfinError> {
+        info!("get_recent_albums called with limit: {:?}, start_index: {:?}", limit, start_index);
         self.get_items_with_sort("MusicAlbum", limit, start_index, "DateCreated", "Descending").await
     }
 
     // Get all songs from a specific album
-    pub async fn get_album_songs(&self, album_id: &str) -> Result<ItemsResponse, Box<dyn std::error::Error>> {
-        let config = self.config.as_ref().ok_or("Not authenticated")?;
-        let url = format!(
-            "{}/Users/{}/Items?ParentId={}&IncludeItemTypes=Audio&Recursive=true&SortBy=ParentIndexNumber,IndexNumber,SortName",
-            config.server_url.trim_end_matches('/'),
-            config.user_id,
-            album_id
-        );
+    pub async fn get_album_songs(&self, album_id: &str) -> Result<ItemsResponse, JellyfinError> {
+        let config = self.config.as_ref().ok_or(JellyfinError::NotAuthenticated)?;
+        self.authed_get(&format!("Users/{}/Items", config.user_id), &[
+            ("ParentId", album_id),
+            ("IncludeItemTypes", "Audio"),
+            ("Recursive", "true"),
+            ("EnableUserData", "true"),
+            ("SortBy", "ParentIndexNumber,IndexNumber,SortName"),
+        ]).await
+    }
 
-        let auth_header = self.get_auth_header()?;
-        let response = self.client.get(&url).header("Authorization", auth_header).send().await?;
+    // Get all songs from a specific artist
+    pub async fn get_artist_songs(&self, artist_id: &str) -> Result<ItemsResponse, JellyfinError> {
+        let config = self.config.as_ref().ok_or(JellyfinError::NotAuthenticated)?;
+        self.authed_get(&format!("Users/{}/Items", config.user_id), &[
+            ("ArtistIds", artist_id),
+            ("IncludeItemTypes", "Audio"),
+            ("Recursive", "true"),
+            ("SortBy", "SortName"),
+        ]).await
+    }
 
-        if !response.status().is_success() {
-            return Err(format!("Failed to get album songs: {}", response.status()).into());
+    // Get songs from a specific playlist
+    pub async fn get_playlist_songs(&self, playlist_id: &str, limit: Option<i32>, start_index: Option<i32>) -> Result<ItemsResponse, JellyfinError> {
+        let limit = limit.map(|limit| limit.to_string());
+        let start_index = start_index.map(|start_index| start_index.to_string());
+        let mut query = vec![
+            ("IncludeItemTypes", "Audio"),
+            ("Recursive", "true"),
+            ("Fields", "BasicSyncInfo,CanDelete,PrimaryImageAspectRatio,ProductionYear"),
+        ];
+        if let Some(limit) = &limit {
+            query.push(("Limit", limit));
+        }
+        if let Some(start_index) = &start_index {
+            query.push(("StartIndex", start_index));
         }
 
-        Ok(response.json().await?)
+        self.authed_get(&format!("Playlists/{}/Items", playlist_id), &query).await
     }
 
-    // Get all songs from a specific artist
-    pub async fn get_artist_songs(&self, artist_id: &str) -> Result<ItemsResponse, Box<dyn std::error::Error>> {
-        let config = self.config.as_ref().ok_or("Not authenticated")?;
-        let url = format!(
-            "{}/Users/{}/Items?ArtistIds={}&IncludeItemTypes=Audio&Recursive=true&SortBy=SortName",
-            config.server_url.trim_end_matches('/'),
-            config.user_id,
-            artist_id
-        );
+    // Create a playlist of the given songs (may be empty), returning its id
+    pub async fn create_playlist(&self, name: &str, item_ids: &[String]) -> Result<String, JellyfinError> {
+        let config = self.config.as_ref().ok_or(JellyfinError::NotAuthenticated)?;
+        let body = serde_json::json!({
+            "Name": name,
+            "Ids": item_ids,
+            "UserId": config.user_id,
+            "MediaType": "Audio",
+        });
+        let created: PlaylistCreationResult = self.authed_post("Playlists", &[], &body).await?;
+        Ok(created.id)
+    }
 
-        let auth_header = self.get_auth_header()?;
-        let response = self.client.get(&url).header("Authorization", auth_header).send().await?;
+    // Append songs to the end of a playlist
+    pub async fn add_to_playlist(&self, playlist_id: &str, item_ids: &[String]) -> Result<(), JellyfinError> {
+        let config = self.config.as_ref().ok_or(JellyfinError::NotAuthenticated)?;
+        self.authed_send(reqwest::Method::POST, &format!("Playlists/{}/Items", playlist_id), &[
+            ("Ids", &item_ids.join(",")),
+            ("UserId", &config.user_id),
+        ], None).await
+    }
 
-        if !response.status().is_success() {
-            return Err(format!("Failed to get artist songs: {}", response.status()).into());
+    // Takes playlist entry ids (MusicItem.playlist_item_id), since a song can be in a playlist more than once
+    pub async fn remove_from_playlist(&self, playlist_id: &str, entry_ids: &[String]) -> Result<(), JellyfinError> {
+        self.authed_send(reqwest::Method::DELETE, &format!("Playlists/{}/Items", playlist_id), &[
+            ("EntryIds", &entry_ids.join(",")),
+        ], None).await
+    }
+
+    pub async fn reorder_playlist_item(&self, playlist_id: &str, entry_id: &str, new_index: u32) -> Result<(), JellyfinError> {
+        let path = format!("Playlists/{}/Items/{}/Move/{}", playlist_id, entry_id, new_index);
+        self.authed_send(reqwest::Method::POST, &path, &[], None).await
+    }
+
+    // Get favorited items of a given type (not cached, favorites change often)
+    pub async fn get_favorites(&self, item_type: &str, limit: Option<i32>, start_index: Option<i32>) -> Result<ItemsResponse, JellyfinError> {
+        let config = self.config.as_ref().ok_or(JellyfinError::NotAuthenticated)?;
+        let limit = limit.map(|limit| limit.to_string());
+        let start_index = start_index.map(|start_index| start_index.to_string());
+        let mut query = vec![
+            ("Filters", "IsFavorite"),
+            ("IncludeItemTypes", item_type),
+            ("Recursive", "true"),
+            ("Fields", "BasicSyncInfo,CanDelete,PrimaryImageAspectRatio,ProductionYear,Genres"),
+            ("SortBy", "SortName"),
+            ("SortOrder", "Ascending"),
+        ];
+        if let Some(limit) = &limit {
+            query.push(("Limit", limit));
+        }
+        if let Some(start_index) = &start_index {
+            query.push(("StartIndex", start_index));
         }
 
-        Ok(response.json().await?)
+        self.authed_get(&format!("Users/{}/Items", config.user_id), &query).await
     }
 
-    // Get songs from a specific playlist
-    pub async fn get_playlist_songs(&self, playlist_id: &str, limit: Option<i32>, start_index: Option<i32>) -> Result<ItemsResponse, Box<dyn std::error::Error>> {
-        let config = self.config.as_ref().ok_or("Not authenticated")?;
-        let mut url = format!(
-            "{}/Playlists/{}/Items?IncludeItemTypes=Audio&Recursive=true&Fields=BasicSyncInfo,CanDelete,PrimaryImageAspectRatio,ProductionYear",
-            config.server_url.trim_end_matches('/'),
-            playlist_id
-        );
+    // Mark or unmark an item as favorite for the current user
+    pub async fn set_favorite(&self, item_id: &str, is_favorite: bool) -> Result<(), JellyfinError> {
+        let config = self.config.as_ref().ok_or(JellyfinError::NotAuthenticated)?;
+        let path = format!("Users/{}/FavoriteItems/{}", config.user_id, item_id);
+        let method = if is_favorite {
+            reqwest::Method::POST
+        } else {
+            reqwest::Method::DELETE
+        };
+        self.authed_send(method, &path, &[], None).await
+    }
+
+    // Tell the server playback of an item began, so it shows up under Now Playing
+    pub async fn report_playback_start(&self, item_id: &str) -> Result<(), JellyfinError> {
+        let body = serde_json::json!({
+            "ItemId": item_id,
+            "PositionTicks": 0,
+            "IsPaused": false,
+            "CanSeek": true,
+        });
+        self.post_session_report("Sessions/Playing", &body).await
+    }
 
-        if let Some(limit) = limit {
-            url.push_str(&format!("&Limit={}", limit));
+    pub async fn report_playback_progress(&self, item_id: &str, position_ticks: i64, is_paused: bool) -> Result<(), JellyfinError> {
+        let body = serde_json::json!({
+            "ItemId": item_id,
+            "PositionTicks": position_ticks,
+            "IsPaused": is_paused,
+            "CanSeek": true,
+        });
+        self.post_session_report("Sessions/Playing/Progress", &body).await
+    }
+
+    // The stopped position is what the server keeps as the item's resume point
+    pub async fn report_playback_stopped(&self, item_id: &str, position_ticks: i64) -> Result<(), JellyfinError> {
+        let body = serde_json::json!({
+            "ItemId": item_id,
+            "PositionTicks": position_ticks,
+        });
+        self.post_session_report("Sessions/Playing/Stopped", &body).await
+    }
+
+    async fn post_session_report(&self, path: &str, body: &serde_json::Value) -> Result<(), JellyfinError> {
+        self.authed_send(reqwest::Method::POST, path, &[], Some(body)).await
+    }
+
+    // Drop all cached listing responses
+    pub fn invalidate_cache(&mut self) {
+        self.cache().clear();
+    }
+
+    // Drop the cached listings of one item type (e.g. "MusicAlbum"), returning how many were removed
+    pub fn invalidate_cache_for(&mut self, item_type: &str) -> usize {
+        let prefix = format!("{}:", item_type);
+        let mut cache = self.cache();
+        let before = cache.len();
+        cache.retain(|key, _| !key.starts_with(&prefix));
+        before - cache.len()
+    }
+
+    // Drop every cached response past its TTL, returning how many were removed
+    pub fn prune_expired_cache(&mut self) -> usize {
+        let ttl = self.cache_ttl();
+        let mut cache = self.cache();
+        let before = cache.len();
+        cache.retain(|_, cached| !cached.is_expired(ttl));
+        let removed = before - cache.len();
+        if removed > 0 {
+            info!("Pruned {} expired cache entries", removed);
         }
-        if let Some(start_index) = start_index {
-            url.push_str(&format!("&StartIndex={}", start_index));
+        removed
+    }
+
+    // Store a response, sweeping out anything stale while we're at it. Nothing is kept with a TTL of 0
+    fn store_cached(&mut self, cache_key: String, cached: CachedResponse) {
+        if self.cache_ttl() == 0 {
+            return;
         }
+        self.prune_expired_cache();
+        self.evict_oldest_cached(MAX_CACHED_RESPONSES - 1);
+        info!("Cached response for key: {}", cache_key);
+        self.cache().insert(cache_key, cached);
+    }
 
-        let auth_header = self.get_auth_header()?;
-        let response = self.client.get(&url).header("Authorization", auth_header).send().await?;
+    // Drop the oldest cached responses until at most `keep` remain
+    fn evict_oldest_cached(&self, keep: usize) {
+        let mut cache = self.cache();
+        if cache.len() <= keep {
+            return;
+        }
 
-        if !response.status().is_success() {
-            return Err(format!("Failed to get playlist songs: {}", response.status()).into());
+        let mut by_age: Vec<(u64, String)> = cache
+            .iter()
+            .map(|(key, cached)| (cached.timestamp, key.clone()))
+            .collect();
+        by_age.sort();
+        let excess = cache.len() - keep;
+        for (_, key) in by_age.into_iter().take(excess) {
+            cache.remove(&key);
         }
+        info!("Evicted {} oldest cache entries", excess);
+    }
 
-        Ok(response.json().await?)
+    // Songs the user played most recently, newest first. Not cached: this changes with every song
+    pub async fn get_recently_played(&self, limit: i32) -> Result<ItemsResponse, JellyfinError> {
+        let config = self.config.as_ref().ok_or(JellyfinError::NotAuthenticated)?;
+        self.authed_get(&format!("Users/{}/Items", config.user_id), &[
+            ("Filters", "IsPlayed"),
+            ("IncludeItemTypes", "Audio"),
+            ("Recursive", "true"),
+            ("Fields", "BasicSyncInfo,CanDelete,PrimaryImageAspectRatio,ProductionYear"),
+            ("EnableUserData", "true"),
+            ("SortBy", "DatePlayed"),
+            ("SortOrder", "Descending"),
+            ("Limit", &limit.to_string()),
+        ]).await
     }
 
-    // Get a single item by ID
-    pub async fn get_item(&self, item_id: &str) -> Result<MusicItem, Box<dyn std::error::Error>> {
-        self.get_item_details(item_id).await
+    // Get the user's most played songs, with genres and play counts. Not cached, like recently played
+    pub async fn get_most_played(&self, limit: i32) -> Result<ItemsResponse, JellyfinError> {
+        let config = self.config.as_ref().ok_or(JellyfinError::NotAuthenticated)?;
+        self.authed_get(&format!("Users/{}/Items", config.user_id), &[
+            ("Filters", "IsPlayed"),
+            ("IncludeItemTypes", "Audio"),
+            ("Recursive", "true"),
+            ("Fields", "BasicSyncInfo,CanDelete,PrimaryImageAspectRatio,ProductionYear,Genres"),
+            ("EnableUserData", "true"),
+            ("SortBy", "PlayCount"),
+            ("SortOrder", "Descending"),
+            ("Limit", &limit.to_string()),
+        ]).await
     }
 
-    // Get songs (bypassing cache for testing pagination)
-    pub async fn get_songs(&mut self, limit: Option<i32>, start_index: Option<i32>) -> Result<ItemsResponse, Box<dyn std::error::Error>> {
-        println!("🎵 get_songs called with limit: {:?}, start_index: {:?}", limit, start_index);
-        
-        let config = self.config.as_ref().ok_or("Not authenticated")?;
-        
-        let mut url = format!(
-            "{}/Users/{}/Items?IncludeItemTypes=Audio&Recursive=true&Fields=BasicSyncInfo,CanDelete,PrimaryImageAspectRatio,ProductionYear&SortBy=SortName&SortOrder=Ascending",
-            config.server_url.trim_end_matches('/'),
-            config.user_id
-        );
+    // Get a random sample of songs in a genre
+    pub async fn get_random_genre_songs(&self, genre: &str, limit: i32) -> Result<ItemsResponse, JellyfinError> {
+        let config = self.config.as_ref().ok_or(JellyfinError::NotAuthenticated)?;
+        self.authed_get(&format!("Users/{}/Items", config.user_id), &[
+            ("Genres", genre),
+            ("IncludeItemTypes", "Audio"),
+            ("Recursive", "true"),
+            ("Fields", "BasicSyncInfo,CanDelete,PrimaryImageAspectRatio,ProductionYear,Genres"),
+            ("SortBy", "Random"),
+            ("Limit", &limit.to_string()),
+        ]).await
+    }
 
-        if let Some(limit) = limit {
-            url.push_str(&format!("&Limit={}", limit));
+    // Get songs carrying a user-defined tag/mood, optionally narrowed to a genre
+    pub async fn get_songs_by_tag(&self, tag: &str, genre: Option<&str>, limit: Option<i32>, start_index: Option<i32>) -> Result<ItemsResponse, JellyfinError> {
+        let config = self.config.as_ref().ok_or(JellyfinError::NotAuthenticated)?;
+        let limit_param = limit.map(|limit| limit.to_string());
+        let start_index_param = start_index.map(|start_index| start_index.to_string());
+        let mut query = vec![
+            ("Tags", tag),
+            ("IncludeItemTypes", "Audio"),
+            ("Recursive", "true"),
+            ("Fields", "BasicSyncInfo,CanDelete,PrimaryImageAspectRatio,ProductionYear"),
+            ("SortBy", "SortName"),
+            ("SortOrder", "Ascending"),
+        ];
+        if let Some(genre) = genre {
+            query.push(("Genres", genre));
         }
-        if let Some(start_index) = start_index {
-            url.push_str(&format!("&StartIndex={}", start_index));
+        if let Some(limit) = &limit_param {
+            query.push(("Limit", limit));
+        }
+        if let Some(start_index) = &start_index_param {
+            query.push(("StartIndex", start_index));
         }
 
-        println!("🔗 Fetching songs URL: {}", url);
+        // An unknown tag simply has no songs
+        let songs = self.authed_get_optional(&format!("Users/{}/Items", config.user_id), &query).await?;
+        Ok(songs.unwrap_or_else(|| ItemsResponse {
+            items: Vec::new(),
+            total_record_count: 0,
+            start_index: start_index.unwrap_or(0),
+        }))
+    }
 
-        let auth_header = self.get_auth_header()?;
-        let response = match self.client
-            .get(&url)
-            .header("Accept", "application/json")
-            .header("Authorization", auth_header)
-            .send()
-            .await {
-                Ok(response) => response,
-                Err(e) => {
-                    println!("Request failed: {}", e);
-                    return Err(format!("Failed to fetch songs: {}", e).into());
-                }
-            };
+    // Get an instant mix of songs similar to the given item
+    pub async fn get_instant_mix(&self, item_id: &str, limit: i32) -> Result<ItemsResponse, JellyfinError> {
+        let config = self.config.as_ref().ok_or(JellyfinError::NotAuthenticated)?;
+        self.authed_get(&format!("Items/{}/InstantMix", item_id), &[
+            ("UserId", &config.user_id),
+            ("Limit", &limit.to_string()),
+            ("Fields", "BasicSyncInfo,CanDelete,PrimaryImageAspectRatio,ProductionYear"),
+        ]).await
+    }
 
-        println!("Response status: {}", response.status());
+    // Get the server's suggestions for the user; None when the server doesn't offer them
+    pub async fn get_suggestions(&self, item_types: &str, limit: i32) -> Result<Option<ItemsResponse>, JellyfinError> {
+        let config = self.config.as_ref().ok_or(JellyfinError::NotAuthenticated)?;
+        self.authed_get_optional("Items/Suggestions", &[
+            ("UserId", &config.user_id),
+            ("Type", item_types),
+            ("Limit", &limit.to_string()),
+            ("EnableTotalRecordCount", "false"),
+        ]).await
+    }
 
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(format!("Server returned error {}: {}", status, error_text).into());
+    // Lyrics for a song, or None when the server has none for it
+    pub async fn get_lyrics(&self, item_id: &str) -> Result<Option<Lyrics>, JellyfinError> {
+        self.authed_get_optional(&format!("Audio/{}/Lyrics", item_id), &[]).await
+    }
+
+    // Get items the server considers similar to the given one (same type)
+    pub async fn get_similar_items(&self, item_id: &str, limit: i32) -> Result<ItemsResponse, JellyfinError> {
+        let config = self.config.as_ref().ok_or(JellyfinError::NotAuthenticated)?;
+        self.authed_get(&format!("Items/{}/Similar", item_id), &[
+            ("UserId", &config.user_id),
+            ("Limit", &limit.to_string()),
+            ("Fields", "BasicSyncInfo,CanDelete,PrimaryImageAspectRatio,ProductionYear"),
+        ]).await
+    }
+
+    // Get a single item by ID
+    pub async fn get_item(&self, item_id: &str) -> Result<MusicItem, JellyfinError> {
+        self.get_item_details(item_id).await
+    }
+
+    // Look up several items at once; ids the server no longer knows are simply missing from the result
+    pub async fn get_items_by_ids(&self, ids: &[String]) -> Result<Vec<MusicItem>, JellyfinError> {
+        let config = self.config.as_ref().ok_or(JellyfinError::NotAuthenticated)?;
+        let items: ItemsResponse = self.authed_get(&format!("Users/{}/Items", config.user_id), &[
+            ("Ids", &ids.join(",")),
+            ("Fields", "BasicSyncInfo,CanDelete,PrimaryImageAspectRatio,ProductionYear,Chapters"),
+        ]).await?;
+        Ok(items.items)
+    }
+
+    // Get songs (bypassing cache for testing pagination)
+    pub async fn get_songs(&mut self, limit: Option<i32>, start_index: Option<i32>, sort_by: &str, sort_order: &str) -> Result<ItemsResponse, JellyfinError> {
+        info!("get_songs called with limit: {:?}, start_index: {:?}, sort: {} {}", limit, start_index, sort_by, sort_order);
+        
+        let config = self.config.as_ref().ok_or(JellyfinError::NotAuthenticated)?;
+        let path = format!("Users/{}/Items", config.user_id);
+        let limit = limit.map(|limit| limit.to_string());
+        let start_index = start_index.map(|start_index| start_index.to_string());
+        let mut query = vec![
+            ("IncludeItemTypes", "Audio"),
+            ("Recursive", "true"),
+            ("Fields", "BasicSyncInfo,CanDelete,PrimaryImageAspectRatio,ProductionYear"),
+            ("SortBy", sort_by),
+            ("SortOrder", sort_order),
+        ];
+        if let Some(limit) = &limit {
+            query.push(("Limit", limit));
+        }
+        if let Some(start_index) = &start_index {
+            query.push(("StartIndex", start_index));
         }
 
-        let items_response: ItemsResponse = match response.json().await {
-            Ok(response) => response,
-            Err(e) => {
-                return Err(format!("Failed to parse songs response: {}", e).into());
-            }
-        };
+        info!("Fetching songs: {} {:?}", path, query);
 
-        println!("🎯 Fetched {} songs (StartIndex: {}, Total: {})", 
+        let items_response: ItemsResponse = self.authed_get(&path, &query).await?;
+
+        debug!("Fetched {} songs (StartIndex: {}, Total: {})", 
             items_response.items.len(), 
             items_response.start_index, 
             items_response.total_record_count
@@ -557,158 +1592,449 @@ impl JellyfinClient {
     }
 
     // Get albums
-    pub async fn get_albums(&mut self, limit: Option<i32>, start_index: Option<i32>) -> Result<ItemsResponse, Box<dyn std::error::Error>> {
-        self.get_items("MusicAlbum", limit, start_index).await
+    pub async fn get_albums(&mut self, limit: Option<i32>, start_index: Option<i32>, sort_by: &str, sort_order: &str) -> Result<ItemsResponse, JellyfinError> {
+        self.get_items_with_sort("MusicAlbum", limit, start_index, sort_by, sort_order).await
     }
 
     // Get artists
-    pub async fn get_artists(&mut self, limit: Option<i32>, start_index: Option<i32>) -> Result<ItemsResponse, Box<dyn std::error::Error>> {
-        self.get_items("MusicArtist", limit, start_index).await
+    pub async fn get_artists(&mut self, limit: Option<i32>, start_index: Option<i32>, sort_by: &str, sort_order: &str) -> Result<ItemsResponse, JellyfinError> {
+        self.get_items_with_sort("MusicArtist", limit, start_index, sort_by, sort_order).await
     }
 
     // Get playlists
-    pub async fn get_playlists(&mut self, limit: Option<i32>, start_index: Option<i32>) -> Result<ItemsResponse, Box<dyn std::error::Error>> {
-        self.get_items("Playlist", limit, start_index).await
+    pub async fn get_playlists(&mut self, limit: Option<i32>, start_index: Option<i32>, sort_by: &str, sort_order: &str) -> Result<ItemsResponse, JellyfinError> {
+        self.get_items_with_sort("Playlist", limit, start_index, sort_by, sort_order).await
     }
 
     // Search across all music items
-    pub async fn search(&self, query: &str, limit: Option<i32>) -> Result<ItemsResponse, Box<dyn std::error::Error>> {
-        let config = self.config.as_ref().ok_or("Not authenticated")?;
-        
-        let mut url = format!(
-            "{}/Users/{}/Items?SearchTerm={}&IncludeItemTypes=Audio,MusicAlbum,MusicArtist,Playlist&Recursive=true&Fields=BasicSyncInfo,CanDelete,PrimaryImageAspectRatio,ProductionYear&SortBy=SortName&SortOrder=Ascending",
-            config.server_url.trim_end_matches('/'),
-            config.user_id,
-            urlencoding::encode(query)
-        );
-
-        if let Some(limit) = limit {
-            url.push_str(&format!("&Limit={}", limit));
+    pub async fn search(&self, query: &str, limit: Option<i32>) -> Result<ItemsResponse, JellyfinError> {
+        let config = self.config.as_ref().ok_or(JellyfinError::NotAuthenticated)?;
+        let path = format!("Users/{}/Items", config.user_id);
+        let limit = limit.map(|limit| limit.to_string());
+        let mut params = vec![
+            ("SearchTerm", query),
+            ("IncludeItemTypes", "Audio,MusicAlbum,MusicArtist,Playlist"),
+            ("Recursive", "true"),
+            ("Fields", "BasicSyncInfo,CanDelete,PrimaryImageAspectRatio,ProductionYear"),
+            ("SortBy", "SortName"),
+            ("SortOrder", "Ascending"),
+        ];
+        if let Some(limit) = &limit {
+            params.push(("Limit", limit));
         }
 
-        println!("Searching: {}", url);
+        debug!("Searching: {} {:?}", path, params);
 
-        let auth_header = self.get_auth_header()?;
-        let response = match self.client
-            .get(&url)
-            .header("Accept", "application/json")
-            .header("Authorization", auth_header)
-            .send()
-            .await {
-                Ok(response) => response,
-                Err(e) => {
-                    println!("Search request failed: {}", e);
-                    return Err(format!("Search failed: {}", e).into());
-                }
-            };
+        let items_response: ItemsResponse = self.authed_get(&path, &params).await?;
 
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(format!("Search returned error {}: {}", status, error_text).into());
+        debug!("Search found {} items for query: {}", items_response.items.len(), query);
+        Ok(items_response)
+    }
+
+    // Get a single item by ID
+    pub async fn get_item_details(&self, item_id: &str) -> Result<MusicItem, JellyfinError> {
+        let config = self.config.as_ref().ok_or(JellyfinError::NotAuthenticated)?;
+        let path = format!("Users/{}/Items/{}", config.user_id, item_id);
+
+        debug!("Fetching item details: {}", path);
+
+        let item: MusicItem = self.authed_get(&path, &[
+            ("Fields", "BasicSyncInfo,CanDelete,PrimaryImageAspectRatio,ProductionYear,ExternalUrls,Chapters"),
+        ]).await?;
+
+        debug!("Fetched item details for: {}", item.name);
+        Ok(item)
+    }
+
+    // Get detailed system info, falling back to the public info if the user isn't allowed to see it
+    pub async fn get_system_info_authenticated(&self) -> Result<SystemInfo, JellyfinError> {
+        let config = self.config.as_ref().ok_or(JellyfinError::NotAuthenticated)?;
+        let path = "System/Info";
+        let response = self.send_authed(reqwest::Method::GET, path, &[], None).await?;
+
+        if response.status() == reqwest::StatusCode::FORBIDDEN {
+            info!("System info requires admin rights, falling back to public info");
+            let public_info = self.get_server_info(&config.server_url).await?;
+            return Ok(public_info.into());
         }
 
-        let items_response: ItemsResponse = match response.json().await {
-            Ok(response) => response,
-            Err(e) => {
-                return Err(format!("Failed to parse search response: {}", e).into());
-            }
-        };
+        let response = Self::check_response(response, path).await?;
+        let mut system_info: SystemInfo = Self::parse_response(response, path).await?;
+        system_info.is_detailed = true;
 
-        println!("Search found {} items for query: {}", items_response.items.len(), query);
-        Ok(items_response)
+        debug!("Fetched detailed system info for: {}", system_info.server_name);
+        Ok(system_info)
     }
 
-    // Get a single item by ID
-    pub async fn get_item_details(&self, item_id: &str) -> Result<MusicItem, Box<dyn std::error::Error>> {
-        let config = self.config.as_ref().ok_or("Not authenticated")?;
-        
-        let url = format!(
-            "{}/Users/{}/Items/{}?Fields=BasicSyncInfo,CanDelete,PrimaryImageAspectRatio,ProductionYear",
-            config.server_url.trim_end_matches('/'),
-            config.user_id,
-            item_id
-        );
+    // Get the server's scheduled tasks; None when the user isn't an administrator
+    pub async fn get_scheduled_tasks(&self) -> Result<Option<Vec<ScheduledTask>>, JellyfinError> {
+        let path = "ScheduledTasks";
+        let response = self.send_authed(reqwest::Method::GET, path, &[("IsHidden", "false")], None).await?;
 
-        println!("Fetching item details: {}", url);
+        if response.status() == reqwest::StatusCode::FORBIDDEN {
+            info!("Scheduled tasks require admin rights");
+            return Ok(None);
+        }
 
-        let auth_header = self.get_auth_header()?;
-        let response = match self.client
-            .get(&url)
-            .header("Accept", "application/json")
-            .header("Authorization", auth_header)
-            .send()
-            .await {
-                Ok(response) => response,
-                Err(e) => {
-                    println!("Request failed: {}", e);
-                    return Err(format!("Failed to fetch item details: {}", e).into());
-                }
-            };
+        let response = Self::check_response(response, path).await?;
+        Ok(Some(Self::parse_response(response, path).await?))
+    }
 
-        println!("Response status: {}", response.status());
+    // Start a scheduled task now; false when the user isn't an administrator
+    pub async fn trigger_scheduled_task(&self, task_id: &str) -> Result<bool, JellyfinError> {
+        let path = format!("ScheduledTasks/Running/{}", task_id);
+        let response = self.send_authed(reqwest::Method::POST, &path, &[], None).await?;
 
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(format!("Server returned error {}: {}", status, error_text).into());
+        if response.status() == reqwest::StatusCode::FORBIDDEN {
+            info!("Triggering scheduled tasks requires admin rights");
+            return Ok(false);
         }
 
-        let item: MusicItem = match response.json().await {
-            Ok(item) => item,
-            Err(e) => {
-                return Err(format!("Failed to parse item response: {}", e).into());
-            }
-        };
+        Self::check_response(response, &path).await?;
+        Ok(true)
+    }
 
-        println!("Fetched item details for: {}", item.name);
-        Ok(item)
+    // Download an image for an item, optionally scaled down to max_width, as (mime_type, bytes)
+    pub async fn get_image_bytes(&self, item_id: &str, image_type: &str, max_width: Option<u32>) -> Result<(String, Vec<u8>), JellyfinError> {
+        let path = format!("Items/{}/Images/{}", item_id, image_type);
+        let max_width = max_width.map(|width| width.to_string());
+        let mut query = Vec::new();
+        if let Some(max_width) = max_width.as_deref() {
+            query.push(("maxWidth", max_width));
+        }
+        let response = self.send_authed(reqwest::Method::GET, &path, &query, None).await?;
+        let response = Self::check_response(response, &path).await?;
+
+        let mime_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("image/jpeg")
+            .to_string();
+        let bytes = response.bytes().await?;
+
+        Ok((mime_type, bytes.to_vec()))
     }
 
-    // Get image URL for an item
-    pub fn get_image_url(&self, item_id: &str, image_type: &str) -> Result<String, Box<dyn std::error::Error>> {
-        let config = self.config.as_ref().ok_or("Not authenticated")?;
-        
-        // Try multiple URL formats that Jellyfin might use
-        let urls = vec![
-            // Standard API format with api_key parameter
-            format!(
-                "{}/Items/{}/Images/{}?api_key={}",
-                config.server_url.trim_end_matches('/'),
-                item_id,
-                image_type,
-                config.access_token
-            ),
-            // Alternative format with X-Emby-Token (Jellyfin sometimes uses this)
-            format!(
-                "{}/Items/{}/Images/{}?X-Emby-Token={}",
-                config.server_url.trim_end_matches('/'),
-                item_id,
-                image_type,
-                config.access_token
-            ),
-            // Format with maxHeight/maxWidth (common in Jellyfin)
-            format!(
-                "{}/Items/{}/Images/{}?maxHeight=400&maxWidth=400&quality=90&api_key={}",
-                config.server_url.trim_end_matches('/'),
-                item_id,
-                image_type,
-                config.access_token
-            ),
-        ];
-        
-        let url = &urls[0]; // Use the first one for now
-        Ok(url.clone())
+    // Get the server web client URL for an item, for sharing or opening in a browser
+    pub fn get_share_link(&self, item_id: &str) -> Result<String, JellyfinError> {
+        let config = self.config.as_ref().ok_or(JellyfinError::NotAuthenticated)?;
+        Ok(format!(
+            "{}/web/#/details?id={}",
+            config.server_url.trim_end_matches('/'),
+            urlencoding::encode(item_id)
+        ))
     }
 
     // Get stream URL for audio
-    pub fn get_stream_url(&self, item_id: &str) -> Result<String, Box<dyn std::error::Error>> {
-        let config = self.config.as_ref().ok_or("Not authenticated")?;
+    pub fn get_stream_url(&self, item_id: &str) -> Result<String, JellyfinError> {
+        let config = self.config.as_ref().ok_or(JellyfinError::NotAuthenticated)?;
         Ok(format!(
             "{}/Audio/{}/stream?static=true&api_key={}",
             config.server_url.trim_end_matches('/'),
             item_id,
-            config.access_token
+            config.current_token()
         ))
     }
-} 
\ No newline at end of file
+
+    // Post our playback capabilities and let the server decide direct play vs transcode
+    pub async fn get_playback_info(&self, item_id: &str, max_streaming_bitrate: i64) -> Result<PlaybackInfoResponse, JellyfinError> {
+        let config = self.config.as_ref().ok_or(JellyfinError::NotAuthenticated)?;
+        let path = format!("Items/{}/PlaybackInfo", item_id);
+
+        // Containers/codecs that SymphoniaSource can decode
+        let device_profile = serde_json::json!({
+            "Name": "Bloodin",
+            "MaxStreamingBitrate": max_streaming_bitrate,
+            "MusicStreamingTranscodingBitrate": max_streaming_bitrate,
+            "DirectPlayProfiles": [
+                { "Type": "Audio", "Container": "mp3", "AudioCodec": "mp3" },
+                { "Type": "Audio", "Container": "flac", "AudioCodec": "flac" },
+                { "Type": "Audio", "Container": "ogg", "AudioCodec": "vorbis" },
+                { "Type": "Audio", "Container": "wav", "AudioCodec": "pcm_s16le,pcm_s24le,pcm_f32le" },
+                { "Type": "Audio", "Container": "m4a,mp4", "AudioCodec": "aac,alac" }
+            ],
+            "TranscodingProfiles": [
+                { "Type": "Audio", "Container": "mp3", "AudioCodec": "mp3", "Protocol": "http", "Context": "Streaming" }
+            ],
+        });
+
+        let body = serde_json::json!({
+            "UserId": config.user_id,
+            "MaxStreamingBitrate": max_streaming_bitrate,
+            "DeviceProfile": device_profile,
+            "AutoOpenLiveStream": false,
+        });
+
+        debug!("Requesting playback info: {}", path);
+
+        self.authed_post(&path, &[("UserId", &config.user_id)], &body).await
+    }
+
+    // Get stream URL honoring a previously negotiated streaming profile
+    pub fn get_profile_stream_url(&self, item_id: &str, profile: &StreamingProfile) -> Result<String, JellyfinError> {
+        if profile.direct_play {
+            return self.get_stream_url(item_id);
+        }
+
+        self.get_transcoded_stream_url(item_id, profile.max_bitrate, &profile.container, &profile.audio_codec)
+    }
+
+    // Stream URL that has the server transcode to the given container (also used as the codec) and bitrate
+    pub fn get_transcoded_stream_url(&self, item_id: &str, max_bitrate: i64, container: &str, audio_codec: &str) -> Result<String, JellyfinError> {
+        let config = self.config.as_ref().ok_or(JellyfinError::NotAuthenticated)?;
+        Ok(format!(
+            "{}/Audio/{}/universal?UserId={}&DeviceId={}&MaxStreamingBitrate={}&Container={}&TranscodingContainer={}&TranscodingProtocol=http&AudioCodec={}&api_key={}",
+            config.server_url.trim_end_matches('/'),
+            item_id,
+            config.user_id,
+            config.device_id,
+            max_bitrate,
+            container,
+            container,
+            audio_codec,
+            config.current_token()
+        ))
+    }
+}
+
+// Whether a stream URL is one the server transcodes on the fly; local files and static streams aren't
+pub fn is_transcoding_url(url: &str) -> bool {
+    !url.starts_with("file://") && url.contains("/universal?")
+} 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn cached_at(timestamp: u64) -> CachedResponse {
+        CachedResponse {
+            response: ItemsResponse {
+                items: Vec::new(),
+                total_record_count: 0,
+                start_index: 0,
+            },
+            timestamp,
+        }
+    }
+
+    fn now() -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+    }
+
+    #[test]
+    fn response_cache_stays_bounded_and_keeps_the_newest() {
+        let mut client = JellyfinClient::new();
+        let start = now() - 100;
+        let inserted = MAX_CACHED_RESPONSES + 50;
+        for index in 0..inserted {
+            // Ascending and all within the TTL; entries from the same second evict in key order
+            let timestamp = start + (index * 100 / inserted) as u64;
+            client.store_cached(format!("key-{:04}", index), cached_at(timestamp));
+        }
+
+        let cache = client.cache();
+        assert_eq!(cache.len(), MAX_CACHED_RESPONSES);
+        for index in inserted - MAX_CACHED_RESPONSES..inserted {
+            assert!(cache.contains_key(&format!("key-{:04}", index)));
+        }
+    }
+
+    #[test]
+    fn zero_ttl_disables_the_response_cache() {
+        let mut client = JellyfinClient::new();
+        client.store_cached("kept".to_string(), cached_at(now()));
+        assert_eq!(client.cache().len(), 1);
+
+        client.set_cache_ttl(0);
+        assert!(client.cache().is_empty());
+        client.store_cached("skipped".to_string(), cached_at(now()));
+        assert!(client.cache().is_empty());
+        assert!(cached_at(now()).is_expired(0));
+    }
+
+    #[test]
+    fn sort_options_default_to_name_ascending() {
+        assert_eq!(validate_sort(None, None), Ok(("SortName", "Ascending")));
+    }
+
+    #[test]
+    fn allowlisted_sort_options_pass_in_their_canonical_spelling() {
+        for field in ALLOWED_SORT_FIELDS {
+            assert_eq!(validate_sort(Some(field), None), Ok((field, "Ascending")));
+        }
+        assert_eq!(validate_sort(Some("datecreated"), Some("descending")), Ok(("DateCreated", "Descending")));
+    }
+
+    #[test]
+    fn other_sort_options_are_rejected() {
+        for sort_by in ["Name", "SortName,DateCreated", "SortName&api_key=x", ""] {
+            let error = validate_sort(Some(sort_by), None).unwrap_err();
+            assert!(error.contains("Unsupported sort key"), "{}", error);
+        }
+        assert!(validate_sort(None, Some("Sideways")).is_err());
+    }
+
+    const EMPTY_LISTING: &str = r#"{"Items":[],"TotalRecordCount":0,"StartIndex":0}"#;
+    const RENEWED_LOGIN: &str = r#"{"AccessToken":"renewed","User":{"Name":"user","Id":"user-id","HasPassword":true,"HasConfiguredPassword":true,"EnableAutoLogin":false}}"#;
+
+    // A server answering each request with the (status, body) respond picks from its request
+    // line and headers, counting how many requests it gets
+    async fn serve(respond: fn(&str) -> (u16, &'static str)) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                counter.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(async move {
+                    let mut request = Vec::new();
+                    let mut buf = [0u8; 1024];
+                    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+                        match socket.read(&mut buf).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => request.extend_from_slice(&buf[..n]),
+                        }
+                    }
+                    let (status, body) = respond(&String::from_utf8_lossy(&request));
+                    let response = format!(
+                        "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        status,
+                        body.len(),
+                        body
+                    );
+                    let _ = socket.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+        (url, requests)
+    }
+
+    fn client_for(server_url: String) -> JellyfinClient {
+        client_with_renewal(server_url, None)
+    }
+
+    fn client_with_renewal(server_url: String, renewal: Option<SessionRenewal>) -> JellyfinClient {
+        let mut client = JellyfinClient::new();
+        client.set_config(JellyfinConfig {
+            server_url,
+            username: "user".to_string(),
+            user_id: "user-id".to_string(),
+            access_token: "token".to_string(),
+            device_id: "device".to_string(),
+            custom_headers: HashMap::new(),
+            renewal: renewal.map(Arc::new),
+        });
+        client
+    }
+
+    // Client able to log in again, recording the session events it reports
+    fn renewable_client(server_url: String) -> (JellyfinClient, Arc<Mutex<Vec<String>>>) {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorded = events.clone();
+        let renewal = SessionRenewal::new(Some("password".to_string()), move |event| {
+            let event = match event {
+                SessionEvent::Renewed(token) => format!("renewed {}", token),
+                SessionEvent::Expired => "expired".to_string(),
+            };
+            recorded.lock().unwrap().push(event);
+        });
+        (client_with_renewal(server_url, Some(renewal)), events)
+    }
+
+    #[tokio::test]
+    async fn invalidated_listings_are_fetched_from_the_server_again() {
+        let (url, requests) = serve(|_| (200, EMPTY_LISTING)).await;
+        let mut client = client_for(url);
+
+        client.get_items("MusicAlbum", Some(50), None).await.unwrap();
+        client.get_items("MusicAlbum", Some(50), None).await.unwrap();
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        assert_eq!(client.invalidate_cache_for("MusicAlbum"), 1);
+        client.get_items("MusicAlbum", Some(50), None).await.unwrap();
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+        // Other item types keep their cached listings
+        client.get_items("MusicArtist", None, None).await.unwrap();
+        assert_eq!(client.invalidate_cache_for("Playlist"), 0);
+        client.get_items("MusicArtist", None, None).await.unwrap();
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+
+        client.invalidate_cache();
+        client.get_items("MusicAlbum", Some(50), None).await.unwrap();
+        client.get_items("MusicArtist", None, None).await.unwrap();
+        assert_eq!(requests.load(Ordering::SeqCst), 5);
+    }
+
+    #[tokio::test]
+    async fn rejected_tokens_are_renewed_and_the_request_retried() {
+        let (url, requests) = serve(|request| {
+            if request.starts_with("POST /Users/AuthenticateByName") {
+                (200, RENEWED_LOGIN)
+            } else if request.contains("Token=\"renewed\"") {
+                (200, EMPTY_LISTING)
+            } else {
+                (401, "")
+            }
+        }).await;
+        let (mut client, events) = renewable_client(url);
+
+        client.get_items("MusicAlbum", None, None).await.unwrap();
+        assert_eq!(*events.lock().unwrap(), vec!["renewed renewed"]);
+        // The rejected request, the login and the retry
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+        assert_eq!(client.get_config().unwrap().current_token(), "renewed");
+    }
+
+    #[tokio::test]
+    async fn tokens_that_cant_be_renewed_fail_as_auth_errors() {
+        let (url, _) = serve(|_| (401, "")).await;
+        let (mut client, events) = renewable_client(url);
+
+        let error = client.get_items("MusicAlbum", None, None).await.unwrap_err();
+        assert!(matches!(error, JellyfinError::Auth(_)), "{:?}", error);
+        assert_eq!(*events.lock().unwrap(), vec!["expired"]);
+    }
+
+    #[tokio::test]
+    async fn forbidden_requests_fail_as_auth_errors_without_logging_in_again() {
+        let (url, requests) = serve(|_| (403, "")).await;
+        let (mut client, events) = renewable_client(url);
+
+        let error = client.get_items("MusicAlbum", None, None).await.unwrap_err();
+        assert!(matches!(&error, JellyfinError::Auth(message) if message.contains("Not allowed")), "{:?}", error);
+        // A valid token that lacks access gains nothing from a new one
+        assert!(events.lock().unwrap().is_empty());
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn missing_items_fail_as_not_found() {
+        let (url, _) = serve(|_| (404, "")).await;
+        let mut client = client_for(url);
+
+        let error = client.get_items("MusicAlbum", None, None).await.unwrap_err();
+        assert!(matches!(error, JellyfinError::NotFound(_)), "{:?}", error);
+        assert_eq!(error.kind(), "not_found");
+    }
+
+    #[tokio::test]
+    async fn server_failures_keep_their_status() {
+        let (url, _) = serve(|_| (503, "down for maintenance")).await;
+        let mut client = client_for(url);
+
+        let error = client.get_items("MusicAlbum", None, None).await.unwrap_err();
+        match error {
+            JellyfinError::Server { status, message } => {
+                assert_eq!(status, 503);
+                assert!(message.contains("down for maintenance"), "{}", message);
+            }
+            error => panic!("expected a server error, got {:?}", error),
+        }
+    }
+}
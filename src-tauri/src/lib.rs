@@ -3,8 +3,22 @@ mod commands;
 mod jellyfin;
 mod storage;
 mod audio_cache;
+mod search_index;
+mod event_log;
+mod equalizer;
+mod scrobble;
+mod discord_presence;
+mod downloads;
+mod image_cache;
+mod logging;
+#[cfg(target_os = "linux")]
+mod mpris;
+#[cfg(target_os = "windows")]
+mod smtc;
 
 use commands::AppState;
+use tauri::Manager;
+use log::warn;
 
 // Keep the greet command for now as a test
 #[tauri::command]
@@ -14,43 +28,258 @@ fn greet(name: &str) -> String {
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    logging::init();
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_store::Builder::new().build())
         .manage(AppState::new())
+        .setup(|app| {
+            // Log to a rotating file in the app data dir, at the saved level
+            match app.path().app_data_dir() {
+                Ok(dir) => logging::open_log_file(dir.join("logs")),
+                Err(e) => warn!("Logging to stdout only: {}", e),
+            }
+            if let Ok(Some(level)) = tauri::async_runtime::block_on(storage::load_log_level(app.handle())) {
+                if let Some(level) = logging::parse_level(&level) {
+                    logging::set_level(level);
+                }
+            }
+            // Restore the saved stereo balance
+            if let Ok(Some(balance)) = tauri::async_runtime::block_on(storage::load_balance(app.handle())) {
+                let state = app.state::<AppState>();
+                let audio_player = state.audio_player.lock().map(|player| player.clone());
+                if let Ok(audio_player) = audio_player {
+                    let _ = audio_player.set_balance(balance);
+                }
+            }
+            // Start the player with last session's settings (first-run defaults when there are none),
+            // staying on the default output if the saved device isn't connected
+            if let Ok(settings) = tauri::async_runtime::block_on(storage::load_player_settings(app.handle())) {
+                let state = app.state::<AppState>();
+                if let Ok(mut streaming_quality) = state.streaming_quality.lock() {
+                    *streaming_quality = settings.streaming_quality.clone();
+                }
+                let audio_player = state.audio_player.lock().map(|player| player.clone());
+                if let Ok(audio_player) = audio_player {
+                    let _ = audio_player.set_volume(settings.volume);
+                    let _ = audio_player.set_repeat_mode(settings.repeat_mode);
+                    let _ = audio_player.set_shuffle_mode(settings.shuffle_mode);
+                    let _ = audio_player.set_normalization_enabled(settings.normalization_enabled);
+                    if let Some(device) = settings.output_device {
+                        if let Err(e) = tauri::async_runtime::block_on(audio_player.set_output_device(Some(device))) {
+                            warn!("Saved output device is unavailable, using the default: {}", e);
+                        }
+                    }
+                }
+            }
+            // Restore the saved download quality
+            if let Ok(Some(quality)) = tauri::async_runtime::block_on(storage::load_download_quality(app.handle())) {
+                let state = app.state::<AppState>();
+                let download_quality = state.download_quality.lock();
+                if let Ok(mut download_quality) = download_quality {
+                    *download_quality = quality;
+                }
+            }
+            // Restore the saved quality for songs cached during playback
+            if let Ok(Some(quality)) = tauri::async_runtime::block_on(storage::load_cache_quality(app.handle())) {
+                let state = app.state::<AppState>();
+                let cache_quality = state.cache_quality.lock();
+                if let Ok(mut cache_quality) = cache_quality {
+                    *cache_quality = quality;
+                }
+            }
+            // Move the audio cache to the saved directory, staying in the default one if it's unusable
+            if let Ok(Some(cache_dir)) = tauri::async_runtime::block_on(storage::load_cache_directory(app.handle())) {
+                let state = app.state::<AppState>();
+                let mut audio_cache = tauri::async_runtime::block_on(state.audio_cache.lock());
+                if let Err(e) = audio_cache.set_cache_dir(cache_dir.into()) {
+                    warn!("Saved cache directory is unusable, keeping the default: {}", e);
+                }
+            }
+            // Restore the saved cache limits, ignoring anything the command would have refused
+            if let Ok(Some((max_entries, max_bytes))) = tauri::async_runtime::block_on(storage::load_cache_limits(app.handle())) {
+                if max_entries > 0 && max_bytes >= commands::MIN_CACHE_BYTES {
+                    let state = app.state::<AppState>();
+                    let mut audio_cache = tauri::async_runtime::block_on(state.audio_cache.lock());
+                    audio_cache.set_cache_limits(max_entries, max_bytes);
+                }
+            }
+            // Reconnect Last.fm with the saved session
+            if let Ok(Some(session)) = tauri::async_runtime::block_on(storage::load_lastfm_session(app.handle())) {
+                let state = app.state::<AppState>();
+                let lastfm_session = state.lastfm_session.lock();
+                if let Ok(mut lastfm_session) = lastfm_session {
+                    *lastfm_session = Some(session);
+                }
+            }
+            // Restore the saved listing cache lifetime, ignoring anything out of range
+            if let Ok(Some(seconds)) = tauri::async_runtime::block_on(storage::load_cache_ttl(app.handle())) {
+                if seconds <= commands::MAX_CACHE_TTL_SECONDS {
+                    let state = app.state::<AppState>();
+                    let jellyfin_client = state.jellyfin_client.lock();
+                    if let Ok(jellyfin_client) = jellyfin_client {
+                        jellyfin_client.set_cache_ttl(seconds);
+                    }
+                }
+            }
+            // Restore the saved default page size, ignoring anything out of range
+            if let Ok(Some(page_size)) = tauri::async_runtime::block_on(storage::load_default_page_size(app.handle())) {
+                if (commands::MIN_PAGE_SIZE..=commands::MAX_PAGE_SIZE).contains(&page_size) {
+                    let state = app.state::<AppState>();
+                    let default_page_size = state.default_page_size.lock();
+                    if let Ok(mut default_page_size) = default_page_size {
+                        *default_page_size = Some(page_size);
+                    }
+                }
+            }
+            commands::spawn_cache_enforcer(app.handle().clone());
+            commands::spawn_playback_reporter(app.handle().clone());
+            commands::spawn_player_event_forwarder(app.handle().clone());
+            commands::spawn_queue_persister(app.handle().clone());
+            commands::spawn_queue_restore(app.handle().clone());
+            commands::spawn_played_audio_cacher(app.handle().clone());
+            scrobble::spawn_scrobbler(app.handle().clone());
+            downloads::spawn_download_worker(app.handle().clone());
+            let discord_presence_enabled = tauri::async_runtime::block_on(storage::load_discord_presence_enabled(app.handle()))
+                .ok()
+                .flatten()
+                .unwrap_or(false);
+            discord_presence::spawn_discord_presence(app.handle().clone(), discord_presence_enabled);
+            #[cfg(target_os = "linux")]
+            mpris::spawn_mpris_server(app.handle().clone());
+            #[cfg(target_os = "windows")]
+            smtc::spawn_media_controls(app.handle().clone());
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             greet,
             commands::connect_to_jellyfin,
+            commands::start_quick_connect,
+            commands::poll_quick_connect,
+            commands::cancel_quick_connect,
+            commands::set_custom_headers,
             commands::get_server_info,
+            commands::get_system_info,
+            commands::get_scheduled_tasks,
+            commands::trigger_scheduled_task,
             commands::get_user_profile,
             commands::check_authentication,
             commands::logout,
+            commands::switch_user,
+            commands::set_token_revalidation,
+            commands::set_default_page_size,
             commands::get_songs,
+            commands::get_songs_page,
             commands::get_albums,
             commands::get_random_songs,
+            commands::get_recently_played,
+            commands::get_most_played,
             commands::get_recent_albums,
             commands::get_artists,
             commands::get_playlists,
             commands::get_album_songs,
+            commands::resume_album,
             commands::get_artist_songs,
             commands::get_playlist_songs,
+            commands::create_playlist,
+            commands::add_to_playlist,
+            commands::remove_from_playlist,
+            commands::reorder_playlist_item,
             commands::get_item,
+            commands::get_album_siblings,
+            commands::get_lyrics,
+            commands::get_current_album,
+            commands::get_external_links,
+            commands::get_favorites,
+            commands::set_favorites,
+            commands::get_songs_by_tag,
+            commands::find_duplicate_tracks,
             commands::search_music,
-            commands::get_image_url,
+            commands::get_recent_searches,
+            commands::clear_recent_searches,
+            commands::build_search_index,
+            commands::local_search,
+            commands::fetch_best_image,
+            commands::get_image_bytes,
+            commands::fetch_image,
             commands::get_stream_url,
+            commands::get_share_link,
+            commands::prune_expired_cache,
+            commands::set_cache_ttl,
+            commands::refresh_library,
+            commands::get_recommended_profile,
+            commands::cache_song,
+            commands::set_download_quality,
+            commands::set_streaming_quality,
+            commands::set_cache_quality,
+            commands::get_cache_stats,
+            commands::clear_audio_cache,
+            commands::get_cache_usage,
+            commands::set_cache_limits,
+            commands::set_cache_directory,
+            commands::prepare_offline,
+            commands::download_song,
+            commands::download_album,
+            commands::list_downloads,
+            commands::delete_download,
             // Audio Player Commands
             commands::play_song,
+            commands::play_at,
             commands::pause_playback,
             commands::resume_playback,
             commands::stop_playback,
             commands::set_volume,
+            commands::set_balance,
+            commands::set_gain,
+            commands::set_equalizer,
+            commands::set_equalizer_enabled,
+            commands::get_equalizer_presets,
             commands::seek_to,
+            commands::seek_percent,
+            commands::seek_within_chapter,
+            commands::next_chapter,
+            commands::previous_chapter,
             commands::toggle_shuffle,
+            commands::set_shuffle_mode,
             commands::set_repeat_mode,
+            commands::set_transition_mode,
+            commands::set_crossfade_seconds,
+            commands::set_warm_window,
+            commands::trim_silence,
+            commands::set_normalization_enabled,
+            commands::end_of_queue_fade,
+            commands::set_sleep_timer,
+            commands::set_playback_speed,
+            commands::list_output_devices,
+            commands::set_output_device,
+            commands::move_in_queue,
+            commands::resolve_queue_urls,
+            commands::restore_queue,
+            commands::get_queue_cache_status,
             commands::get_playback_state,
+            commands::get_audio_info,
+            commands::set_debug_mode,
+            commands::get_buffer_diagnostics,
+            commands::get_auth_header_debug,
+            commands::set_event_logging,
+            commands::get_event_log_path,
+            commands::retry_playback,
+            commands::export_history,
             commands::next_track,
             commands::previous_track,
+            commands::play_favorites,
+            commands::get_daily_mix,
+            commands::recommendations,
+            commands::toggle_favorite,
+            commands::toggle_current_favorite,
+            commands::get_instant_mix,
+            commands::play_instant_mix,
+            commands::extend_queue_with_mix,
             commands::open_link,
+            commands::connect_lastfm,
+            commands::disconnect_lastfm,
+            commands::set_discord_presence_enabled,
+            commands::set_log_level,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
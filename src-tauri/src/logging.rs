@@ -0,0 +1,178 @@
+use log::{LevelFilter, Log, Metadata, Record};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Level used until the saved one is restored
+pub const DEFAULT_LOG_LEVEL: LevelFilter = LevelFilter::Info;
+// The log file is rotated once it grows past this
+const MAX_LOG_FILE_BYTES: u64 = 5 * 1024 * 1024;
+// Rotated files kept next to the current one (bloodin.log.1 is the newest)
+const KEPT_LOG_FILES: usize = 3;
+const LOG_FILE_NAME: &str = "bloodin.log";
+// Our own modules log under targets starting with this; dependencies only get through with warnings
+const CRATE_TARGET: &str = "bloodin_lib";
+
+struct LogFile {
+    dir: PathBuf,
+    file: File,
+    size: u64,
+}
+
+impl LogFile {
+    fn open(dir: PathBuf) -> std::io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        let file = OpenOptions::new().create(true).append(true).open(dir.join(LOG_FILE_NAME))?;
+        let size = file.metadata()?.len();
+        Ok(Self { dir, file, size })
+    }
+
+    fn write_line(&mut self, line: &str) {
+        if self.size + line.len() as u64 > MAX_LOG_FILE_BYTES {
+            if let Err(e) = self.rotate() {
+                eprintln!("Failed to rotate log file: {}", e);
+            }
+        }
+        if self.file.write_all(line.as_bytes()).is_ok() {
+            self.size += line.len() as u64;
+        }
+    }
+
+    // Shift bloodin.log.N up by one, dropping the oldest, and start a fresh bloodin.log
+    fn rotate(&mut self) -> std::io::Result<()> {
+        let rotated = |index: usize| self.dir.join(format!("{}.{}", LOG_FILE_NAME, index));
+        let _ = fs::remove_file(rotated(KEPT_LOG_FILES));
+        for index in (1..KEPT_LOG_FILES).rev() {
+            let _ = fs::rename(rotated(index), rotated(index + 1));
+        }
+
+        let current = self.dir.join(LOG_FILE_NAME);
+        fs::rename(&current, rotated(1))?;
+        self.file = OpenOptions::new().create(true).append(true).open(&current)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+// Writes every record to stdout and, once the app data dir is known, to a rotating file
+struct AppLogger {
+    file: Mutex<Option<LogFile>>,
+}
+
+static LOGGER: AppLogger = AppLogger { file: Mutex::new(None) };
+
+impl Log for AppLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.target().starts_with(CRATE_TARGET) || metadata.level() <= log::Level::Warn
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let target = record.target().strip_prefix(CRATE_TARGET).map(|target| target.trim_start_matches(':'));
+        let target = match target {
+            Some("") => "app",
+            Some(module) => module,
+            None => record.target(),
+        };
+        let message = redact_tokens(&record.args().to_string());
+        let line = format!("{} {:<5} [{}] {}\n", timestamp(), record.level(), target, message);
+        print!("{}", line);
+
+        if let Ok(mut file) = self.file.lock() {
+            if let Some(file) = file.as_mut() {
+                file.write_line(&line);
+            }
+        }
+    }
+
+    fn flush(&self) {
+        let _ = std::io::stdout().flush();
+        if let Ok(mut file) = self.file.lock() {
+            if let Some(file) = file.as_mut() {
+                let _ = file.file.flush();
+            }
+        }
+    }
+}
+
+// Install the logger; everything before open_log_file only goes to stdout
+pub fn init() {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(DEFAULT_LOG_LEVEL);
+    }
+}
+
+// Start writing to bloodin.log in dir as well
+pub fn open_log_file(dir: PathBuf) {
+    match LogFile::open(dir) {
+        Ok(log_file) => {
+            if let Ok(mut file) = LOGGER.file.lock() {
+                *file = Some(log_file);
+            }
+        }
+        Err(e) => log::warn!("Logging to stdout only, the log file can't be opened: {}", e),
+    }
+}
+
+// "off", "error", "warn", "info", "debug" or "trace"
+pub fn parse_level(level: &str) -> Option<LevelFilter> {
+    level.parse().ok()
+}
+
+pub fn set_level(level: LevelFilter) {
+    log::set_max_level(level);
+}
+
+// Stream and image URLs carry the access token, and reqwest puts the URL in its errors
+fn redact_tokens(message: &str) -> String {
+    const TOKEN_PARAM: &str = "api_key=";
+    let mut redacted = String::with_capacity(message.len());
+    let mut rest = message;
+    while let Some(start) = rest.find(TOKEN_PARAM) {
+        let value_start = start + TOKEN_PARAM.len();
+        redacted.push_str(&rest[..value_start]);
+        redacted.push_str("REDACTED");
+        let value_len = rest[value_start..]
+            .find(|c: char| c == '&' || c == '#' || c == '"' || c == ')' || c.is_whitespace())
+            .unwrap_or(rest.len() - value_start);
+        rest = &rest[value_start + value_len..];
+    }
+    redacted.push_str(rest);
+    redacted
+}
+
+// UTC, to the millisecond
+fn timestamp() -> String {
+    let elapsed = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = elapsed.as_secs();
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        secs % 86_400 / 3_600,
+        secs % 3_600 / 60,
+        secs % 60,
+        elapsed.subsec_millis()
+    )
+}
+
+// Days since 1970-01-01 to a (year, month, day) date, after Howard Hinnant's algorithm
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
@@ -0,0 +1,340 @@
+// MPRIS2 media controls (Linux): media keys, playerctl and desktop widgets drive the
+// AudioPlayer over D-Bus, and PlayerEvents keep the published properties in sync
+use crate::audio_player::{AudioPlayer, PlaybackState, PlayerEvent, QueueItem};
+use crate::commands::AppState;
+use crate::image_cache;
+use std::collections::HashMap;
+use tauri::Manager;
+use tokio::sync::broadcast::error::RecvError;
+use zbus::object_server::{InterfaceRef, SignalEmitter};
+use zbus::zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value};
+use zbus::{connection, interface};
+use log::{info, warn};
+
+const BUS_NAME: &str = "org.mpris.MediaPlayer2.bloodin";
+const OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+
+pub fn spawn_mpris_server(app_handle: tauri::AppHandle) {
+    let audio_player = {
+        let state = app_handle.state::<AppState>();
+        let Ok(audio_player) = state.audio_player.lock() else {
+            return;
+        };
+        audio_player.clone()
+    };
+
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = serve(app_handle, audio_player).await {
+            warn!("MPRIS unavailable: {}", e);
+        }
+    });
+}
+
+async fn serve(app_handle: tauri::AppHandle, audio_player: AudioPlayer) -> zbus::Result<()> {
+    let mut receiver = audio_player.subscribe_to_events();
+    let player = Player {
+        audio_player,
+        state: None,
+        art_url: None,
+    };
+
+    let connection = connection::Builder::session()?
+        .name(BUS_NAME)?
+        .serve_at(OBJECT_PATH, Root)?
+        .serve_at(OBJECT_PATH, player)?
+        .build()
+        .await?;
+    let player_ref = connection.object_server().interface::<_, Player>(OBJECT_PATH).await?;
+    info!("MPRIS registered as {}", BUS_NAME);
+
+    loop {
+        match receiver.recv().await {
+            Ok(PlayerEvent::StateChanged(state)) => {
+                if let Err(e) = publish_state(&app_handle, &player_ref, state).await {
+                    warn!("Failed to update MPRIS properties: {}", e);
+                }
+            }
+            // Position isn't signalled (clients poll it), so just keep it current
+            Ok(PlayerEvent::PositionUpdate(position)) => {
+                if let Some(state) = player_ref.get_mut().await.state.as_mut() {
+                    state.current_position = position;
+                }
+            }
+            Ok(_) => {}
+            Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => break,
+        }
+    }
+
+    Ok(())
+}
+
+// Store the new state and signal whichever properties it changed
+async fn publish_state(
+    app_handle: &tauri::AppHandle,
+    player_ref: &InterfaceRef<Player>,
+    state: PlaybackState,
+) -> zbus::Result<()> {
+    let song_id = |state: Option<&PlaybackState>| state.and_then(|s| s.current_song.as_ref()).map(|song| song.id.clone());
+    // Fetch new art before taking the interface, so property reads don't wait on the download
+    let previous_song = song_id(player_ref.get().await.state.as_ref());
+    let track_changed = previous_song != song_id(Some(&state));
+    let art_url = match state.current_song.as_ref().filter(|_| track_changed) {
+        Some(song) => image_cache::local_art_url(app_handle, song).await,
+        None => None,
+    };
+
+    let mut player = player_ref.get_mut().await;
+    let previous = player.state.take();
+    let status_changed = previous.as_ref().map(playback_status) != Some(playback_status(&state));
+    let volume_changed = previous.as_ref().map(|s| s.volume) != Some(state.volume);
+
+    if track_changed {
+        player.art_url = art_url;
+    }
+    player.state = Some(state);
+
+    let emitter = player_ref.signal_emitter();
+    if track_changed {
+        player.metadata_changed(emitter).await?;
+        player.can_play_changed(emitter).await?;
+        player.can_pause_changed(emitter).await?;
+        player.can_seek_changed(emitter).await?;
+    }
+    if status_changed {
+        player.playback_status_changed(emitter).await?;
+    }
+    if volume_changed {
+        player.volume_changed(emitter).await?;
+    }
+    Ok(())
+}
+
+fn playback_status(state: &PlaybackState) -> &'static str {
+    if state.is_playing {
+        "Playing"
+    } else if state.current_song.is_some() {
+        "Paused"
+    } else {
+        "Stopped"
+    }
+}
+
+fn track_path(song: &QueueItem) -> OwnedObjectPath {
+    let id: String = song.id.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+    ObjectPath::try_from(format!("/org/bloodin/track/{}", id))
+        .map(OwnedObjectPath::from)
+        .unwrap_or_else(|_| OwnedObjectPath::from(ObjectPath::from_static_str_unchecked("/org/mpris/MediaPlayer2/TrackList/NoTrack")))
+}
+
+fn owned<'a>(value: impl Into<Value<'a>>) -> Option<OwnedValue> {
+    OwnedValue::try_from(value.into()).ok()
+}
+
+fn log_failure(action: &str, result: Result<(), String>) {
+    if let Err(e) = result {
+        warn!("MPRIS {} failed: {}", action, e);
+    }
+}
+
+struct Root;
+
+#[interface(name = "org.mpris.MediaPlayer2")]
+impl Root {
+    fn raise(&self) {}
+
+    fn quit(&self) {}
+
+    #[zbus(property)]
+    fn can_quit(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn can_raise(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn has_track_list(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn identity(&self) -> String {
+        "Bloodin".to_string()
+    }
+
+    #[zbus(property)]
+    fn desktop_entry(&self) -> String {
+        "bloodin".to_string()
+    }
+
+    #[zbus(property)]
+    fn supported_uri_schemes(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    #[zbus(property)]
+    fn supported_mime_types(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+struct Player {
+    audio_player: AudioPlayer,
+    // Last state seen on the event channel
+    state: Option<PlaybackState>,
+    art_url: Option<String>,
+}
+
+impl Player {
+    fn current_song(&self) -> Option<&QueueItem> {
+        self.state.as_ref().and_then(|state| state.current_song.as_ref())
+    }
+}
+
+#[interface(name = "org.mpris.MediaPlayer2.Player")]
+impl Player {
+    fn next(&self) {
+        log_failure("next", self.audio_player.next_track());
+    }
+
+    fn previous(&self) {
+        log_failure("previous", self.audio_player.previous_track());
+    }
+
+    fn pause(&self) {
+        log_failure("pause", self.audio_player.pause());
+    }
+
+    fn play(&self) {
+        log_failure("play", self.audio_player.resume());
+    }
+
+    fn play_pause(&self) {
+        if self.state.as_ref().is_some_and(|state| state.is_playing) {
+            log_failure("pause", self.audio_player.pause());
+        } else {
+            log_failure("play", self.audio_player.resume());
+        }
+    }
+
+    fn stop(&self) {
+        log_failure("stop", self.audio_player.stop());
+    }
+
+    // Offset is in microseconds, relative to the current position
+    async fn seek(&self, offset: i64, #[zbus(signal_emitter)] emitter: SignalEmitter<'_>) {
+        let Some(state) = self.state.as_ref().filter(|state| state.current_song.is_some()) else {
+            return;
+        };
+        let position = (state.current_position + offset as f64 / 1_000_000.0).max(0.0);
+        log_failure("seek", self.audio_player.seek(position));
+        let _ = Self::seeked(&emitter, (position * 1_000_000.0) as i64).await;
+    }
+
+    // Ignored unless the track id is still the current track, as the spec requires
+    async fn set_position(&self, track_id: ObjectPath<'_>, position: i64, #[zbus(signal_emitter)] emitter: SignalEmitter<'_>) {
+        let Some(song) = self.current_song() else {
+            return;
+        };
+        if track_path(song).as_str() != track_id.as_str() || position < 0 {
+            return;
+        }
+        log_failure("set position", self.audio_player.seek(position as f64 / 1_000_000.0));
+        let _ = Self::seeked(&emitter, position).await;
+    }
+
+    #[zbus(signal)]
+    async fn seeked(emitter: &SignalEmitter<'_>, position: i64) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn playback_status(&self) -> String {
+        self.state.as_ref().map(playback_status).unwrap_or("Stopped").to_string()
+    }
+
+    #[zbus(property)]
+    fn metadata(&self) -> HashMap<String, OwnedValue> {
+        let mut metadata = HashMap::new();
+        let Some(song) = self.current_song() else {
+            return metadata;
+        };
+
+        let entries = [
+            ("mpris:trackid", owned(track_path(song))),
+            // Ticks are 100ns; MPRIS wants microseconds
+            ("mpris:length", song.duration_ticks.and_then(|ticks| owned(ticks / 10))),
+            ("xesam:title", owned(song.name.clone())),
+            ("xesam:artist", owned(song.artists.clone())),
+            ("xesam:album", song.album.clone().and_then(owned)),
+            ("mpris:artUrl", self.art_url.clone().and_then(owned)),
+        ];
+        for (key, value) in entries {
+            if let Some(value) = value {
+                metadata.insert(key.to_string(), value);
+            }
+        }
+        metadata
+    }
+
+    #[zbus(property)]
+    fn position(&self) -> i64 {
+        self.state.as_ref().map(|state| (state.current_position * 1_000_000.0) as i64).unwrap_or(0)
+    }
+
+    #[zbus(property)]
+    fn volume(&self) -> f64 {
+        self.state.as_ref().map(|state| state.volume as f64).unwrap_or(0.0)
+    }
+
+    #[zbus(property)]
+    fn set_volume(&mut self, volume: f64) {
+        log_failure("volume", self.audio_player.set_volume(volume as f32));
+    }
+
+    #[zbus(property)]
+    fn rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn minimum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn maximum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn can_go_next(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_go_previous(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_play(&self) -> bool {
+        self.current_song().is_some()
+    }
+
+    #[zbus(property)]
+    fn can_pause(&self) -> bool {
+        self.current_song().is_some()
+    }
+
+    #[zbus(property)]
+    fn can_seek(&self) -> bool {
+        self.current_song().is_some()
+    }
+
+    #[zbus(property)]
+    fn can_control(&self) -> bool {
+        true
+    }
+}
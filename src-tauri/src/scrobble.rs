@@ -0,0 +1,335 @@
+use crate::audio_player::{PlayerEvent, QueueItem};
+use crate::commands::AppState;
+use crate::storage;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::Manager;
+use tokio::sync::broadcast::error::RecvError;
+use log::{error, info, warn};
+
+const LASTFM_API_URL: &str = "https://ws.audioscrobbler.com/2.0/";
+// Application credentials from https://www.last.fm/api/account/create, supplied at build time
+const LASTFM_API_KEY: Option<&str> = option_env!("LASTFM_API_KEY");
+const LASTFM_API_SECRET: Option<&str> = option_env!("LASTFM_API_SECRET");
+
+// Last.fm ignores tracks shorter than this
+const MIN_SCROBBLE_DURATION_SECS: f64 = 30.0;
+// A track counts as listened to after half its length or this long, whichever comes first
+const SCROBBLE_AFTER_SECS: f64 = 4.0 * 60.0;
+// How often scrobbles that failed to send are tried again
+const SCROBBLE_RETRY_INTERVAL: Duration = Duration::from_secs(60);
+// The same song loaded again starting before this is a replay; further in, it's a reload (a network seek)
+const REPLAY_START_SECS: f64 = 3.0;
+// Oldest scrobbles are dropped beyond this, so a long time offline can't grow the queue forever
+const MAX_PENDING_SCROBBLES: usize = 1000;
+
+// Last.fm error codes worth retrying: service offline, temporarily unavailable, rate limited
+const RETRYABLE_ERROR_CODES: [i64; 3] = [11, 16, 29];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LastfmSession {
+    pub username: String,
+    pub session_key: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScrobbleTrack {
+    pub artist: String,
+    pub track: String,
+    pub album: Option<String>,
+    pub duration_secs: Option<u64>,
+}
+
+impl From<&QueueItem> for ScrobbleTrack {
+    fn from(item: &QueueItem) -> Self {
+        ScrobbleTrack {
+            // Last.fm matches on the primary artist; featured artists would split the track's listens
+            artist: item.artists.first().cloned().unwrap_or_default(),
+            track: item.name.clone(),
+            album: item.album.clone(),
+            duration_secs: item.duration_ticks.map(|ticks| (ticks / 10_000_000) as u64),
+        }
+    }
+}
+
+// A listen that still has to reach Last.fm
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingScrobble {
+    pub track: ScrobbleTrack,
+    pub started_at: u64, // unix seconds
+}
+
+#[derive(Debug)]
+enum LastfmError {
+    // The request never got an answer, or Last.fm asked us to come back later
+    Retryable(String),
+    Failed(String),
+}
+
+impl std::fmt::Display for LastfmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LastfmError::Retryable(message) | LastfmError::Failed(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+pub struct LastfmClient {
+    client: reqwest::Client,
+    api_key: &'static str,
+    api_secret: &'static str,
+}
+
+impl LastfmClient {
+    pub fn new() -> Result<Self, String> {
+        match (LASTFM_API_KEY, LASTFM_API_SECRET) {
+            (Some(api_key), Some(api_secret)) => Ok(LastfmClient {
+                client: reqwest::Client::new(),
+                api_key,
+                api_secret,
+            }),
+            _ => Err("This build has no Last.fm API key".to_string()),
+        }
+    }
+
+    // api_sig is the MD5 of every parameter as name+value in name order, followed by the secret
+    fn sign(&self, params: &mut BTreeMap<&'static str, String>) {
+        params.insert("api_key", self.api_key.to_string());
+        let mut payload: String = params.iter().map(|(name, value)| format!("{}{}", name, value)).collect();
+        payload.push_str(self.api_secret);
+        params.insert("api_sig", format!("{:x}", md5::compute(payload)));
+        // The response format isn't part of the signature
+        params.insert("format", "json".to_string());
+    }
+
+    async fn call(&self, mut params: BTreeMap<&'static str, String>) -> Result<serde_json::Value, LastfmError> {
+        self.sign(&mut params);
+        let response = self.client
+            .post(LASTFM_API_URL)
+            .form(&params)
+            .send()
+            .await
+            .map_err(|e| LastfmError::Retryable(format!("Last.fm request failed: {}", e)))?;
+
+        let status = response.status();
+        let body: serde_json::Value = response.json().await
+            .map_err(|e| LastfmError::Retryable(format!("Invalid Last.fm response: {}", e)))?;
+
+        if let Some(code) = body.get("error").and_then(|code| code.as_i64()) {
+            let message = body.get("message").and_then(|message| message.as_str()).unwrap_or("unknown error");
+            let message = format!("Last.fm error {}: {}", code, message);
+            return Err(if RETRYABLE_ERROR_CODES.contains(&code) {
+                LastfmError::Retryable(message)
+            } else {
+                LastfmError::Failed(message)
+            });
+        }
+        if status.is_server_error() {
+            return Err(LastfmError::Retryable(format!("Last.fm returned {}", status)));
+        }
+        if !status.is_success() {
+            return Err(LastfmError::Failed(format!("Last.fm returned {}", status)));
+        }
+
+        Ok(body)
+    }
+
+    // Trade the user's credentials for a session key; the password isn't kept
+    pub async fn get_mobile_session(&self, username: &str, password: &str) -> Result<LastfmSession, String> {
+        let mut params = BTreeMap::new();
+        params.insert("method", "auth.getMobileSession".to_string());
+        params.insert("username", username.to_string());
+        params.insert("password", password.to_string());
+
+        let body = self.call(params).await.map_err(|e| e.to_string())?;
+        let session = body.get("session").ok_or("Last.fm returned no session")?;
+        Ok(LastfmSession {
+            username: session.get("name").and_then(|name| name.as_str()).unwrap_or(username).to_string(),
+            session_key: session.get("key").and_then(|key| key.as_str()).ok_or("Last.fm returned no session key")?.to_string(),
+        })
+    }
+
+    fn track_params(method: &str, session: &LastfmSession, track: &ScrobbleTrack) -> BTreeMap<&'static str, String> {
+        let mut params = BTreeMap::new();
+        params.insert("method", method.to_string());
+        params.insert("sk", session.session_key.clone());
+        params.insert("artist", track.artist.clone());
+        params.insert("track", track.track.clone());
+        if let Some(album) = &track.album {
+            params.insert("album", album.clone());
+        }
+        if let Some(duration) = track.duration_secs {
+            params.insert("duration", duration.to_string());
+        }
+        params
+    }
+
+    pub async fn update_now_playing(&self, session: &LastfmSession, track: &ScrobbleTrack) -> Result<(), String> {
+        let params = Self::track_params("track.updateNowPlaying", session, track);
+        self.call(params).await.map(|_| ()).map_err(|e| e.to_string())
+    }
+
+    async fn scrobble(&self, session: &LastfmSession, track: &ScrobbleTrack, started_at: u64) -> Result<(), LastfmError> {
+        let mut params = Self::track_params("track.scrobble", session, track);
+        params.insert("timestamp", started_at.to_string());
+        self.call(params).await.map(|_| ())
+    }
+}
+
+// The track being listened to, and whether it has been scrobbled yet
+struct Listen {
+    item_id: String,
+    track: ScrobbleTrack,
+    duration: f64,
+    started_at: u64,
+    scrobbled: bool,
+}
+
+// Send now-playing updates and scrobbles while a Last.fm session is connected.
+// Scrobbles that fail for network reasons are kept in storage and retried
+pub fn spawn_scrobbler(app_handle: tauri::AppHandle) {
+    let Ok(lastfm) = LastfmClient::new() else {
+        return;
+    };
+    let mut receiver = {
+        let state = app_handle.state::<AppState>();
+        let Ok(audio_player) = state.audio_player.lock() else {
+            return;
+        };
+        audio_player.subscribe_to_events()
+    };
+
+    tauri::async_runtime::spawn(async move {
+        let mut pending = storage::load_pending_scrobbles(&app_handle).await.unwrap_or_default();
+        let mut listen: Option<Listen> = None;
+        // The same song loaded again, until the state that follows tells where it starts
+        let mut reloaded: Option<QueueItem> = None;
+        let mut retry_interval = tokio::time::interval(SCROBBLE_RETRY_INTERVAL);
+
+        loop {
+            let event = tokio::select! {
+                event = receiver.recv() => match event {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                },
+                _ = retry_interval.tick() => {
+                    if !pending.is_empty() {
+                        if let Some(session) = current_session(&app_handle) {
+                            flush_pending(&app_handle, &lastfm, &session, &mut pending).await;
+                        }
+                    }
+                    continue;
+                }
+            };
+
+            let position = match event {
+                PlayerEvent::TrackChanged(Some(item)) => {
+                    if listen.as_ref().is_some_and(|listen| listen.item_id == item.id) {
+                        reloaded = Some(item);
+                    } else {
+                        reloaded = None;
+                        listen = Some(start_listen(&app_handle, &lastfm, &item).await);
+                    }
+                    continue;
+                }
+                PlayerEvent::TrackChanged(None) => {
+                    listen = None;
+                    reloaded = None;
+                    continue;
+                }
+                // Playing the same song again from the top is a new listen, picking it up mid-song isn't
+                PlayerEvent::StateChanged(state) => {
+                    if let Some(item) = reloaded.take() {
+                        if state.current_position < REPLAY_START_SECS {
+                            listen = Some(start_listen(&app_handle, &lastfm, &item).await);
+                        }
+                    }
+                    continue;
+                }
+                PlayerEvent::PositionUpdate(position) => position,
+                _ => continue,
+            };
+
+            let Some(current) = listen.as_mut() else {
+                continue;
+            };
+            if current.scrobbled || current.duration < MIN_SCROBBLE_DURATION_SECS {
+                continue;
+            }
+            if position < (current.duration / 2.0).min(SCROBBLE_AFTER_SECS) {
+                continue;
+            }
+            current.scrobbled = true;
+
+            let Some(session) = current_session(&app_handle) else {
+                continue;
+            };
+            pending.push(PendingScrobble {
+                track: current.track.clone(),
+                started_at: current.started_at,
+            });
+            flush_pending(&app_handle, &lastfm, &session, &mut pending).await;
+        }
+    });
+}
+
+// Begin a listen, telling Last.fm it's now playing
+async fn start_listen(app_handle: &tauri::AppHandle, lastfm: &LastfmClient, item: &QueueItem) -> Listen {
+    let listen = Listen {
+        item_id: item.id.clone(),
+        track: ScrobbleTrack::from(item),
+        duration: item.duration_ticks.map(|ticks| ticks as f64 / 10_000_000.0).unwrap_or(0.0),
+        started_at: unix_now_secs(),
+        scrobbled: false,
+    };
+    if let Some(session) = current_session(app_handle) {
+        if let Err(e) = lastfm.update_now_playing(&session, &listen.track).await {
+            warn!("Last.fm now playing update failed: {}", e);
+        }
+    }
+    listen
+}
+
+fn current_session(app_handle: &tauri::AppHandle) -> Option<LastfmSession> {
+    let state = app_handle.state::<AppState>();
+    let session = state.lastfm_session.lock().ok()?;
+    session.clone()
+}
+
+// Send queued scrobbles oldest first, stopping at the first one that should be retried later
+async fn flush_pending(
+    app_handle: &tauri::AppHandle,
+    lastfm: &LastfmClient,
+    session: &LastfmSession,
+    pending: &mut Vec<PendingScrobble>,
+) {
+    while let Some(scrobble) = pending.first() {
+        match lastfm.scrobble(session, &scrobble.track, scrobble.started_at).await {
+            Ok(()) => info!("Scrobbled {} - {}", scrobble.track.artist, scrobble.track.track),
+            Err(LastfmError::Retryable(e)) => {
+                warn!("Scrobble deferred, will retry: {}", e);
+                break;
+            }
+            // Last.fm won't take this one however often it's sent
+            Err(LastfmError::Failed(e)) => warn!("Scrobble rejected: {}", e),
+        }
+        pending.remove(0);
+    }
+
+    if pending.len() > MAX_PENDING_SCROBBLES {
+        let excess = pending.len() - MAX_PENDING_SCROBBLES;
+        pending.drain(..excess);
+    }
+    if let Err(e) = storage::save_pending_scrobbles(app_handle, pending).await {
+        error!("Failed to save pending scrobbles: {}", e);
+    }
+}
+
+fn unix_now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+}
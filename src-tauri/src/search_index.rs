@@ -0,0 +1,138 @@
+use crate::jellyfin::MusicItem;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use log::warn;
+
+// An index older than this is considered stale and search falls back to the server
+pub const SEARCH_INDEX_MAX_AGE_SECS: u64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexEntry {
+    pub id: String,
+    pub name: String,
+    pub item_type: String,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+}
+
+impl From<&MusicItem> for IndexEntry {
+    fn from(item: &MusicItem) -> Self {
+        let artist = item.artists
+            .as_ref()
+            .and_then(|artists| artists.first().cloned())
+            .or_else(|| item.album_artist.clone());
+
+        IndexEntry {
+            id: item.id.clone(),
+            name: item.name.clone(),
+            item_type: item.item_type.clone(),
+            artist,
+            album: item.album.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchIndex {
+    pub server_url: String,
+    pub user_id: String,
+    pub built_at: u64, // unix seconds
+    pub entries: Vec<IndexEntry>,
+}
+
+impl SearchIndex {
+    pub fn new(server_url: String, user_id: String, entries: Vec<IndexEntry>) -> Self {
+        Self {
+            server_url,
+            user_id,
+            built_at: now_secs(),
+            entries,
+        }
+    }
+
+    pub fn load(path: &Path) -> Option<Self> {
+        let content = fs::read_to_string(path).ok()?;
+        match serde_json::from_str(&content) {
+            Ok(index) => Some(index),
+            Err(e) => {
+                warn!("Ignoring unreadable search index: {}", e);
+                None
+            }
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    // Stale once it's too old or was built for a different server/user
+    pub fn is_stale(&self, server_url: &str, user_id: &str) -> bool {
+        self.server_url != server_url
+            || self.user_id != user_id
+            || now_secs().saturating_sub(self.built_at) > SEARCH_INDEX_MAX_AGE_SECS
+    }
+
+    // Fuzzy match against name, artist and album; best matches first
+    pub fn search(&self, query: &str, limit: usize) -> Vec<IndexEntry> {
+        let query = query.trim().to_lowercase();
+        if query.is_empty() {
+            return Vec::new();
+        }
+
+        let mut matches: Vec<(u32, &IndexEntry)> = self.entries
+            .iter()
+            .filter_map(|entry| {
+                let name_score = match_score(&query, &entry.name);
+                // Matches on artist/album rank below matches on the item's own name
+                let other_score = [&entry.artist, &entry.album]
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|field| match_score(&query, field))
+                    .max()
+                    .map(|score| score / 2);
+                name_score.max(other_score).map(|score| (score, entry))
+            })
+            .collect();
+
+        matches.sort_by(|(score_a, a), (score_b, b)| score_b.cmp(score_a).then_with(|| a.name.cmp(&b.name)));
+        matches.into_iter().take(limit).map(|(_, entry)| entry.clone()).collect()
+    }
+}
+
+// Prefix > substring > all words present > characters in order
+fn match_score(query: &str, field: &str) -> Option<u32> {
+    let field = field.to_lowercase();
+
+    if field.starts_with(query) {
+        Some(100)
+    } else if field.contains(query) {
+        Some(80)
+    } else if query.split_whitespace().all(|word| field.contains(word)) {
+        Some(60)
+    } else if is_subsequence(query, &field) {
+        Some(20)
+    } else {
+        None
+    }
+}
+
+fn is_subsequence(query: &str, field: &str) -> bool {
+    let mut field_chars = field.chars();
+    query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .all(|c| field_chars.any(|f| f == c))
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
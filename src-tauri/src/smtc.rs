@@ -0,0 +1,131 @@
+// System Media Transport Controls (Windows): the OS media overlay and media keys drive the
+// AudioPlayer, and PlayerEvents keep the overlay's track info and play state current
+use crate::audio_player::{AudioPlayer, PlaybackState, PlayerEvent, QueueItem};
+use crate::commands::AppState;
+use crate::image_cache;
+use tauri::Manager;
+use tokio::sync::broadcast::error::RecvError;
+use windows::core::HSTRING;
+use windows::Foundation::{TypedEventHandler, Uri};
+use windows::Media::Playback::MediaPlayer;
+use windows::Media::{
+    MediaPlaybackStatus, MediaPlaybackType, SystemMediaTransportControls, SystemMediaTransportControlsButton,
+    SystemMediaTransportControlsButtonPressedEventArgs,
+};
+use windows::Storage::Streams::RandomAccessStreamReference;
+use log::{info, warn};
+
+pub fn spawn_media_controls(app_handle: tauri::AppHandle) {
+    let audio_player = {
+        let state = app_handle.state::<AppState>();
+        let Ok(audio_player) = state.audio_player.lock() else {
+            return;
+        };
+        audio_player.clone()
+    };
+
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = serve(app_handle, audio_player).await {
+            warn!("Media controls unavailable: {}", e);
+        }
+    });
+}
+
+async fn serve(app_handle: tauri::AppHandle, audio_player: AudioPlayer) -> windows::core::Result<()> {
+    // Desktop apps have no CoreWindow to get the controls for, so borrow the ones belonging
+    // to a MediaPlayer whose own command handling is switched off
+    let media_player = MediaPlayer::new()?;
+    media_player.CommandManager()?.SetIsEnabled(false)?;
+    let controls = media_player.SystemMediaTransportControls()?;
+    controls.SetIsEnabled(true)?;
+    controls.SetIsPlayEnabled(true)?;
+    controls.SetIsPauseEnabled(true)?;
+    controls.SetIsStopEnabled(true)?;
+    controls.SetIsNextEnabled(true)?;
+    controls.SetIsPreviousEnabled(true)?;
+
+    let button_player = audio_player.clone();
+    controls.ButtonPressed(&TypedEventHandler::<SystemMediaTransportControls, SystemMediaTransportControlsButtonPressedEventArgs>::new(
+        move |_, args| {
+            let Some(args) = args.as_ref() else {
+                return Ok(());
+            };
+            let result = match args.Button()? {
+                SystemMediaTransportControlsButton::Play => button_player.resume(),
+                SystemMediaTransportControlsButton::Pause => button_player.pause(),
+                SystemMediaTransportControlsButton::Stop => button_player.stop(),
+                SystemMediaTransportControlsButton::Next => button_player.next_track(),
+                SystemMediaTransportControlsButton::Previous => button_player.previous_track(),
+                _ => Ok(()),
+            };
+            if let Err(e) = result {
+                warn!("Media control button failed: {}", e);
+            }
+            Ok(())
+        },
+    ))?;
+    info!("Media transport controls registered");
+
+    let mut receiver = audio_player.subscribe_to_events();
+    loop {
+        let result = match receiver.recv().await {
+            Ok(PlayerEvent::StateChanged(state)) => controls.SetPlaybackStatus(playback_status(&state)),
+            Ok(PlayerEvent::TrackChanged(song)) => {
+                let art_url = match song.as_ref() {
+                    Some(song) => image_cache::local_art_url(&app_handle, song).await,
+                    None => None,
+                };
+                update_display(&controls, song.as_ref(), art_url)
+            }
+            Ok(_) => Ok(()),
+            Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => break,
+        };
+        if let Err(e) = result {
+            warn!("Failed to update media controls: {}", e);
+        }
+    }
+
+    // Keeps the controls alive for as long as the player runs
+    drop(media_player);
+    Ok(())
+}
+
+fn playback_status(state: &PlaybackState) -> MediaPlaybackStatus {
+    if state.is_playing {
+        MediaPlaybackStatus::Playing
+    } else if state.current_song.is_some() {
+        MediaPlaybackStatus::Paused
+    } else {
+        MediaPlaybackStatus::Stopped
+    }
+}
+
+fn update_display(
+    controls: &SystemMediaTransportControls,
+    song: Option<&QueueItem>,
+    art_url: Option<String>,
+) -> windows::core::Result<()> {
+    let updater = controls.DisplayUpdater()?;
+    let Some(song) = song else {
+        updater.ClearAll()?;
+        return updater.Update();
+    };
+
+    updater.SetType(MediaPlaybackType::Music)?;
+    let music = updater.MusicProperties()?;
+    music.SetTitle(&HSTRING::from(song.name.as_str()))?;
+    music.SetArtist(&HSTRING::from(song.artists.join(", ")))?;
+    music.SetAlbumTitle(&HSTRING::from(song.album.as_deref().unwrap_or_default()))?;
+
+    // Art is best effort: there's none while offline unless the cached file carries it
+    if let Some(url) = art_url {
+        let thumbnail = Uri::CreateUri(&HSTRING::from(url)).and_then(|uri| RandomAccessStreamReference::CreateFromUri(&uri));
+        match thumbnail {
+            Ok(thumbnail) => updater.SetThumbnail(&thumbnail)?,
+            Err(e) => warn!("Failed to load media controls artwork: {}", e),
+        }
+    }
+
+    updater.Update()
+}
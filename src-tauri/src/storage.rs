@@ -1,5 +1,20 @@
+use crate::audio_cache::DownloadQuality;
+use crate::audio_player::{self, QueueSnapshot, RepeatMode, ShuffleMode, DEFAULT_VOLUME};
 use crate::jellyfin::JellyfinConfig;
+use crate::scrobble::{LastfmSession, PendingScrobble};
+use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use log::warn;
+
+// Secrets go to the OS secret store; jellyfin.json and lastfm.json keep everything else
+const KEYRING_SERVICE: &str = "bloodin";
+const KEYRING_TOKEN_ACCOUNT: &str = "jellyfin-access-token";
+const KEYRING_LASTFM_ACCOUNT: &str = "lastfm-session-key";
+
+fn keyring_entry(account: &str) -> keyring::Result<keyring::Entry> {
+    keyring::Entry::new(KEYRING_SERVICE, account)
+}
 
 pub async fn save_jellyfin_config(
     app_handle: &tauri::AppHandle,
@@ -7,8 +22,15 @@ pub async fn save_jellyfin_config(
 ) -> Result<(), Box<dyn std::error::Error>> {
     let store = tauri_plugin_store::StoreBuilder::new(app_handle, PathBuf::from("jellyfin.json")).build()?;
     
+    // Without a usable keyring the token stays in the file, as before
+    let mut stored = config.clone();
+    match keyring_entry(KEYRING_TOKEN_ACCOUNT).and_then(|entry| entry.set_password(&config.access_token)) {
+        Ok(()) => stored.access_token.clear(),
+        Err(e) => warn!("OS keyring unavailable, storing access token in jellyfin.json: {}", e),
+    }
+    
     // Store the configuration
-    store.set("config", serde_json::to_value(config)?);
+    store.set("config", serde_json::to_value(&stored)?);
     store.save()?;
     
     Ok(())
@@ -26,7 +48,17 @@ pub async fn load_jellyfin_config(
     
     match store.get("config") {
         Some(value) => {
-            let config: JellyfinConfig = serde_json::from_value(value.clone())?;
+            let mut config: JellyfinConfig = serde_json::from_value(value.clone())?;
+            // An empty token in the file means it was saved to the keyring
+            if config.access_token.is_empty() {
+                match keyring_entry(KEYRING_TOKEN_ACCOUNT).and_then(|entry| entry.get_password()) {
+                    Ok(token) => config.access_token = token,
+                    Err(e) => {
+                        warn!("Saved access token not found in the OS keyring: {}", e);
+                        return Ok(None);
+                    }
+                }
+            }
             Ok(Some(config))
         }
         None => Ok(None),
@@ -41,5 +73,500 @@ pub async fn clear_jellyfin_config(
     store.delete("config");
     store.save()?;
     
+    match keyring_entry(KEYRING_TOKEN_ACCOUNT).and_then(|entry| entry.delete_credential()) {
+        Ok(()) | Err(keyring::Error::NoEntry) => {}
+        Err(e) => warn!("Failed to remove access token from the OS keyring: {}", e),
+    }
+    
+    Ok(())
+}
+
+pub async fn save_balance(
+    app_handle: &tauri::AppHandle,
+    balance: f32,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let store = tauri_plugin_store::StoreBuilder::new(app_handle, PathBuf::from("player.json")).build()?;
+    
+    store.set("balance", serde_json::to_value(balance)?);
+    store.save()?;
+    
+    Ok(())
+}
+
+pub async fn load_balance(
+    app_handle: &tauri::AppHandle,
+) -> Result<Option<f32>, Box<dyn std::error::Error>> {
+    let store = tauri_plugin_store::StoreBuilder::new(app_handle, PathBuf::from("player.json")).build()?;
+    
+    if store.reload().is_err() {
+        return Ok(None);
+    }
+    
+    match store.get("balance") {
+        Some(value) => Ok(Some(serde_json::from_value(value.clone())?)),
+        None => Ok(None),
+    }
+}
+
+pub async fn save_download_quality(
+    app_handle: &tauri::AppHandle,
+    quality: &DownloadQuality,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let store = tauri_plugin_store::StoreBuilder::new(app_handle, PathBuf::from("player.json")).build()?;
+    
+    store.set("download_quality", serde_json::to_value(quality)?);
+    store.save()?;
+    
+    Ok(())
+}
+
+pub async fn load_download_quality(
+    app_handle: &tauri::AppHandle,
+) -> Result<Option<DownloadQuality>, Box<dyn std::error::Error>> {
+    let store = tauri_plugin_store::StoreBuilder::new(app_handle, PathBuf::from("player.json")).build()?;
+    
+    if store.reload().is_err() {
+        return Ok(None);
+    }
+    
+    match store.get("download_quality") {
+        Some(value) => Ok(Some(serde_json::from_value(value.clone())?)),
+        None => Ok(None),
+    }
+}
+
+pub async fn save_cache_quality(
+    app_handle: &tauri::AppHandle,
+    quality: &DownloadQuality,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let store = tauri_plugin_store::StoreBuilder::new(app_handle, PathBuf::from("player.json")).build()?;
+    
+    store.set("cache_quality", serde_json::to_value(quality)?);
+    store.save()?;
+    
+    Ok(())
+}
+
+pub async fn load_cache_quality(
+    app_handle: &tauri::AppHandle,
+) -> Result<Option<DownloadQuality>, Box<dyn std::error::Error>> {
+    let store = tauri_plugin_store::StoreBuilder::new(app_handle, PathBuf::from("player.json")).build()?;
+    
+    if store.reload().is_err() {
+        return Ok(None);
+    }
+    
+    match store.get("cache_quality") {
+        Some(value) => Ok(Some(serde_json::from_value(value.clone())?)),
+        None => Ok(None),
+    }
+}
+
+// None forgets the custom directory so the default is used again
+pub async fn save_cache_directory(
+    app_handle: &tauri::AppHandle,
+    cache_dir: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let store = tauri_plugin_store::StoreBuilder::new(app_handle, PathBuf::from("player.json")).build()?;
+    
+    match cache_dir {
+        Some(cache_dir) => store.set("cache_directory", serde_json::to_value(cache_dir)?),
+        None => {
+            store.delete("cache_directory");
+        }
+    }
+    store.save()?;
+    
+    Ok(())
+}
+
+pub async fn load_cache_directory(
+    app_handle: &tauri::AppHandle,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let store = tauri_plugin_store::StoreBuilder::new(app_handle, PathBuf::from("player.json")).build()?;
+    
+    if store.reload().is_err() {
+        return Ok(None);
+    }
+    
+    match store.get("cache_directory") {
+        Some(value) => Ok(Some(serde_json::from_value(value.clone())?)),
+        None => Ok(None),
+    }
+}
+
+pub async fn save_cache_limits(
+    app_handle: &tauri::AppHandle,
+    max_entries: usize,
+    max_bytes: u64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let store = tauri_plugin_store::StoreBuilder::new(app_handle, PathBuf::from("player.json")).build()?;
+    
+    store.set("cache_max_entries", serde_json::to_value(max_entries)?);
+    store.set("cache_max_bytes", serde_json::to_value(max_bytes)?);
+    store.save()?;
+    
     Ok(())
-} 
\ No newline at end of file
+}
+
+// The file count and byte limits, or None until they've been set
+pub async fn load_cache_limits(
+    app_handle: &tauri::AppHandle,
+) -> Result<Option<(usize, u64)>, Box<dyn std::error::Error>> {
+    let store = tauri_plugin_store::StoreBuilder::new(app_handle, PathBuf::from("player.json")).build()?;
+    
+    if store.reload().is_err() {
+        return Ok(None);
+    }
+    
+    match (store.get("cache_max_entries"), store.get("cache_max_bytes")) {
+        (Some(max_entries), Some(max_bytes)) => Ok(Some((
+            serde_json::from_value(max_entries.clone())?,
+            serde_json::from_value(max_bytes.clone())?,
+        ))),
+        _ => Ok(None),
+    }
+}
+
+// Player settings restored at startup; anything missing from settings.json takes its first-run default
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlayerSettings {
+    pub volume: f32,
+    pub repeat_mode: RepeatMode,
+    #[serde(alias = "is_shuffled", deserialize_with = "audio_player::deserialize_shuffle_mode")]
+    pub shuffle_mode: ShuffleMode,
+    pub normalization_enabled: bool,
+    pub output_device: Option<String>, // None plays through the system default
+    pub streaming_quality: DownloadQuality,
+}
+
+impl Default for PlayerSettings {
+    fn default() -> Self {
+        PlayerSettings {
+            volume: DEFAULT_VOLUME,
+            repeat_mode: RepeatMode::None,
+            shuffle_mode: ShuffleMode::Off,
+            normalization_enabled: false,
+            output_device: None,
+            streaming_quality: DownloadQuality::Original,
+        }
+    }
+}
+
+pub async fn save_player_settings(
+    app_handle: &tauri::AppHandle,
+    settings: &PlayerSettings,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let store = tauri_plugin_store::StoreBuilder::new(app_handle, PathBuf::from("settings.json")).build()?;
+    
+    store.set("player", serde_json::to_value(settings)?);
+    store.save()?;
+    
+    Ok(())
+}
+
+pub async fn load_player_settings(
+    app_handle: &tauri::AppHandle,
+) -> Result<PlayerSettings, Box<dyn std::error::Error>> {
+    let store = tauri_plugin_store::StoreBuilder::new(app_handle, PathBuf::from("settings.json")).build()?;
+    
+    if store.reload().is_err() {
+        return Ok(PlayerSettings::default());
+    }
+    
+    match store.get("player") {
+        Some(value) => Ok(serde_json::from_value(value.clone())?),
+        None => Ok(PlayerSettings::default()),
+    }
+}
+
+pub async fn save_discord_presence_enabled(
+    app_handle: &tauri::AppHandle,
+    enabled: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let store = tauri_plugin_store::StoreBuilder::new(app_handle, PathBuf::from("settings.json")).build()?;
+    
+    store.set("discord_presence_enabled", serde_json::to_value(enabled)?);
+    store.save()?;
+    
+    Ok(())
+}
+
+pub async fn load_discord_presence_enabled(
+    app_handle: &tauri::AppHandle,
+) -> Result<Option<bool>, Box<dyn std::error::Error>> {
+    let store = tauri_plugin_store::StoreBuilder::new(app_handle, PathBuf::from("settings.json")).build()?;
+    
+    if store.reload().is_err() {
+        return Ok(None);
+    }
+    
+    match store.get("discord_presence_enabled") {
+        Some(value) => Ok(Some(serde_json::from_value(value.clone())?)),
+        None => Ok(None),
+    }
+}
+
+pub async fn save_log_level(
+    app_handle: &tauri::AppHandle,
+    level: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let store = tauri_plugin_store::StoreBuilder::new(app_handle, PathBuf::from("settings.json")).build()?;
+    
+    store.set("log_level", serde_json::to_value(level)?);
+    store.save()?;
+    
+    Ok(())
+}
+
+pub async fn load_log_level(
+    app_handle: &tauri::AppHandle,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let store = tauri_plugin_store::StoreBuilder::new(app_handle, PathBuf::from("settings.json")).build()?;
+    
+    if store.reload().is_err() {
+        return Ok(None);
+    }
+    
+    match store.get("log_level") {
+        Some(value) => Ok(Some(serde_json::from_value(value.clone())?)),
+        None => Ok(None),
+    }
+}
+
+pub async fn save_default_page_size(
+    app_handle: &tauri::AppHandle,
+    page_size: i32,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let store = tauri_plugin_store::StoreBuilder::new(app_handle, PathBuf::from("library.json")).build()?;
+    
+    store.set("default_page_size", serde_json::to_value(page_size)?);
+    store.save()?;
+    
+    Ok(())
+}
+
+pub async fn load_default_page_size(
+    app_handle: &tauri::AppHandle,
+) -> Result<Option<i32>, Box<dyn std::error::Error>> {
+    let store = tauri_plugin_store::StoreBuilder::new(app_handle, PathBuf::from("library.json")).build()?;
+    
+    if store.reload().is_err() {
+        return Ok(None);
+    }
+    
+    match store.get("default_page_size") {
+        Some(value) => Ok(Some(serde_json::from_value(value.clone())?)),
+        None => Ok(None),
+    }
+}
+
+pub async fn save_cache_ttl(
+    app_handle: &tauri::AppHandle,
+    seconds: u64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let store = tauri_plugin_store::StoreBuilder::new(app_handle, PathBuf::from("library.json")).build()?;
+    
+    store.set("cache_ttl_seconds", serde_json::to_value(seconds)?);
+    store.save()?;
+    
+    Ok(())
+}
+
+pub async fn load_cache_ttl(
+    app_handle: &tauri::AppHandle,
+) -> Result<Option<u64>, Box<dyn std::error::Error>> {
+    let store = tauri_plugin_store::StoreBuilder::new(app_handle, PathBuf::from("library.json")).build()?;
+    
+    if store.reload().is_err() {
+        return Ok(None);
+    }
+    
+    match store.get("cache_ttl_seconds") {
+        Some(value) => Ok(Some(serde_json::from_value(value.clone())?)),
+        None => Ok(None),
+    }
+}
+
+pub async fn save_queue(
+    app_handle: &tauri::AppHandle,
+    snapshot: &QueueSnapshot,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let store = tauri_plugin_store::StoreBuilder::new(app_handle, PathBuf::from("queue.json")).build()?;
+    
+    // Stream URLs carry the access token, and restore_queue builds fresh ones anyway
+    let mut snapshot = snapshot.clone();
+    for item in &mut snapshot.items {
+        item.stream_url.clear();
+        item.remote_stream_url = None;
+        item.original_stream_url = None;
+    }
+    store.set("queue", serde_json::to_value(&snapshot)?);
+    store.save()?;
+    
+    Ok(())
+}
+
+pub async fn load_queue(
+    app_handle: &tauri::AppHandle,
+) -> Result<Option<QueueSnapshot>, Box<dyn std::error::Error>> {
+    let store = tauri_plugin_store::StoreBuilder::new(app_handle, PathBuf::from("queue.json")).build()?;
+    
+    if store.reload().is_err() {
+        return Ok(None);
+    }
+    
+    match store.get("queue") {
+        Some(value) => Ok(Some(serde_json::from_value(value.clone())?)),
+        None => Ok(None),
+    }
+}
+
+// None forgets the session, disconnecting Last.fm
+pub async fn save_lastfm_session(
+    app_handle: &tauri::AppHandle,
+    session: Option<&LastfmSession>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let store = tauri_plugin_store::StoreBuilder::new(app_handle, PathBuf::from("lastfm.json")).build()?;
+    
+    match session {
+        Some(session) => {
+            // Without a usable keyring the session key stays in the file
+            let mut stored = session.clone();
+            match keyring_entry(KEYRING_LASTFM_ACCOUNT).and_then(|entry| entry.set_password(&session.session_key)) {
+                Ok(()) => stored.session_key.clear(),
+                Err(e) => warn!("OS keyring unavailable, storing Last.fm session key in lastfm.json: {}", e),
+            }
+            store.set("session", serde_json::to_value(&stored)?);
+        }
+        None => {
+            store.delete("session");
+            match keyring_entry(KEYRING_LASTFM_ACCOUNT).and_then(|entry| entry.delete_credential()) {
+                Ok(()) | Err(keyring::Error::NoEntry) => {}
+                Err(e) => warn!("Failed to remove Last.fm session key from the OS keyring: {}", e),
+            }
+        }
+    }
+    store.save()?;
+    
+    Ok(())
+}
+
+pub async fn load_lastfm_session(
+    app_handle: &tauri::AppHandle,
+) -> Result<Option<LastfmSession>, Box<dyn std::error::Error>> {
+    let store = tauri_plugin_store::StoreBuilder::new(app_handle, PathBuf::from("lastfm.json")).build()?;
+    
+    if store.reload().is_err() {
+        return Ok(None);
+    }
+    
+    match store.get("session") {
+        Some(value) => {
+            let mut session: LastfmSession = serde_json::from_value(value.clone())?;
+            // An empty key in the file means it was saved to the keyring
+            if session.session_key.is_empty() {
+                match keyring_entry(KEYRING_LASTFM_ACCOUNT).and_then(|entry| entry.get_password()) {
+                    Ok(session_key) => session.session_key = session_key,
+                    Err(e) => {
+                        warn!("Saved Last.fm session key not found in the OS keyring: {}", e);
+                        return Ok(None);
+                    }
+                }
+            }
+            Ok(Some(session))
+        }
+        None => Ok(None),
+    }
+}
+
+pub async fn save_pending_scrobbles(
+    app_handle: &tauri::AppHandle,
+    scrobbles: &[PendingScrobble],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let store = tauri_plugin_store::StoreBuilder::new(app_handle, PathBuf::from("lastfm.json")).build()?;
+    
+    store.set("pending_scrobbles", serde_json::to_value(scrobbles)?);
+    store.save()?;
+    
+    Ok(())
+}
+
+pub async fn load_pending_scrobbles(
+    app_handle: &tauri::AppHandle,
+) -> Result<Vec<PendingScrobble>, Box<dyn std::error::Error>> {
+    let store = tauri_plugin_store::StoreBuilder::new(app_handle, PathBuf::from("lastfm.json")).build()?;
+    
+    if store.reload().is_err() {
+        return Ok(Vec::new());
+    }
+    
+    match store.get("pending_scrobbles") {
+        Some(value) => Ok(serde_json::from_value(value.clone())?),
+        None => Ok(Vec::new()),
+    }
+}
+
+// Longest search history we keep
+const MAX_RECENT_SEARCHES: usize = 20;
+// Shorter queries are search-as-you-type noise, not worth remembering
+const MIN_RECENT_SEARCH_LENGTH: usize = 2;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecentSearch {
+    pub query: String,
+    pub searched_at: u64, // unix seconds
+}
+
+pub async fn load_recent_searches(
+    app_handle: &tauri::AppHandle,
+) -> Result<Vec<RecentSearch>, Box<dyn std::error::Error>> {
+    let store = tauri_plugin_store::StoreBuilder::new(app_handle, PathBuf::from("search.json")).build()?;
+    
+    if store.reload().is_err() {
+        return Ok(Vec::new());
+    }
+    
+    match store.get("recent_searches") {
+        Some(value) => Ok(serde_json::from_value(value.clone())?),
+        None => Ok(Vec::new()),
+    }
+}
+
+pub async fn record_recent_search(
+    app_handle: &tauri::AppHandle,
+    query: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let query = query.trim();
+    if query.chars().count() < MIN_RECENT_SEARCH_LENGTH {
+        return Ok(());
+    }
+    
+    let mut searches = load_recent_searches(app_handle).await?;
+    
+    // Most recent first, without duplicates
+    searches.retain(|search| !search.query.eq_ignore_ascii_case(query));
+    searches.insert(0, RecentSearch {
+        query: query.to_string(),
+        searched_at: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+    });
+    searches.truncate(MAX_RECENT_SEARCHES);
+    
+    let store = tauri_plugin_store::StoreBuilder::new(app_handle, PathBuf::from("search.json")).build()?;
+    store.set("recent_searches", serde_json::to_value(&searches)?);
+    store.save()?;
+    
+    Ok(())
+}
+
+pub async fn clear_recent_searches(
+    app_handle: &tauri::AppHandle,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let store = tauri_plugin_store::StoreBuilder::new(app_handle, PathBuf::from("search.json")).build()?;
+    
+    store.delete("recent_searches");
+    store.save()?;
+    
+    Ok(())
+}
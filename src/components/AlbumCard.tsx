@@ -26,7 +26,7 @@ export default function AlbumCard({
   useEffect(() => {
     const loadImage = async () => {
       if (album.ImageTags && Object.keys(album.ImageTags).length > 0) {
-        const url = await JellyfinApiService.getImageUrl(album.Id, "Primary");
+        const url = await JellyfinApiService.fetchImage(album.Id, "Primary", 400);
         if (url) setImageUrl(url);
       }
     };
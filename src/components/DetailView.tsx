@@ -113,7 +113,7 @@ export default function DetailView({ type }: DetailViewProps) {
       if (response.success) {
         setSongs(response.items || []);
         setTotalSongs(response.total_count || 0);
-        setHasMore((response.items?.length || 0) < (response.total_count || 0));
+        setHasMore(response.has_more);
       }
     } catch (error) {
       console.error("Error loading initial playlist songs:", error);
@@ -135,7 +135,7 @@ export default function DetailView({ type }: DetailViewProps) {
       );
       if (response.success && response.items) {
         setSongs((prev) => [...prev, ...response.items!]);
-        setHasMore(songs.length + response.items.length < totalSongs);
+        setHasMore(response.has_more);
       }
     } catch (error) {
       console.error("Error loading more playlist songs:", error);
@@ -237,7 +237,7 @@ export default function DetailView({ type }: DetailViewProps) {
 
         // Load image for album, placeholder for others
         if (type === "album") {
-          const url = await JellyfinApiService.getImageUrl(id, "Primary");
+          const url = await JellyfinApiService.fetchImage(id, "Primary", 600);
           if (url) {
             setImageUrl(url);
           }
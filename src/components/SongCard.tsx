@@ -26,7 +26,7 @@ export default function SongCard({
 
   useEffect(() => {
     const loadImage = async () => {
-      const url = await JellyfinApiService.getImageUrl(song.Id, "Primary");
+      const url = await JellyfinApiService.fetchImage(song.Id, "Primary", 400);
       if (url) setImageUrl(url);
     };
     loadImage();
@@ -54,12 +54,13 @@ function SongRow({
   useEffect(() => {
     const loadImage = async () => {
       if (song.ImageTags && Object.keys(song.ImageTags).length > 0) {
-        const url = await JellyfinApiService.getImageUrl(song.Id, "Primary");
+        const url = await JellyfinApiService.fetchImage(song.Id, "Primary", 96);
         if (url) setImageUrl(url);
       } else if (song.AlbumId) {
-        const url = await JellyfinApiService.getImageUrl(
+        const url = await JellyfinApiService.fetchImage(
           song.AlbumId,
-          "Primary"
+          "Primary",
+          96
         );
         if (url) setImageUrl(url);
       }
@@ -258,9 +258,10 @@ export const AudioPlayerProvider: React.FC<{ children: React.ReactNode }> = ({
       } else {
         try {
           // Try to get album art from the song
-          const imageUrl = await JellyfinApiService.getImageUrl(
+          const imageUrl = await JellyfinApiService.fetchImage(
             song.id,
-            "Primary"
+            "Primary",
+            400
           );
           albumArt = imageUrl || undefined;
           // Cache the result
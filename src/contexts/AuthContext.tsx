@@ -5,7 +5,7 @@ import {
   useEffect,
   ReactNode,
 } from "react";
-import { JellyfinApiService } from "../services/jellyfinApi";
+import { JellyfinApiService, errorMessage } from "../services/jellyfinApi";
 import type {
   AuthCheckResult,
   ConnectResult,
@@ -85,7 +85,7 @@ export function AuthProvider({ children }: AuthProviderProps) {
       console.error("Login failed:", error);
       return {
         success: false,
-        message: `Login failed: ${error}`,
+        message: `Login failed: ${errorMessage(error)}`,
       };
     } finally {
       setIsLoading(false);
@@ -1,6 +1,6 @@
 import { useState } from "react";
 import { useAuth } from "../contexts/AuthContext";
-import { JellyfinApiService } from "../services/jellyfinApi";
+import { JellyfinApiService, errorMessage } from "../services/jellyfinApi";
 import type { ConnectionForm } from "../types/jellyfin";
 import { IconPlayerPlay, IconWifi, IconLogin } from "@tabler/icons-react";
 
@@ -33,7 +33,7 @@ const LoginPage: React.FC<LoginPageProps> = ({ onLogin }) => {
         setError(result.message);
       }
     } catch (error) {
-      setError(`Connection failed: ${error}`);
+      setError(`Connection failed: ${errorMessage(error)}`);
     } finally {
       setIsLoading(false);
     }
@@ -60,7 +60,7 @@ const LoginPage: React.FC<LoginPageProps> = ({ onLogin }) => {
         setError(result.message);
       }
     } catch (error) {
-      setError(`Failed to connect to server: ${error}`);
+      setError(`Failed to connect to server: ${errorMessage(error)}`);
     } finally {
       setIsLoading(false);
     }
@@ -30,7 +30,7 @@ export default function SongsPage() {
       if (response.success) {
         setSongs(response.items || []);
         setTotalSongs(response.total_count || 0);
-        setHasMore((response.items?.length || 0) < (response.total_count || 0));
+        setHasMore(response.has_more);
       }
     } catch (error) {
       console.error("Error loading songs:", error);
@@ -51,7 +51,7 @@ export default function SongsPage() {
       );
       if (response.success && response.items) {
         setSongs((prev) => [...prev, ...response.items!]);
-        setHasMore(songs.length + response.items.length < totalSongs);
+        setHasMore(response.has_more);
       }
     } catch (error) {
       console.error("Error loading more songs:", error);
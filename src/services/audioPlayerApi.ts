@@ -6,7 +6,7 @@ export interface PlaybackState {
   current_position: number; // in seconds
   duration: number; // in seconds
   volume: number; // 0.0 to 1.0
-  is_shuffled: boolean;
+  shuffle_mode: ShuffleMode;
   repeat_mode: "None" | "One" | "All";
   current_song?: QueueItem;
 }
@@ -22,6 +22,7 @@ export interface QueueItem {
 }
 
 export type RepeatMode = "None" | "One" | "All";
+export type ShuffleMode = "Off" | "Tracks" | "Albums";
 
 // Frontend-friendly conversion functions
 export const convertBackendPlaybackState = (backendState: PlaybackState) => ({
@@ -29,7 +30,8 @@ export const convertBackendPlaybackState = (backendState: PlaybackState) => ({
   currentPosition: backendState.current_position,
   duration: backendState.duration,
   volume: backendState.volume,
-  isShuffled: backendState.is_shuffled,
+  isShuffled: backendState.shuffle_mode !== "Off",
+  shuffleMode: backendState.shuffle_mode,
   repeatMode: backendState.repeat_mode.toLowerCase() as "none" | "one" | "all",
   currentSong: backendState.current_song
     ? convertBackendQueueItem(backendState.current_song)
@@ -128,6 +130,15 @@ export class AudioPlayerAPI {
     }
   }
 
+  static async setShuffleMode(mode: "off" | "tracks" | "albums"): Promise<boolean> {
+    try {
+      return await invoke<boolean>("set_shuffle_mode", { mode });
+    } catch (error) {
+      console.error("Failed to set shuffle mode:", error);
+      throw error;
+    }
+  }
+
   static async setRepeatMode(mode: "none" | "one" | "all"): Promise<boolean> {
     try {
       const backendMode = convertRepeatModeToBackend(mode);
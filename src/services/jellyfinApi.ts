@@ -6,8 +6,17 @@ import type {
   AuthCheckResult,
   ConnectionForm,
   MusicLibraryResult,
+  CommandError,
 } from "../types/jellyfin";
 
+// Text to show for a rejected command
+export function errorMessage(error: unknown): string {
+  if (typeof error === "object" && error !== null && "message" in error) {
+    return (error as CommandError).message;
+  }
+  return String(error);
+}
+
 export class JellyfinApiService {
   /**
    * Connect to a Jellyfin server and authenticate
@@ -26,7 +35,7 @@ export class JellyfinApiService {
       console.error("Failed to connect to Jellyfin:", error);
       return {
         success: false,
-        message: `Connection failed: ${error}`,
+        message: `Connection failed: ${errorMessage(error)}`,
       };
     }
   }
@@ -44,7 +53,7 @@ export class JellyfinApiService {
       console.error("Failed to get server info:", error);
       return {
         success: false,
-        message: `Failed to get server info: ${error}`,
+        message: `Failed to get server info: ${errorMessage(error)}`,
       };
     }
   }
@@ -60,7 +69,7 @@ export class JellyfinApiService {
       console.error("Failed to get user profile:", error);
       return {
         success: false,
-        message: `Failed to get user profile: ${error}`,
+        message: `Failed to get user profile: ${errorMessage(error)}`,
       };
     }
   }
@@ -126,7 +135,7 @@ export class JellyfinApiService {
       console.error("Failed to get songs:", error);
       return {
         success: false,
-        message: `Failed to get songs: ${error}`,
+        message: `Failed to get songs: ${errorMessage(error)}`,
       };
     }
   }
@@ -148,7 +157,7 @@ export class JellyfinApiService {
       console.error("Failed to get albums:", error);
       return {
         success: false,
-        message: `Failed to get albums: ${error}`,
+        message: `Failed to get albums: ${errorMessage(error)}`,
       };
     }
   }
@@ -170,7 +179,7 @@ export class JellyfinApiService {
       console.error("Failed to get artists:", error);
       return {
         success: false,
-        message: `Failed to get artists: ${error}`,
+        message: `Failed to get artists: ${errorMessage(error)}`,
       };
     }
   }
@@ -192,7 +201,7 @@ export class JellyfinApiService {
       console.error("Failed to get playlists:", error);
       return {
         success: false,
-        message: `Failed to get playlists: ${error}`,
+        message: `Failed to get playlists: ${errorMessage(error)}`,
       };
     }
   }
@@ -214,41 +223,27 @@ export class JellyfinApiService {
       console.error("Failed to search music:", error);
       return {
         success: false,
-        message: `Search failed: ${error}`,
+        message: `Search failed: ${errorMessage(error)}`,
       };
     }
   }
 
   /**
-   * Get image URL for an item
+   * Get an item's image as a data URL, fetched by the backend so no token appears in the src
    */
-  static async getImageUrl(
+  static async fetchImage(
     itemId: string,
-    imageType: string = "Primary"
+    imageType: string = "Primary",
+    maxWidth?: number
   ): Promise<string | null> {
     try {
-      const result = await invoke<string>("get_image_url", {
-        itemId: itemId,
-        imageType: imageType,
+      return await invoke<string | null>("fetch_image", {
+        itemId,
+        imageType,
+        maxWidth,
       });
-
-      // Test if the image URL is accessible
-      if (result) {
-        return new Promise((resolve) => {
-          const img = new Image();
-          img.onload = () => {
-            resolve(result);
-          };
-          img.onerror = () => {
-            resolve(null);
-          };
-          img.src = result;
-        });
-      }
-
-      return result;
     } catch (error) {
-      console.error("Failed to get image URL:", error);
+      console.error("Failed to fetch image:", error);
       return null;
     }
   }
@@ -282,7 +277,7 @@ export class JellyfinApiService {
       console.error("Failed to get random songs:", error);
       return {
         success: false,
-        message: `Failed to get random songs: ${error}`,
+        message: `Failed to get random songs: ${errorMessage(error)}`,
       };
     }
   }
@@ -307,7 +302,7 @@ export class JellyfinApiService {
       console.error("Failed to get recent albums:", error);
       return {
         success: false,
-        message: `Failed to get recent albums: ${error}`,
+        message: `Failed to get recent albums: ${errorMessage(error)}`,
       };
     }
   }
@@ -325,7 +320,7 @@ export class JellyfinApiService {
       console.error("Failed to get album songs:", error);
       return {
         success: false,
-        message: `Failed to get album songs: ${error}`,
+        message: `Failed to get album songs: ${errorMessage(error)}`,
       };
     }
   }
@@ -343,7 +338,7 @@ export class JellyfinApiService {
       console.error("Failed to get artist songs:", error);
       return {
         success: false,
-        message: `Failed to get artist songs: ${error}`,
+        message: `Failed to get artist songs: ${errorMessage(error)}`,
       };
     }
   }
@@ -367,7 +362,7 @@ export class JellyfinApiService {
       console.error("Failed to get playlist songs:", error);
       return {
         success: false,
-        message: `Failed to get playlist songs: ${error}`,
+        message: `Failed to get playlist songs: ${errorMessage(error)}`,
       };
     }
   }
@@ -391,7 +386,7 @@ export class JellyfinApiService {
       console.error("Failed to get item:", error);
       return {
         success: false,
-        message: `Failed to get item: ${error}`,
+        message: `Failed to get item: ${errorMessage(error)}`,
       };
     }
   }
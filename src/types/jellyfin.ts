@@ -15,24 +15,41 @@ export interface UserProfile {
   enable_auto_login: boolean;
 }
 
+// Why a request to the server failed; message is the text to show
+export interface JellyfinError {
+  kind: "network" | "auth" | "not_found" | "server" | "parse" | "not_authenticated";
+  message: string;
+  status?: number;
+}
+
+// What a command rejects with; failures that didn't come from the server are kind "other"
+export interface CommandError {
+  kind: JellyfinError["kind"] | "other";
+  message: string;
+  status?: number;
+}
+
 // Tauri Command Response Types
 export interface ConnectResult {
   success: boolean;
   message: string;
   user_name?: string;
   server_name?: string;
+  error?: JellyfinError;
 }
 
 export interface ServerInfoResult {
   success: boolean;
   message: string;
   server_info?: ServerInfo;
+  error?: JellyfinError;
 }
 
 export interface UserProfileResult {
   success: boolean;
   message: string;
   user_profile?: UserProfile;
+  error?: JellyfinError;
 }
 
 export interface AuthCheckResult {
@@ -66,7 +83,9 @@ export interface MusicItem {
   ArtistItems?: NameIdPair[];
   ImageTags?: Record<string, string>;
   BackdropImageTags?: string[];
+  ImageBlurHashes?: Record<string, Record<string, string>>;
   ChildCount?: number;
+  PlaylistItemId?: string;
 }
 
 export interface UserData {
@@ -86,6 +105,9 @@ export interface MusicLibraryResult {
   message: string;
   items?: MusicItem[];
   total_count?: number;
+  start_index?: number;
+  has_more: boolean;
+  error?: JellyfinError;
 }
 
 // Helper functions